@@ -18,39 +18,288 @@
 
 use anyhow::{Context, Result};
 use colored::Colorize;
-use std::collections::{HashMap, HashSet};
+use serde_derive::Serialize;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::env;
 use std::fs;
+use std::io::{self, Write};
+use std::os::unix::fs::PermissionsExt;
 use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use walkdir::WalkDir;
 
 use crate::config::{
-    yabridge_clap_home, yabridge_vst2_home, yabridge_vst3_home, Config, Vst2InstallationLocation,
-    YabridgeFiles, CLAP_CHAINLOADER_NAME, VST2_CHAINLOADER_NAME, VST3_CHAINLOADER_NAME,
-    YABRIDGE_HOST_32_EXE_NAME, YABRIDGE_HOST_EXE_NAME,
+    find_config_file, yabridge_clap_home, yabridge_vst2_home, yabridge_vst3_home,
+    yabridgectl_directories, Config, DeviceConcurrency, DirectorySettings, RelativePathBase,
+    SearchBatch, SearchOverrides, ShortcutHandling, Vst2InstallationLocation, YabridgeFiles,
+    CLAP_CHAINLOADER_NAME, VST2_CHAINLOADER_NAME, VST3_CHAINLOADER_NAME, YABRIDGE_HOST_32_EXE_NAME,
+    YABRIDGE_HOST_EXE_NAME,
 };
-use crate::files::{self, ClapPlugin, NativeFile, Plugin, Vst2Plugin};
+use crate::daw::{self, DawIntegration};
+use crate::error::{ConflictError, InterruptedError};
+use crate::files::{
+    self, ClapPlugin, CopyState, InstallState, LibArchitecture, NativeFile, Plugin, SearchResults,
+    SkipCounter, SkipReason, SymlinkState, Traversal, Vst2Plugin,
+};
+use crate::filesystem::{self, FileSystem};
+use crate::index::{Index, INDEX_FILE_NAME, YABRIDGE_VERSION};
 use crate::util::{self, get_file_type};
 use crate::util::{verify_external_dependencies, verify_path_setup, verify_wine_setup};
 use crate::vst3_moduleinfo::ModuleInfo;
+use plan::{PlanOptions, PlannedAction};
 
 pub mod blacklist;
+pub mod plan;
 
 /// Add a direcotry to the plugin locations. Duplicates get ignord because we're using ordered sets.
-pub fn add_directory(config: &mut Config, path: PathBuf) -> Result<()> {
+/// Adding a directory that's already in the list will replace its settings. With `strict_paths`,
+/// the directory needs to be inside of a Wine prefix or inside of one of the `allowed_bases`.
+pub fn add_directory(
+    config: &mut Config,
+    path: PathBuf,
+    settings: DirectorySettings,
+    strict_paths: bool,
+) -> Result<()> {
+    if strict_paths
+        && !util::is_in_wine_prefix(&path)
+        && !config
+            .allowed_bases
+            .iter()
+            .any(|base| path.starts_with(base))
+    {
+        anyhow::bail!(
+            "'{}' does not appear to be inside of a Wine prefix. With strict paths enabled, only \
+             directories inside of a Wine prefix or inside of one of the directories added with \
+             'yabridgectl set --allow-base' can be added, since adding a large unrelated directory \
+             would result in a very slow scan. Use '--force' to add this directory anyways.",
+            path.display()
+        );
+    }
+
+    if settings == DirectorySettings::default() {
+        config.directory_settings.remove(&path);
+    } else {
+        config.directory_settings.insert(path.clone(), settings);
+    }
     config.plugin_dirs.insert(path);
     config.write()
 }
 
+/// Options passed to `yabridgectl adopt`, see `main()` for the definitions of these options.
+pub struct AdoptOptions {
+    pub path: PathBuf,
+    pub strict_paths: bool,
+}
+
+/// Add a plugin directory whose plugins were already set up by hand, and record the bridges for
+/// those plugins in the index. `yabridgectl sync` leaves up to date copies alone, so the only
+/// things that would still change are outdated bridges, symlinked bridges, and VST2 bridges that
+/// are not in the location yabridgectl has been configured to use. These are all reported here.
+pub fn adopt_directory(config: &mut Config, options: &AdoptOptions) -> Result<()> {
+    // Like with `yabridgectl sync`, the bridges can't be compared to anything without these
+    let files: YabridgeFiles = config.files()?;
+    let settings = config
+        .directory_settings
+        .get(&options.path)
+        .cloned()
+        .unwrap_or_default();
+    add_directory(config, options.path.clone(), settings, options.strict_paths)?;
+
+    let results = config
+        .search_directories(&SearchOverrides {
+            only_dir: Some(options.path.clone()),
+            ..Default::default()
+        })
+        .context("Failure while searching for plugins")?;
+    let search_results = match results.into_iter().next() {
+        Some((_, search_results)) => search_results,
+        None => return Ok(()),
+    };
+
+    let size_and_hash =
+        |path: &Path| Some((path.metadata().ok()?.len(), util::hash_file(path).ok()?));
+    let vst2_chainloader = size_and_hash(&files.vst2_chainloader);
+    let vst3_chainloader = files
+        .vst3_chainloader
+        .as_ref()
+        .and_then(|(path, _)| size_and_hash(path));
+    let clap_chainloader = files
+        .clap_chainloader
+        .as_ref()
+        .and_then(|(path, _)| size_and_hash(path));
+
+    let mut index = Index::read()?;
+    let vst2_location = search_results.vst2_location(config);
+    let mut num_adopted = 0;
+    let mut num_needs_sync = 0;
+    let mut num_misplaced_vst2 = 0;
+    let mut not_set_up: Vec<&Path> = Vec::new();
+    println!("Bridges found in '{}':", options.path.display());
+    for plugin in &search_results.plugins {
+        // VST2 plugins may have been set up in either location, and there's no way to tell which
+        // location a manual setup used without looking at both of them
+        let (chainloader, candidates) = match plugin {
+            Plugin::Vst2(vst2_plugin) => (
+                vst2_chainloader,
+                vec![
+                    (
+                        vst2_plugin.inline_native_target(),
+                        Some(Vst2InstallationLocation::Inline),
+                    ),
+                    (
+                        vst2_plugin.centralized_native_target(config, Some(&files)),
+                        Some(Vst2InstallationLocation::Centralized),
+                    ),
+                ],
+            ),
+            Plugin::Vst3(_) => (
+                vst3_chainloader,
+                vec![(
+                    plugin.native_target(config, vst2_location, Some(&files)),
+                    None,
+                )],
+            ),
+            Plugin::Clap(_) => (
+                clap_chainloader,
+                vec![(
+                    plugin.native_target(config, vst2_location, Some(&files)),
+                    None,
+                )],
+            ),
+        };
+
+        let found: Vec<(InstallState, Option<Vst2InstallationLocation>)> = candidates
+            .into_iter()
+            .map(|(path, location)| {
+                (
+                    InstallState::new(get_file_type(path), chainloader, Some(&files)),
+                    location,
+                )
+            })
+            .filter(|(state, _)| *state != InstallState::NotInstalled)
+            .collect();
+        if found.is_empty() {
+            not_set_up.push(plugin.path());
+            continue;
+        }
+
+        for (state, location) in found {
+            let bridge_path = state.path().unwrap_or_else(|| plugin.path());
+            let inference = match &state {
+                InstallState::Copy(path, CopyState::UpToDate | CopyState::Outdated)
+                    if !files::is_bridge(filesystem::current(), path) =>
+                {
+                    String::from("not a yabridge bridge, left alone")
+                }
+                InstallState::Copy(
+                    path,
+                    copy_state @ (CopyState::UpToDate | CopyState::Outdated),
+                ) => {
+                    if index.record_adopted_bridge(util::normalize_path(path)) {
+                        num_adopted += 1;
+                    }
+                    if *copy_state == CopyState::Outdated {
+                        num_needs_sync += 1;
+                        String::from("adopted, will be updated during the next sync")
+                    } else {
+                        String::from("adopted")
+                    }
+                }
+                InstallState::Symlink(_, SymlinkState::Ok) => {
+                    num_needs_sync += 1;
+                    String::from("will be replaced with a copy during the next sync")
+                }
+                InstallState::Foreign(_) => {
+                    String::from("set up by a different yabridge installation, not adopted")
+                }
+                _ => {
+                    num_needs_sync += 1;
+                    String::from("will be set up again during the next sync")
+                }
+            };
+            if location.map_or(false, |location| location != vst2_location) {
+                num_misplaced_vst2 += 1;
+            }
+
+            println!(
+                "- {} {} ({}, {})",
+                plugin.path().display(),
+                "->".dimmed(),
+                bridge_path.display(),
+                state,
+            );
+            println!("  {inference}");
+        }
+    }
+
+    if !not_set_up.is_empty() {
+        println!("\nNot set up yet, these will be set up during the next sync:");
+        for path in not_set_up {
+            println!("- {}", path.display());
+        }
+    }
+
+    index.write()?;
+
+    println!(
+        "\nAdopted {} {}, {} {} still need to be changed by 'yabridgectl sync'",
+        num_adopted,
+        if num_adopted == 1 {
+            "bridge"
+        } else {
+            "bridges"
+        },
+        num_needs_sync,
+        if num_needs_sync == 1 {
+            "bridge"
+        } else {
+            "bridges"
+        },
+    );
+    if num_misplaced_vst2 > 0 && !search_results.read_only {
+        eprintln!(
+            "\n{}",
+            util::wrap(&format!(
+                "{}: {} VST2 {} set up {}, but yabridgectl is configured to use the {} VST2 \
+                 location. The next sync will set up these plugins again in the configured \
+                 location, and the existing bridges will be treated as leftovers. Run \
+                 'yabridgectl set --vst2-location={}' first to keep them where they are.",
+                "WARNING".red(),
+                num_misplaced_vst2,
+                if num_misplaced_vst2 == 1 {
+                    "bridge was"
+                } else {
+                    "bridges were"
+                },
+                match vst2_location {
+                    Vst2InstallationLocation::Centralized => "inline next to the plugins",
+                    Vst2InstallationLocation::Inline => "in the centralized location",
+                },
+                vst2_location,
+                match vst2_location {
+                    Vst2InstallationLocation::Centralized => "inline",
+                    Vst2InstallationLocation::Inline => "centralized",
+                },
+            ))
+        );
+    }
+
+    Ok(())
+}
+
 /// Remove a direcotry to the plugin locations. The path is assumed to be part of
 /// `config.plugin_dirs`, otherwise this is silently ignored.
 pub fn remove_directory(config: &mut Config, path: &Path) -> Result<()> {
     // We've already verified that this path is in `config.plugin_dirs`
     config.plugin_dirs.remove(path);
+    config.directory_settings.remove(path);
     config.write()?;
 
     // Ask the user to remove any leftover files to prevent possible future problems and out of date
     // copies
-    let orphan_files = files::index(path, &HashSet::new()).so_files;
+    let orphan_files =
+        files::index(path, &HashSet::new(), &files::IndexOptions::default()).so_files;
     if !orphan_files.is_empty() {
         println!(
             "Warning: Found {} leftover .so files still in this directory:",
@@ -61,85 +310,507 @@ pub fn remove_directory(config: &mut Config, path: &Path) -> Result<()> {
             println!("- {}", file.path().display());
         }
 
-        match promptly::prompt_opt::<String, &str>(
+        if util::confirm(
             "\nWould you like to remove these files? Entering anything other than YES will leave \
              these files intact",
         ) {
-            Ok(Some(answer)) if answer == "YES" => {
-                for file in &orphan_files {
-                    util::remove_file(file.path())?;
-                }
+            for file in &orphan_files {
+                util::remove_file(file.path())?;
+            }
+
+            println!("\nRemoved {} files", orphan_files.len());
+        }
+    }
+
+    Ok(())
+}
+
+/// Options passed to `yabridgectl move`, see `main()` for the definitions of these options.
+pub struct MoveOptions {
+    /// The plugin directory as it appears in the config file.
+    pub from: PathBuf,
+    /// The directory's new location. This should already exist.
+    pub to: PathBuf,
+    pub fix_links: bool,
+}
+
+/// Update a plugin directory's path after the directory has been moved somewhere else, keeping its
+/// settings, its blacklist entries, and the notes for its plugins. Existing bridges and symlinks
+/// are left alone unless `fix_links` is set, in which case the symlinks in the centralized
+/// locations that point into the old directory are pointed at the new location.
+pub fn move_directory(config: &mut Config, options: &MoveOptions) -> Result<()> {
+    let (from, to) = (&options.from, &options.to);
+    if config.plugin_dirs.contains(to) {
+        anyhow::bail!("'{}' has already been added", to.display());
+    }
+
+    config.plugin_dirs.remove(from);
+    config.plugin_dirs.insert(to.clone());
+    if let Some(settings) = config.directory_settings.remove(from) {
+        config.directory_settings.insert(to.clone(), settings);
+    }
+    config.blacklist = std::mem::take(&mut config.blacklist)
+        .into_iter()
+        .map(|path| match path.strip_prefix(from) {
+            Ok(suffix) => to.join(suffix),
+            Err(_) => path,
+        })
+        .collect();
+    config.write()?;
+
+    let mut index = Index::read()?;
+    index.move_directory(from, to);
+    index.write()?;
+
+    println!("Moved '{}' to '{}'", from.display(), to.display());
+
+    // The Windows plugins are symlinked to the centralized locations, and those symlinks still
+    // point to the old location
+    let normalized_from = util::normalize_path(from);
+    let mut num_fixed_links = 0;
+    let mut num_broken_links = 0;
+    for home in [
+        yabridge_vst2_home(config),
+        yabridge_vst3_home(config),
+        yabridge_clap_home(),
+    ] {
+        for entry in WalkDir::new(home)
+            .follow_links(false)
+            .into_iter()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path_is_symlink())
+        {
+            let symlink_path = entry.path();
+            let symlink_dir = symlink_path.parent().unwrap();
+            let target = match symlink_path.read_link() {
+                Ok(target) => target,
+                Err(_) => continue,
+            };
+            let old_target = util::normalize_path(&symlink_dir.join(&target));
+            let new_target = match old_target.strip_prefix(&normalized_from) {
+                Ok(suffix) => to.join(suffix),
+                Err(_) => continue,
+            };
+
+            if !options.fix_links {
+                num_broken_links += 1;
+                continue;
+            }
 
-                println!("\nRemoved {} files", orphan_files.len());
+            // Relative symlinks stay relative
+            util::remove_file(symlink_path)?;
+            if target.is_relative() {
+                util::symlink(util::relative_path(&new_target, symlink_dir), symlink_path)?;
+            } else {
+                util::symlink(&new_target, symlink_path)?;
             }
-            _ => {}
+            num_fixed_links += 1;
         }
     }
 
+    if num_fixed_links > 0 {
+        println!(
+            "Pointed {} {} at the new location",
+            num_fixed_links,
+            if num_fixed_links == 1 {
+                "symlink"
+            } else {
+                "symlinks"
+            }
+        );
+    }
+    if num_broken_links > 0 {
+        println!(
+            "\n{}",
+            util::wrap(&format!(
+                "{} {} in the centralized plugin locations still point to the old location. Rerun \
+                 this command with '--fix-links' or run 'yabridgectl sync' to fix them.",
+                num_broken_links,
+                if num_broken_links == 1 {
+                    "symlink"
+                } else {
+                    "symlinks"
+                }
+            ))
+        );
+    }
+
     Ok(())
 }
 
-/// List the plugin locations.
-pub fn list_directories(config: &Config) -> Result<()> {
-    for directory in &config.plugin_dirs {
-        println!("{}", directory.display());
+/// Options passed to `yabridgectl list`, see `main()` for the definitions of these options.
+pub struct ListOptions {
+    pub numbered: bool,
+    pub verbose: bool,
+    pub json: bool,
+}
+
+/// A plugin directory as printed by `yabridgectl list --json`.
+#[derive(Serialize)]
+struct ListedPluginDir<'a> {
+    #[serde(flatten)]
+    directory: EffectivePluginDir<'a>,
+    /// The path as it's stored in the config file, if it's stored relative to some base directory.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    relative_path: Option<PathBuf>,
+    /// The number of plugins found in the directory during the last sync. Only included with
+    /// `--verbose`, and only if the directory has been synced before.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    num_plugins: Option<usize>,
+}
+
+/// List the plugin locations. With the numbered option these are prefixed with the numbers that
+/// can be passed to `yabridgectl rm`.
+pub fn list_directories(config: &Config, options: &ListOptions) -> Result<()> {
+    // When paths are stored relative to some base directory, we'll also show the relative path as
+    // it's stored in the config file
+    let base = config
+        .relative_to
+        .map(|relative_to| relative_to.path())
+        .transpose()?;
+    // The plugin counts are taken from the last sync so this doesn't need to search the directories
+    let index = if options.verbose {
+        Some(Index::read()?)
+    } else {
+        None
+    };
+    let directories: Vec<ListedPluginDir> = config
+        .sorted_plugin_dirs()
+        .into_iter()
+        .map(|directory| ListedPluginDir {
+            directory: EffectivePluginDir::new(config, directory),
+            relative_path: match &base {
+                Some(base) if directory.starts_with(base) => {
+                    Some(RelativePathBase::relativize(base, directory))
+                }
+                _ => None,
+            },
+            num_plugins: index
+                .as_ref()
+                .and_then(|index| index.directory(directory))
+                .map(|info| info.num_plugins),
+        })
+        .collect();
+
+    if options.json {
+        println!(
+            "{}",
+            serde_jsonrc::to_string(&directories).context("Could not format JSON")?
+        );
+
+        return Ok(());
+    }
+
+    for (number, directory) in directories.into_iter().enumerate() {
+        let prefix = if options.numbered {
+            format!("{}: ", number + 1)
+        } else {
+            String::new()
+        };
+
+        let mut line = format!("{}{}", prefix, directory.directory.path.display());
+        if let Some(relative_path) = &directory.relative_path {
+            line.push_str(&format!(" ({})", relative_path.display()));
+        }
+        if options.verbose {
+            let mut details: Vec<String> = Vec::new();
+            if let Some(num_plugins) = directory.num_plugins {
+                details.push(format!(
+                    "{} {}",
+                    num_plugins,
+                    if num_plugins == 1 {
+                        "plugin"
+                    } else {
+                        "plugins"
+                    }
+                ));
+            }
+            if directory.directory.no_recurse {
+                details.push(String::from("no recurse"));
+            }
+            if directory.directory.read_only {
+                details.push(String::from("read-only"));
+            }
+            if !directory.directory.groups.is_empty() {
+                details.push(format!("groups: {}", directory.directory.groups.join(" ")));
+            }
+            if !details.is_empty() {
+                line.push_str(&format!(" [{}]", details.join(", ")));
+            }
+        }
+
+        println!("{line}");
     }
 
     Ok(())
 }
 
+/// Options passed to `yabridgectl status`, see `main()` for the definitions of these options.
+pub struct StatusOptions {
+    pub check_host_match: bool,
+    pub group: Option<String>,
+    pub exclude_dirs: Vec<PathBuf>,
+    pub group_by: StatusGrouping,
+    pub include_hidden: bool,
+    pub no_recurse: bool,
+    pub no_cache: bool,
+    pub skip_system_dlls: bool,
+    pub sort: StatusSorting,
+    pub traversal: Traversal,
+    pub time: bool,
+    pub verbose: bool,
+    pub json: bool,
+    pub json_stream: bool,
+}
+
+/// A line printed by `yabridgectl status --json-stream`. Every line is a JSON object with a `type`
+/// field. The first line is a header, followed by one line per plugin, and the last line contains
+/// a summary.
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum StatusStreamItem<'a> {
+    Header {
+        yabridgectl_version: &'static str,
+        /// Either `centralized` or `inline`.
+        vst2_location: String,
+        /// The plugin directories that were searched.
+        directories: Vec<&'a Path>,
+    },
+    Plugin {
+        /// The plugin directory the plugin was found in.
+        directory: &'a Path,
+        path: &'a Path,
+        /// Either `VST2`, `VST3`, or `CLAP`.
+        format: &'static str,
+        /// The plugin's architecture, formatted using `LibArchitecture`'s `Display`
+        /// implementation.
+        architecture: String,
+        /// The bridge's installation status, formatted using `InstallState`'s `Display`
+        /// implementation.
+        status: String,
+        /// The copy or symlink of yabridge's chainloader the host will load, if it exists.
+        bridge: Option<&'a Path>,
+    },
+    Summary {
+        plugins: usize,
+        /// The number of plugins for each installation status, keyed by the same strings as used
+        /// for the plugins' `status` fields.
+        statuses: BTreeMap<String, usize>,
+    },
+}
+#[derive(Serialize)]
+struct HostMatch<'a> {
+    path: &'a Path,
+    /// Either `VST2`, `VST3`, or `CLAP`.
+    format: &'static str,
+    /// The plugin's architecture, formatted using `LibArchitecture`'s `Display` implementation.
+    architecture: String,
+    /// The `yabridge-host.exe` or `yabridge-host-32.exe` used for the plugin, or `null` if there's
+    /// no host binary matching the plugin's architecture.
+    host: Option<&'a Path>,
+}
+
+/// How the plugins are sorted within a directory or group in `yabridgectl status`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatusSorting {
+    /// Sort the plugins alphabetically by their path.
+    Name,
+    /// List the plugins that have not been set up correctly first.
+    Status,
+    /// List the most recently modified Windows plugin files first.
+    Mtime,
+}
+
+/// The sorting key for a single plugin in `yabridgectl status`. Plugins with the same sorting key
+/// keep their alphabetical order.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum StatusSortKey {
+    Name,
+    Status(u8),
+    Mtime(Reverse<Option<SystemTime>>),
+}
+
+impl StatusSorting {
+    fn key(&self, plugin_path: &Path, status: &InstallState) -> StatusSortKey {
+        match self {
+            StatusSorting::Name => StatusSortKey::Name,
+            StatusSorting::Status => StatusSortKey::Status(match status {
+                InstallState::NotInstalled => 0,
+                InstallState::Invalid(_) => 1,
+                InstallState::Symlink(_, SymlinkState::Broken | SymlinkState::UnreadableTarget) => {
+                    2
+                }
+                InstallState::Copy(_, CopyState::Corrupt) => 3,
+                InstallState::Foreign(_) => 4,
+                InstallState::Symlink(_, SymlinkState::Ok) => 5,
+                InstallState::Copy(_, CopyState::Outdated) => 6,
+                InstallState::Copy(_, CopyState::UpToDate) => 7,
+            }),
+            // Plugins whose modification time can't be read end up at the end of the list
+            StatusSorting::Mtime => StatusSortKey::Mtime(Reverse(
+                fs::metadata(plugin_path)
+                    .and_then(|metadata| metadata.modified())
+                    .ok(),
+            )),
+        }
+    }
+}
+
+/// How the plugins are grouped in `yabridgectl status`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatusGrouping {
+    /// List the plugins under the plugin directory they were found in.
+    Directory,
+    /// Group the plugins by how their bridge has been set up.
+    Method,
+    /// Group the plugins by their architecture.
+    Arch,
+    /// Group the plugins by their plugin format.
+    Kind,
+}
+
 /// Print the current configuration and the installation status for all found plugins.
-pub fn show_status(config: &Config) -> Result<()> {
+pub fn show_status(config: &Config, options: &StatusOptions, out: &mut dyn Write) -> Result<()> {
+    let mut timings = Timings::default();
+    let indexing_start_time = Instant::now();
+    let index = Index::read()?;
+    // Directories that haven't changed since the last sync don't need to be searched again. The
+    // cached plugins were found using the settings from the config file, so they can't be used with
+    // options that change the search or when the config file has been changed since then.
+    let config_changed = find_config_file()?
+        .and_then(|path| util::modification_time(&path))
+        .zip(index.last_sync)
+        .map_or(true, |(config_mtime, last_sync)| {
+            config_mtime / 1_000_000_000 >= last_sync as i64
+        });
+    let cached_plugins = if options.no_cache
+        || options.no_recurse
+        || options.include_hidden
+        || options.skip_system_dlls
+        || config_changed
+    {
+        HashMap::new()
+    } else {
+        index.cached_plugins()
+    };
+    let overrides = SearchOverrides {
+        no_recurse: options.no_recurse,
+        include_hidden: options.include_hidden,
+        traversal: options.traversal,
+        group: options.group.clone(),
+        exclude_dirs: options.exclude_dirs.clone(),
+        skip_system_dlls: options.skip_system_dlls,
+        cached_plugins,
+        ..Default::default()
+    };
+    if options.json_stream {
+        return stream_status(config, &overrides, out);
+    }
     let results = config
-        .search_directories()
+        .search_directories(&overrides)
         .context("Failure while searching for plugins")?;
+    timings.indexing = indexing_start_time.elapsed();
+    if options.check_host_match && options.json {
+        return print_host_matches(config, &results, out);
+    }
+    let index = if options.verbose { Some(index) } else { None };
 
-    println!(
+    // A one line summary of the setup so a pasted `yabridgectl status` output contains everything
+    // at a glance
+    let num_plugins: usize = results
+        .iter()
+        .map(|(_, search_results)| search_results.plugins.len())
+        .sum();
+    writeln!(
+        out,
+        "yabridgectl {}, {} VST2 location, {} plugin {}, {} {}\n",
+        YABRIDGE_VERSION,
+        config.effective_vst2_location(),
+        results.len(),
+        if results.len() == 1 {
+            "directory"
+        } else {
+            "directories"
+        },
+        num_plugins,
+        if num_plugins == 1 {
+            "plugin"
+        } else {
+            "plugins"
+        },
+    )?;
+
+    writeln!(
+        out,
         "yabridge path: {}",
         config
             .yabridge_home
             .as_ref()
             .map(|path| format!("'{}'", path.display()))
             .unwrap_or_else(|| String::from("<auto>"))
-    );
+    )?;
 
-    match config.vst2_location {
+    print_vst2_location_override(config, out)?;
+    match config.effective_vst2_location() {
         Vst2InstallationLocation::Centralized => {
-            println!("VST2 location: '{}'", yabridge_vst2_home().display());
+            writeln!(
+                out,
+                "VST2 location: '{}'",
+                yabridge_vst2_home(config).display()
+            )?;
         }
         Vst2InstallationLocation::Inline => {
-            println!("VST2 location: inline next to the Windows plugin file");
+            writeln!(out, "VST2 location: inline next to the Windows plugin file")?;
         }
     }
     // These are, but just from a UX point of view it might be nice to have as a reminder
-    println!("VST3 location: '{}'", yabridge_vst3_home().display());
-    println!("CLAP location: '{}'\n", yabridge_clap_home().display());
+    writeln!(
+        out,
+        "VST3 location: '{}'",
+        yabridge_vst3_home(config).display()
+    )?;
+    writeln!(out, "CLAP location: '{}'\n", yabridge_clap_home().display())?;
+
+    if options.verbose {
+        let excluded_dirs = config.excluded_plugin_dirs(&options.exclude_dirs);
+        if !excluded_dirs.is_empty() {
+            writeln!(out, "Excluded for this run:")?;
+            for directory in excluded_dirs {
+                writeln!(out, "- {}", directory.display())?;
+            }
+            writeln!(out)?;
+        }
+    }
 
     let files = config.files();
     match &files {
         Ok(files) => {
-            println!(
+            writeln!(
+                out,
                 "{VST2_CHAINLOADER_NAME}: '{}' ({})",
                 files.vst2_chainloader.display(),
                 files.vst2_chainloader_arch,
-            );
-            println!(
+            )?;
+            writeln!(
+                out,
                 "{VST3_CHAINLOADER_NAME}: {}",
                 files
                     .vst3_chainloader
                     .as_ref()
                     .map(|(path, arch)| format!("'{}' ({})", path.display(), arch))
                     .unwrap_or_else(|| "<not found>".red().to_string())
-            );
-            println!(
+            )?;
+            writeln!(
+                out,
                 "{CLAP_CHAINLOADER_NAME}: {}\n",
                 files
                     .clap_chainloader
                     .as_ref()
                     .map(|(path, arch)| format!("'{}' ({})", path.display(), arch))
                     .unwrap_or_else(|| "<not found>".red().to_string())
-            );
-            println!(
+            )?;
+            writeln!(
+                out,
                 "{YABRIDGE_HOST_EXE_NAME}: {}",
                 files
                     .yabridge_host_exe
@@ -148,8 +819,9 @@ pub fn show_status(config: &Config) -> Result<()> {
                     .zip(files.yabridge_host_exe_so.as_ref())
                     .map(|(path, _)| format!("'{}'", path.display()))
                     .unwrap_or_else(|| "<not found>".red().to_string())
-            );
-            println!(
+            )?;
+            writeln!(
+                out,
                 "{YABRIDGE_HOST_32_EXE_NAME}: {}",
                 files
                     .yabridge_host_32_exe
@@ -157,21 +829,42 @@ pub fn show_status(config: &Config) -> Result<()> {
                     .zip(files.yabridge_host_32_exe_so.as_ref())
                     .map(|(path, _)| format!("'{}'", path.display()))
                     .unwrap_or_else(|| "<not found>".red().to_string())
-            );
+            )?;
         }
         Err(err) => {
-            println!("Could not find yabridge's files: {}", err);
+            writeln!(out, "Could not find yabridge's files: {}", err)?;
         }
     }
 
+    // When not grouping by directory, the plugin lines are collected here first. The keys are used
+    // to order the groups, and they contain the group's header.
+    let mut groups: BTreeMap<(usize, String), Vec<(StatusSortKey, String)>> = BTreeMap::new();
+    let mut num_without_host = 0;
     for (path, search_results) in results {
         // Always print these paths with trailing slashes for consistency's sake because paths can
         // be added both with and without a trailing slash
-        println!("\n{}", path.join("").display());
+        let mut installation_status =
+            search_results.installation_status(config, files.as_ref().ok());
+        if options.group_by == StatusGrouping::Directory {
+            writeln!(out, "\n{}", path.join("").display())?;
+            if let Some(note) = files
+                .as_ref()
+                .ok()
+                .and_then(|files| different_filesystem_note(path, files))
+            {
+                writeln!(out, "{} {}", "note:".bright_white(), note)?;
+            }
+            if let Some(note) = mixed_bridges_note(&installation_status) {
+                writeln!(out, "{} {}", "note:".bright_white(), note)?;
+            }
+        }
+        timings
+            .indexing_per_directory
+            .push((path.to_owned(), search_results.search_time));
 
-        for (plugin_path, (plugin, status)) in
-            search_results.installation_status(config, files.as_ref().ok())
-        {
+        installation_status
+            .sort_by_cached_key(|(plugin_path, (_, status))| options.sort.key(plugin_path, status));
+        for (plugin_path, (plugin, status)) in installation_status {
             let plugin_type = match plugin {
                 Plugin::Vst2(Vst2Plugin { architecture, .. }) => {
                     format!("{}, {}", "VST2".cyan(), architecture)
@@ -190,27 +883,234 @@ pub fn show_status(config: &Config) -> Result<()> {
             };
 
             // This made more sense when we supported symlinking `libyabridge-*.so`, but we should
-            // display _something_ to indicate that the plugin is set up correctly
-            let status_str = match status {
-                Some(NativeFile::Regular(_)) => "synced".green(),
-                // This should not occur, but we'll display it just in case it does happen
-                Some(NativeFile::Symlink(_)) => "symlink".yellow(),
-                Some(NativeFile::Directory(_)) => "invalid".red(),
-                None => "not yet synced".into(),
+            // display _something_ to indicate that the plugin is set up correctly. Symlinks should
+            // not occur, but we'll display them just in case they do.
+            let status_str = match &status {
+                InstallState::Copy(_, CopyState::UpToDate) => status.to_string().green(),
+                InstallState::Copy(_, CopyState::Outdated)
+                | InstallState::Symlink(_, SymlinkState::Ok)
+                | InstallState::Foreign(_) => status.to_string().yellow(),
+                InstallState::Copy(_, CopyState::Corrupt)
+                | InstallState::Symlink(_, SymlinkState::Broken | SymlinkState::UnreadableTarget)
+                | InstallState::Invalid(_) => status.to_string().red(),
+                InstallState::NotInstalled => status.to_string().normal(),
             };
 
-            println!(
-                "  {} :: {}, {}",
-                plugin_path
-                    .strip_prefix(path)
-                    .unwrap_or(&plugin_path)
-                    .display(),
+            // With `--check-host-match` we'll point out the plugins that can't be loaded because
+            // there's no `yabridge-host` binary for their architecture
+            let host_str = match &files {
+                Ok(files)
+                    if options.check_host_match
+                        && host_binary(files, plugin.architecture()).is_none() =>
+                {
+                    num_without_host += 1;
+                    format!(", {}", "no matching host".red())
+                }
+                _ => String::new(),
+            };
+
+            // With `--verbose` we'll also show which version of yabridge last set up the plugin.
+            // Plugins set up by older versions of yabridgectl won't have this information.
+            let installed_by_str = match (&index, &status) {
+                (Some(index), InstallState::Copy(bridge_path, _)) => {
+                    match index.bridge(bridge_path) {
+                        Some(info) if info.adopted => String::from(", adopted from a manual setup"),
+                        Some(info) => format!(", installed by yabridge {}", info.installed_by),
+                        None => String::from(", installed by an unknown version of yabridge"),
+                    }
+                }
+                _ => String::new(),
+            };
+            // Notes added with `yabridgectl note` are shown on their own line below the plugin
+            let note_str = match &index {
+                Some(index) => index
+                    .note(&plugin_path)
+                    .map(|note| format!("\n    {} {}", "note:".bright_white(), note))
+                    .unwrap_or_default(),
+                None => String::new(),
+            };
+            // Also make it clear which plugins come from the cache and which ones were searched for
+            let cached_str = if options.verbose && search_results.cached {
+                ", cached"
+            } else {
+                ""
+            };
+
+            // The full path is shown when the plugins are not listed under their directory
+            let displayed_path = match options.group_by {
+                StatusGrouping::Directory => plugin_path.strip_prefix(path).unwrap_or(&plugin_path),
+                _ => &plugin_path,
+            };
+            let line = format!(
+                "  {} :: {}, {}{}{}{}{}",
+                displayed_path.display(),
                 plugin_type,
-                status_str
+                status_str,
+                host_str,
+                installed_by_str,
+                cached_str,
+                note_str
             );
+
+            let group = match options.group_by {
+                StatusGrouping::Directory => {
+                    writeln!(out, "{}", line)?;
+                    continue;
+                }
+                StatusGrouping::Method => match status {
+                    InstallState::Copy(..) => (0, String::from("Copies")),
+                    InstallState::Symlink(..) => (1, String::from("Symlinks")),
+                    InstallState::Foreign(_) => (2, String::from("Other yabridge installations")),
+                    InstallState::Invalid(_) => (3, String::from("Invalid")),
+                    InstallState::NotInstalled => (4, String::from("Not yet synced")),
+                },
+                StatusGrouping::Arch => match plugin.architecture() {
+                    LibArchitecture::Lib32 => (0, String::from("32-bit")),
+                    LibArchitecture::Lib64 => (1, String::from("64-bit")),
+                },
+                StatusGrouping::Kind => match plugin {
+                    Plugin::Vst2(_) => (0, String::from("VST2")),
+                    Plugin::Vst3(_) => (1, String::from("VST3")),
+                    Plugin::Clap(_) => (2, String::from("CLAP")),
+                },
+            };
+            groups
+                .entry(group)
+                .or_default()
+                .push((options.sort.key(&plugin_path, &status), line));
+        }
+    }
+
+    for ((_, header), mut lines) in groups {
+        // This is a stable sort, so plugins with the same key are still ordered by their directory
+        lines.sort_by(|(a, _), (b, _)| a.cmp(b));
+        writeln!(out, "\n{} ({})", header, lines.len())?;
+        for (_, line) in lines {
+            writeln!(out, "{}", line)?;
+        }
+    }
+
+    if options.check_host_match && files.is_ok() {
+        writeln!(out)?;
+        match num_without_host {
+            0 => writeln!(out, "All plugins have a matching host binary")?,
+            n => writeln!(
+                out,
+                "{} {} won't load because there's no host binary for their architecture, see \
+                 'yabridgectl doctor'",
+                n,
+                if n == 1 { "plugin" } else { "plugins" }
+            )?,
+        }
+    }
+
+    if options.time {
+        writeln!(out)?;
+        timings.print(options.verbose, config.slow_directory_threshold(), out)?;
+    }
+
+    Ok(())
+}
+
+/// Print the installation status for every plugin as newline-delimited JSON, see
+/// [`StatusStreamItem`]. This is used for `yabridgectl status --json-stream`. The header is written
+/// before anything is searched, and the plugin directories are then searched and written one at a
+/// time so frontends can show the results before every directory has been searched.
+fn stream_status(config: &Config, overrides: &SearchOverrides, out: &mut dyn Write) -> Result<()> {
+    let write_item = |out: &mut dyn Write, item: &StatusStreamItem| -> Result<()> {
+        writeln!(
+            out,
+            "{}",
+            serde_jsonrc::to_string(item).context("Could not format JSON")?
+        )?;
+
+        Ok(())
+    };
+
+    // These are in the same order `Config::search_directories()` would return them in
+    let mut plugin_dirs = config.searched_plugin_dirs(overrides)?;
+    plugin_dirs.sort_by(|a, b| util::compare_paths(a, b));
+    write_item(
+        out,
+        &StatusStreamItem::Header {
+            yabridgectl_version: YABRIDGE_VERSION,
+            vst2_location: config.effective_vst2_location().to_string(),
+            directories: plugin_dirs.iter().map(|path| path.as_path()).collect(),
+        },
+    )?;
+    out.flush()?;
+
+    // Missing files are reported as part of the status, just like in the regular output
+    let files = config.files().ok();
+    let mut num_plugins = 0;
+    let mut statuses: BTreeMap<String, usize> = BTreeMap::new();
+    for path in plugin_dirs {
+        let (directory, search_results) = config
+            .search_directory(path, overrides)
+            .context("Failure while searching for plugins")?;
+        for (_, (plugin, status)) in search_results.installation_status(config, files.as_ref()) {
+            let status_str = status.to_string();
+            write_item(
+                out,
+                &StatusStreamItem::Plugin {
+                    directory,
+                    path: plugin.path(),
+                    format: match plugin {
+                        Plugin::Vst2(_) => "VST2",
+                        Plugin::Vst3(_) => "VST3",
+                        Plugin::Clap(_) => "CLAP",
+                    },
+                    architecture: plugin.architecture().to_string(),
+                    status: status_str.clone(),
+                    bridge: status.path(),
+                },
+            )?;
+
+            num_plugins += 1;
+            *statuses.entry(status_str).or_default() += 1;
         }
+
+        out.flush()?;
     }
 
+    write_item(
+        out,
+        &StatusStreamItem::Summary {
+            plugins: num_plugins,
+            statuses,
+        },
+    )
+}
+
+/// Print every plugin along with the host binary that would be used to load it as a JSON array.
+/// This is used for `yabridgectl status --check-host-match --json`.
+fn print_host_matches(
+    config: &Config,
+    results: &[(&Path, SearchResults)],
+    out: &mut dyn Write,
+) -> Result<()> {
+    let files = config.files()?;
+    let host_matches: Vec<HostMatch> = results
+        .iter()
+        .flat_map(|(_, search_results)| &search_results.plugins)
+        .map(|plugin| HostMatch {
+            path: plugin.path(),
+            format: match plugin {
+                Plugin::Vst2(_) => "VST2",
+                Plugin::Vst3(_) => "VST3",
+                Plugin::Clap(_) => "CLAP",
+            },
+            architecture: plugin.architecture().to_string(),
+            host: host_binary(&files, plugin.architecture()),
+        })
+        .collect();
+
+    writeln!(
+        out,
+        "{}",
+        serde_jsonrc::to_string(&host_matches).context("Could not format JSON")?
+    )?;
+
     Ok(())
 }
 
@@ -220,6 +1120,36 @@ pub struct SetOptions<'a> {
     pub path_auto: bool,
     pub vst2_location: Option<&'a str>,
     pub no_verify: Option<bool>,
+    pub include_hidden: Option<bool>,
+    pub relative_symlinks: Option<bool>,
+    pub relative_to: Option<&'a str>,
+    pub vst2_target: Option<PathBuf>,
+    pub vst2_target_default: bool,
+    pub vst3_target: Option<PathBuf>,
+    pub vst3_target_default: bool,
+    pub subdirectory_template: Option<&'a str>,
+    pub subdirectory_template_default: bool,
+    pub default_excludes: Vec<String>,
+    pub clear_default_excludes: bool,
+    pub vst2_extensions: Vec<String>,
+    pub clear_vst2_extensions: bool,
+    pub min_plugin_size: Option<u64>,
+    pub max_plugin_size: Option<u64>,
+    pub max_plugins: Option<usize>,
+    pub slow_directory_threshold: Option<u64>,
+    pub skip_system_dlls: Option<bool>,
+    pub system_dlls: Vec<String>,
+    pub clear_system_dlls: bool,
+    pub use_ignore_files: Option<bool>,
+    pub shortcuts: Option<&'a str>,
+    pub daws: Vec<&'a str>,
+    pub clear_daws: bool,
+    pub strict_paths: Option<bool>,
+    pub allowed_bases: Vec<PathBuf>,
+    pub clear_allowed_bases: bool,
+    pub bridge_mode: Option<u32>,
+    pub overwrite_unrelated_files: Option<bool>,
+    pub hashed_names: Option<bool>,
 }
 
 /// Change configuration settings. The actual options are defined in the clap [app](clap::App).
@@ -243,65 +1173,2845 @@ pub fn set_settings(config: &mut Config, options: &SetOptions) -> Result<()> {
         config.no_verify = no_verify;
     }
 
-    config.write()
-}
+    if let Some(include_hidden) = options.include_hidden {
+        config.include_hidden = include_hidden;
+    }
 
-/// Options passed to `yabridgectl sync`, see `main()` for the definitions of these options.
-pub struct SyncOptions {
-    pub force: bool,
-    pub no_verify: bool,
-    pub prune: bool,
-    pub verbose: bool,
-}
+    if let Some(relative_symlinks) = options.relative_symlinks {
+        config.relative_symlinks = relative_symlinks;
+    }
 
-/// Set up yabridge for all Windows VST2 plugins in the plugin directories. Will also remove orphan
-/// `.so` files if the prune option is set.
-pub fn do_sync(config: &mut Config, options: &SyncOptions) -> Result<()> {
-    let files: YabridgeFiles = config.files()?;
-    let vst2_chainloader_hash = util::hash_file(&files.vst2_chainloader)?;
-    let vst3_chainloader_hash = files
-        .vst3_chainloader
-        .as_ref()
-        .map(|(path, _)| util::hash_file(path))
-        .transpose()?;
-    let clap_chainloader_hash = files
-        .clap_chainloader
-        .as_ref()
-        .map(|(path, _)| util::hash_file(path))
-        .transpose()?;
+    match options.relative_to {
+        Some("config") => config.relative_to = Some(RelativePathBase::Config),
+        Some("home") => config.relative_to = Some(RelativePathBase::Home),
+        Some("none") => config.relative_to = None,
+        Some(s) => unreachable!("Unexpected path base '{}'", s),
+        None => (),
+    }
 
-    // Better not add another plugin format!
-    match (&files.vst3_chainloader, &files.clap_chainloader) {
-        (Some((vst3_chainloader_path, _)), Some((clap_chainloader_path, _))) => {
-            println!("Setting up VST2, VST3, and CLAP plugins using:");
-            println!("- {}", files.vst2_chainloader.display());
-            println!("- {}", vst3_chainloader_path.display());
-            println!("- {}\n", clap_chainloader_path.display());
-        }
-        (Some((vst3_chainloader_path, _)), None) => {
-            println!("Setting up VST2 and VST3 plugins using:");
-            println!("- {}", files.vst2_chainloader.display());
-            println!("- {}\n", vst3_chainloader_path.display());
-        }
-        (None, Some((clap_chainloader_path, _))) => {
-            println!("Setting up VST2 and CLAP plugins using:");
-            println!("- {}", files.vst2_chainloader.display());
-            println!("- {}\n", clap_chainloader_path.display());
-        }
-        (None, None) => {
-            println!("Setting up VST2 plugins using:");
-            println!("- {}\n", files.vst2_chainloader.display());
-        }
+    // A custom VST2 target directory only makes sense with the centralized installation location
+    if let Some(vst2_target) = &options.vst2_target {
+        config.vst2_target = Some(vst2_target.clone());
+        config.vst2_location = Vst2InstallationLocation::Centralized;
     }
 
-    let results = config
-        .search_directories()
-        .context("Failure while searching for plugins")?;
+    if options.vst2_target_default {
+        config.vst2_target = None;
+    }
+
+    if let Some(vst3_target) = &options.vst3_target {
+        config.vst3_target = Some(vst3_target.clone());
+    }
+
+    if options.vst3_target_default {
+        config.vst3_target = None;
+    }
+
+    if let Some(subdirectory_template) = options.subdirectory_template {
+        config.subdirectory_template = Some(subdirectory_template.to_owned());
+    }
+
+    if options.subdirectory_template_default {
+        config.subdirectory_template = None;
+    }
+
+    match options.shortcuts {
+        Some("ignore") => config.shortcuts = ShortcutHandling::Ignore,
+        Some("shortcut") => config.shortcuts = ShortcutHandling::Shortcut,
+        Some("target") => config.shortcuts = ShortcutHandling::Target,
+        Some(s) => unreachable!("Unexpected shortcut handling '{}'", s),
+        None => (),
+    }
+
+    if let Some(use_ignore_files) = options.use_ignore_files {
+        config.use_ignore_files = use_ignore_files;
+    }
+
+    // Clearing the list first makes it possible to replace the patterns in a single invocation
+    if options.clear_default_excludes {
+        config.default_excludes.clear();
+    }
+
+    config
+        .default_excludes
+        .extend(options.default_excludes.iter().cloned());
+
+    // The extensions are matched case-insensitively, and `.vst` and `vst` mean the same thing
+    if options.clear_vst2_extensions {
+        config.vst2_extensions.clear();
+    }
+
+    config.vst2_extensions.extend(
+        options
+            .vst2_extensions
+            .iter()
+            .map(|extension| extension.trim_start_matches('.').to_ascii_lowercase()),
+    );
+
+    if let Some(min_plugin_size) = options.min_plugin_size {
+        config.min_plugin_size = Some(min_plugin_size);
+    }
+
+    if let Some(max_plugin_size) = options.max_plugin_size {
+        config.max_plugin_size = Some(max_plugin_size);
+    }
+
+    if let Some(max_plugins) = options.max_plugins {
+        config.max_plugins = Some(max_plugins);
+    }
+
+    if let Some(slow_directory_threshold) = options.slow_directory_threshold {
+        config.slow_directory_threshold = Some(slow_directory_threshold);
+    }
+
+    if let Some(skip_system_dlls) = options.skip_system_dlls {
+        config.skip_system_dlls = skip_system_dlls;
+    }
+
+    if options.clear_system_dlls {
+        config.system_dlls.clear();
+    }
+
+    config
+        .system_dlls
+        .extend(options.system_dlls.iter().cloned());
+
+    if options.clear_daws {
+        config.daw_integrations.clear();
+    }
+
+    for name in &options.daws {
+        match daw::parse_daw_integration(name) {
+            Some(integration) => config.daw_integrations.insert(integration),
+            None => unreachable!("Unexpected DAW '{}'", name),
+        };
+    }
+
+    if let Some(strict_paths) = options.strict_paths {
+        config.strict_paths = strict_paths;
+    }
+
+    if options.clear_allowed_bases {
+        config.allowed_bases.clear();
+    }
+
+    config
+        .allowed_bases
+        .extend(options.allowed_bases.iter().cloned());
+
+    if let Some(bridge_mode) = options.bridge_mode {
+        config.bridge_mode = Some(bridge_mode);
+    }
+
+    if let Some(overwrite_unrelated_files) = options.overwrite_unrelated_files {
+        config.overwrite_unrelated_files = overwrite_unrelated_files;
+    }
+
+    if let Some(hashed_names) = options.hashed_names {
+        config.hashed_names = hashed_names;
+    }
+
+    config.write()
+}
+
+/// Reset a setting to its default value, see `Config::unset()`.
+pub fn unset_setting(config: &mut Config, setting: &str) -> Result<()> {
+    config.unset(setting)?;
+    config.write()?;
+
+    println!("Reset '{}' to its default value", setting);
+
+    Ok(())
+}
+
+/// Options passed to `yabridgectl config show`, see `main()` for the definitions of these options.
+pub struct ShowConfigOptions {
+    pub json: bool,
+}
+
+/// The effective configuration printed by `yabridgectl config show`. This is also the format used
+/// with `--json`.
+#[derive(Serialize)]
+struct EffectiveConfig<'a> {
+    config_file: Option<PathBuf>,
+    version: i64,
+    /// The `yabridge_home` setting from the config file. `None` means that yabridge's files are
+    /// searched for in the default locations.
+    yabridge_home: Option<&'a Path>,
+    /// The directory yabridge's files were actually found in, if they could be found.
+    yabridge_files_dir: Option<PathBuf>,
+    vst2_location: Vst2InstallationLocation,
+    /// Whether `vst2_location` comes from `--vst2-location` or `$YABRIDGECTL_VST2_LOCATION`
+    /// instead of from the config file.
+    vst2_location_overridden: bool,
+    vst2_home: PathBuf,
+    vst3_home: PathBuf,
+    clap_home: PathBuf,
+    /// `None` means that the plugins' original subdirectories are recreated.
+    subdirectory_template: Option<&'a str>,
+    no_verify: bool,
+    include_hidden: bool,
+    relative_symlinks: bool,
+    relative_to: Option<RelativePathBase>,
+    shortcuts: ShortcutHandling,
+    default_excludes: &'a BTreeSet<String>,
+    /// File extensions other than `.dll` used for VST2 plugins.
+    vst2_extensions: &'a BTreeSet<String>,
+    /// The size limits for plugin files in bytes, `None` means that there's no limit.
+    min_plugin_size: Option<u64>,
+    max_plugin_size: Option<u64>,
+    /// `None` means that there's no limit.
+    max_plugins: Option<usize>,
+    /// In seconds, with the default value filled in.
+    slow_directory_threshold: u64,
+    skip_system_dlls: bool,
+    /// The additions to and removals from the built-in system DLL patterns.
+    system_dlls: &'a BTreeSet<String>,
+    use_ignore_files: bool,
+    daw_integrations: &'a BTreeSet<DawIntegration>,
+    /// The permissions for copied bridges as an octal string, `None` means that the permissions
+    /// are determined by the umask.
+    bridge_mode: Option<String>,
+    overwrite_unrelated_files: bool,
+    hashed_names: bool,
+    strict_paths: bool,
+    allowed_bases: &'a BTreeSet<PathBuf>,
+    blacklist: &'a BTreeSet<PathBuf>,
+    plugin_dirs: Vec<EffectivePluginDir<'a>>,
+}
+
+/// A plugin directory along with its settings, as part of [`EffectiveConfig`].
+#[derive(Serialize)]
+struct EffectivePluginDir<'a> {
+    path: &'a Path,
+    no_recurse: bool,
+    read_only: bool,
+    groups: Vec<&'a str>,
+}
+
+impl<'a> EffectivePluginDir<'a> {
+    fn new(config: &'a Config, path: &'a Path) -> Self {
+        let settings = config.directory_settings.get(path);
+        EffectivePluginDir {
+            path,
+            no_recurse: settings.map_or(false, |settings| settings.no_recurse),
+            read_only: settings.map_or(false, |settings| settings.read_only),
+            groups: settings
+                .map(|settings| settings.groups.iter().map(|group| group.as_str()).collect())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// Print the configuration yabridgectl is going to use after applying the command line and
+/// environment variable overrides, with the default values filled in.
+pub fn show_config(
+    config: &Config,
+    options: &ShowConfigOptions,
+    out: &mut dyn Write,
+) -> Result<()> {
+    let effective_config = EffectiveConfig {
+        config_file: find_config_file()?,
+        version: config.version,
+        yabridge_home: config.yabridge_home.as_deref(),
+        yabridge_files_dir: config.files().ok().and_then(|files| {
+            files
+                .vst2_chainloader
+                .parent()
+                .map(|directory| directory.to_owned())
+        }),
+        vst2_location: config.effective_vst2_location(),
+        vst2_location_overridden: config.vst2_location_override.is_some(),
+        vst2_home: yabridge_vst2_home(config),
+        vst3_home: yabridge_vst3_home(config),
+        clap_home: yabridge_clap_home(),
+        subdirectory_template: config.subdirectory_template.as_deref(),
+        no_verify: config.no_verify,
+        include_hidden: config.include_hidden,
+        relative_symlinks: config.relative_symlinks,
+        relative_to: config.relative_to,
+        shortcuts: config.shortcuts,
+        default_excludes: &config.default_excludes,
+        vst2_extensions: &config.vst2_extensions,
+        min_plugin_size: config.min_plugin_size,
+        max_plugin_size: config.max_plugin_size,
+        max_plugins: config.max_plugins,
+        slow_directory_threshold: config.slow_directory_threshold().as_secs(),
+        skip_system_dlls: config.skip_system_dlls,
+        system_dlls: &config.system_dlls,
+        use_ignore_files: config.use_ignore_files,
+        daw_integrations: &config.daw_integrations,
+        bridge_mode: config.bridge_mode.map(|mode| format!("{mode:04o}")),
+        overwrite_unrelated_files: config.overwrite_unrelated_files,
+        hashed_names: config.hashed_names,
+        strict_paths: config.strict_paths,
+        allowed_bases: &config.allowed_bases,
+        blacklist: &config.blacklist,
+        plugin_dirs: config
+            .sorted_plugin_dirs()
+            .into_iter()
+            .map(|path| EffectivePluginDir::new(config, path))
+            .collect(),
+    };
+
+    if options.json {
+        writeln!(
+            out,
+            "{}",
+            serde_jsonrc::to_string(&effective_config).context("Could not format JSON")?
+        )?;
+
+        return Ok(());
+    }
+
+    let display_path = |path: &Option<PathBuf>| match path {
+        Some(path) => format!("'{}'", path.display()),
+        None => "<not found>".red().to_string(),
+    };
+
+    writeln!(
+        out,
+        "config file: {}",
+        display_path(&effective_config.config_file)
+    )?;
+    writeln!(out, "config version: {}", effective_config.version)?;
+    match effective_config.yabridge_home {
+        Some(path) => writeln!(out, "yabridge path: '{}'", path.display())?,
+        None => writeln!(out, "yabridge path: <auto>")?,
+    }
+    writeln!(
+        out,
+        "yabridge files: {}",
+        display_path(&effective_config.yabridge_files_dir)
+    )?;
+    writeln!(
+        out,
+        "VST2 location: {}{}",
+        effective_config.vst2_location,
+        if effective_config.vst2_location_overridden {
+            " (overridden for this run)"
+        } else {
+            ""
+        }
+    )?;
+    writeln!(
+        out,
+        "VST2 directory: '{}'",
+        effective_config.vst2_home.display()
+    )?;
+    writeln!(
+        out,
+        "VST3 directory: '{}'",
+        effective_config.vst3_home.display()
+    )?;
+    writeln!(
+        out,
+        "CLAP directory: '{}'",
+        effective_config.clap_home.display()
+    )?;
+    match effective_config.subdirectory_template {
+        Some(template) => writeln!(out, "subdirectory template: '{}'", template)?,
+        None => writeln!(out, "subdirectory template: <default>")?,
+    }
+    writeln!(out, "no verify: {}", effective_config.no_verify)?;
+    writeln!(out, "include hidden: {}", effective_config.include_hidden)?;
+    writeln!(
+        out,
+        "relative symlinks: {}",
+        effective_config.relative_symlinks
+    )?;
+    match effective_config.relative_to {
+        Some(relative_to) => writeln!(out, "paths relative to: {}", relative_to)?,
+        None => writeln!(out, "paths relative to: none")?,
+    }
+    writeln!(out, "shortcuts: {}", effective_config.shortcuts)?;
+    writeln!(
+        out,
+        "additional VST2 extensions: {}",
+        display_vst2_extensions(effective_config.vst2_extensions)
+    )?;
+    writeln!(
+        out,
+        "minimum plugin size: {}",
+        display_size_limit(effective_config.min_plugin_size)
+    )?;
+    writeln!(
+        out,
+        "maximum plugin size: {}",
+        display_size_limit(effective_config.max_plugin_size)
+    )?;
+    writeln!(
+        out,
+        "maximum plugins: {}",
+        display_plugin_limit(effective_config.max_plugins)
+    )?;
+    writeln!(
+        out,
+        "slow directory threshold: {}s",
+        effective_config.slow_directory_threshold
+    )?;
+    writeln!(
+        out,
+        "skip system DLLs: {}",
+        effective_config.skip_system_dlls
+    )?;
+    writeln!(
+        out,
+        "system DLLs: {}",
+        display_system_dlls(effective_config.system_dlls)
+    )?;
+    writeln!(
+        out,
+        "use ignore files: {}",
+        effective_config.use_ignore_files
+    )?;
+    writeln!(
+        out,
+        "daw integrations: {}",
+        display_daw_integrations(effective_config.daw_integrations)
+    )?;
+    writeln!(
+        out,
+        "bridge mode: {}",
+        effective_config
+            .bridge_mode
+            .as_deref()
+            .unwrap_or("<default>")
+    )?;
+    writeln!(
+        out,
+        "overwrite unrelated files: {}",
+        effective_config.overwrite_unrelated_files
+    )?;
+    writeln!(out, "hashed names: {}", effective_config.hashed_names)?;
+    writeln!(out, "strict paths: {}", effective_config.strict_paths)?;
+
+    writeln!(out, "\nplugin directories:")?;
+    for plugin_dir in &effective_config.plugin_dirs {
+        let mut settings = Vec::new();
+        if plugin_dir.no_recurse {
+            settings.push("no recurse");
+        }
+        if plugin_dir.read_only {
+            settings.push("read-only");
+        }
+        let groups = format!("groups: {}", plugin_dir.groups.join(" "));
+        if !plugin_dir.groups.is_empty() {
+            settings.push(&groups);
+        }
+
+        if settings.is_empty() {
+            writeln!(out, "- {}", plugin_dir.path.display())?;
+        } else {
+            writeln!(
+                out,
+                "- {} ({})",
+                plugin_dir.path.display(),
+                settings.join(", ")
+            )?;
+        }
+    }
+
+    writeln!(out, "\ndefault excludes:")?;
+    for pattern in effective_config.default_excludes {
+        writeln!(out, "- {}", pattern)?;
+    }
+
+    writeln!(out, "\nallowed bases:")?;
+    for path in effective_config.allowed_bases {
+        writeln!(out, "- {}", path.display())?;
+    }
+
+    writeln!(out, "\nblacklist:")?;
+    for path in effective_config.blacklist {
+        writeln!(out, "- {}", path.display())?;
+    }
+
+    Ok(())
+}
+
+/// Format a plugin size limit for `yabridgectl config show` and `yabridgectl config diff`.
+fn display_size_limit(size: Option<u64>) -> String {
+    match size {
+        Some(size) => util::format_size(size),
+        None => String::from("<none>"),
+    }
+}
+
+/// Format the `max_plugins` limit for `yabridgectl config show` and `yabridgectl config diff`.
+fn display_plugin_limit(limit: Option<usize>) -> String {
+    match limit {
+        Some(limit) => limit.to_string(),
+        None => String::from("<none>"),
+    }
+}
+
+/// Format the additional VST2 extensions for `yabridgectl config show` and `yabridgectl config
+/// diff`.
+fn display_vst2_extensions(extensions: &BTreeSet<String>) -> String {
+    if extensions.is_empty() {
+        String::from("<none>")
+    } else {
+        extensions
+            .iter()
+            .map(|extension| format!(".{extension}"))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// Format the changes to the built-in system DLL patterns for `yabridgectl config show` and
+/// `yabridgectl config diff`.
+fn display_system_dlls(patterns: &BTreeSet<String>) -> String {
+    if patterns.is_empty() {
+        String::from("<default>")
+    } else {
+        patterns.iter().cloned().collect::<Vec<_>>().join(", ")
+    }
+}
+
+/// Format the `bridge_mode` setting for `yabridgectl config diff`.
+fn display_bridge_mode(mode: Option<u32>) -> String {
+    match mode {
+        Some(mode) => format!("{mode:04o}"),
+        None => String::from("<default>"),
+    }
+}
+
+/// Format the `allowed_bases` setting for `yabridgectl config diff`.
+fn display_allowed_bases(paths: &BTreeSet<PathBuf>) -> String {
+    if paths.is_empty() {
+        String::from("<none>")
+    } else {
+        paths
+            .iter()
+            .map(|path| format!("'{}'", path.display()))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// Format the enabled DAW integrations for `yabridgectl config show` and `yabridgectl config diff`.
+fn display_daw_integrations(integrations: &BTreeSet<DawIntegration>) -> String {
+    if integrations.is_empty() {
+        String::from("<none>")
+    } else {
+        integrations
+            .iter()
+            .map(|integration| integration.to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// Options passed to `yabridgectl config diff`, see `main()` for the definitions of these options.
+pub struct DiffConfigOptions<'a> {
+    pub other_config: &'a Path,
+    pub json: bool,
+}
+
+/// The differences between two config files printed by `yabridgectl config diff`. This is also the
+/// format used with `--json`. Everything is described from the point of view of the current
+/// config, so `only_current` contains the entries that are missing in the other config file.
+#[derive(Serialize)]
+struct ConfigDiff<'a> {
+    settings: Vec<SettingDiff>,
+    plugin_dirs: SetDiff<'a, PathBuf>,
+    directory_settings: Vec<DirectorySettingsDiff<'a>>,
+    blacklist: SetDiff<'a, PathBuf>,
+    default_excludes: SetDiff<'a, String>,
+}
+
+/// A single setting that has a different value in the other config file.
+#[derive(Serialize)]
+struct SettingDiff {
+    name: &'static str,
+    current: String,
+    other: String,
+}
+
+/// The entries that only occur in one of the two configs.
+#[derive(Serialize)]
+struct SetDiff<'a, T> {
+    only_current: Vec<&'a T>,
+    only_other: Vec<&'a T>,
+}
+
+impl<'a, T: Ord> SetDiff<'a, T> {
+    fn new(current: &'a BTreeSet<T>, other: &'a BTreeSet<T>) -> Self {
+        SetDiff {
+            only_current: current.difference(other).collect(),
+            only_other: other.difference(current).collect(),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.only_current.is_empty() && self.only_other.is_empty()
+    }
+}
+
+/// A plugin directory that's present in both configs, but with different settings.
+#[derive(Serialize)]
+struct DirectorySettingsDiff<'a> {
+    path: &'a Path,
+    current: DirectorySettings,
+    other: DirectorySettings,
+}
+
+/// Compare the current effective configuration to another config file, for instance one copied
+/// from another machine. This doesn't modify anything.
+pub fn diff_config(config: &Config, options: &DiffConfigOptions) -> Result<()> {
+    let other_config = Config::read_file(options.other_config)?;
+
+    let display_path = |path: &Option<PathBuf>| match path {
+        Some(path) => path.display().to_string(),
+        None => String::from("<default>"),
+    };
+    let display_template = |template: &Option<String>| match template {
+        Some(template) => format!("'{template}'"),
+        None => String::from("<default>"),
+    };
+    let display_relative_to = |relative_to: &Option<RelativePathBase>| match relative_to {
+        Some(relative_to) => relative_to.to_string(),
+        None => String::from("none"),
+    };
+    let settings: Vec<SettingDiff> = [
+        (
+            "yabridge_home",
+            display_path(&config.yabridge_home),
+            display_path(&other_config.yabridge_home),
+        ),
+        (
+            "vst2_location",
+            config.effective_vst2_location().to_string(),
+            other_config.vst2_location.to_string(),
+        ),
+        (
+            "vst2_target",
+            display_path(&config.vst2_target),
+            display_path(&other_config.vst2_target),
+        ),
+        (
+            "vst3_target",
+            display_path(&config.vst3_target),
+            display_path(&other_config.vst3_target),
+        ),
+        (
+            "subdirectory_template",
+            display_template(&config.subdirectory_template),
+            display_template(&other_config.subdirectory_template),
+        ),
+        (
+            "no_verify",
+            config.no_verify.to_string(),
+            other_config.no_verify.to_string(),
+        ),
+        (
+            "include_hidden",
+            config.include_hidden.to_string(),
+            other_config.include_hidden.to_string(),
+        ),
+        (
+            "relative_symlinks",
+            config.relative_symlinks.to_string(),
+            other_config.relative_symlinks.to_string(),
+        ),
+        (
+            "relative_to",
+            display_relative_to(&config.relative_to),
+            display_relative_to(&other_config.relative_to),
+        ),
+        (
+            "shortcuts",
+            config.shortcuts.to_string(),
+            other_config.shortcuts.to_string(),
+        ),
+        (
+            "vst2_extensions",
+            display_vst2_extensions(&config.vst2_extensions),
+            display_vst2_extensions(&other_config.vst2_extensions),
+        ),
+        (
+            "min_plugin_size",
+            display_size_limit(config.min_plugin_size),
+            display_size_limit(other_config.min_plugin_size),
+        ),
+        (
+            "max_plugin_size",
+            display_size_limit(config.max_plugin_size),
+            display_size_limit(other_config.max_plugin_size),
+        ),
+        (
+            "max_plugins",
+            display_plugin_limit(config.max_plugins),
+            display_plugin_limit(other_config.max_plugins),
+        ),
+        (
+            "slow_directory_threshold",
+            format!("{}s", config.slow_directory_threshold().as_secs()),
+            format!("{}s", other_config.slow_directory_threshold().as_secs()),
+        ),
+        (
+            "use_ignore_files",
+            config.use_ignore_files.to_string(),
+            other_config.use_ignore_files.to_string(),
+        ),
+        (
+            "skip_system_dlls",
+            config.skip_system_dlls.to_string(),
+            other_config.skip_system_dlls.to_string(),
+        ),
+        (
+            "system_dlls",
+            display_system_dlls(&config.system_dlls),
+            display_system_dlls(&other_config.system_dlls),
+        ),
+        (
+            "daw_integrations",
+            display_daw_integrations(&config.daw_integrations),
+            display_daw_integrations(&other_config.daw_integrations),
+        ),
+        (
+            "bridge_mode",
+            display_bridge_mode(config.bridge_mode),
+            display_bridge_mode(other_config.bridge_mode),
+        ),
+        (
+            "overwrite_unrelated_files",
+            config.overwrite_unrelated_files.to_string(),
+            other_config.overwrite_unrelated_files.to_string(),
+        ),
+        (
+            "hashed_names",
+            config.hashed_names.to_string(),
+            other_config.hashed_names.to_string(),
+        ),
+        (
+            "strict_paths",
+            config.strict_paths.to_string(),
+            other_config.strict_paths.to_string(),
+        ),
+        (
+            "allowed_bases",
+            display_allowed_bases(&config.allowed_bases),
+            display_allowed_bases(&other_config.allowed_bases),
+        ),
+    ]
+    .into_iter()
+    .filter(|(_, current, other)| current != other)
+    .map(|(name, current, other)| SettingDiff {
+        name,
+        current,
+        other,
+    })
+    .collect();
+
+    let directory_settings: Vec<DirectorySettingsDiff> = config
+        .plugin_dirs
+        .intersection(&other_config.plugin_dirs)
+        .filter_map(|path| {
+            let current = config
+                .directory_settings
+                .get(path)
+                .cloned()
+                .unwrap_or_default();
+            let other = other_config
+                .directory_settings
+                .get(path)
+                .cloned()
+                .unwrap_or_default();
+
+            if current != other {
+                Some(DirectorySettingsDiff {
+                    path,
+                    current,
+                    other,
+                })
+            } else {
+                None
+            }
+        })
+        .collect();
+
+    let diff = ConfigDiff {
+        settings,
+        plugin_dirs: SetDiff::new(&config.plugin_dirs, &other_config.plugin_dirs),
+        directory_settings,
+        blacklist: SetDiff::new(&config.blacklist, &other_config.blacklist),
+        default_excludes: SetDiff::new(&config.default_excludes, &other_config.default_excludes),
+    };
+
+    if options.json {
+        println!(
+            "{}",
+            serde_jsonrc::to_string(&diff).context("Could not format JSON")?
+        );
+
+        return Ok(());
+    }
+
+    if diff.settings.is_empty()
+        && diff.plugin_dirs.is_empty()
+        && diff.directory_settings.is_empty()
+        && diff.blacklist.is_empty()
+        && diff.default_excludes.is_empty()
+    {
+        println!(
+            "The current config and '{}' are identical",
+            options.other_config.display()
+        );
+
+        return Ok(());
+    }
+
+    // Everything is printed like a diff from the current config to the other config
+    println!(
+        "Differences between the current config and '{}':",
+        options.other_config.display()
+    );
+
+    if !diff.settings.is_empty() {
+        println!("\nsettings:");
+        for setting in &diff.settings {
+            println!(
+                "{} {}: {} {} {}",
+                "~".yellow(),
+                setting.name,
+                setting.current,
+                "->".bright_white(),
+                setting.other
+            );
+        }
+    }
+
+    let print_set_diff = |header: &str, only_current: Vec<String>, only_other: Vec<String>| {
+        if only_current.is_empty() && only_other.is_empty() {
+            return;
+        }
+
+        println!("\n{}:", header);
+        for entry in only_current {
+            println!("{} {}", "-".red(), entry);
+        }
+        for entry in only_other {
+            println!("{} {}", "+".green(), entry);
+        }
+    };
+    let display_paths = |paths: &[&PathBuf]| -> Vec<String> {
+        paths
+            .iter()
+            .map(|path| path.display().to_string())
+            .collect()
+    };
+
+    print_set_diff(
+        "plugin directories",
+        display_paths(&diff.plugin_dirs.only_current),
+        display_paths(&diff.plugin_dirs.only_other),
+    );
+    if !diff.directory_settings.is_empty() {
+        println!("\nplugin directory settings:");
+        for directory in &diff.directory_settings {
+            let display_groups = |groups: &BTreeSet<String>| {
+                format!(
+                    "[{}]",
+                    groups.iter().cloned().collect::<Vec<String>>().join(", ")
+                )
+            };
+            println!(
+                "{} {}: no recurse {} {} {}, read-only {} {} {}, groups {} {} {}",
+                "~".yellow(),
+                directory.path.display(),
+                directory.current.no_recurse,
+                "->".bright_white(),
+                directory.other.no_recurse,
+                directory.current.read_only,
+                "->".bright_white(),
+                directory.other.read_only,
+                display_groups(&directory.current.groups),
+                "->".bright_white(),
+                display_groups(&directory.other.groups)
+            );
+        }
+    }
+    print_set_diff(
+        "blacklist",
+        display_paths(&diff.blacklist.only_current),
+        display_paths(&diff.blacklist.only_other),
+    );
+    print_set_diff(
+        "default excludes",
+        diff.default_excludes
+            .only_current
+            .iter()
+            .map(|pattern| pattern.to_string())
+            .collect(),
+        diff.default_excludes
+            .only_other
+            .iter()
+            .map(|pattern| pattern.to_string())
+            .collect(),
+    );
+
+    Ok(())
+}
+
+/// Options passed to `yabridgectl diff`, see `main()` for the definitions of these options.
+pub struct DiffOptions {
+    pub include_hidden: bool,
+    pub no_recurse: bool,
+    pub traversal: Traversal,
+    pub json: bool,
+}
+
+/// The changes printed by `yabridgectl diff`. This is also the format used with `--json`.
+#[derive(Default, Serialize)]
+struct SyncDiff {
+    /// Plugins that have not been set up yet.
+    added: Vec<PathBuf>,
+    /// Plugins whose bridge is out of date, for instance because yabridge has been updated since
+    /// the plugin was set up.
+    updated: Vec<PathBuf>,
+    /// Leftover files that no longer belong to any plugin. These are removed when running
+    /// `yabridgectl sync --prune`.
+    removed: Vec<PathBuf>,
+}
+
+/// Show which plugins would be set up, updated, or removed on the next `yabridgectl sync`, without
+/// changing anything. This uses the same plan as `yabridgectl sync`, and compares the bridges for
+/// the plugins it would set up against the bridges that are currently on disk.
+pub fn show_diff(config: &Config, options: &DiffOptions, out: &mut dyn Write) -> Result<()> {
+    let files: YabridgeFiles = config.files()?;
+    let results = config
+        .search_directories(&SearchOverrides {
+            no_recurse: options.no_recurse,
+            include_hidden: options.include_hidden,
+            traversal: options.traversal,
+            ..Default::default()
+        })
+        .context("Failure while searching for plugins")?;
+    let index = Index::read()?;
+
+    let vst2_chainloader_hash = util::hash_file(&files.vst2_chainloader)?;
+    let vst3_chainloader_hash = files
+        .vst3_chainloader
+        .as_ref()
+        .map(|(path, _)| util::hash_file(path))
+        .transpose()?;
+    let clap_chainloader_hash = files
+        .clap_chainloader
+        .as_ref()
+        .map(|(path, _)| util::hash_file(path))
+        .transpose()?;
+
+    let plan = plan::plan_sync(config, &files, &results, &index, &PlanOptions::default());
+
+    let mut diff = SyncDiff::default();
+    for directory in &plan.directories {
+        for &(plugin, action) in &directory.plugins {
+            if action != PlannedAction::SetUp {
+                continue;
+            }
+
+            // The plan only sets up plugins whose format is supported by this build of yabridge
+            let chainloader_hash = match plugin {
+                Plugin::Vst2(_) => Some(vst2_chainloader_hash),
+                Plugin::Vst3(_) => vst3_chainloader_hash,
+                Plugin::Clap(_) => clap_chainloader_hash,
+            };
+
+            let target = plugin.native_target(config, directory.vst2_location, Some(&files));
+            match fs::symlink_metadata(&target) {
+                Ok(metadata)
+                    if metadata.file_type().is_file()
+                        && Some(util::hash_file(&target)?) == chainloader_hash => {}
+                Ok(_) => diff.updated.push(plugin.path().to_owned()),
+                Err(_) => diff.added.push(plugin.path().to_owned()),
+            }
+        }
+    }
+
+    // `yabridgectl sync --prune` doesn't remove anything while a plugin directory may not be
+    // mounted
+    if plan.possibly_unmounted_dirs.is_empty() {
+        diff.removed = plan
+            .orphans
+            .iter()
+            .map(|file| file.path().to_owned())
+            .collect();
+    }
+
+    for paths in [&mut diff.added, &mut diff.updated, &mut diff.removed] {
+        paths.sort_by(|a, b| util::compare_paths(a, b));
+        paths.dedup();
+    }
+
+    if options.json {
+        writeln!(
+            out,
+            "{}",
+            serde_jsonrc::to_string(&diff).context("Could not format JSON")?
+        )?;
+    } else if diff.added.is_empty() && diff.updated.is_empty() && diff.removed.is_empty() {
+        writeln!(out, "Everything is up to date")?;
+    } else {
+        for path in &diff.added {
+            writeln!(out, "{} {}", "+".green(), path.display())?;
+        }
+        for path in &diff.updated {
+            writeln!(out, "{} {}", "~".yellow(), path.display())?;
+        }
+        for path in &diff.removed {
+            writeln!(out, "{} {}", "-".red(), path.display())?;
+        }
+
+        writeln!(
+            out,
+            "\n{} to set up, {} to update, {} to remove with 'yabridgectl sync --prune'",
+            diff.added.len(),
+            diff.updated.len(),
+            diff.removed.len()
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Options passed to `yabridgectl config repair`, see `main()` for the definitions of these
+/// options.
+pub struct RepairConfigOptions {
+    pub keep_missing: bool,
+}
+
+/// Fix common problems in the config file using `Config::repair()`. The original config file is
+/// backed up to `config.toml.bak` before the repaired config is written.
+pub fn repair_config(options: &RepairConfigOptions) -> Result<()> {
+    let config_path = match find_config_file()? {
+        Some(path) => path,
+        None => {
+            println!("There is no config file to repair");
+            return Ok(());
+        }
+    };
+
+    let (config, changes) = Config::repair(&config_path, options.keep_missing)?;
+    if changes.is_empty() {
+        println!("Nothing to repair in '{}'", config_path.display());
+        return Ok(());
+    }
+
+    println!("Repaired '{}':", config_path.display());
+    for change in changes {
+        println!("- {}", change);
+    }
+
+    let backup_path = config_path.with_extension("toml.bak");
+    util::copy_or_reflink(&config_path, &backup_path)?;
+    config.write()?;
+
+    println!(
+        "\nThe original config file has been backed up to '{}'",
+        backup_path.display()
+    );
+
+    Ok(())
+}
+
+/// Options passed to `yabridgectl import`, see `main()` for the definitions of these options.
+pub struct ImportConfigOptions<'a> {
+    pub path: &'a Path,
+    pub check: bool,
+}
+
+/// Replace the config file with the config file at `options.path`, after checking that it can be
+/// used on this machine. With `options.check` the config file is only checked. Either way this
+/// fails if the config file has problems that would prevent `yabridgectl sync` from working.
+pub fn import_config(options: &ImportConfigOptions) -> Result<()> {
+    let mut config = Config::read_file(options.path)?;
+
+    let (errors, warnings) = check_imported_config(&config);
+    for error in &errors {
+        println!("{} {}", "Problem:".red(), error);
+    }
+    for warning in &warnings {
+        println!("{} {}", "Note:".bright_white(), warning);
+    }
+    if !errors.is_empty() {
+        anyhow::bail!(
+            "'{}' can't be used on this machine, found {} {}",
+            options.path.display(),
+            errors.len(),
+            if errors.len() == 1 {
+                "problem"
+            } else {
+                "problems"
+            }
+        );
+    }
+
+    if options.check {
+        println!(
+            "'{}' can be imported{}",
+            options.path.display(),
+            if warnings.is_empty() {
+                ""
+            } else {
+                ", see the notes above"
+            }
+        );
+        return Ok(());
+    }
+
+    if let Some(config_path) = find_config_file()? {
+        let backup_path = config_path.with_extension("toml.bak");
+        util::copy_or_reflink(&config_path, &backup_path)?;
+        println!(
+            "The current config file has been backed up to '{}'",
+            backup_path.display()
+        );
+    }
+
+    // The setup checks from the other machine don't say anything about this machine
+    config.last_known_config = None;
+    config.write()?;
+
+    println!("Imported '{}'", options.path.display());
+
+    Ok(())
+}
+
+/// Find the problems that would prevent a config file copied from another machine from working on
+/// this machine. Returns the problems that need to be fixed before the config can be imported,
+/// followed by problems that only affect some plugins or settings.
+fn check_imported_config(config: &Config) -> (Vec<String>, Vec<String>) {
+    let mut errors: Vec<String> = Vec::new();
+    let mut warnings: Vec<String> = Vec::new();
+
+    if let Err(err) = config.files() {
+        errors.push(format!("{:#}", err));
+    }
+
+    for path in &config.plugin_dirs {
+        if !path.exists() {
+            errors.push(format!(
+                "Plugin directory '{}' does not exist",
+                path.display()
+            ));
+        } else if !path.is_dir() {
+            errors.push(format!(
+                "Plugin directory '{}' is not a directory",
+                path.display()
+            ));
+        }
+    }
+
+    for (name, target) in [
+        ("vst2_target", &config.vst2_target),
+        ("vst3_target", &config.vst3_target),
+    ] {
+        if let Some(target) = target {
+            if target.exists() && !target.is_dir() {
+                errors.push(format!(
+                    "The {} '{}' is not a directory",
+                    name,
+                    target.display()
+                ));
+            }
+        }
+    }
+    if config.vst2_target.is_some() && config.vst2_location == Vst2InstallationLocation::Inline {
+        warnings.push(String::from(
+            "The vst2_target setting is not used with the inline VST2 installation location",
+        ));
+    }
+
+    for path in &config.blacklist {
+        if config.plugin_dirs.iter().any(|dir| dir.starts_with(path)) {
+            warnings.push(format!(
+                "Blacklist entry '{}' hides an entire plugin directory",
+                path.display()
+            ));
+        } else if !config.plugin_dirs.iter().any(|dir| path.starts_with(dir)) {
+            warnings.push(format!(
+                "Blacklist entry '{}' is not inside of any plugin directory",
+                path.display()
+            ));
+        } else if !path.exists() {
+            warnings.push(format!(
+                "Blacklist entry '{}' does not exist",
+                path.display()
+            ));
+        }
+    }
+
+    for path in config.directory_settings.keys() {
+        if !config.plugin_dirs.contains(path) {
+            warnings.push(format!(
+                "There are directory settings for '{}', but it is not a plugin directory",
+                path.display()
+            ));
+        }
+    }
+
+    (errors, warnings)
+}
+
+/// Options passed to `yabridgectl note`, see `main()` for the definitions of these options.
+pub struct NoteOptions<'a> {
+    pub plugin: Option<&'a Path>,
+    pub note: Option<&'a str>,
+    pub remove: bool,
+    pub list: bool,
+    pub json: bool,
+}
+
+/// Add, remove, show, or list the notes attached to plugins. These are stored in the index, so
+/// they don't affect how plugins are set up.
+pub fn note(options: &NoteOptions) -> Result<()> {
+    let mut index = Index::read()?;
+    if options.list {
+        // With `--json` this is printed as an object mapping plugin paths to their notes
+        if options.json {
+            println!(
+                "{}",
+                serde_jsonrc::to_string(&index.notes).context("Could not format JSON")?
+            );
+        } else if index.notes.is_empty() {
+            println!("No plugins have notes, use 'yabridgectl note <plugin> <note>' to add one");
+        } else {
+            for (path, note) in &index.notes {
+                println!("{}: {}", path.display(), note);
+            }
+        }
+
+        return Ok(());
+    }
+
+    // The plugin path is required by clap when `--list` is not used
+    let plugin = options.plugin.unwrap();
+    match (options.note, options.remove) {
+        (Some(note), _) => {
+            index.set_note(plugin, note.to_owned());
+            index.write()?;
+        }
+        (None, true) => {
+            if index.remove_note(plugin).is_none() {
+                anyhow::bail!("'{}' does not have a note", plugin.display());
+            }
+            index.write()?;
+        }
+        (None, false) => match index.note(plugin) {
+            Some(note) => println!("{}", note),
+            None => println!("'{}' does not have a note", plugin.display()),
+        },
+    }
+
+    Ok(())
+}
+
+/// Remove yabridgectl's index file and cache directory. The config file is never removed. Nothing
+/// happens if these files don't exist.
+pub fn clean_cache() -> Result<()> {
+    let xdg_dirs = yabridgectl_directories()?;
+    let backend = filesystem::current();
+
+    let mut removed_paths: Vec<PathBuf> = Vec::new();
+    let index_path = xdg_dirs.get_data_home().join(INDEX_FILE_NAME);
+    if backend.symlink_metadata(&index_path).is_ok() {
+        util::remove_file(&index_path)?;
+        removed_paths.push(index_path);
+    }
+
+    let cache_home = xdg_dirs.get_cache_home();
+    if backend.metadata(&cache_home).is_ok() {
+        util::remove_dir_all(&cache_home)?;
+        removed_paths.push(cache_home);
+    }
+
+    if removed_paths.is_empty() {
+        println!("There was nothing to remove");
+    } else {
+        println!("Removed:");
+        for path in removed_paths {
+            println!("- {}", path.display());
+        }
+    }
+
+    Ok(())
+}
+
+/// Print the contents of yabridgectl's index file as formatted JSON, for use in bug reports. The
+/// file is parsed as plain JSON instead of as an [`Index`] so fields that this version of
+/// yabridgectl doesn't know about are also shown.
+pub fn dump_index() -> Result<()> {
+    let (index_path, index) = read_raw_index()?;
+
+    eprintln!("Contents of '{}':", index_path.display());
+    println!(
+        "{}",
+        serde_jsonrc::to_string_pretty(&index).context("Could not format JSON")?
+    );
+
+    Ok(())
+}
+
+/// Read the index file as plain JSON, returning the file's path along with its contents.
+fn read_raw_index() -> Result<(PathBuf, serde_jsonrc::Value)> {
+    let index_path = yabridgectl_directories()?
+        .find_data_file(INDEX_FILE_NAME)
+        .context("The index file does not exist yet, it is created by 'yabridgectl sync'")?;
+    let index: serde_jsonrc::Value = serde_jsonrc::from_str(&util::read_to_string(&index_path)?)
+        .with_context(|| format!("Failed to parse '{}'", index_path.display()))?;
+
+    Ok((index_path, index))
+}
+
+/// Print everything that causes files to be skipped during indexing, in the order these rules are
+/// applied. Used with `--verbose`.
+fn print_exclusions(
+    config: &Config,
+    excluded_dirs: &[&Path],
+    skip_system_dlls: bool,
+    min_plugin_size: Option<u64>,
+    max_plugin_size: Option<u64>,
+) {
+    if excluded_dirs.is_empty()
+        && config.blacklist.is_empty()
+        && config.default_excludes.is_empty()
+        && !skip_system_dlls
+        && min_plugin_size.is_none()
+        && max_plugin_size.is_none()
+    {
+        return;
+    }
+
+    println!("Exclusion rules, applied in this order:");
+    for directory in excluded_dirs {
+        println!("- excluded for this run: {}", directory.display());
+    }
+    for path in &config.blacklist {
+        println!("- blacklisted: {}", path.display());
+    }
+    for pattern in &config.default_excludes {
+        println!("- default exclude: {}", pattern);
+    }
+    if skip_system_dlls {
+        println!(
+            "- likely a dependency: {}",
+            config.system_dll_patterns().join(", ")
+        );
+    }
+    if let Some(size) = min_plugin_size {
+        println!("- smaller than: {}", util::format_size(size));
+    }
+    if let Some(size) = max_plugin_size {
+        println!("- larger than: {}", util::format_size(size));
+    }
+    println!();
+}
+
+/// If `directory` is on a different filesystem than yabridge's files, return a note explaining
+/// what that means for the bridges set up for the plugins in that directory. This is purely
+/// informational, everything still works.
+fn different_filesystem_note(directory: &Path, files: &YabridgeFiles) -> Option<String> {
+    match util::same_filesystem(directory, &files.vst2_chainloader) {
+        Some(false) => Some(format!(
+            "This directory is on a different filesystem than '{VST2_CHAINLOADER_NAME}', so \
+             bridges created here are copied instead of reflinked, and relative symlinks will \
+             cross mount points"
+        )),
+        _ => None,
+    }
+}
+
+/// If a plugin directory's bridges are a mix of copies and symlinks, for instance because they were
+/// set up by different versions of yabridgectl, return a note with the number of bridges of each
+/// kind. This is not a problem in itself, but it makes the status output confusing.
+fn mixed_bridges_note(
+    installation_status: &[(PathBuf, (&Plugin, InstallState))],
+) -> Option<String> {
+    let num_copies = installation_status
+        .iter()
+        .filter(|(_, (_, status))| matches!(status, InstallState::Copy(..)))
+        .count();
+    let num_symlinks = installation_status
+        .iter()
+        .filter(|(_, (_, status))| matches!(status, InstallState::Symlink(..)))
+        .count();
+    if num_copies == 0 || num_symlinks == 0 {
+        return None;
+    }
+
+    Some(format!(
+        "This directory contains both copied bridges ({num_copies}) and symlinked bridges \
+         ({num_symlinks}), run 'yabridgectl sync' to replace the symlinks with copies"
+    ))
+}
+
+/// Print a notice when the VST2 location has been overridden for this run through the
+/// `--vst2-location` option or the `YABRIDGECTL_VST2_LOCATION` environment variable, and the
+/// location differs from the one stored in the config file.
+fn print_vst2_location_override(config: &Config, out: &mut dyn Write) -> io::Result<()> {
+    let effective_location = config.effective_vst2_location();
+    if effective_location != config.vst2_location {
+        writeln!(
+            out,
+            "Using the '{}' VST2 location for this run instead of the configured '{}' location\n",
+            effective_location, config.vst2_location
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Options passed to `yabridgectl duplicates`, see `main()` for the definitions of these options.
+pub struct DuplicatesOptions {
+    pub include_hidden: bool,
+    pub no_recurse: bool,
+    pub json: bool,
+}
+
+/// List the sets of plugins with identical contents across all plugin directories. This doesn't
+/// modify anything.
+pub fn show_duplicates(config: &Config, options: &DuplicatesOptions) -> Result<()> {
+    let results = config
+        .search_directories(&SearchOverrides {
+            no_recurse: options.no_recurse,
+            include_hidden: options.include_hidden,
+            ..Default::default()
+        })
+        .context("Failure while searching for plugins")?;
+    let duplicates: Vec<Vec<&Path>> = files::find_duplicates(
+        results
+            .iter()
+            .flat_map(|(_, search_results)| &search_results.plugins),
+    )
+    .into_iter()
+    .map(|set| set.into_iter().map(|plugin| plugin.path()).collect())
+    .collect();
+
+    // With `--json` this is printed as an array containing arrays of paths
+    if options.json {
+        println!(
+            "{}",
+            serde_jsonrc::to_string(&duplicates).context("Could not format JSON")?
+        );
+
+        return Ok(());
+    }
+
+    if duplicates.is_empty() {
+        println!("No duplicate plugins found");
+        return Ok(());
+    }
+
+    println!(
+        "Found {} {} of duplicate plugins. With 'yabridgectl sync --keep-first' only the first \
+         plugin in each set is set up.",
+        duplicates.len(),
+        if duplicates.len() == 1 { "set" } else { "sets" }
+    );
+    for set in duplicates {
+        println!();
+        for (i, path) in set.into_iter().enumerate() {
+            if i == 0 {
+                println!("- {}", path.display());
+            } else {
+                println!("- {} {}", path.display(), "(duplicate)".yellow());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Options passed to `yabridgectl loadable`, see `main()` for the definitions of these options.
+pub struct LoadableOptions {
+    pub include_hidden: bool,
+    pub no_recurse: bool,
+    pub json: bool,
+}
+
+/// A plugin printed by `yabridgectl loadable`. This is also the format used for `--json`.
+#[derive(Serialize)]
+struct LoadablePlugin<'a> {
+    path: &'a Path,
+    /// Either `VST2`, `VST3`, or `CLAP`.
+    format: &'static str,
+    /// The plugin's architecture, formatted using `LibArchitecture`'s `Display` implementation.
+    architecture: String,
+    /// The copy or symlink of yabridge's chainloader the host will load.
+    bridge: &'a Path,
+}
+
+/// List the plugins a host would be able to load right now. These are the plugins that have an up
+/// to date bridge, a readable plugin file, and a host binary matching the plugin's architecture.
+/// Plugins without the correct exported entry points are never found in the first place. This
+/// doesn't modify anything.
+pub fn show_loadable(config: &Config, options: &LoadableOptions) -> Result<()> {
+    let files = config.files()?;
+    let results = config
+        .search_directories(&SearchOverrides {
+            no_recurse: options.no_recurse,
+            include_hidden: options.include_hidden,
+            ..Default::default()
+        })
+        .context("Failure while searching for plugins")?;
+
+    let statuses: Vec<_> = results
+        .iter()
+        .flat_map(|(_, search_results)| search_results.installation_status(config, Some(&files)))
+        .collect();
+    let loadable: Vec<LoadablePlugin> = statuses
+        .iter()
+        .filter(|(_, (plugin, status))| is_loadable(plugin, status, &files))
+        .filter_map(|(_, (plugin, status))| {
+            Some(LoadablePlugin {
+                path: plugin.path(),
+                format: match plugin {
+                    Plugin::Vst2(_) => "VST2",
+                    Plugin::Vst3(_) => "VST3",
+                    Plugin::Clap(_) => "CLAP",
+                },
+                architecture: plugin.architecture().to_string(),
+                bridge: status.path()?,
+            })
+        })
+        .collect();
+
+    if options.json {
+        println!(
+            "{}",
+            serde_jsonrc::to_string(&loadable).context("Could not format JSON")?
+        );
+
+        return Ok(());
+    }
+
+    if loadable.is_empty() {
+        println!(
+            "No plugins can be loaded right now, 'yabridgectl status' and 'yabridgectl doctor' \
+             show why"
+        );
+        return Ok(());
+    }
+
+    for plugin in &loadable {
+        println!(
+            "- {} ({}, {})",
+            plugin.path.display(),
+            plugin.format,
+            plugin.architecture
+        );
+    }
+    println!(
+        "\n{} of {} {} can be loaded",
+        loadable.len(),
+        statuses.len(),
+        if statuses.len() == 1 {
+            "plugin"
+        } else {
+            "plugins"
+        }
+    );
+
+    Ok(())
+}
+
+/// Whether a host would be able to load `plugin` right now. This requires an up to date bridge, a
+/// readable plugin file, and a host binary matching the plugin's architecture. Used for both
+/// `yabridgectl loadable` and `yabridgectl query`.
+fn is_loadable(plugin: &Plugin, status: &InstallState, files: &YabridgeFiles) -> bool {
+    matches!(
+        status,
+        InstallState::Copy(_, CopyState::UpToDate) | InstallState::Symlink(_, SymlinkState::Ok)
+    ) && host_binary(files, plugin.architecture()).is_some()
+        && util::verify_readable(plugin.path()).is_ok()
+}
+
+/// Set up a fake VST2 plugin in a temporary directory using the same code `yabridgectl sync` uses,
+/// check that the results are detected correctly, and then remove the temporary directory again.
+/// This uses the symlink settings from the config file. The user's plugins are never touched.
+pub fn self_test(config: &Config) -> Result<()> {
+    if filesystem::is_dry_run() {
+        anyhow::bail!("The self-test can't be run with '--dry-run'");
+    }
+
+    let files = config.files()?;
+    let backend = &filesystem::RealFileSystem;
+    let chainloader_size = fs::metadata(&files.vst2_chainloader)
+        .with_context(|| format!("Could not read '{}'", files.vst2_chainloader.display()))?
+        .len();
+    let chainloader_hash = util::hash_file(&files.vst2_chainloader)?;
+    let symlink_method = if config.relative_symlinks {
+        InstallationMethod::RelativeSymlink
+    } else {
+        InstallationMethod::Symlink
+    };
+    let copy_options = InstallOptions {
+        force: false,
+        preserve_mtime: false,
+        mode: None,
+        overwrite_unrelated: false,
+        method: InstallationMethod::Copy,
+    };
+    let symlink_options = InstallOptions {
+        force: true,
+        overwrite_unrelated: true,
+        method: symlink_method,
+        ..copy_options
+    };
+
+    let test_dir =
+        std::env::temp_dir().join(format!("yabridgectl-self-test-{}", std::process::id()));
+    util::create_dir_all(test_dir.join("centralized"))?;
+    let result = (|| -> Result<Vec<(String, bool)>> {
+        let plugin_path = test_dir.join("Self Test.dll");
+        util::write(&plugin_path, b"MZ")?;
+
+        // This mirrors the centralized VST2 installation location: a copy of the chainloader, with
+        // the Windows plugin symlinked next to it
+        let bridge_path = test_dir.join("centralized").join("Self Test.so");
+        let symlink_path = bridge_path.with_extension("dll");
+        let mut checks: Vec<(String, bool)> = Vec::new();
+
+        let created = install_file(
+            backend,
+            &copy_options,
+            &files.vst2_chainloader,
+            Some(chainloader_hash),
+            &bridge_path,
+        )?;
+        checks.push((format!("copying '{}'", VST2_CHAINLOADER_NAME), created));
+        checks.push((
+            String::from("detecting the copy as up to date"),
+            InstallState::new(
+                util::get_file_type(bridge_path.clone()),
+                Some((chainloader_size, chainloader_hash)),
+                Some(&files),
+            ) == InstallState::Copy(bridge_path.clone(), CopyState::UpToDate),
+        ));
+        checks.push((
+            format!(
+                "detecting the copy as a {} library",
+                files.vst2_chainloader_arch
+            ),
+            util::get_elf_architecture(&bridge_path).ok() == Some(files.vst2_chainloader_arch),
+        ));
+        checks.push((
+            String::from("skipping the copy when syncing again"),
+            !install_file(
+                backend,
+                &copy_options,
+                &files.vst2_chainloader,
+                Some(chainloader_hash),
+                &bridge_path,
+            )?,
+        ));
+
+        install_file(backend, &symlink_options, &plugin_path, None, &symlink_path)?;
+        checks.push((
+            String::from("symlinking the Windows plugin"),
+            util::verify_readable(&symlink_path).is_ok(),
+        ));
+
+        Ok(checks)
+    })();
+    util::remove_dir_all(&test_dir)?;
+    let checks = result?;
+
+    println!(
+        "Tested the copy installation method with {} symlinks in '{}':",
+        if config.relative_symlinks {
+            "relative"
+        } else {
+            "absolute"
+        },
+        test_dir.display()
+    );
+    for (description, passed) in &checks {
+        if *passed {
+            println!("- {}: {}", description, "passed".green());
+        } else {
+            println!("- {}: {}", description, "failed".red());
+        }
+    }
+
+    match checks.iter().filter(|(_, passed)| !passed).count() {
+        0 => Ok(()),
+        1 => anyhow::bail!("1 check failed"),
+        n => anyhow::bail!("{n} checks failed"),
+    }
+}
+
+/// The host binary used for plugins with architecture `arch`. Just like in `yabridgectl doctor`,
+/// both the `.exe` script and the actual Winelib binary need to exist.
+fn host_binary(files: &YabridgeFiles, arch: LibArchitecture) -> Option<&Path> {
+    let (host_exe, host_exe_so) = match arch {
+        LibArchitecture::Lib64 => (&files.yabridge_host_exe, &files.yabridge_host_exe_so),
+        LibArchitecture::Lib32 => (&files.yabridge_host_32_exe, &files.yabridge_host_32_exe_so),
+    };
+
+    host_exe.as_deref().filter(|_| host_exe_so.is_some())
+}
+
+/// Options passed to `yabridgectl query`, see `main()` for the definitions of these options.
+pub struct QueryOptions<'a> {
+    /// The plugin's path, or its name without the file extension.
+    pub plugin: &'a str,
+    pub include_hidden: bool,
+    pub no_recurse: bool,
+    pub json: bool,
+}
+
+/// A plugin printed by `yabridgectl query`. This is also the format used for `--json`.
+#[derive(Serialize)]
+struct QueriedPlugin<'a> {
+    path: &'a Path,
+    /// The plugin directory the plugin was found in.
+    directory: &'a Path,
+    /// Either `VST2`, `VST3`, or `CLAP`.
+    format: &'static str,
+    /// The plugin's architecture, formatted using `LibArchitecture`'s `Display` implementation.
+    architecture: String,
+    /// The bridge's installation status, formatted using `InstallState`'s `Display`
+    /// implementation.
+    status: String,
+    /// The copy or symlink of yabridge's chainloader the host will load, if it exists.
+    bridge: Option<&'a Path>,
+    /// Where the bridge will be created for the current VST2 location setting.
+    target: PathBuf,
+    /// The host binary used for this plugin's architecture, or `None` if it could not be found.
+    host: Option<&'a Path>,
+    /// Whether the plugin would show up in `yabridgectl loadable`.
+    loadable: bool,
+    /// The note added with `yabridgectl note`.
+    note: Option<&'a str>,
+}
+
+/// Print everything yabridgectl knows about a single plugin. The plugin can be specified either by
+/// its path or by its file name without the extension, in which case every plugin with that name is
+/// shown. Returns an error if no matching plugins were found.
+pub fn query(config: &Config, options: &QueryOptions) -> Result<()> {
+    let files = config.files()?;
+    let index = Index::read()?;
+    let results = config
+        .search_directories(&SearchOverrides {
+            no_recurse: options.no_recurse,
+            include_hidden: options.include_hidden,
+            ..Default::default()
+        })
+        .context("Failure while searching for plugins")?;
+
+    let query_path = Path::new(options.plugin);
+    let normalized_query_path = query_path
+        .canonicalize()
+        .ok()
+        .map(|path| util::normalize_path(&path));
+    let query_name = options.plugin.to_lowercase();
+    let matches_query = |plugin: &Plugin| match &normalized_query_path {
+        Some(query_path) => &util::normalize_path(plugin.path()) == query_path,
+        None => plugin.path().file_stem().map_or(false, |stem| {
+            stem.to_string_lossy().to_lowercase() == query_name
+        }),
+    };
+
+    let statuses: Vec<(&Path, Vec<_>)> = results
+        .iter()
+        .map(|(directory, search_results)| {
+            (
+                *directory,
+                search_results.installation_status(config, Some(&files)),
+            )
+        })
+        .collect();
+    let vst2_locations: HashMap<&Path, Vst2InstallationLocation> = results
+        .iter()
+        .map(|(directory, search_results)| (*directory, search_results.vst2_location(config)))
+        .collect();
+    let queried: Vec<QueriedPlugin> = statuses
+        .iter()
+        .flat_map(|(directory, installation_status)| {
+            installation_status
+                .iter()
+                .map(move |(_, (plugin, status))| (*directory, plugin, status))
+        })
+        .filter(|(_, plugin, _)| matches_query(plugin))
+        .map(|(directory, plugin, status)| QueriedPlugin {
+            path: plugin.path(),
+            directory,
+            format: match plugin {
+                Plugin::Vst2(_) => "VST2",
+                Plugin::Vst3(_) => "VST3",
+                Plugin::Clap(_) => "CLAP",
+            },
+            architecture: plugin.architecture().to_string(),
+            status: status.to_string(),
+            bridge: status.path(),
+            target: plugin.native_target(config, vst2_locations[directory], Some(&files)),
+            host: host_binary(&files, plugin.architecture()),
+            loadable: is_loadable(plugin, status, &files),
+            note: index.note(plugin.path()),
+        })
+        .collect();
+
+    if queried.is_empty() {
+        // Blacklisted plugins are never found during the search, so this needs a separate check
+        let is_blacklisted = normalized_query_path.as_ref().map_or(false, |query_path| {
+            config
+                .blacklist
+                .iter()
+                .any(|path| query_path.starts_with(util::normalize_path(path)))
+        });
+        if is_blacklisted {
+            anyhow::bail!(
+                "'{}' is blacklisted, use 'yabridgectl blacklist list' to see the blacklist",
+                options.plugin
+            );
+        } else {
+            anyhow::bail!(
+                "Could not find a plugin matching '{}' in the plugin directories",
+                options.plugin
+            );
+        }
+    }
+
+    if options.json {
+        println!(
+            "{}",
+            serde_jsonrc::to_string(&queried).context("Could not format JSON")?
+        );
+
+        return Ok(());
+    }
+
+    let display_path = |path: Option<&Path>| match path {
+        Some(path) => format!("'{}'", path.display()),
+        None => "<not found>".red().to_string(),
+    };
+    for (i, plugin) in queried.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+
+        println!("{}", plugin.path.display());
+        println!("  directory: '{}'", plugin.directory.display());
+        println!("  format: {}", plugin.format);
+        println!("  architecture: {}", plugin.architecture);
+        println!("  status: {}", plugin.status);
+        println!("  bridge: {}", display_path(plugin.bridge));
+        println!("  target: '{}'", plugin.target.display());
+        println!("  host: {}", display_path(plugin.host));
+        println!(
+            "  loadable: {}",
+            if plugin.loadable {
+                "yes".green()
+            } else {
+                "no".red()
+            }
+        );
+        if let Some(note) = plugin.note {
+            println!("  note: {}", note);
+        }
+    }
+
+    Ok(())
+}
+
+/// Options passed to `yabridgectl doctor`, see `main()` for the definitions of these options.
+pub struct DoctorOptions<'a> {
+    pub include_hidden: bool,
+    pub no_recurse: bool,
+    pub json: bool,
+    /// Also write a diagnostic report to this path. If this is a directory, then the report is
+    /// written to a timestamped file in that directory.
+    pub report: Option<&'a Path>,
+    /// The fixes enabled through `--fix`. Nothing is changed when this is empty.
+    pub fixes: BTreeSet<DoctorFix>,
+}
+
+/// The problems `yabridgectl doctor --fix` can fix. These are all limited to the problems found by
+/// `yabridgectl doctor`. Anything that would remove or change a lot of files, like switching to
+/// another installation method, is only ever recommended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DoctorFix {
+    /// Remove plugin directories that no longer exist from the config.
+    MissingDirectories,
+    /// Point symlinked bridges with a missing target at the current chainloaders again.
+    BrokenSymlinks,
+    /// Give copied bridges the permissions from the `bridge_mode` setting.
+    Permissions,
+    /// Add the directory containing the host binaries to the login shell's search path when they
+    /// can't be found. This asks for confirmation first.
+    SearchPath,
+}
+
+/// The results from `yabridgectl doctor`. This is also the format used for `--json`.
+#[derive(Serialize)]
+struct DoctorReport<'a> {
+    /// The number of plugins found for each architecture, keyed by `LibArchitecture`'s `Display`
+    /// implementation.
+    plugins: BTreeMap<String, usize>,
+    /// The host binaries that were found, or `None` if they could not be found.
+    hosts: BTreeMap<&'static str, Option<&'a Path>>,
+    /// The Wine prefix that was checked, set through `--prefix` or `$WINEPREFIX`.
+    wine_prefix: PathBuf,
+    /// The prefix's architecture, formatted using `LibArchitecture`'s `Display` implementation.
+    wine_prefix_arch: String,
+    problems: Vec<DoctorProblem>,
+    /// Informational notes about the setup that don't prevent plugins from working.
+    notes: Vec<String>,
+    /// The changes made with `--fix`. The problems that were fixed are not included in `problems`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    fixed: Vec<String>,
+}
+
+/// The diagnostic report written by `yabridgectl doctor --report`. This bundles the output of
+/// several other commands into a single file that can be attached to a bug report. Nothing is
+/// scrubbed from this report.
+#[derive(Serialize)]
+struct DiagnosticReport<'a> {
+    /// When the report was generated, in seconds since the Unix epoch.
+    generated_at: u64,
+    yabridgectl_version: &'static str,
+    /// The output of `wine --version`, or `None` if Wine could not be run.
+    wine_version: Option<String>,
+    /// The output of `yabridgectl config show --json`.
+    config: serde_jsonrc::Value,
+    /// The output of `yabridgectl status --verbose`, without colors.
+    status: String,
+    doctor: &'a DoctorReport<'a>,
+    /// The contents of the index file as shown by `yabridgectl dump-index`, or `None` if it does
+    /// not exist yet.
+    index: Option<serde_jsonrc::Value>,
+}
+
+/// A problem found by `yabridgectl doctor` that prevents some plugins from working.
+#[derive(Serialize)]
+struct DoctorProblem {
+    /// A short machine readable identifier for the problem.
+    kind: &'static str,
+    message: String,
+    /// What the user can do to fix the problem.
+    recommendation: String,
+}
+
+/// Check the setup for problems that would prevent plugins from loading. This checks whether
+/// there's a host binary available for each of the plugin architectures that were found, and
+/// whether the plugin directories and bridges are still intact. The problems enabled through
+/// `options.fixes` are fixed instead of reported. Returns an error if any problems remain so the
+/// command exits with a non-zero exit code.
+pub fn doctor(config: &mut Config, options: &DoctorOptions) -> Result<()> {
+    let mut problems: Vec<DoctorProblem> = Vec::new();
+    let mut notes: Vec<String> = Vec::new();
+    let mut fixed: Vec<String> = Vec::new();
+
+    // This is done before searching so the removed directories don't need to be searched
+    let missing_dirs: Vec<PathBuf> = config
+        .plugin_dirs
+        .iter()
+        .filter(|path| !path.exists())
+        .cloned()
+        .collect();
+    if !missing_dirs.is_empty() {
+        if options.fixes.contains(&DoctorFix::MissingDirectories) {
+            for path in &missing_dirs {
+                config.plugin_dirs.remove(path);
+                config.directory_settings.remove(path);
+                fixed.push(format!(
+                    "Removed the plugin directory '{}' since it no longer exists",
+                    path.display()
+                ));
+            }
+            config.write()?;
+        } else {
+            problems.push(DoctorProblem {
+                kind: "missing_directories",
+                message: format!(
+                    "{} plugin {} no longer {}: {}",
+                    missing_dirs.len(),
+                    if missing_dirs.len() == 1 {
+                        "directory"
+                    } else {
+                        "directories"
+                    },
+                    if missing_dirs.len() == 1 {
+                        "exists"
+                    } else {
+                        "exist"
+                    },
+                    missing_dirs
+                        .iter()
+                        .map(|path| format!("'{}'", path.display()))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+                recommendation: String::from(
+                    "If these directories are on a drive that's not mounted right now, then mount \
+                     it. Otherwise remove them with 'yabridgectl rm', or with \
+                     'yabridgectl doctor --fix=missing-dirs'.",
+                ),
+            });
+        }
+    }
+
+    let results = config
+        .search_directories(&SearchOverrides {
+            no_recurse: options.no_recurse,
+            include_hidden: options.include_hidden,
+            ..Default::default()
+        })
+        .context("Failure while searching for plugins")?;
+    let mut num_plugins: BTreeMap<LibArchitecture, usize> = BTreeMap::new();
+    for plugin in results
+        .iter()
+        .flat_map(|(_, search_results)| &search_results.plugins)
+    {
+        *num_plugins.entry(plugin.architecture()).or_default() += 1;
+    }
+
+    let files = config.files();
+    let mut hosts: BTreeMap<&'static str, Option<&Path>> = BTreeMap::new();
+    match &files {
+        Ok(files) => {
+            // Just like in `yabridgectl status`, both the `.exe` script and the actual Winelib
+            // binary need to exist
+            let host_exe = files
+                .yabridge_host_exe
+                .as_deref()
+                .filter(|_| files.yabridge_host_exe_so.is_some());
+            let host_32_exe = files
+                .yabridge_host_32_exe
+                .as_deref()
+                .filter(|_| files.yabridge_host_32_exe_so.is_some());
+            hosts.insert(YABRIDGE_HOST_EXE_NAME, host_exe);
+            hosts.insert(YABRIDGE_HOST_32_EXE_NAME, host_32_exe);
+
+            for (path, _) in &results {
+                if let Some(note) = different_filesystem_note(path, files) {
+                    notes.push(format!("'{}': {}", path.display(), note));
+                }
+            }
+
+            for (arch, host_name, host) in [
+                (LibArchitecture::Lib64, YABRIDGE_HOST_EXE_NAME, host_exe),
+                (
+                    LibArchitecture::Lib32,
+                    YABRIDGE_HOST_32_EXE_NAME,
+                    host_32_exe,
+                ),
+            ] {
+                let num_arch_plugins = num_plugins.get(&arch).copied().unwrap_or(0);
+                if num_arch_plugins == 0 || host.is_some() {
+                    continue;
+                }
+
+                // When yabridge is installed somewhere other than `~/.local/share/yabridge`, the
+                // host binaries may exist but not be in the search path
+                let unlisted_host = files.vst2_chainloader.with_file_name(host_name);
+                let unlisted_host_dir = unlisted_host.parent().unwrap();
+                if unlisted_host.with_extension("exe.so").exists() {
+                    if options.fixes.contains(&DoctorFix::SearchPath) {
+                        if let Some(profile) = add_to_search_path(unlisted_host_dir)? {
+                            fixed.push(format!(
+                                "Added '{}' to the search path in '{}', log out and back in for \
+                                 this to take effect",
+                                unlisted_host_dir.display(),
+                                profile.display()
+                            ));
+                            continue;
+                        }
+                    }
+
+                    problems.push(DoctorProblem {
+                        kind: match arch {
+                            LibArchitecture::Lib32 => "missing_32_bit_host",
+                            LibArchitecture::Lib64 => "missing_64_bit_host",
+                        },
+                        message: format!(
+                            "Found '{}', but '{}' is not in the search path",
+                            unlisted_host.display(),
+                            unlisted_host_dir.display()
+                        ),
+                        recommendation: String::from(
+                            "Add this directory to your login shell's PATH environment variable, \
+                             or run 'yabridgectl doctor --fix=search-path' to do that \
+                             automatically.",
+                        ),
+                    });
+                    continue;
+                }
+
+                problems.push(DoctorProblem {
+                    kind: match arch {
+                        LibArchitecture::Lib32 => "missing_32_bit_host",
+                        LibArchitecture::Lib64 => "missing_64_bit_host",
+                    },
+                    message: format!(
+                        "Found {} {} {}, but '{}' could not be found",
+                        num_arch_plugins,
+                        arch,
+                        if num_arch_plugins == 1 {
+                            "plugin"
+                        } else {
+                            "plugins"
+                        },
+                        host_name
+                    ),
+                    recommendation: match arch {
+                        LibArchitecture::Lib32 => format!(
+                            "Install your distro's 32-bit Wine packages (for instance 'wine32' \
+                             on Debian and Ubuntu), and make sure '{YABRIDGE_HOST_32_EXE_NAME}' \
+                             and '{YABRIDGE_HOST_32_EXE_NAME}.so' from the yabridge release are \
+                             installed next to '{YABRIDGE_HOST_EXE_NAME}'."
+                        ),
+                        LibArchitecture::Lib64 => format!(
+                            "Install a 64-bit version of Wine, and make sure \
+                             '{YABRIDGE_HOST_EXE_NAME}' and '{YABRIDGE_HOST_EXE_NAME}.so' from the \
+                             yabridge release are installed in '~/.local/share/yabridge' or \
+                             somewhere in your search path."
+                        ),
+                    },
+                });
+            }
+        }
+        Err(err) => problems.push(DoctorProblem {
+            kind: "missing_files",
+            message: format!("Could not find yabridge's files: {err}"),
+            recommendation: String::from(
+                "Make sure yabridge is installed, or use 'yabridgectl set --path=<path>' to tell \
+                 yabridgectl where yabridge's files can be found.",
+            ),
+        }),
+    }
+
+    let statuses: Vec<(&Path, Vec<_>)> = results
+        .iter()
+        .map(|(path, search_results)| {
+            (
+                *path,
+                search_results.installation_status(config, files.as_ref().ok()),
+            )
+        })
+        .collect();
+    for (path, installation_status) in &statuses {
+        if let Some(note) = mixed_bridges_note(installation_status) {
+            notes.push(format!("'{}': {}", path.display(), note));
+        }
+    }
+
+    // Symlinked bridges break when yabridge is moved or reinstalled somewhere else. These are
+    // replaced with copies of the current chainloaders, just like `yabridgectl sync` would do.
+    let mut repaired_symlinks: HashSet<&Path> = HashSet::new();
+    if let (true, Ok(files)) = (options.fixes.contains(&DoctorFix::BrokenSymlinks), &files) {
+        for (_, (plugin, status)) in statuses
+            .iter()
+            .flat_map(|(_, installation_status)| installation_status)
+        {
+            let path = match status {
+                InstallState::Symlink(path, SymlinkState::Broken) => path,
+                _ => continue,
+            };
+            let chainloader = match plugin {
+                Plugin::Vst2(_) => Some(&files.vst2_chainloader),
+                Plugin::Vst3(_) => files.vst3_chainloader.as_ref().map(|(path, _)| path),
+                Plugin::Clap(_) => files.clap_chainloader.as_ref().map(|(path, _)| path),
+            };
+            if let Some(chainloader) = chainloader {
+                install_file(
+                    filesystem::current(),
+                    &InstallOptions {
+                        force: true,
+                        preserve_mtime: false,
+                        mode: config.bridge_mode,
+                        overwrite_unrelated: config.overwrite_unrelated_files,
+                        method: InstallationMethod::Copy,
+                    },
+                    chainloader,
+                    None,
+                    path,
+                )?;
+                fixed.push(format!(
+                    "Replaced the broken symlink '{}' with a copy of '{}'",
+                    path.display(),
+                    chainloader.display()
+                ));
+                repaired_symlinks.insert(path);
+            }
+        }
+    }
+
+    // Bridges that exist but can't be loaded. Outdated copies are fine since the next sync will
+    // update them.
+    let broken_bridges: Vec<String> = statuses
+        .iter()
+        .flat_map(|(_, installation_status)| installation_status)
+        .filter(|(_, (_, status))| {
+            matches!(
+                status,
+                InstallState::Copy(_, CopyState::Corrupt)
+                    | InstallState::Symlink(
+                        _,
+                        SymlinkState::Broken | SymlinkState::UnreadableTarget
+                    )
+                    | InstallState::Invalid(_)
+            )
+        })
+        .filter_map(|(_, (_, status))| {
+            status
+                .path()
+                .filter(|path| !repaired_symlinks.contains(path))
+                .map(|path| format!("'{}' ({})", path.display(), status))
+        })
+        .collect();
+    if !broken_bridges.is_empty() {
+        problems.push(DoctorProblem {
+            kind: "broken_bridges",
+            message: format!(
+                "Found {} broken {}: {}",
+                broken_bridges.len(),
+                if broken_bridges.len() == 1 {
+                    "bridge"
+                } else {
+                    "bridges"
+                },
+                broken_bridges.join(", ")
+            ),
+            recommendation: String::from(
+                "Remove these files and rerun 'yabridgectl sync' to set the plugins up again. \
+                 Broken symlinks can also be fixed with 'yabridgectl doctor \
+                 --fix=broken-symlinks'.",
+            ),
+        });
+    }
+
+    // Copies made before `bridge_mode` was set don't get the new permissions until the next sync
+    if let Some(mode) = config.bridge_mode {
+        let wrong_permissions: Vec<&Path> = statuses
+            .iter()
+            .flat_map(|(_, installation_status)| installation_status)
+            .filter_map(|(_, (_, status))| match status {
+                InstallState::Copy(path, _) => Some(path.as_path()),
+                _ => None,
+            })
+            .filter(|path| {
+                fs::metadata(path).map_or(false, |metadata| {
+                    metadata.permissions().mode() & 0o7777 != mode
+                })
+            })
+            .collect();
+        if !wrong_permissions.is_empty() {
+            if options.fixes.contains(&DoctorFix::Permissions) {
+                for path in &wrong_permissions {
+                    util::set_permissions(path, mode)?;
+                }
+                fixed.push(format!(
+                    "Changed the permissions of {} {} to {:04o}",
+                    wrong_permissions.len(),
+                    if wrong_permissions.len() == 1 {
+                        "bridge"
+                    } else {
+                        "bridges"
+                    },
+                    mode
+                ));
+            } else {
+                problems.push(DoctorProblem {
+                    kind: "bridge_permissions",
+                    message: format!(
+                        "{} {} not have the permissions from the 'bridge_mode' setting ({:04o}): \
+                         {}",
+                        wrong_permissions.len(),
+                        if wrong_permissions.len() == 1 {
+                            "bridge does"
+                        } else {
+                            "bridges do"
+                        },
+                        mode,
+                        wrong_permissions
+                            .iter()
+                            .map(|path| format!("'{}'", path.display()))
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ),
+                    recommendation: String::from(
+                        "Rerun 'yabridgectl sync', or run 'yabridgectl doctor --fix=permissions' \
+                         to only change the permissions.",
+                    ),
+                });
+            }
+        }
+    }
+
+    // Wine would silently create a new prefix here the first time yabridge runs
+    let wine_prefix = util::wine_prefix();
+    if !wine_prefix.join("system.reg").exists() {
+        notes.push(format!(
+            "'{}' is not an initialized Wine prefix, it will be created the first time Wine runs",
+            wine_prefix.display()
+        ));
+    }
+
+    let report = DoctorReport {
+        plugins: num_plugins
+            .iter()
+            .map(|(arch, count)| (arch.to_string(), *count))
+            .collect(),
+        hosts,
+        wine_prefix_arch: util::get_wine_prefix_arch().to_string(),
+        wine_prefix,
+        problems,
+        notes,
+        fixed,
+    };
+    if options.json {
+        println!(
+            "{}",
+            serde_jsonrc::to_string(&report).context("Could not format JSON")?
+        );
+    } else {
+        let num_checked: usize = num_plugins.values().sum();
+        println!(
+            "Checked {} {} from {} plugin {}",
+            num_checked,
+            if num_checked == 1 {
+                "plugin"
+            } else {
+                "plugins"
+            },
+            results.len(),
+            if results.len() == 1 {
+                "directory"
+            } else {
+                "directories"
+            }
+        );
+        for (arch, count) in &report.plugins {
+            println!("- {arch}: {count}");
+        }
+        for (host_name, host) in &report.hosts {
+            println!(
+                "{host_name}: {}",
+                host.map(|path| format!("'{}'", path.display()))
+                    .unwrap_or_else(|| "<not found>".red().to_string())
+            );
+        }
+        println!(
+            "Wine prefix: '{}' ({})",
+            report.wine_prefix.display(),
+            report.wine_prefix_arch
+        );
+
+        println!();
+        for change in &report.fixed {
+            println!("{} {}", "Fixed:".green(), change);
+        }
+        if !report.fixed.is_empty() {
+            println!();
+        }
+        if report.problems.is_empty() {
+            println!("{}", "No problems found".green());
+        }
+        for problem in &report.problems {
+            println!("{} {}", "Problem:".red(), problem.message);
+            println!("  {}\n", problem.recommendation);
+        }
+        for note in &report.notes {
+            println!("{} {}", "Note:".bright_white(), note);
+        }
+    }
+
+    // The report is also written when problems were found, since that's when it's needed the most
+    if let Some(report_path) = options.report {
+        let report_path = write_diagnostic_report(config, options, &report, report_path)?;
+        eprintln!("Wrote a diagnostic report to '{}'", report_path.display());
+    }
+
+    match report.problems.len() {
+        0 => Ok(()),
+        1 => anyhow::bail!("Found 1 problem"),
+        n => anyhow::bail!("Found {n} problems"),
+    }
+}
+
+/// Add a line to the login shell's profile that appends `directory` to the `PATH` environment
+/// variable, after asking for confirmation. Returns the path to the profile, or `None` if the login
+/// shell is not supported or if the user declined. Nothing is written if the profile already
+/// contains the directory.
+fn add_to_search_path(directory: &Path) -> Result<Option<PathBuf>> {
+    let home = match env::var_os("HOME") {
+        Some(home) => PathBuf::from(home),
+        None => return Ok(None),
+    };
+    let shell = env::var("SHELL").unwrap_or_default();
+    let (profile, line) = match Path::new(&shell).file_name().and_then(|name| name.to_str()) {
+        Some("bash") if home.join(".bash_profile").exists() => (
+            home.join(".bash_profile"),
+            format!("export PATH=\"$PATH:{}\"", directory.display()),
+        ),
+        Some("ash" | "bash" | "dash" | "ksh" | "sh") => (
+            home.join(".profile"),
+            format!("export PATH=\"$PATH:{}\"", directory.display()),
+        ),
+        Some("zsh") => (
+            home.join(".zprofile"),
+            format!("export PATH=\"$PATH:{}\"", directory.display()),
+        ),
+        Some("fish") => (
+            home.join(".config/fish/conf.d/yabridge.fish"),
+            format!("set -gx PATH $PATH '{}'", directory.display()),
+        ),
+        _ => return Ok(None),
+    };
+
+    let contents = if profile.exists() {
+        util::read_to_string(&profile)?
+    } else {
+        String::new()
+    };
+    if contents.lines().any(|existing_line| existing_line == line) {
+        return Ok(Some(profile));
+    }
+
+    if !util::confirm(&format!(
+        "\nAdd '{}' to '{}'? Entering anything other than YES will leave the file as is",
+        line,
+        profile.display()
+    )) {
+        return Ok(None);
+    }
+
+    let separator = if contents.is_empty() || contents.ends_with('\n') {
+        ""
+    } else {
+        "\n"
+    };
+    util::create_dir_all(profile.parent().unwrap())?;
+    util::write(
+        &profile,
+        format!("{contents}{separator}\n# Added by yabridgectl\n{line}\n"),
+    )?;
+
+    Ok(Some(profile))
+}
+
+/// Write a [`DiagnosticReport`] containing `doctor_report` to `path`, or to a timestamped file in
+/// `path` if it's a directory. Returns the path of the written file. Failures in the individual
+/// diagnostics are included in the report instead of aborting.
+fn write_diagnostic_report(
+    config: &Config,
+    options: &DoctorOptions,
+    doctor_report: &DoctorReport,
+    path: &Path,
+) -> Result<PathBuf> {
+    let generated_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    let report_path = if path.is_dir() {
+        path.join(format!("yabridgectl-report-{generated_at}.json"))
+    } else {
+        path.to_owned()
+    };
+
+    // The escape codes would otherwise end up in the report
+    colored::control::set_override(false);
+
+    let mut config_json: Vec<u8> = Vec::new();
+    let effective_config =
+        match show_config(config, &ShowConfigOptions { json: true }, &mut config_json) {
+            Ok(()) => {
+                serde_jsonrc::from_slice(&config_json).context("Could not parse the config")?
+            }
+            Err(err) => serde_jsonrc::Value::String(format!("Error: {err:#}")),
+        };
+
+    let mut status: Vec<u8> = Vec::new();
+    if let Err(err) = show_status(
+        config,
+        &StatusOptions {
+            check_host_match: false,
+            group: None,
+            exclude_dirs: Vec::new(),
+            group_by: StatusGrouping::Directory,
+            include_hidden: options.include_hidden,
+            no_recurse: options.no_recurse,
+            no_cache: false,
+            skip_system_dlls: false,
+            sort: StatusSorting::Name,
+            traversal: Traversal::DepthFirst,
+            time: false,
+            verbose: true,
+            json: false,
+            json_stream: false,
+        },
+        &mut status,
+    ) {
+        writeln!(status, "Error: {err:#}")?;
+    }
+
+    let report = DiagnosticReport {
+        generated_at,
+        yabridgectl_version: YABRIDGE_VERSION,
+        wine_version: util::wine_version().ok(),
+        config: effective_config,
+        status: String::from_utf8_lossy(&status).into_owned(),
+        doctor: doctor_report,
+        index: read_raw_index().ok().map(|(_, index)| index),
+    };
+    util::write_atomic(
+        &report_path,
+        serde_jsonrc::to_string_pretty(&report).context("Could not format JSON")?,
+    )
+    .with_context(|| format!("Could not write the report to '{}'", report_path.display()))?;
+
+    Ok(report_path)
+}
+
+/// Options passed to `yabridgectl stats`, see `main()` for the definitions of these options.
+pub struct StatsOptions {
+    pub include_hidden: bool,
+    pub no_recurse: bool,
+    pub json: bool,
+}
+
+/// The aggregate numbers printed by `yabridgectl stats`. This is also the format used for
+/// `--json`.
+#[derive(Serialize, Default)]
+struct Stats {
+    num_directories: usize,
+    num_plugins: usize,
+    /// The number of plugins per plugin format.
+    kinds: BTreeMap<String, usize>,
+    /// The number of plugins per architecture, keyed by `LibArchitecture`'s `Display`
+    /// implementation.
+    architectures: BTreeMap<String, usize>,
+    /// The number of plugins per installation status, using the same names as in
+    /// `yabridgectl status`.
+    statuses: BTreeMap<String, usize>,
+    /// The number of leftover files that no longer belong to a plugin. These would be removed by
+    /// `yabridgectl sync --prune`. This is `None` if yabridge's files could not be found.
+    num_orphans: Option<usize>,
+    /// The combined size of all bridges in bytes. Reflinked copies are counted at their full size,
+    /// so this is an upper bound.
+    bridges_size: u64,
+    /// When `yabridgectl sync` last finished, in seconds since the Unix epoch.
+    last_sync: Option<u64>,
+}
+
+/// Print aggregate numbers for the entire setup. Unlike `yabridgectl status`, this doesn't list
+/// the individual plugins.
+pub fn show_stats(config: &Config, options: &StatsOptions) -> Result<()> {
+    let results = config
+        .search_directories(&SearchOverrides {
+            no_recurse: options.no_recurse,
+            include_hidden: options.include_hidden,
+            ..Default::default()
+        })
+        .context("Failure while searching for plugins")?;
+    let index = Index::read()?;
+    let files = config.files().ok();
+
+    let mut stats = Stats {
+        num_directories: results.len(),
+        last_sync: index.last_sync,
+        ..Default::default()
+    };
+    for (_, search_results) in &results {
+        for (_, (plugin, status)) in search_results.installation_status(config, files.as_ref()) {
+            stats.num_plugins += 1;
+            *stats
+                .kinds
+                .entry(String::from(match plugin {
+                    Plugin::Vst2(_) => "VST2",
+                    Plugin::Vst3(_) => "VST3",
+                    Plugin::Clap(_) => "CLAP",
+                }))
+                .or_default() += 1;
+            *stats
+                .architectures
+                .entry(plugin.architecture().to_string())
+                .or_default() += 1;
+            *stats.statuses.entry(status.to_string()).or_default() += 1;
+
+            if let InstallState::Copy(bridge_path, _) = &status {
+                stats.bridges_size +=
+                    fs::metadata(bridge_path).map_or(0, |metadata| metadata.len());
+            }
+        }
+    }
+    // These are the same leftover files `yabridgectl sync` would report
+    stats.num_orphans = files.as_ref().map(|files| {
+        plan::plan_sync(config, files, &results, &index, &PlanOptions::default())
+            .orphans
+            .len()
+    });
+
+    if options.json {
+        println!(
+            "{}",
+            serde_jsonrc::to_string(&stats).context("Could not format JSON")?
+        );
+
+        return Ok(());
+    }
+
+    println!(
+        "{} {} in {} plugin {}",
+        stats.num_plugins,
+        if stats.num_plugins == 1 {
+            "plugin"
+        } else {
+            "plugins"
+        },
+        stats.num_directories,
+        if stats.num_directories == 1 {
+            "directory"
+        } else {
+            "directories"
+        }
+    );
+    for (label, counts) in [
+        ("By format", &stats.kinds),
+        ("By architecture", &stats.architectures),
+        ("By status", &stats.statuses),
+    ] {
+        if counts.is_empty() {
+            continue;
+        }
+
+        println!("\n{label}:");
+        for (name, count) in counts {
+            println!("- {name}: {count}");
+        }
+    }
+
+    println!();
+    match stats.num_orphans {
+        Some(num_orphans) => println!(
+            "Leftover files: {}{}",
+            num_orphans,
+            if num_orphans > 0 {
+                " (remove these with 'yabridgectl sync --prune')"
+            } else {
+                ""
+            }
+        ),
+        None => println!("Leftover files: <unknown>"),
+    }
+    println!(
+        "Disk space used by bridges: {:.1} MiB",
+        stats.bridges_size as f64 / (1024.0 * 1024.0)
+    );
+    match stats.last_sync {
+        Some(last_sync) => {
+            let elapsed = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |now| now.as_secs().saturating_sub(last_sync));
+            println!("Last sync: {}", format_elapsed(elapsed));
+        }
+        None => println!("Last sync: <unknown>"),
+    }
+
+    Ok(())
+}
+
+/// Format a number of seconds as a rough relative time, like `3 days ago`.
+fn format_elapsed(seconds: u64) -> String {
+    let (amount, unit) = match seconds {
+        0..=59 => return String::from("just now"),
+        60..=3599 => (seconds / 60, "minute"),
+        3600..=86399 => (seconds / 3600, "hour"),
+        _ => (seconds / 86400, "day"),
+    };
+
+    format!(
+        "{} {}{} ago",
+        amount,
+        unit,
+        if amount == 1 { "" } else { "s" }
+    )
+}
+
+/// Options passed to `yabridgectl sync`, see `main()` for the definitions of these options.
+#[derive(Default)]
+pub struct SyncOptions {
+    pub fail_fast: bool,
+    pub force: bool,
+    pub ignore_errors_exit_zero: bool,
+    pub group: Option<String>,
+    pub exclude_dirs: Vec<PathBuf>,
+    pub include_hidden: bool,
+    pub install_missing_only: bool,
+    pub keep_first: bool,
+    pub no_recurse: bool,
+    pub traversal: Traversal,
+    pub device_concurrency: DeviceConcurrency,
+    pub min_size: Option<u64>,
+    pub max_size: Option<u64>,
+    /// Overrides the `max_plugins` setting for this sync.
+    pub max_plugins: Option<usize>,
+    pub skip_system_dlls: bool,
+    /// Remove the bridges for `.dll` files that don't export any VST2 entry points.
+    pub skip_non_vst: bool,
+    pub no_verify: bool,
+    /// Set up plugins even if there is no Wine host with the plugin's architecture.
+    pub no_arch_check: bool,
+    pub json: bool,
+    pub preserve_mtime: bool,
+    pub prune: bool,
+    /// Print the execution plan instead of syncing.
+    pub jobs_report: bool,
+    /// Don't set up plugins that haven't been modified within this period, and prune their bridges.
+    pub older_than: Option<Duration>,
+    pub relative_symlinks: Option<bool>,
+    pub time: bool,
+    pub verbose: bool,
+}
+
+/// Set up yabridge for all Windows VST2 plugins in the plugin directories. Will also remove orphan
+/// `.so` files if the prune option is set.
+pub fn do_sync(config: &mut Config, options: &SyncOptions) -> Result<()> {
+    // This needs to stay the very first thing we do. If yabridge's libraries can't be found, then
+    // we should abort with a `MissingChainloaderError` before anything is changed on disk instead
+    // of creating bridges that point to files that don't exist.
+    let files: YabridgeFiles = config.files()?;
+    if options.jobs_report {
+        return print_sync_plan(config, options);
+    }
+
+    let vst2_chainloader_hash = util::hash_file(&files.vst2_chainloader)?;
+    let vst3_chainloader_hash = files
+        .vst3_chainloader
+        .as_ref()
+        .map(|(path, _)| util::hash_file(path))
+        .transpose()?;
+    let clap_chainloader_hash = files
+        .clap_chainloader
+        .as_ref()
+        .map(|(path, _)| util::hash_file(path))
+        .transpose()?;
+
+    print_vst2_location_override(config, &mut io::stdout())?;
+
+    // Better not add another plugin format!
+    match (&files.vst3_chainloader, &files.clap_chainloader) {
+        (Some((vst3_chainloader_path, _)), Some((clap_chainloader_path, _))) => {
+            println!("Setting up VST2, VST3, and CLAP plugins using:");
+            println!("- {}", files.vst2_chainloader.display());
+            println!("- {}", vst3_chainloader_path.display());
+            println!("- {}\n", clap_chainloader_path.display());
+        }
+        (Some((vst3_chainloader_path, _)), None) => {
+            println!("Setting up VST2 and VST3 plugins using:");
+            println!("- {}", files.vst2_chainloader.display());
+            println!("- {}\n", vst3_chainloader_path.display());
+        }
+        (None, Some((clap_chainloader_path, _))) => {
+            println!("Setting up VST2 and CLAP plugins using:");
+            println!("- {}", files.vst2_chainloader.display());
+            println!("- {}\n", clap_chainloader_path.display());
+        }
+        (None, None) => {
+            println!("Setting up VST2 plugins using:");
+            println!("- {}\n", files.vst2_chainloader.display());
+        }
+    }
+
+    // Pressing Ctrl+C stops the sync after the current file operation has finished, so the bridges
+    // and the index never end up in an inconsistent state
+    util::handle_interrupts()?;
+
+    let mut timings = Timings::default();
+    let indexing_start_time = Instant::now();
+    let results = config
+        .search_directories(&SearchOverrides {
+            no_recurse: options.no_recurse,
+            include_hidden: options.include_hidden,
+            traversal: options.traversal,
+            group: options.group.clone(),
+            exclude_dirs: options.exclude_dirs.clone(),
+            device_concurrency: options.device_concurrency,
+            min_size: options.min_size,
+            max_size: options.max_size,
+            skip_system_dlls: options.skip_system_dlls,
+            ..Default::default()
+        })
+        .context("Failure while searching for plugins")?;
+    timings.indexing = indexing_start_time.elapsed();
+    let filesystem_start_time = Instant::now();
+    if util::is_interrupted() {
+        return Err(InterruptedError.into());
+    }
+
+    // Accidentally adding a directory like the home directory could otherwise result in thousands
+    // of bridges being created all over the place. Nothing has been written at this point.
+    let num_plugins: usize = results
+        .iter()
+        .map(|(_, search_results)| search_results.plugins.len())
+        .sum();
+    if let Some(max_plugins) = options.max_plugins.or(config.max_plugins) {
+        if num_plugins > max_plugins && !options.force {
+            anyhow::bail!(
+                "Found {num_plugins} plugins, which is more than the limit of {max_plugins}. \
+                 Nothing has been changed. Check whether all of the plugin directories listed by \
+                 'yabridgectl list' are correct. If they are, then rerun this command with a \
+                 higher '--max-plugins' limit, or with '--force' to ignore the limit. The limit \
+                 can be changed permanently with 'yabridgectl set --max-plugins <n>'."
+            );
+        }
+    }
+    if options.verbose {
+        print_exclusions(
+            config,
+            &config.excluded_plugin_dirs(&options.exclude_dirs),
+            options.skip_system_dlls || config.skip_system_dlls,
+            options.min_size.or(config.min_plugin_size),
+            options.max_size.or(config.max_plugin_size),
+        );
+    }
+
+    // Used to keep track of which version of yabridge created each bridge
+    let mut index = Index::read()?;
+    // Users who already set up plugins with an older version of yabridgectl don't need the message
+    // printed after the first sync
+    let is_first_sync = index.bridges.is_empty() && !index.first_sync_message_shown;
+    // The Windows plugin files are symlinked to the bridged plugins. These symlinks can optionally
+    // use relative paths so they keep working when the plugins and the plugin directories are
+    // moved together.
+    let symlink_method = if options
+        .relative_symlinks
+        .unwrap_or(config.relative_symlinks)
+    {
+        InstallationMethod::RelativeSymlink
+    } else {
+        InstallationMethod::Symlink
+    };
+    let copy_options = InstallOptions {
+        force: options.force,
+        preserve_mtime: options.preserve_mtime,
+        mode: config.bridge_mode,
+        overwrite_unrelated: config.overwrite_unrelated_files,
+        method: InstallationMethod::Copy,
+    };
+    // The Windows plugins are always symlinked again, but the `Resources` directories are only
+    // recreated with `--force`
+    let symlink_options = InstallOptions {
+        force: true,
+        mode: None,
+        method: symlink_method,
+        ..copy_options
+    };
+    let resources_options = InstallOptions {
+        force: options.force,
+        ..symlink_options
+    };
+    // This is the dry-run backend when using `--dry-run`
+    let backend = filesystem::current();
 
     // Before doing anything, make sure `~/.{clap,vst,vst3}/yabridge` are not symlinks to one of the
     // plugin directories. See
     // https://github.com/robbert-vdh/yabridge/issues/185#issuecomment-1166274104.
-    let bail_if_unsafe_symlink = |plugin_home: &Path, plugin_home_display| {
+    let bail_if_unsafe_symlink = |plugin_home: &Path, plugin_home_display: &str| {
         if let Ok(canonical_plugin_home) = fs::canonicalize(&plugin_home) {
             if canonical_plugin_home != plugin_home {
                 for plugin_dir in &config.plugin_dirs {
@@ -323,11 +4033,11 @@ pub fn do_sync(config: &mut Config, options: &SyncOptions) -> Result<()> {
         Ok(())
     };
 
-    let vst2_home = yabridge_vst2_home();
-    let vst3_home = yabridge_vst3_home();
+    let vst2_home = yabridge_vst2_home(config);
+    let vst3_home = yabridge_vst3_home(config);
     let clap_home = yabridge_clap_home();
-    bail_if_unsafe_symlink(&vst2_home, "~/.vst/yabridge")?;
-    bail_if_unsafe_symlink(&vst3_home, "~/.vst3/yabridge")?;
+    bail_if_unsafe_symlink(&vst2_home, &vst2_home.display().to_string())?;
+    bail_if_unsafe_symlink(&vst3_home, &vst3_home.display().to_string())?;
     bail_if_unsafe_symlink(&clap_home, "~/.clap/yabridge")?;
 
     // Keep track of some global statistics
@@ -346,438 +4056,777 @@ pub fn do_sync(config: &mut Config, options: &SyncOptions) -> Result<()> {
     let mut new_plugins: HashSet<PathBuf> = HashSet::new();
     // The files we skipped during the scan because they turned out to not be plugins
     let mut skipped_dll_files: Vec<PathBuf> = Vec::new();
-    // `.so` files and unused files/bundles we found during scanning that don't belong to a known
-    // plugin. `~/{.clap,.vst,.vst3}/yabridge` will be searched for these files after setting up all
-    // plugins.
-    let mut orphan_files: Vec<NativeFile> = Vec::new();
-    // When using the centralized VST2 installation location in `~/.vst/yabridge` we'll want to
-    // track all unmanaged files in that directory and add them to the orphans list
-    let mut known_centralized_vst2_files: HashSet<PathBuf> = HashSet::new();
-    // Since VST3 bundles contain multiple files from multiple sources (native library files from
-    // yabridge, and symlinks to Windows VST3 modules or bundles), cleaning up orphan VST3 files is
-    // a bit more complicated. We want to clean both `.vst3` bundles that weren't used by anything
-    // during the syncing process, so we'll keep track of which VST3 files we touched per-bundle. We
-    // can then at the end remove all unkonwn bundles, and all unkonwn files within a bundle.
-    let mut known_centralized_vst3_files: HashMap<PathBuf, HashSet<PathBuf>> = HashMap::new();
-    // Similar for CLAP, but since CLAP doesn't use bundles this works the same way as with VST2.
-    let mut known_centralized_clap_files: HashSet<PathBuf> = HashSet::new();
-    for (path, search_results) in results {
-        // Orphan files in the centralized directories need to be detected separately
-        orphan_files.extend(
-            search_results
-                .vst2_inline_orphans(config)
-                .into_iter()
-                .cloned(),
+    // Hidden directories that were not searched, only shown with `--verbose`
+    let mut skipped_hidden_dirs: Vec<PathBuf> = Vec::new();
+    // Plugin files matching one of the exclude patterns, also only shown with `--verbose`
+    let mut excluded_files: Vec<PathBuf> = Vec::new();
+    // Plugin files outside of the size limits along with their sizes, also only shown with
+    // `--verbose`
+    let mut size_filtered_files: Vec<(PathBuf, u64)> = Vec::new();
+    // Libraries that are likely dependencies of the plugins, also only shown with `--verbose`
+    let mut system_dll_files: Vec<PathBuf> = Vec::new();
+    // Files and directories matching a `.yabridgeignore` file, also only shown with `--verbose`
+    let mut ignored_paths: Vec<PathBuf> = Vec::new();
+    // The number of files skipped for every reason, summarized at the end
+    let mut skip_counter = SkipCounter::default();
+    // Plugins that could not be set up because of an error. These are only collected when not using
+    // `--fail-fast`.
+    let mut failed_plugins: Vec<PathBuf> = Vec::new();
+    // Plugins that were skipped because there is no Wine host with a matching architecture. A
+    // bridge for these plugins would never be able to load them.
+    let mut hostless_plugins: Vec<(PathBuf, LibArchitecture)> = Vec::new();
+
+    // Which plugins should be set up and which files are leftovers is decided up front. This is
+    // shared with `yabridgectl diff` and `yabridgectl stats`.
+    let plan = plan::plan_sync(
+        config,
+        &files,
+        &results,
+        &index,
+        &PlanOptions {
+            force: options.force,
+            keep_first: options.keep_first,
+            install_missing_only: options.install_missing_only,
+            older_than: options.older_than,
+            no_arch_check: options.no_arch_check,
+            skip_non_vst: options.skip_non_vst,
+            partial: options.install_missing_only
+                || options.group.is_some()
+                || !options.exclude_dirs.is_empty(),
+        },
+    );
+    for warning in &plan.warnings {
+        eprintln!("{warning}\n");
+    }
+    // Existing files that were not created by yabridge and that would have been replaced by a
+    // bridge. See the `overwrite_unrelated_files` option. Most of these are found while planning.
+    let mut conflicting_files: Vec<PathBuf> = plan.conflicting_files.clone();
+    for ((path, search_results), directory) in results.iter().zip(&plan.directories) {
+        if util::is_interrupted() {
+            break;
+        }
+
+        let directory_start_time = Instant::now();
+        timings
+            .indexing_per_directory
+            .push((path.to_path_buf(), search_results.search_time));
+
+        // Read-only directories always use the centralized VST2 location
+        let vst2_location = directory.vst2_location;
+
+        skip_counter.add(SkipReason::Blacklisted, search_results.num_blacklisted);
+        skip_counter.add(SkipReason::Excluded, search_results.excluded_files.len());
+        skip_counter.add(
+            SkipReason::OutsideSizeLimits,
+            search_results.size_filtered_files.len(),
         );
-        skipped_dll_files.extend(search_results.skipped_files);
+        skip_counter.add(
+            SkipReason::SystemLibrary,
+            search_results.system_dll_files.len(),
+        );
+        skip_counter.add(SkipReason::Ignored, search_results.ignored_paths.len());
+        skip_counter.add(SkipReason::Hidden, search_results.skipped_hidden_dirs.len());
+        skip_counter.add(SkipReason::Unreadable, search_results.num_unreadable);
+        skip_counter.add(SkipReason::NotAPlugin, search_results.skipped_files.len());
+        skipped_dll_files.extend(search_results.skipped_files.iter().cloned());
+        skipped_hidden_dirs.extend(search_results.skipped_hidden_dirs.iter().cloned());
+        excluded_files.extend(search_results.excluded_files.iter().cloned());
+        size_filtered_files.extend(search_results.size_filtered_files.iter().cloned());
+        system_dll_files.extend(search_results.system_dll_files.iter().cloned());
+        ignored_paths.extend(search_results.ignored_paths.iter().cloned());
+
+        // If a directory suddenly can't be read or no longer contains any plugins, then we'll keep
+        // the old information in the index so the directory stays protected until it has been
+        // mounted again. If the directory can still be read and it still contains plugins, then
+        // the missing plugins have genuinely been removed and pruning can go ahead.
+        let plugins_hash = files::hash_plugin_paths(&search_results.plugins, path);
+        let previous_info = index.directory(path).cloned();
+        if !plan.possibly_unmounted_dirs.iter().any(|dir| dir == path) {
+            index.record_directory(
+                path.to_path_buf(),
+                &search_results.plugins,
+                &search_results.directories,
+                !(options.no_recurse
+                    || options.include_hidden
+                    || options.min_size.is_some()
+                    || options.max_size.is_some()
+                    || options.skip_system_dlls),
+            );
+        }
 
         if options.verbose {
             // Always print these paths with trailing slashes for consistency's sake because paths
             // can be added both with and without a trailing slash
-            println!("{}", path.join("").display());
+            let plugins_changed = matches!(
+                previous_info.and_then(|info| info.plugins_hash),
+                Some(previous_hash) if previous_hash != plugins_hash
+            );
+            if plugins_changed {
+                println!(
+                    "{} {}",
+                    path.join("").display(),
+                    "(the plugins changed since the last sync)".bright_white()
+                );
+            } else {
+                println!("{}", path.join("").display());
+            }
         }
 
-        for plugin in search_results.plugins {
-            // If verbose mode is enabled we'll print the path to the plugin after setting it up
-            let plugin_path: PathBuf = match plugin {
-                // VST2 plugins can be set up in either `~/.vst/yabridge` or inline with the
-                // plugin's `.dll` file
-                Plugin::Vst2(vst2_plugin) => {
-                    match config.vst2_location {
-                        Vst2InstallationLocation::Centralized => {
-                            let target_native_plugin_path = vst2_plugin.centralized_native_target();
-                            let target_windows_plugin_path =
-                                vst2_plugin.centralized_windows_target();
-                            let normalized_target_native_plugin_path =
-                                util::normalize_path(&target_native_plugin_path);
-
-                            let mut is_new = known_centralized_vst2_files
-                                .insert(target_native_plugin_path.clone());
-                            is_new |= known_centralized_vst2_files
-                                .insert(target_windows_plugin_path.clone());
-                            if !is_new {
-                                eprintln!(
-                                    "{}",
-                                    util::wrap(&format!(
-                                        "{}: '{}' has already been provided by another Wine \
-                                         prefix or plugin directory, skipping it\n",
-                                        "WARNING".red(),
-                                        target_windows_plugin_path.display(),
-                                    ))
-                                );
+        for &(plugin, action) in &directory.plugins {
+            if util::is_interrupted() {
+                break;
+            }
+
+            // Used to tell new bridges apart from updated ones in the verbose output
+            let native_target = plugin.native_target(config, vst2_location, Some(&files));
+            let was_installed = backend.symlink_metadata(&native_target).is_ok();
+            // VST2 plugins found through the `vst2_extensions` setting are marked as such
+            let vst2_extension = match plugin {
+                Plugin::Vst2(vst2_plugin) if !vst2_plugin.has_dll_extension() => {
+                    vst2_plugin.extension()
+                }
+                _ => None,
+            };
+
+            if action == PlannedAction::Skip(SkipReason::NoMatchingHost) {
+                if options.verbose {
+                    println!(
+                        "  {} {}",
+                        plugin
+                            .path()
+                            .strip_prefix(path)
+                            .unwrap_or_else(|_| plugin.path())
+                            .display(),
+                        format!("(no {} host)", plugin.architecture()).red()
+                    );
+                }
 
-                                continue;
+                hostless_plugins.push((plugin.path().to_owned(), plugin.architecture()));
+            }
+            if let PlannedAction::Skip(reason) = action {
+                skip_counter.increment(reason);
+                continue;
+            }
+
+            // Failing to set up a single plugin doesn't abort the syncing process unless
+            // `--fail-fast` was passed
+            let original_plugin_path = plugin.path().to_owned();
+            let setup_result = (|| -> Result<PathBuf> {
+                match plugin {
+                    // VST2 plugins can be set up in either `~/.vst/yabridge` or inline with the
+                    // plugin's `.dll` file
+                    Plugin::Vst2(vst2_plugin) => {
+                        match vst2_location {
+                            Vst2InstallationLocation::Centralized => {
+                                let target_native_plugin_path =
+                                    vst2_plugin.centralized_native_target(config, Some(&files));
+                                let target_windows_plugin_path =
+                                    vst2_plugin.centralized_windows_target(config, Some(&files));
+                                let normalized_target_native_plugin_path =
+                                    util::normalize_path(&target_native_plugin_path);
+
+                                // In the centralized mode we'll create a copy of
+                                // `libyabridge-chainloader-vst2.so` to (a subdirectory of)
+                                // `~/.vst/yabridge`, and then we'll symlink the Windows VST2 plugin
+                                // `.dll` file right next to it
+                                util::create_dir_all(target_native_plugin_path.parent().unwrap())?;
+                                if install_file(
+                                    backend,
+                                    &copy_options,
+                                    &files.vst2_chainloader,
+                                    Some(vst2_chainloader_hash),
+                                    &target_native_plugin_path,
+                                )? {
+                                    new_plugins
+                                        .insert(normalized_target_native_plugin_path.clone());
+                                } else {
+                                    skip_counter.increment(SkipReason::UpToDate);
+                                }
+                                managed_plugins.insert(normalized_target_native_plugin_path);
+
+                                install_file(
+                                    backend,
+                                    &symlink_options,
+                                    &vst2_plugin.path,
+                                    None,
+                                    &target_windows_plugin_path,
+                                )?;
                             }
+                            Vst2InstallationLocation::Inline => {
+                                let target_path = vst2_plugin.inline_native_target();
+                                let normalized_target_path = util::normalize_path(&target_path);
 
-                            // In the centralized mode we'll create a copy of
-                            // `libyabridge-chainloader-vst2.so` to (a subdirectory of)
-                            // `~/.vst/yabridge`, and then we'll symlink the Windows VST2 plugin
-                            // `.dll` file right next to it
-                            util::create_dir_all(target_native_plugin_path.parent().unwrap())?;
-                            if install_file(
-                                options.force,
-                                InstallationMethod::Copy,
-                                &files.vst2_chainloader,
-                                Some(vst2_chainloader_hash),
-                                &target_native_plugin_path,
-                            )? {
-                                new_plugins.insert(normalized_target_native_plugin_path.clone());
+                                // Since we skip some files, we'll also keep track of how many new
+                                // file we've actually set up
+                                if install_file(
+                                    backend,
+                                    &copy_options,
+                                    &files.vst2_chainloader,
+                                    Some(vst2_chainloader_hash),
+                                    &target_path,
+                                )? {
+                                    new_plugins.insert(normalized_target_path.clone());
+                                } else {
+                                    skip_counter.increment(SkipReason::UpToDate);
+                                }
+                                managed_plugins.insert(normalized_target_path);
                             }
-                            managed_plugins.insert(normalized_target_native_plugin_path);
+                        }
+
+                        Ok(vst2_plugin.path.clone())
+                    }
+                    // And then create merged bundles for the VST3 plugins:
+                    // https://developer.steinberg.help/display/VST/Plug-in+Format+Structure#PluginFormatStructure-MergedBundle
+                    Plugin::Vst3(module) => {
+                        let target_native_module_path =
+                            module.target_native_module_path(config, Some(&files));
+                        let target_windows_module_path = module.target_windows_module_path(config);
+                        let normalized_native_module_path =
+                            util::normalize_path(&target_native_module_path);
+
+                        // We're building a merged VST3 bundle containing both a copy or symlink to
+                        // `libyabridge-chainloader-vst3.so` and the Windows VST3 plugin. The path
+                        // to this native module will depend on whether
+                        // `libyabridge-chainloader-vst3.so` is a 32-bit or a 64-bit library file.
+                        util::create_dir_all(target_native_module_path.parent().unwrap())?;
+                        if install_file(
+                            backend,
+                            &copy_options,
+                            &files.vst3_chainloader.as_ref().unwrap().0,
+                            vst3_chainloader_hash,
+                            &target_native_module_path,
+                        )? {
+                            // We're counting the native `.so` files and not the Windows VST3
+                            // plugins because even though the 32-bit and 64-bit versions of a
+                            // plugin are technically separate plugins, we can only use one at a
+                            // time anyways because of how these bundles work
+                            new_plugins.insert(normalized_native_module_path.clone());
+                        } else {
+                            skip_counter.increment(SkipReason::UpToDate);
+                        }
+                        managed_plugins.insert(normalized_native_module_path.clone());
+
+                        // We'll then symlink the Windows VST3 module to that bundle to create a
+                        // merged bundle:
+                        // https://developer.steinberg.help/display/VST/Plug-in+Format+Structure#PluginFormatStructure-MergedBundle
+                        util::create_dir_all(target_windows_module_path.parent().unwrap())?;
+                        install_file(
+                            backend,
+                            &symlink_options,
+                            &module.original_module_path(),
+                            None,
+                            &target_windows_module_path,
+                        )?;
+
+                        // If `module` is a bundle, then it may contain a `Resources` directory with
+                        // screenshots and documentation
+                        // TODO: Also symlink presets, but this is a bit more involved. See
+                        //       https://developer.steinberg.help/display/VST/Preset+Locations
+                        if let Some(original_resources_dir) = module.original_resources_dir() {
+                            let target_resources_dir = module.target_resources_dir(config);
 
                             install_file(
-                                true,
-                                InstallationMethod::Symlink,
-                                &vst2_plugin.path,
+                                backend,
+                                &resources_options,
+                                &original_resources_dir,
                                 None,
-                                &target_windows_plugin_path,
+                                &target_resources_dir,
                             )?;
                         }
-                        Vst2InstallationLocation::Inline => {
-                            let target_path = vst2_plugin.inline_native_target();
-                            let normalized_target_path = util::normalize_path(&target_path);
-
-                            // Since we skip some files, we'll also keep track of how many new file we've
-                            // actually set up
-                            if install_file(
-                                options.force,
-                                InstallationMethod::Copy,
-                                &files.vst2_chainloader,
-                                Some(vst2_chainloader_hash),
-                                &target_path,
-                            )? {
-                                new_plugins.insert(normalized_target_path.clone());
+
+                        // If the plugin has a VST 3.7.10 moduleinfo file, then we'll rewrite the
+                        // byte orders of the class IDs stored within the file and then write it to
+                        // the bridged VST3 bundle.
+                        // https://steinbergmedia.github.io/vst3_dev_portal/pages/Technical+Documentation/VST+Module+Architecture/ModuleInfo-JSON.html
+                        if let Some(original_moduleinfo_path) = module.original_moduleinfo_path() {
+                            let target_moduleinfo_path = module.target_moduleinfo_path(config);
+
+                            let result = util::read_to_string(&original_moduleinfo_path)
+                                .and_then(|module_info_json| {
+                                    serde_jsonrc::from_str(&module_info_json)
+                                        .context("Could not parse JSON file")
+                                })
+                                .and_then(|mut module_info: ModuleInfo| {
+                                    module_info.rewrite_uid_byte_orders()?;
+                                    Ok(module_info)
+                                })
+                                .and_then(|converted_module_info| {
+                                    let converted_json =
+                                        serde_jsonrc::to_string_pretty(&converted_module_info)
+                                            .context("Could not format JSON file")?;
+                                    util::write(&target_moduleinfo_path, converted_json)
+                                });
+                            if let Err(error) = result {
+                                eprintln!(
+                                    "Error converting '{}', skipping...\n{}",
+                                    original_moduleinfo_path.display(),
+                                    error
+                                );
                             }
-                            managed_plugins.insert(normalized_target_path);
                         }
+
+                        Ok(module.original_path().to_path_buf())
+                    }
+                    Plugin::Clap(clap_plugin) => {
+                        let target_native_plugin_path = clap_plugin.native_target(Some(&files));
+                        let target_windows_plugin_path = clap_plugin.windows_target(Some(&files));
+                        let normalized_target_native_plugin_path =
+                            util::normalize_path(&target_native_plugin_path);
+
+                        // Because CLAP uses the same file extension on all platforms, this needs to
+                        // work slightly different compared to the VST2 bridging. Here we'll create
+                        // a copy of the chainloader as a `foo.clap` file in `~/.clap/yabridge`, and
+                        // we'll then symlink the Windows `.clap` file to `foo.clap-win` next to
+                        // `foo.clap`. That allows yabridge to find the Windows CLAP plugin while
+                        // also preventing DAWs from indexing it themselves.
+                        util::create_dir_all(target_native_plugin_path.parent().unwrap())?;
+                        if install_file(
+                            backend,
+                            &copy_options,
+                            &files.clap_chainloader.as_ref().unwrap().0,
+                            clap_chainloader_hash,
+                            &target_native_plugin_path,
+                        )? {
+                            new_plugins.insert(normalized_target_native_plugin_path.clone());
+                        } else {
+                            skip_counter.increment(SkipReason::UpToDate);
+                        }
+                        managed_plugins.insert(normalized_target_native_plugin_path);
+
+                        // So this ends up symlinking the original Windows `.clap` file to a
+                        // `.clap-win` file in `~/.clap/yabridge`
+                        install_file(
+                            backend,
+                            &symlink_options,
+                            &clap_plugin.path,
+                            None,
+                            &target_windows_plugin_path,
+                        )?;
+
+                        Ok(clap_plugin.path.clone())
                     }
+                }
+            })();
+
+            // If verbose mode is enabled we'll print the path to the plugin after setting it up
+            let plugin_path: PathBuf = match setup_result {
+                Ok(plugin_path) => plugin_path,
+                // These are skipped like any other file, even when using `--fail-fast`
+                Err(err) if err.is::<ConflictError>() => {
+                    eprintln!("{}", util::wrap(&format!("{}: {}\n", "WARNING".red(), err)));
+
+                    skip_counter.increment(SkipReason::Conflict);
+                    conflicting_files.extend(
+                        err.downcast_ref::<ConflictError>()
+                            .map(|conflict| conflict.0.clone()),
+                    );
+                    continue;
+                }
+                Err(err) if options.fail_fast => {
+                    return Err(err.context(format!(
+                        "Could not set up '{}'",
+                        original_plugin_path.display()
+                    )));
+                }
+                Err(err) => {
+                    eprintln!(
+                        "{}: Could not set up '{}': {:?}\n",
+                        "ERROR".red(),
+                        original_plugin_path.display(),
+                        err
+                    );
+                    failed_plugins.push(original_plugin_path);
+                    continue;
+                }
+            };
+
+            // This reads like a diff: `+` for new bridges, `~` for bridges that were updated, and
+            // no marker for plugins that were already up to date
+            if options.verbose {
+                let marker = match (
+                    new_plugins.contains(&util::normalize_path(&native_target)),
+                    was_installed,
+                ) {
+                    (true, false) => "+".green().to_string(),
+                    (true, true) => "~".yellow().to_string(),
+                    (false, _) => String::from(" "),
+                };
+                let extension_str = match vst2_extension {
+                    Some(extension) => format!(" (VST2 plugin with the '.{extension}' extension)"),
+                    None => String::new(),
+                };
+
+                println!(
+                    "{} {}{}",
+                    marker,
+                    plugin_path
+                        .strip_prefix(path)
+                        .unwrap_or(&plugin_path)
+                        .display(),
+                    extension_str.bright_white()
+                );
+            }
+        }
+
+        if options.verbose {
+            println!();
+        }
+
+        timings
+            .filesystem_per_directory
+            .push((path.to_path_buf(), directory_start_time.elapsed()));
+    }
+
+    // Pruning and everything else after this point requires every plugin to have been processed.
+    // The bridges that have already been set up are still recorded so the next sync knows about
+    // them.
+    if util::is_interrupted() {
+        for path in &new_plugins {
+            index.record_bridge(path.clone());
+        }
+        index.write()?;
+
+        println!(
+            "\nSet up {} new or updated {} before being interrupted, rerun 'yabridgectl sync' to \
+             finish setting up the remaining plugins",
+            new_plugins.len(),
+            if new_plugins.len() == 1 {
+                "bridge"
+            } else {
+                "bridges"
+            }
+        );
+        return Err(InterruptedError.into());
+    }
+
+    // We'll print the skipped files all at once to prevetn clutter
+    let num_skipped_files = skipped_dll_files.len();
+    if options.verbose && !skipped_dll_files.is_empty() {
+        println!("Skipped files, these do not export any VST2, VST3, or CLAP entry points:");
+        for path in skipped_dll_files {
+            println!("- {} ({})", path.display(), files::non_plugin_reason(&path));
+        }
+        println!();
+    }
+    if options.verbose && !excluded_files.is_empty() {
+        println!("Excluded files, these match one of the default exclude patterns:");
+        for path in excluded_files {
+            println!("- {}", path.display());
+        }
+        println!();
+    }
+    if options.verbose && !size_filtered_files.is_empty() {
+        println!("Excluded files, these are outside of the plugin size limits:");
+        for (path, size) in size_filtered_files {
+            println!("- {} ({})", path.display(), util::format_size(size));
+        }
+        println!();
+    }
+    if options.verbose && !system_dll_files.is_empty() {
+        println!("Skipped files, these are likely dependencies rather than plugins:");
+        for path in system_dll_files {
+            println!("- {}", path.display());
+        }
+        println!();
+    }
+    if options.verbose && !ignored_paths.is_empty() {
+        println!("Ignored paths, these match a .yabridgeignore file:");
+        for path in ignored_paths {
+            println!("- {}", path.display());
+        }
+        println!();
+    }
+    if options.verbose && !skipped_hidden_dirs.is_empty() {
+        println!("Skipped hidden directories, use '--include-hidden' to search these:");
+        for path in skipped_hidden_dirs {
+            println!("- {}", path.display());
+        }
+        println!();
+    }
+
+    // Pruning while one of the plugin directories is not mounted would remove all of the bridges
+    // for that directory's plugins. Similarly, the files belonging to plugins that could not be set
+    // up would also show up as leftovers.
+    let prune =
+        options.prune && plan.possibly_unmounted_dirs.is_empty() && failed_plugins.is_empty();
+    if options.prune && !failed_plugins.is_empty() {
+        eprintln!(
+            "{}",
+            util::wrap(&format!(
+                "{}: Not pruning any files because some plugins could not be set up\n",
+                "WARNING".red()
+            ))
+        );
+    }
+    if !plan.possibly_unmounted_dirs.is_empty() {
+        eprintln!(
+            "{}",
+            util::wrap(&format!(
+                "{}: The following directories contained plugins during the last sync but are now \
+                 empty or can't be read. They may be on a drive or network share that is not \
+                 currently mounted{}:",
+                "WARNING".red(),
+                if options.prune {
+                    ", so nothing will be pruned. Rerun with '--force' to prune anyways"
+                } else {
+                    ""
+                },
+            ))
+        );
+        for path in &plan.possibly_unmounted_dirs {
+            eprintln!("- {}", path.display());
+        }
+        eprintln!();
+    }
 
-                    vst2_plugin.path
-                }
-                // And then create merged bundles for the VST3 plugins:
-                // https://developer.steinberg.help/display/VST/Plug-in+Format+Structure#PluginFormatStructure-MergedBundle
-                Plugin::Vst3(module) => {
-                    // Only set up VST3 plugins when yabridge has been compiled with VST3 support
-                    if vst3_chainloader_hash.is_none() {
-                        continue;
-                    }
+    // The conflicting files weren't created by us either, so pruning should never remove them. The
+    // plan only contains the conflicts that could be predicted.
+    let mut orphan_files = plan.orphans;
+    orphan_files.retain(|file| !conflicting_files.iter().any(|path| path == file.path()));
 
-                    let target_bundle_home = module.target_bundle_home();
-                    let target_native_module_path = module.target_native_module_path(Some(&files));
-                    let target_windows_module_path = module.target_windows_module_path();
-                    let normalized_native_module_path =
-                        util::normalize_path(&target_native_module_path);
-
-                    // 32-bit and 64-bit versions of the plugin can live inside of the same bundle),
-                    // but it's not possible to use the exact same plugin from multiple Wine
-                    // prefixes at the same time so we'll warn when that happens
-                    let managed_vst3_bundle_files = known_centralized_vst3_files
-                        .entry(target_bundle_home.clone())
-                        .or_insert_with(HashSet::new);
-                    if managed_vst3_bundle_files.contains(&target_windows_module_path) {
-                        eprintln!(
-                            "{}",
-                            util::wrap(&format!(
-                                "{}: The {} version of '{}' has already been provided by another \
-                                 Wine prefix or plugin directory, skipping '{}'\n",
-                                "WARNING".red(),
-                                module.architecture,
-                                module.target_bundle_home().display(),
-                                module.original_module_path().display(),
-                            ))
-                        );
-
-                        continue;
-                    }
+    // Leftover bridges from another yabridge installation, like a fork of yabridge, were not
+    // created by us and they may still be in use by that installation. These are never pruned.
+    let foreign_files = plan.foreign_orphans;
+    if !foreign_files.is_empty() {
+        println!(
+            "Found {} {} from another yabridge installation, these will not be pruned:",
+            foreign_files.len(),
+            if foreign_files.len() == 1 {
+                "leftover bridge"
+            } else {
+                "leftover bridges"
+            }
+        );
+        for file in &foreign_files {
+            println!("- {}", file.path().display());
+        }
+        println!();
+    }
 
-                    // We're building a merged VST3 bundle containing both a copy or symlink to
-                    // `libyabridge-chainloader-vst3.so` and the Windows VST3 plugin. The path to
-                    // this native module will depend on whether `libyabridge-chainloader-vst3.so`
-                    // is a 32-bit or a 64-bit library file.
-                    util::create_dir_all(target_native_module_path.parent().unwrap())?;
-                    if install_file(
-                        options.force,
-                        InstallationMethod::Copy,
-                        &files.vst3_chainloader.as_ref().unwrap().0,
-                        vst3_chainloader_hash,
-                        &target_native_module_path,
-                    )? {
-                        // We're counting the native `.so` files and not the Windows VST3 plugins
-                        // because even though the 32-bit and 64-bit versions of a plugin are
-                        // technically separate plugins, we can only use one at a time anyways
-                        // because of how these bundles work
-                        new_plugins.insert(normalized_native_module_path.clone());
-                    }
-                    managed_plugins.insert(normalized_native_module_path.clone());
-                    managed_vst3_bundle_files.insert(target_native_module_path);
-
-                    // We'll then symlink the Windows VST3 module to that bundle to create a merged
-                    // bundle: https://developer.steinberg.help/display/VST/Plug-in+Format+Structure#PluginFormatStructure-MergedBundle
-                    util::create_dir_all(target_windows_module_path.parent().unwrap())?;
-                    install_file(
-                        true,
-                        InstallationMethod::Symlink,
-                        &module.original_module_path(),
-                        None,
-                        &target_windows_module_path,
-                    )?;
-                    managed_vst3_bundle_files.insert(target_windows_module_path);
-
-                    // If `module` is a bundle, then it may contain a `Resources` directory with
-                    // screenshots and documentation
-                    // TODO: Also symlink presets, but this is a bit more involved. See
-                    //       https://developer.steinberg.help/display/VST/Preset+Locations
-                    if let Some(original_resources_dir) = module.original_resources_dir() {
-                        let target_resources_dir = module.target_resources_dir();
+    // With `hashed_names`, updating yabridge gives the bridges new names. The bridges and symlinks
+    // using an older name are replaced by the new ones, so they're removed even without `--prune`.
+    // Plugins that could not be set up may still need their old bridges.
+    let outdated_names: Vec<NativeFile> = if config.hashed_names && failed_plugins.is_empty() {
+        let current_names: HashSet<PathBuf> = plan
+            .known_centralized_vst2_files
+            .iter()
+            .chain(plan.known_centralized_clap_files.iter())
+            .filter_map(|path| files::strip_name_hash(path))
+            .collect();
+        let (outdated_names, other_orphans) = orphan_files.into_iter().partition(|file| {
+            !matches!(file, NativeFile::Directory(_))
+                && current_names.contains(
+                    &files::strip_name_hash(file.path()).unwrap_or_else(|| file.path().to_owned()),
+                )
+        });
+        orphan_files = other_orphans;
 
-                        install_file(
-                            false,
-                            InstallationMethod::Symlink,
-                            &original_resources_dir,
-                            None,
-                            &target_resources_dir,
-                        )?;
-                        managed_vst3_bundle_files.insert(target_resources_dir);
-                    }
+        outdated_names
+    } else {
+        Vec::new()
+    };
+    for file in &outdated_names {
+        util::remove_file(file.path())?;
+    }
+    if options.verbose && !outdated_names.is_empty() {
+        println!("Removed bridges with outdated names:");
+        for file in &outdated_names {
+            println!("{} {}", "-".red(), file.path().display());
+        }
+        println!();
+    }
 
-                    // If the plugin has a VST 3.7.10 moduleinfo file, then we'll rewrite the byte
-                    // orders of the class IDs stored within the file and then write it to the
-                    // bridged VST3 bundle.
-                    // https://steinbergmedia.github.io/vst3_dev_portal/pages/Technical+Documentation/VST+Module+Architecture/ModuleInfo-JSON.html
-                    if let Some(original_moduleinfo_path) = module.original_moduleinfo_path() {
-                        let target_moduleinfo_path = module.target_moduleinfo_path();
-
-                        let result = util::read_to_string(&original_moduleinfo_path)
-                            .and_then(|module_info_json| {
-                                serde_jsonrc::from_str(&module_info_json)
-                                    .context("Could not parse JSON file")
-                            })
-                            .and_then(|mut module_info: ModuleInfo| {
-                                module_info.rewrite_uid_byte_orders()?;
-                                Ok(module_info)
-                            })
-                            .and_then(|converted_module_info| {
-                                let converted_json =
-                                    serde_jsonrc::to_string_pretty(&converted_module_info)
-                                        .context("Could not format JSON file")?;
-                                util::write(&target_moduleinfo_path, converted_json)
-                            });
-                        match result {
-                            Ok(_) => {
-                                managed_vst3_bundle_files.insert(target_moduleinfo_path);
-                            }
-                            Err(error) => {
-                                eprintln!(
-                                    "Error converting '{}', skipping...\n{}",
-                                    original_moduleinfo_path.display(),
-                                    error
-                                );
-                            }
-                        }
-                    }
+    // The DAW integrations should only trigger a rescan if this sync actually changed something
+    let num_removed_files = outdated_names.len() + if prune { orphan_files.len() } else { 0 };
 
-                    module.original_path().to_path_buf()
-                }
-                Plugin::Clap(clap_plugin) => {
-                    // Only set up CLAP plugins when yabridge has been compiled with CLAP support
-                    if clap_chainloader_hash.is_none() {
-                        continue;
-                    }
+    // Always warn about leftover files since those might cause warnings or errors when a VST host
+    // tries to load them
+    remove_leftover_files(orphan_files, prune)?;
 
-                    let target_native_plugin_path = clap_plugin.native_target();
-                    let target_windows_plugin_path = clap_plugin.windows_target();
-                    let normalized_target_native_plugin_path =
-                        util::normalize_path(&target_native_plugin_path);
-
-                    let mut is_new =
-                        known_centralized_clap_files.insert(target_native_plugin_path.clone());
-                    is_new |=
-                        known_centralized_clap_files.insert(target_windows_plugin_path.clone());
-                    if !is_new {
-                        eprintln!(
-                            "{}",
-                            util::wrap(&format!(
-                                "{}: '{}' has already been provided by another Wine prefix or \
-                                 plugin directory, skipping it\n",
-                                "WARNING".red(),
-                                target_windows_plugin_path.display(),
-                            ))
-                        );
-
-                        continue;
-                    }
+    // Notes are kept for as long as the plugin exists, even if it's not set up right now
+    let removed_notes = index.prune_notes();
+    if !removed_notes.is_empty() {
+        println!("Removed the notes for plugins that no longer exist:");
+        for (path, note) in &removed_notes {
+            println!("- {}: {}", path.display(), note);
+        }
+        println!();
+    }
 
-                    // Because CLAP uses the same file extension on all platforms, this needs to
-                    // work slightly different compared to the VST2 bridging. Here we'll create a
-                    // copy of the chainloader as a `foo.clap` file in `~/.clap/yabridge`, and we'll
-                    // then symlink the Windows `.clap` file to `foo.clap-win` next to `foo.clap`.
-                    // That allows yabridge to find the Windows CLAP plugin while also preventing
-                    // DAWs from indexing it themselves.
-                    util::create_dir_all(target_native_plugin_path.parent().unwrap())?;
-                    if install_file(
-                        options.force,
-                        InstallationMethod::Copy,
-                        &files.clap_chainloader.as_ref().unwrap().0,
-                        clap_chainloader_hash,
-                        &target_native_plugin_path,
-                    )? {
-                        new_plugins.insert(normalized_target_native_plugin_path.clone());
-                    }
-                    managed_plugins.insert(normalized_target_native_plugin_path);
-
-                    // So this ends up symlinking the original Windows `.clap` file to a `.clap-win`
-                    // file in `~/.clap/yabridge`
-                    install_file(
-                        true,
-                        InstallationMethod::Symlink,
-                        &clap_plugin.path,
-                        None,
-                        &target_windows_plugin_path,
-                    )?;
-
-                    clap_plugin.path
-                }
-            };
+    for path in &new_plugins {
+        index.record_bridge(path.clone());
+    }
+    index.prune_bridges();
+    index.prune_directories(&config.plugin_dirs);
+    index.record_sync();
+    index.write()?;
+    timings.filesystem = Some(filesystem_start_time.elapsed());
 
-            if options.verbose {
+    // Don't mind the ugly format string, the existence of the symlink-based installation method
+    // should be hidden as much as possible until it gets removed in yabridge 4.0
+    if options.install_missing_only {
+        println!(
+            "Finished setting up {} previously missing plugins, skipped {} non-plugin .dll files",
+            new_plugins.len(),
+            num_skipped_files
+        );
+    } else {
+        println!(
+            "Finished setting up {} plugins ({} {}), skipped {} non-plugin .dll files",
+            managed_plugins.len(),
+            new_plugins.len(),
+            // With `--force` every bridge gets recreated, so calling them new would be misleading
+            if options.force { "rewritten" } else { "new" },
+            num_skipped_files
+        );
+    }
+
+    // With `--json` this summary is printed as a `{"skipped": {"<reason>": <count>}}` object so it
+    // can be parsed by other tools
+    if options.json {
+        println!(
+            "{}",
+            serde_jsonrc::to_string(&JsonSyncSummary {
+                skipped: &skip_counter,
+                failed: &failed_plugins,
+                conflicts: &conflicting_files,
+                no_matching_host: &hostless_plugins
+                    .iter()
+                    .map(|(path, _)| path.as_path())
+                    .collect::<Vec<_>>(),
+                timings: if options.time {
+                    Some(timings.to_json(config.slow_directory_threshold()))
+                } else {
+                    None
+                },
+            })
+            .context("Could not format JSON")?
+        );
+    } else if !skip_counter.is_empty() {
+        println!("Skipped files by reason:");
+        for (reason, count) in skip_counter.iter() {
+            println!("- {reason}: {count}");
+        }
+    }
+
+    if !config.daw_integrations.is_empty() && (!new_plugins.is_empty() || num_removed_files > 0) {
+        let removed_caches = daw::trigger_rescans(&config.daw_integrations)?;
+        if options.verbose && !filesystem::is_dry_run() {
+            for (integration, path) in &removed_caches {
                 println!(
-                    "  {}",
-                    plugin_path
-                        .strip_prefix(path)
-                        .unwrap_or(&plugin_path)
-                        .display()
+                    "Removed {}'s plugin cache at '{}'",
+                    integration,
+                    path.display()
                 );
             }
         }
+    }
 
-        if options.verbose {
-            println!();
+    if !conflicting_files.is_empty() && !options.json {
+        println!(
+            "Left alone because they were not created by yabridge, see the \
+             'overwrite_unrelated_files' option:"
+        );
+        for path in &conflicting_files {
+            println!("- {}", path.display());
         }
     }
 
-    // We'll print the skipped files all at once to prevetn clutter
-    let num_skipped_files = skipped_dll_files.len();
-    if options.verbose && !skipped_dll_files.is_empty() {
-        println!("Skipped files:");
-        for path in skipped_dll_files {
-            println!("- {}", path.display());
+    if !hostless_plugins.is_empty() && !options.json {
+        println!(
+            "Skipped because there is no Wine host for them, see 'yabridgectl doctor' or use \
+             '--no-arch-check' to set them up anyway:"
+        );
+        for (path, arch) in &hostless_plugins {
+            println!("- {} (no {} host)", path.display(), arch);
         }
-        println!();
     }
 
-    // We've already kept track of orphan `.dll` files in the plugin directories, but now we need to
-    // do something similar for orphan files in `~/.vst/yabridge`, `~/.vst3/yabridge`, and
-    // `~/.clap/yabridge`. For VST3 plugins we'll want to remove both unmanaged VST3 bundles in
-    // `~/.vst3/yabridge` as well as unmanged files within managed bundles. That's why we'll
-    // immediately filter out known files within VST3 bundles. For VST2 and CLAP plugins we can
-    // simply treat any file in `~/{.clap,.vst}/yabridge` that we did not add to
-    // `known_centralized_{clap,vst2}_files` as an orphan. We'll want to do this regardless of the
-    // VST2 installation location setting so switching between the two modes and then pruning works
-    // as expected.
-    // TODO: Move this elsewhere
-    let centralized_vst2_files = WalkDir::new(vst2_home)
-        .follow_links(true)
-        .same_file_system(true)
-        .into_iter()
-        .filter_map(|e| {
-            let path = match e {
-                Ok(entry) => entry.path().to_owned(),
-                Err(err) => err.path()?.to_owned(),
-            };
+    // All other plugins have been set up at this point, but the command should still fail
+    if !failed_plugins.is_empty() {
+        println!("Failed to set up:");
+        for path in &failed_plugins {
+            println!("- {}", path.display());
+        }
 
-            if !path.is_dir() && matches!(path.extension()?.to_str()?, "dll" | "so") {
-                Some(path)
+        let message = format!(
+            "{} {} could not be set up, see the errors above",
+            failed_plugins.len(),
+            if failed_plugins.len() == 1 {
+                "plugin"
             } else {
-                None
+                "plugins"
             }
-        });
-    let centralized_clap_files = WalkDir::new(clap_home)
-        .follow_links(true)
-        .same_file_system(true)
-        .into_iter()
-        .filter_map(|e| {
-            let path = match e {
-                Ok(entry) => entry.path().to_owned(),
-                Err(err) => err.path()?.to_owned(),
-            };
+        );
+        if !options.ignore_errors_exit_zero {
+            anyhow::bail!(message);
+        }
 
-            if !path.is_dir() && matches!(path.extension()?.to_str()?, "clap" | "clap-win" | "so") {
-                Some(path)
-            } else {
-                None
-            }
-        });
-    let installed_vst3_bundles = WalkDir::new(vst3_home)
-        .follow_links(true)
-        .same_file_system(true)
-        .into_iter()
-        .filter_entry(|entry| entry.file_type().is_dir())
-        .filter_map(|e| {
-            let path = match e {
-                Ok(entry) => entry.path().to_owned(),
-                Err(err) => err.path()?.to_owned(),
-            };
+        eprintln!(
+            "\n{}: {}. Exiting with a zero exit code because of '--ignore-errors-exit-zero'.\n",
+            "WARNING".red(),
+            message
+        );
+    }
 
-            if path.extension()?.to_str()? == "vst3" {
-                Some(path)
-            } else {
-                None
-            }
-        });
+    // Skipping the post-installation seting checks can be done only for this invocation of
+    // `yabridgectl sync`, or it can be skipped permanently through a config file option
+    if !(options.no_verify || config.no_verify || filesystem::is_dry_run()) {
+        // The path setup is to make sure that the `libyabridge-chainloader-{clap,vst2,vst3}.so`
+        // copies can find `yabridge-host.exe` and by extension the plugin libraries. That last part
+        // should already be the case if we get to this point though.
+        verify_path_setup(config)?;
 
-    orphan_files.extend(centralized_vst2_files.filter_map(|path| {
-        if known_centralized_vst2_files.contains(&path) {
-            None
-        } else {
-            get_file_type(path)
-        }
-    }));
-    orphan_files.extend(centralized_clap_files.filter_map(|path| {
-        if known_centralized_clap_files.contains(&path) {
-            None
-        } else {
-            get_file_type(path)
-        }
-    }));
-    for bundle_path in installed_vst3_bundles {
-        match known_centralized_vst3_files.get(&bundle_path) {
-            None => orphan_files.push(NativeFile::Directory(bundle_path)),
-            Some(managed_vst3_bundle_files) => {
-                // Find orphan files and symlinks within this bundle. We need this to be able to
-                // switch between 32-bit and 64-bit versions of both yabridge and the Windows plugin
-                orphan_files.extend(
-                    WalkDir::new(bundle_path)
-                        .follow_links(false)
-                        .into_iter()
-                        .filter_map(|e| {
-                            let path = match e {
-                                Ok(entry) => entry.path().to_owned(),
-                                Err(err) => err.path()?.to_owned(),
-                            };
-
-                            let managed_file = managed_vst3_bundle_files.contains(&path);
-                            match get_file_type(path).unwrap() {
-                                // Don't remove directories, since we're not tracking the
-                                // directories within the bundle
-                                NativeFile::Directory(_) => None,
-                                unknown_file if !managed_file => Some(unknown_file),
-                                _ => None,
-                            }
-                        }),
-                );
-            }
-        }
+        // This check is only performed once per combination of Wine and yabridge versions
+        verify_wine_setup(config)?;
+
+        // Yabridge uses D-Bus notifications to relay important information when something's very
+        // wrong, so we'll check whether `libdbus-1.so` is available (even though it would be very
+        // odd if it isn't)
+        verify_external_dependencies()?;
     }
 
-    // Always warn about leftover files since those might cause warnings or errors when a VST host
-    // tries to load them
+    // After the very first sync that set up any plugins we'll print some pointers on what to do
+    // next. This is only ever shown once.
+    if is_first_sync && !managed_plugins.is_empty() && !options.json && !filesystem::is_dry_run() {
+        println!(
+            "\n{}",
+            util::wrap(&format!(
+                "{}: yabridge has now been set up for {} plugins. Rescan your plugins in your \
+                 DAW to start using them. To bridge plugins from other locations, add those \
+                 directories with 'yabridgectl add <path>' and then run 'yabridgectl sync' \
+                 again. 'yabridgectl status' shows all plugins yabridgectl has found.",
+                "You're all set".green(),
+                managed_plugins.len()
+            ))
+        );
+
+        index.first_sync_message_shown = true;
+        index.write()?;
+    }
+
+    if options.time {
+        println!();
+        timings.print(
+            options.verbose,
+            config.slow_directory_threshold(),
+            &mut io::stdout(),
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Print the leftover files found during a sync, and remove them if `prune` is set. Directories
+/// that are empty after removing these files are also removed.
+fn remove_leftover_files(mut orphan_files: Vec<NativeFile>, prune: bool) -> Result<()> {
     if !orphan_files.is_empty() {
         let leftover_files_str = if orphan_files.len() == 1 {
             format!("{} leftover file", orphan_files.len())
         } else {
             format!("{} leftover files", orphan_files.len())
         };
-        if options.prune {
+        if prune {
             println!("Removing {}:", leftover_files_str);
         } else {
             println!(
@@ -790,8 +4839,13 @@ pub fn do_sync(config: &mut Config, options: &SyncOptions) -> Result<()> {
         //       cleaned before their parent directories
         orphan_files.sort_by(|a, b| b.path().cmp(a.path()));
         for file in orphan_files.into_iter() {
-            println!("- {}", file.path().display());
-            if options.prune {
+            // Files that are actually being removed get a red marker, like in a diff
+            if prune {
+                println!("{} {}", "-".red(), file.path().display());
+            } else {
+                println!("- {}", file.path().display());
+            }
+            if prune {
                 match &file {
                     NativeFile::Regular(path) | NativeFile::Symlink(path) => {
                         util::remove_file(path)?;
@@ -802,10 +4856,11 @@ pub fn do_sync(config: &mut Config, options: &SyncOptions) -> Result<()> {
                 }
 
                 // If the directory `file` was in is now empty, then we'll also recursively prune
-                // the empty subdirectory
-                let mut parent_dir = file.path().parent();
+                // the empty subdirectory. Nothing has actually been removed during a dry-run, so
+                // the directories wouldn't be empty yet.
+                let mut parent_dir = file.path().parent().filter(|_| !filesystem::is_dry_run());
                 while let Some(dir) =
-                    parent_dir.and_then(|dir| fs::remove_dir(dir).ok().map(|_| dir))
+                    parent_dir.and_then(|dir| util::remove_dir(dir).ok().map(|_| dir))
                 {
                     parent_dir = dir.parent();
                 }
@@ -815,73 +4870,379 @@ pub fn do_sync(config: &mut Config, options: &SyncOptions) -> Result<()> {
         println!();
     }
 
-    // Don't mind the ugly format string, the existence of the symlink-based installation method
-    // should be hidden as much as possible until it gets removed in yabridge 4.0
-    println!(
-        "Finished setting up {} plugins ({} new), skipped {} non-plugin .dll files",
-        managed_plugins.len(),
-        new_plugins.len(),
-        num_skipped_files
-    );
+    Ok(())
+}
+
+/// When the Windows plugin file belonging to `plugin` was last modified, if that can be read.
+fn plugin_modified(plugin: &Plugin) -> Option<SystemTime> {
+    let path = match plugin {
+        Plugin::Vst2(vst2_plugin) => vst2_plugin.path.clone(),
+        Plugin::Vst3(module) => module.original_module_path(),
+        Plugin::Clap(clap_plugin) => clap_plugin.path.clone(),
+    };
+
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// The execution plan printed by `yabridgectl sync --jobs-report`.
+#[derive(Serialize)]
+struct SyncPlan<'a> {
+    device_concurrency: String,
+    /// The batches of plugin directories that are searched in parallel.
+    search_batches: Vec<SearchBatch<'a>>,
+    /// The steps performed after searching the plugin directories, in order. These all happen on a
+    /// single thread.
+    steps: Vec<String>,
+}
+
+/// Print the order in which `yabridgectl sync` would search the plugin directories and what it
+/// would do after that, without doing any of it.
+fn print_sync_plan(config: &Config, options: &SyncOptions) -> Result<()> {
+    let search_batches = config.search_plan(&SearchOverrides {
+        group: options.group.clone(),
+        exclude_dirs: options.exclude_dirs.clone(),
+        device_concurrency: options.device_concurrency,
+        ..Default::default()
+    })?;
+
+    let mut steps = vec![String::from(
+        "set up the plugins one at a time, sorted by plugin directory and path",
+    )];
+    if options.install_missing_only {
+        steps.push(String::from(
+            "skip the plugins that have already been set up",
+        ));
+    }
+    if let Some(older_than) = options.older_than {
+        steps.push(format!(
+            "skip the plugins that were last modified more than {}",
+            format_elapsed(older_than.as_secs())
+        ));
+    }
+    let prune_disabled_by = if options.install_missing_only {
+        Some("--install-missing-only")
+    } else if options.group.is_some() {
+        Some("--group")
+    } else if !options.exclude_dirs.is_empty() {
+        Some("--exclude-dir")
+    } else {
+        None
+    };
+    match (options.prune, prune_disabled_by) {
+        (_, Some(option)) => steps.push(format!(
+            "don't look for leftover bridges because of '{option}'"
+        )),
+        (true, None) => steps.push(String::from(
+            "remove leftover bridges, unless a plugin directory seems to be unmounted or a \
+             plugin could not be set up",
+        )),
+        (false, None) => steps.push(String::from("report leftover bridges")),
+    }
+    steps.push(String::from("update the index"));
+    if !config.daw_integrations.is_empty() {
+        steps.push(format!(
+            "make {} rescan their plugins if anything changed",
+            display_daw_integrations(&config.daw_integrations)
+        ));
+    }
+    if !(options.no_verify || config.no_verify) {
+        steps.push(String::from("run the post-installation setup checks"));
+    }
+
+    let plan = SyncPlan {
+        device_concurrency: options.device_concurrency.to_string(),
+        search_batches,
+        steps,
+    };
+    if options.json {
+        println!(
+            "{}",
+            serde_jsonrc::to_string(&plan).context("Could not format JSON")?
+        );
 
-    // Skipping the post-installation seting checks can be done only for this invocation of
-    // `yabridgectl sync`, or it can be skipped permanently through a config file option
-    if options.no_verify || config.no_verify {
         return Ok(());
     }
 
-    // The path setup is to make sure that the `libyabridge-chainloader-{clap,vst2,vst3}.so` copies can
-    // find `yabridge-host.exe` and by extension the plugin libraries. That last part should already
-    // be the case if we get to this point though.
-    verify_path_setup(config)?;
+    println!(
+        "Searching the plugin directories with '{}' device concurrency. The batches are searched \
+         in parallel, and the directories within a batch are searched one after the other:",
+        plan.device_concurrency
+    );
+    for (batch_idx, batch) in plan.search_batches.iter().enumerate() {
+        let mut details = Vec::new();
+        if let Some(device) = &batch.device {
+            details.push(format!("device {device}"));
+        }
+        match batch.rotational {
+            Some(true) => details.push(String::from("rotational")),
+            Some(false) => details.push(String::from("not rotational")),
+            None => (),
+        }
 
-    // This check is only performed once per combination of Wine and yabridge versions
-    verify_wine_setup(config)?;
+        if details.is_empty() {
+            println!("- batch {}", batch_idx + 1);
+        } else {
+            println!("- batch {} ({})", batch_idx + 1, details.join(", "));
+        }
+        for directory in &batch.directories {
+            println!("  - {}", directory.display());
+        }
+    }
 
-    // Yabridge uses D-Bus notifications to relay important information when something's very wrong,
-    // so we'll check whether `libdbus-1.so` is available (even though it would be very odd if it
-    // isn't)
-    verify_external_dependencies()?;
+    println!("\nAfter all directories have been searched:");
+    for (step_idx, step) in plan.steps.iter().enumerate() {
+        println!("{}. {}", step_idx + 1, step);
+    }
 
     Ok(())
 }
 
+/// The summary printed at the end of `yabridgectl sync` when the `--json` option is set.
+#[derive(Serialize)]
+struct JsonSyncSummary<'a> {
+    skipped: &'a SkipCounter,
+    /// The plugins that could not be set up because of an error.
+    failed: &'a [PathBuf],
+    /// Existing files that were left alone because they were not created by yabridge.
+    conflicts: &'a [PathBuf],
+    /// Plugins that were skipped because there is no Wine host for their architecture.
+    no_matching_host: &'a [&'a Path],
+    /// Only included when the `--time` option is set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timings: Option<JsonTimings<'a>>,
+}
+
+/// Wall clock times spent in the different phases of `yabridgectl status` and `yabridgectl sync`.
+/// These are printed at the end of the command when the `--time` option is set so it's possible to
+/// tell slow disk walks apart from slow file operations.
+#[derive(Default)]
+struct Timings {
+    /// The time spent indexing and searching all plugin directories.
+    indexing: Duration,
+    /// Same as `indexing`, but for each directory. Since directories are searched in parallel, these
+    /// won't add up to `indexing`.
+    indexing_per_directory: Vec<(PathBuf, Duration)>,
+    /// The time spent setting up plugins and removing leftover files. Only set when syncing.
+    filesystem: Option<Duration>,
+    /// The time spent setting up the plugins from each directory.
+    filesystem_per_directory: Vec<(PathBuf, Duration)>,
+}
+
+/// The timings included in `yabridgectl sync --json`'s summary when the `--time` option is set, in
+/// seconds.
+#[derive(Serialize)]
+struct JsonTimings<'a> {
+    indexing: f64,
+    filesystem: Option<f64>,
+    directories: Vec<JsonDirectoryTimings<'a>>,
+}
+
+/// The timings for a single plugin directory, see [`JsonTimings`].
+#[derive(Serialize)]
+struct JsonDirectoryTimings<'a> {
+    path: &'a Path,
+    indexing: f64,
+    filesystem: Option<f64>,
+    /// Whether either of these timings exceeded the `slow_directory_threshold`.
+    slow: bool,
+}
+
+impl Timings {
+    /// The directories that took longer than `threshold` to index or to set up, along with the
+    /// longer of the two times and what that time was spent on.
+    fn slow_directories(&self, threshold: Duration) -> Vec<(&Path, Duration, &'static str)> {
+        self.indexing_per_directory
+            .iter()
+            .map(|(path, duration)| (path.as_path(), *duration, "search for"))
+            .chain(
+                self.filesystem_per_directory
+                    .iter()
+                    .map(|(path, duration)| (path.as_path(), *duration, "set up")),
+            )
+            .filter(|(_, duration, _)| *duration > threshold)
+            .collect()
+    }
+
+    /// The timings in the format used for `--json`.
+    fn to_json(&self, threshold: Duration) -> JsonTimings<'_> {
+        let slow_directories: HashSet<&Path> = self
+            .slow_directories(threshold)
+            .into_iter()
+            .map(|(path, _, _)| path)
+            .collect();
+
+        JsonTimings {
+            indexing: self.indexing.as_secs_f64(),
+            filesystem: self.filesystem.map(|duration| duration.as_secs_f64()),
+            directories: self
+                .indexing_per_directory
+                .iter()
+                .map(|(path, indexing)| JsonDirectoryTimings {
+                    path,
+                    indexing: indexing.as_secs_f64(),
+                    filesystem: self
+                        .filesystem_per_directory
+                        .iter()
+                        .find(|(other_path, _)| other_path == path)
+                        .map(|(_, duration)| duration.as_secs_f64()),
+                    slow: slow_directories.contains(path.as_path()),
+                })
+                .collect(),
+        }
+    }
+
+    /// Print the timings. With `verbose` set this also includes the timings for each directory.
+    /// Directories that took longer than `slow_threshold` are always pointed out.
+    fn print(
+        &self,
+        verbose: bool,
+        slow_threshold: Duration,
+        out: &mut dyn Write,
+    ) -> io::Result<()> {
+        writeln!(out, "Indexing: {:.2?}", self.indexing)?;
+        if verbose {
+            for (path, duration) in &self.indexing_per_directory {
+                writeln!(out, "- {}: {:.2?}", path.join("").display(), duration)?;
+            }
+        }
+
+        if let Some(filesystem) = self.filesystem {
+            writeln!(out, "Filesystem operations: {:.2?}", filesystem)?;
+            if verbose {
+                for (path, duration) in &self.filesystem_per_directory {
+                    writeln!(out, "- {}: {:.2?}", path.join("").display(), duration)?;
+                }
+            }
+        }
+
+        // A single slow directory, like one on a network share, is usually what makes the entire
+        // command slow
+        for (path, duration, activity) in self.slow_directories(slow_threshold) {
+            writeln!(
+                out,
+                "\n{}",
+                util::wrap(&format!(
+                    "{}: It took {:.2?} to {} the plugins in '{}', which is longer than the \
+                     {:?} threshold. This directory may be on a slow disk or network share.",
+                    "WARNING".red(),
+                    duration,
+                    activity,
+                    path.join("").display(),
+                    slow_threshold,
+                ))
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
 // TODO: Clean this up, in the past this was part of a yabridgectl setting and the enum was simply
 //       reused here
+#[derive(Clone, Copy)]
 enum InstallationMethod {
     Copy,
     Symlink,
+    /// Same as `Symlink`, but the symlink's target is relative to the symlink's location. See the
+    /// `relative_symlinks` config option.
+    RelativeSymlink,
 }
 
-/// Create a copy or symlink of `from` to `to`. Depending on `force`, we might not actually create a
-/// new copy or symlink if `to` matches `from_hash`.
-fn install_file(
+/// How [`install_file()`] should set up a file.
+#[derive(Clone, Copy)]
+struct InstallOptions {
+    /// Recreate the file even if it's already up to date.
     force: bool,
+    /// Give copies the same modification time as the file they were copied from. This does nothing
+    /// for symlinks.
+    preserve_mtime: bool,
+    /// The permissions copies should have, if they should be changed. This does nothing for
+    /// symlinks.
+    mode: Option<u32>,
+    /// Replace existing files that were not created by yabridge instead of returning a
+    /// [`ConflictError`].
+    overwrite_unrelated: bool,
     method: InstallationMethod,
+}
+
+/// Create a copy or symlink of `from` to `to`. Unless `options.force` is set, we might not actually
+/// create a new copy or symlink if `to` matches `from_hash`. Everything is read and written through
+/// `backend`.
+fn install_file(
+    backend: &dyn FileSystem,
+    options: &InstallOptions,
     from: &Path,
     from_hash: Option<i64>,
     to: &Path,
 ) -> Result<bool> {
+    let InstallOptions {
+        force,
+        preserve_mtime,
+        mode,
+        overwrite_unrelated,
+        method,
+    } = *options;
+
     // We'll only recreate existing files when updating yabridge, when switching between the symlink
     // and copy installation methods, or when the `force` option is set. If the target file already
     // exists and does not require updating, we'll just skip the file since some DAWs will otherwise
-    // unnecessarily reindex the file. We check `std::fs::symlink_metadata` instead of
-    // `Path::exists()` because the latter reports false for broken symlinks.
-    if let Ok(metadata) = fs::symlink_metadata(&to) {
+    // unnecessarily reindex the file. We check `symlink_metadata()` instead of `metadata()` because
+    // the latter fails for broken symlinks.
+    let link_target = match method {
+        InstallationMethod::RelativeSymlink => {
+            util::relative_path(from, to.parent().unwrap_or_else(|| Path::new("/")))
+        }
+        _ => from.to_owned(),
+    };
+    if let Ok(metadata) = backend.symlink_metadata(to) {
+        // This also applies with `force`, since that's only meant to recreate our own bridges
+        let is_unrelated = match method {
+            InstallationMethod::Copy => files::is_unrelated_file(backend, to),
+            InstallationMethod::Symlink | InstallationMethod::RelativeSymlink => {
+                files::is_unrelated_symlink_target(backend, to)
+            }
+        };
+        if !overwrite_unrelated && is_unrelated {
+            return Err(ConflictError(to.to_owned()).into());
+        }
+
         match (force, &method) {
             (false, InstallationMethod::Copy) => {
                 // If the target file is already a real file (not a symlink) and its hash is the
                 // same as that of the `from` file we're trying to copy there, then we don't have to
                 // do anything
                 if let Some(hash) = from_hash {
-                    if metadata.file_type().is_file() && util::hash_file(to)? == hash {
+                    if metadata.file_type == filesystem::FileType::File
+                        && util::hash_contents(&backend.read(to)?) == hash
+                    {
+                        // Copies made before `--preserve-mtime` was used still need their
+                        // modification times fixed, but they don't count as updated files
+                        if preserve_mtime {
+                            let mtime = backend.metadata(from)?.modified;
+                            if metadata.modified != mtime {
+                                backend.set_modified(to, mtime)?;
+                            }
+                        }
+                        // The same applies to copies made before `bridge_mode` was changed
+                        if let Some(mode) = mode {
+                            if metadata.mode != mode {
+                                backend.set_permissions(to, mode)?;
+                            }
+                        }
+
                         return Ok(false);
                     }
                 }
             }
-            (false, InstallationMethod::Symlink) => {
-                // If the target file is already a symlink to `from`, then we can skip this file
-                if metadata.file_type().is_symlink() && to.read_link()? == from {
+            (false, InstallationMethod::Symlink | InstallationMethod::RelativeSymlink) => {
+                // If the target file is already a symlink to `from`, then we can skip this file.
+                // Switching between relative and absolute symlinks will recreate the symlink.
+                if metadata.file_type == filesystem::FileType::Symlink
+                    && backend.read_link(to)? == link_target
+                {
+                    warn_unreadable_symlink(backend, to, from);
                     return Ok(false);
                 }
             }
@@ -889,17 +5250,361 @@ fn install_file(
             (true, _) => (),
         }
 
-        util::remove_file(&to)?;
+        backend.remove_file(to)?;
     };
 
     match method {
         InstallationMethod::Copy => {
-            util::copy_or_reflink(from, to)?;
+            backend.copy(from, to)?;
+            if preserve_mtime {
+                backend.set_modified(to, backend.metadata(from)?.modified)?;
+            }
+            if let Some(mode) = mode {
+                backend.set_permissions(to, mode)?;
+            }
         }
-        InstallationMethod::Symlink => {
-            util::symlink(from, to)?;
+        InstallationMethod::Symlink | InstallationMethod::RelativeSymlink => {
+            backend.symlink(&link_target, to)?;
+            warn_unreadable_symlink(backend, to, from);
         }
     }
 
     Ok(true)
 }
+
+/// Print a warning if the symlink at `symlink` can't be read through, even though it was created
+/// successfully. During a dry-run the symlink doesn't exist, so `target` is checked instead.
+fn warn_unreadable_symlink(backend: &dyn FileSystem, symlink: &Path, target: &Path) {
+    let path = if filesystem::is_dry_run() {
+        target
+    } else {
+        symlink
+    };
+    if let Err(err) = backend.verify_readable(path) {
+        eprintln!(
+            "{}: '{}' points to '{}', which can't be read: {:#}",
+            "WARNING".red(),
+            symlink.display(),
+            target.display(),
+            err
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::MissingChainloaderError;
+    use crate::error;
+    use crate::filesystem::MemoryFileSystem;
+    use crate::util::TempDir;
+
+    /// Copy files without any of the optional behavior.
+    const COPY_OPTIONS: InstallOptions = InstallOptions {
+        force: false,
+        preserve_mtime: false,
+        mode: None,
+        overwrite_unrelated: false,
+        method: InstallationMethod::Copy,
+    };
+
+    /// A filesystem containing a chainloader and a Windows plugin in `/plugins`.
+    fn test_filesystem(chainloader_contents: &[u8]) -> MemoryFileSystem {
+        let backend = MemoryFileSystem::new();
+        backend.create_dir_all(Path::new("/yabridge")).unwrap();
+        backend.create_dir_all(Path::new("/plugins")).unwrap();
+        backend
+            .write(
+                Path::new("/yabridge/libyabridge-chainloader-vst2.so"),
+                chainloader_contents,
+            )
+            .unwrap();
+        backend
+            .write(Path::new("/plugins/Plugin.dll"), b"MZ")
+            .unwrap();
+
+        backend
+    }
+
+    /// What's already at the location [`install_file()`] writes to.
+    enum Existing {
+        Nothing,
+        /// A regular file with these contents and permissions.
+        File(&'static [u8], u32),
+        Symlink(&'static str),
+    }
+
+    /// A single [`install_file()`] call, and the expected state of the target file afterwards.
+    struct InstallCase {
+        name: &'static str,
+        options: InstallOptions,
+        existing: Existing,
+        /// Whether a file should have been created, or `None` if this should be a conflict.
+        created: Option<bool>,
+        contents: &'static [u8],
+        symlink_target: Option<&'static str>,
+        mode: u32,
+    }
+
+    #[test]
+    fn install_file_only_replaces_outdated_bridges() {
+        let chainloader = Path::new("/yabridge/libyabridge-chainloader-vst2.so");
+        let bridge_contents = b"\x7fELF libyabridge-vst2.so";
+        let plugin = "/plugins/Plugin.dll";
+        let symlink = InstallOptions {
+            force: true,
+            method: InstallationMethod::Symlink,
+            ..COPY_OPTIONS
+        };
+
+        let cases = [
+            // Files and symlink targets that weren't created by yabridge are left alone, even with
+            // `force`
+            InstallCase {
+                name: "foreign file",
+                options: COPY_OPTIONS,
+                existing: Existing::File(b"\x7fELF something else", 0o644),
+                created: None,
+                contents: b"\x7fELF something else",
+                symlink_target: None,
+                mode: 0o644,
+            },
+            InstallCase {
+                name: "foreign file with force",
+                options: InstallOptions {
+                    force: true,
+                    ..COPY_OPTIONS
+                },
+                existing: Existing::File(b"\x7fELF something else", 0o644),
+                created: None,
+                contents: b"\x7fELF something else",
+                symlink_target: None,
+                mode: 0o644,
+            },
+            InstallCase {
+                name: "foreign symlink target",
+                options: symlink,
+                existing: Existing::File(b"MZ something else", 0o644),
+                created: None,
+                contents: b"MZ something else",
+                symlink_target: None,
+                mode: 0o644,
+            },
+            // Outdated bridges and symlinks pointing somewhere else are replaced
+            InstallCase {
+                name: "outdated copy",
+                options: COPY_OPTIONS,
+                existing: Existing::File(b"\x7fELF libyabridge-vst2.so, old", 0o644),
+                created: Some(true),
+                contents: bridge_contents,
+                symlink_target: None,
+                mode: 0o644,
+            },
+            InstallCase {
+                name: "other symlink",
+                options: symlink,
+                existing: Existing::Symlink("/plugins/Other.dll"),
+                created: Some(true),
+                contents: b"MZ",
+                symlink_target: Some(plugin),
+                mode: 0o777,
+            },
+            // Up to date copies are left alone, but they do get the new permissions
+            InstallCase {
+                name: "new copy",
+                options: InstallOptions {
+                    mode: Some(0o600),
+                    ..COPY_OPTIONS
+                },
+                existing: Existing::Nothing,
+                created: Some(true),
+                contents: bridge_contents,
+                symlink_target: None,
+                mode: 0o600,
+            },
+            InstallCase {
+                name: "up to date copy",
+                options: InstallOptions {
+                    mode: Some(0o600),
+                    ..COPY_OPTIONS
+                },
+                existing: Existing::File(bridge_contents, 0o644),
+                created: Some(false),
+                contents: bridge_contents,
+                symlink_target: None,
+                mode: 0o600,
+            },
+        ];
+
+        for case in cases {
+            let name = case.name;
+            let backend = test_filesystem(bridge_contents);
+            let (from, from_hash, to) = match case.options.method {
+                InstallationMethod::Copy => (
+                    chainloader,
+                    Some(util::hash_contents(bridge_contents)),
+                    Path::new("/plugins/Plugin.so"),
+                ),
+                _ => (Path::new(plugin), None, Path::new("/plugins/Symlink.dll")),
+            };
+            match case.existing {
+                Existing::Nothing => (),
+                Existing::File(contents, mode) => {
+                    backend.write(to, contents).unwrap();
+                    backend.set_permissions(to, mode).unwrap();
+                }
+                Existing::Symlink(target) => backend.symlink(Path::new(target), to).unwrap(),
+            }
+
+            let result = install_file(&backend, &case.options, from, from_hash, to);
+            match case.created {
+                Some(created) => assert_eq!(result.unwrap(), created, "{name}"),
+                None => assert!(result.unwrap_err().is::<ConflictError>(), "{name}"),
+            }
+            assert_eq!(backend.read(to).unwrap(), case.contents, "{name}");
+            assert_eq!(
+                backend.read_link(to).ok().as_deref(),
+                case.symlink_target.map(Path::new),
+                "{name}"
+            );
+            assert_eq!(
+                backend.symlink_metadata(to).unwrap().mode,
+                case.mode,
+                "{name}"
+            );
+        }
+    }
+
+    #[test]
+    fn sync_without_chainloader_changes_nothing() {
+        let yabridge_home = TempDir::new("sync-without-chainloader-home");
+        let plugin_dir = TempDir::new("sync-without-chainloader-plugins");
+        fs::write(plugin_dir.path().join("Plugin.dll"), b"MZ").unwrap();
+        // This would be pruned as a leftover if sync got that far
+        fs::write(plugin_dir.path().join("Removed.so"), b"\x7fELF").unwrap();
+
+        let mut config = Config {
+            yabridge_home: Some(yabridge_home.path().to_owned()),
+            plugin_dirs: BTreeSet::from([plugin_dir.path().to_owned()]),
+            ..Default::default()
+        };
+        let err = do_sync(
+            &mut config,
+            &SyncOptions {
+                prune: true,
+                ..Default::default()
+            },
+        )
+        .unwrap_err();
+        assert!(err.is::<MissingChainloaderError>());
+        assert_eq!(error::exit_code(&err), error::ERROR_EXIT_CODE);
+
+        let mut files: Vec<_> = fs::read_dir(plugin_dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        files.sort();
+        assert_eq!(files, ["Plugin.dll", "Removed.so"]);
+        assert_eq!(
+            fs::read(plugin_dir.path().join("Removed.so")).unwrap(),
+            b"\x7fELF"
+        );
+    }
+
+    /// Run `command` during a dry-run with `backend` in place of the real filesystem, and check
+    /// that it did not change anything. The same command is then run without `--dry-run` to make
+    /// sure it would actually have made changes.
+    fn assert_dry_run_changes_nothing(backend: MemoryFileSystem, command: impl Fn() -> Result<()>) {
+        let backend: &'static MemoryFileSystem = Box::leak(Box::new(backend));
+        let before = backend.snapshot();
+
+        filesystem::with_backend(backend, true, &command).unwrap();
+        assert_eq!(backend.snapshot(), before);
+
+        filesystem::with_backend(backend, false, &command).unwrap();
+        assert_ne!(backend.snapshot(), before);
+    }
+
+    #[test]
+    fn pruning_changes_nothing_during_dry_run() {
+        let backend = MemoryFileSystem::new();
+        backend
+            .create_dir_all(Path::new("/plugins/Nested"))
+            .unwrap();
+        backend
+            .create_dir_all(Path::new("/vst3/Old.vst3/Contents"))
+            .unwrap();
+        backend
+            .write(Path::new("/plugins/Nested/Old.so"), b"\x7fELF")
+            .unwrap();
+        backend
+            .symlink(Path::new("Old.so"), Path::new("/plugins/Nested/Link.so"))
+            .unwrap();
+
+        assert_dry_run_changes_nothing(backend, || {
+            remove_leftover_files(
+                vec![
+                    NativeFile::Regular(PathBuf::from("/plugins/Nested/Old.so")),
+                    NativeFile::Symlink(PathBuf::from("/plugins/Nested/Link.so")),
+                    NativeFile::Directory(PathBuf::from("/vst3/Old.vst3")),
+                ],
+                true,
+            )
+        });
+    }
+
+    #[test]
+    fn cleaning_the_cache_changes_nothing_during_dry_run() {
+        let xdg_dirs = yabridgectl_directories().unwrap();
+        let data_home = xdg_dirs.get_data_home();
+        let cache_home = xdg_dirs.get_cache_home();
+
+        let backend = MemoryFileSystem::new();
+        backend.create_dir_all(&data_home).unwrap();
+        backend.create_dir_all(&cache_home).unwrap();
+        backend
+            .write(&data_home.join(INDEX_FILE_NAME), b"{}")
+            .unwrap();
+        backend.write(&cache_home.join("cached"), b"").unwrap();
+
+        assert_dry_run_changes_nothing(backend, clean_cache);
+    }
+
+    #[test]
+    fn config_changes_change_nothing_during_dry_run() {
+        type Command = fn(&mut Config) -> Result<()>;
+        let commands: [(&str, Command); 4] = [
+            ("add", |config| {
+                add_directory(
+                    config,
+                    PathBuf::from("/plugins"),
+                    DirectorySettings::default(),
+                    false,
+                )
+            }),
+            ("rm", |config| {
+                remove_directory(config, Path::new("/does/not/exist"))
+            }),
+            ("config unset", |config| {
+                unset_setting(config, "skip_system_dlls")
+            }),
+            ("note", |_| {
+                note(&NoteOptions {
+                    plugin: Some(Path::new("/plugins/Plugin.dll")),
+                    note: Some("A note"),
+                    remove: false,
+                    list: false,
+                    json: false,
+                })
+            }),
+        ];
+
+        for (name, command) in commands {
+            println!("Running '{name}'");
+            assert_dry_run_changes_nothing(
+                test_filesystem(b""),
+                || command(&mut Config::default()),
+            );
+        }
+    }
+}
@@ -0,0 +1,576 @@
+// yabridge: a Wine plugin bridge
+// Copyright (C) 2020-2022 Robbert van der Helm
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Decides what `yabridgectl sync` should do with every plugin and which existing files are
+//! leftovers, without changing anything on disk. `yabridgectl diff` and `yabridgectl stats` use the
+//! same plan so they can't disagree with what `yabridgectl sync` actually does.
+
+use colored::Colorize;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use walkdir::WalkDir;
+
+use super::{host_binary, plugin_modified};
+use crate::config::{
+    yabridge_clap_home, yabridge_vst2_home, yabridge_vst3_home, Config, Vst2InstallationLocation,
+    YabridgeFiles,
+};
+use crate::error::ConflictError;
+use crate::files::{self, LibArchitecture, NativeFile, Plugin, SearchResults, SkipReason};
+use crate::filesystem;
+use crate::index::{DirectoryInfo, Index};
+use crate::util::{self, get_file_type};
+
+/// The options passed to `yabridgectl sync` that change the plan. The defaults match a plain
+/// `yabridgectl sync`.
+#[derive(Debug, Default)]
+pub struct PlanOptions {
+    pub force: bool,
+    pub keep_first: bool,
+    pub install_missing_only: bool,
+    pub older_than: Option<Duration>,
+    pub no_arch_check: bool,
+    /// Treat bridges for `.dll` files that aren't VST2 plugins as orphans. Otherwise these are left
+    /// alone for now.
+    pub skip_non_vst: bool,
+    /// Set when only some of the plugin directories are being synced, for instance because of
+    /// `--group` or `--exclude-dir`. The bridges for the other directories' plugins would look like
+    /// leftovers, so no leftovers are reported at all in that case.
+    pub partial: bool,
+}
+
+/// What should happen to a single plugin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlannedAction {
+    /// The plugin's bridge should be created, or updated if it's outdated.
+    SetUp,
+    /// The plugin should be skipped for this reason.
+    Skip(SkipReason),
+}
+
+/// The plugins from a single plugin directory along with what should happen to them.
+pub struct PlannedDirectory<'a> {
+    /// The VST2 installation location used for this directory, see
+    /// `SearchResults::vst2_location()`.
+    pub vst2_location: Vst2InstallationLocation,
+    /// The plugins in the order they should be set up in.
+    pub plugins: Vec<(&'a Plugin, PlannedAction)>,
+}
+
+/// What `yabridgectl sync` should do, as computed by [`plan_sync()`].
+pub struct SetupPlan<'a> {
+    /// The plugin directories in the order they should be synced in.
+    pub directories: Vec<PlannedDirectory<'a>>,
+    /// Warnings about skipped plugins. These should be printed before syncing.
+    pub warnings: Vec<String>,
+    /// Plugin directories that contained plugins during the last sync but are now empty or can't be
+    /// read. They may be on a drive that is not mounted right now, so nothing should be pruned.
+    pub possibly_unmounted_dirs: Vec<PathBuf>,
+    /// Existing files that were not created by yabridge where a plugin's bridge should be. These
+    /// are left alone unless the `overwrite_unrelated_files` option is set.
+    pub conflicting_files: Vec<PathBuf>,
+    /// The files in `~/.vst/yabridge` that belong to one of the plugins.
+    pub known_centralized_vst2_files: HashSet<PathBuf>,
+    /// The files in `~/.vst3/yabridge` that belong to one of the plugins, per bundle.
+    pub known_centralized_vst3_files: HashMap<PathBuf, HashSet<PathBuf>>,
+    /// The files in `~/.clap/yabridge` that belong to one of the plugins.
+    pub known_centralized_clap_files: HashSet<PathBuf>,
+    /// Bridges and other files that don't belong to any of the plugins. These are removed by
+    /// `yabridgectl sync --prune`.
+    pub orphans: Vec<NativeFile>,
+    /// Leftover bridges created by another yabridge installation. These are never pruned since
+    /// that installation may still be using them.
+    pub foreign_orphans: Vec<NativeFile>,
+}
+
+/// Decide what `yabridgectl sync` should do with the plugins in `results`, and find the files in
+/// the plugin directories and in `~/.{clap,vst,vst3}/yabridge` that don't belong to any of them.
+/// `index` is used to detect plugin directories that are likely not mounted.
+pub fn plan_sync<'a>(
+    config: &Config,
+    files: &YabridgeFiles,
+    results: &'a [(&'a Path, SearchResults)],
+    index: &Index,
+    options: &PlanOptions,
+) -> SetupPlan<'a> {
+    let mut plan = SetupPlan {
+        directories: Vec::new(),
+        warnings: Vec::new(),
+        possibly_unmounted_dirs: Vec::new(),
+        conflicting_files: Vec::new(),
+        known_centralized_vst2_files: HashSet::new(),
+        known_centralized_vst3_files: HashMap::new(),
+        known_centralized_clap_files: HashSet::new(),
+        orphans: Vec::new(),
+        foreign_orphans: Vec::new(),
+    };
+
+    // With `--keep-first`, only the first plugin in every set of plugins with identical contents is
+    // set up. These are the plugin paths and architectures of the other plugins.
+    let skipped_duplicates: HashSet<(PathBuf, LibArchitecture)> = if options.keep_first {
+        files::find_duplicates(
+            results
+                .iter()
+                .flat_map(|(_, search_results)| &search_results.plugins),
+        )
+        .into_iter()
+        .flat_map(|set| set.into_iter().skip(1))
+        .map(|plugin| (plugin.path().to_owned(), plugin.architecture()))
+        .collect()
+    } else {
+        HashSet::new()
+    };
+    // The centralized bridges plugins without a matching Wine host may have been set up with
+    // before. These are not leftovers, since the plugins will work again once the host has been
+    // installed. The name hashes are stripped so this also covers bridges from before updating
+    // yabridge.
+    let mut hostless_targets: Vec<PathBuf> = Vec::new();
+    // Bridges next to `.dll` files that aren't VST2 plugins, kept because `--skip-non-vst` was not
+    // passed
+    let mut num_kept_non_vst_bridges = 0;
+
+    for (path, search_results) in results {
+        let vst2_location = search_results.vst2_location(config);
+        let non_vst_bridges =
+            if options.skip_non_vst || vst2_location == Vst2InstallationLocation::Centralized {
+                HashSet::new()
+            } else {
+                search_results.non_vst_bridges()
+            };
+        num_kept_non_vst_bridges += non_vst_bridges.len();
+        plan.orphans.extend(
+            search_results
+                .vst2_inline_orphans(config)
+                .into_iter()
+                .filter(|file| !non_vst_bridges.contains(file.path()))
+                .cloned(),
+        );
+
+        // If a directory suddenly can't be read or no longer contains any plugins, then it may be
+        // on a drive that isn't mounted right now. If the directory can still be read and it still
+        // contains plugins, then the missing plugins have genuinely been removed. Directories last
+        // synced by older yabridgectl versions don't have a hash, so we'll fall back to comparing
+        // the number of plugins for those.
+        let plugins_changed = match index.directory(path) {
+            Some(DirectoryInfo {
+                plugins_hash: Some(previous_hash),
+                ..
+            }) => *previous_hash != files::hash_plugin_paths(&search_results.plugins, path),
+            Some(info) => info.num_plugins != search_results.plugins.len(),
+            None => false,
+        };
+        let is_readable = filesystem::current().read_dir(path).is_ok();
+        if (!is_readable || (search_results.plugins.is_empty() && plugins_changed))
+            && !options.force
+        {
+            plan.possibly_unmounted_dirs.push(path.to_path_buf());
+        }
+
+        // Plugins with `.dll` files that only differ in casing would cause the `.so` files to
+        // overwrite each other on case-insensitive filesystems, so we'll skip these
+        let mut case_collisions: HashSet<&Path> = HashSet::new();
+        for paths in &search_results.case_collisions {
+            let mut warning = format!(
+                "{}: The following files only differ in casing, {}:",
+                "WARNING".red(),
+                if options.force {
+                    "setting them up anyways because '--force' was used"
+                } else {
+                    "skipping them. Rerun with '--force' to set them up anyways"
+                },
+            );
+            warning = util::wrap(&warning);
+            for path in paths {
+                warning.push_str(&format!("\n- {}", path.display()));
+            }
+            plan.warnings.push(warning);
+
+            if !options.force {
+                case_collisions.extend(paths.iter().map(|path| path.as_path()));
+            }
+        }
+
+        let mut directory = PlannedDirectory {
+            vst2_location,
+            plugins: Vec::new(),
+        };
+        for plugin in &search_results.plugins {
+            let action = if case_collisions.contains(plugin.path()) {
+                PlannedAction::Skip(SkipReason::CaseCollision)
+            } else if skipped_duplicates
+                .contains(&(plugin.path().to_owned(), plugin.architecture()))
+            {
+                PlannedAction::Skip(SkipReason::Duplicate)
+            } else {
+                plan_plugin(
+                    config,
+                    files,
+                    options,
+                    &mut plan,
+                    &mut hostless_targets,
+                    vst2_location,
+                    plugin,
+                )
+            };
+            directory.plugins.push((plugin, action));
+        }
+
+        plan.directories.push(directory);
+    }
+
+    if num_kept_non_vst_bridges > 0 {
+        plan.warnings.push(util::wrap(&format!(
+            "{}: Keeping the bridges for {} '.dll' files that are not VST plugins. Rerun with \
+             '--skip-non-vst' to remove these as leftovers. This will become the default in a \
+             future version of yabridgectl.",
+            "WARNING".red(),
+            num_kept_non_vst_bridges,
+        )));
+    }
+
+    find_orphans(config, files, &mut plan, hostless_targets, options.partial);
+
+    plan
+}
+
+/// Decide what should happen to a single plugin, and record the files belonging to it in `plan`.
+/// Plugins without a matching Wine host have their bridges' paths added to `hostless_targets`.
+fn plan_plugin(
+    config: &Config,
+    files: &YabridgeFiles,
+    options: &PlanOptions,
+    plan: &mut SetupPlan,
+    hostless_targets: &mut Vec<PathBuf>,
+    vst2_location: Vst2InstallationLocation,
+    plugin: &Plugin,
+) -> PlannedAction {
+    let native_target = plugin.native_target(config, vst2_location, Some(files));
+    let was_installed = filesystem::current()
+        .symlink_metadata(&native_target)
+        .is_ok();
+
+    // Plugins that have already been set up are not touched at all with `--install-missing-only`,
+    // even if they're outdated or broken
+    if options.install_missing_only && was_installed {
+        return PlannedAction::Skip(SkipReason::AlreadyInstalled);
+    }
+
+    // With `--older-than`, plugins that haven't been modified in a long time are no longer set up
+    // so their bridges end up being pruned as leftovers. Files that turn out not to be bridges are
+    // never removed this way, so those plugins are set up as usual.
+    let is_stale = options.older_than.map_or(false, |older_than| {
+        plugin_modified(plugin).map_or(false, |modified| {
+            modified
+                .elapsed()
+                .map_or(false, |elapsed| elapsed > older_than)
+        })
+    });
+    if is_stale && (!was_installed || files::is_bridge(filesystem::current(), &native_target)) {
+        // Inline VST2 bridges are never considered to be leftovers while their plugin still exists,
+        // so these need to be added separately
+        if was_installed
+            && matches!(plugin, Plugin::Vst2(_))
+            && vst2_location == Vst2InstallationLocation::Inline
+        {
+            plan.orphans.extend(get_file_type(native_target));
+        }
+
+        return PlannedAction::Skip(SkipReason::Stale);
+    }
+
+    if !options.no_arch_check && host_binary(files, plugin.architecture()).is_none() {
+        match plugin {
+            Plugin::Vst2(vst2_plugin) => hostless_targets.extend([
+                vst2_plugin.centralized_native_target(config, Some(files)),
+                vst2_plugin.centralized_windows_target(config, Some(files)),
+            ]),
+            Plugin::Vst3(module) => hostless_targets.push(module.target_bundle_home(config)),
+            Plugin::Clap(clap_plugin) => hostless_targets.extend([
+                clap_plugin.native_target(Some(files)),
+                clap_plugin.windows_target(Some(files)),
+            ]),
+        }
+
+        return PlannedAction::Skip(SkipReason::NoMatchingHost);
+    }
+
+    let duplicate_warning = |target: &Path| {
+        util::wrap(&format!(
+            "{}: '{}' has already been provided by another Wine prefix or plugin directory, \
+             skipping it",
+            "WARNING".red(),
+            target.display(),
+        ))
+    };
+    match plugin {
+        Plugin::Vst2(vst2_plugin) => match vst2_location {
+            Vst2InstallationLocation::Centralized => {
+                let target_windows_plugin_path =
+                    vst2_plugin.centralized_windows_target(config, Some(files));
+
+                let mut is_new = plan
+                    .known_centralized_vst2_files
+                    .insert(native_target.clone());
+                is_new |= plan
+                    .known_centralized_vst2_files
+                    .insert(target_windows_plugin_path.clone());
+                if !is_new {
+                    plan.warnings
+                        .push(duplicate_warning(&target_windows_plugin_path));
+                    return PlannedAction::Skip(SkipReason::Duplicate);
+                }
+            }
+            Vst2InstallationLocation::Inline => {
+                // Yabridge looks for a `.dll` file next to the bridge, so this can't work for
+                // plugins with a different or uppercase extension
+                if !vst2_plugin.supports_inline_location() {
+                    plan.warnings.push(util::wrap(&format!(
+                        "{}: '{}' doesn't use a lowercase '.dll' extension, so it can only be set \
+                         up using the centralized VST2 location, skipping it",
+                        "WARNING".red(),
+                        vst2_plugin.path.display(),
+                    )));
+                    return PlannedAction::Skip(SkipReason::NeedsCentralizedLocation);
+                }
+            }
+        },
+        Plugin::Vst3(module) => {
+            // Only set up VST3 plugins when yabridge has been compiled with VST3 support
+            if files.vst3_chainloader.is_none() {
+                return PlannedAction::Skip(SkipReason::UnsupportedFormat);
+            }
+
+            // 32-bit and 64-bit versions of the plugin can live inside of the same bundle, but it's
+            // not possible to use the exact same plugin from multiple Wine prefixes at the same
+            // time so we'll warn when that happens
+            let target_windows_module_path = module.target_windows_module_path(config);
+            let managed_vst3_bundle_files = plan
+                .known_centralized_vst3_files
+                .entry(module.target_bundle_home(config))
+                .or_default();
+            if managed_vst3_bundle_files.contains(&target_windows_module_path) {
+                plan.warnings.push(util::wrap(&format!(
+                    "{}: The {} version of '{}' has already been provided by another Wine prefix \
+                     or plugin directory, skipping '{}'",
+                    "WARNING".red(),
+                    module.architecture,
+                    module.target_bundle_home(config).display(),
+                    module.original_module_path().display(),
+                )));
+                return PlannedAction::Skip(SkipReason::Duplicate);
+            }
+
+            managed_vst3_bundle_files.insert(native_target.clone());
+            managed_vst3_bundle_files.insert(target_windows_module_path);
+            if module.original_resources_dir().is_some() {
+                managed_vst3_bundle_files.insert(module.target_resources_dir(config));
+            }
+            if module.original_moduleinfo_path().is_some() {
+                managed_vst3_bundle_files.insert(module.target_moduleinfo_path(config));
+            }
+        }
+        Plugin::Clap(clap_plugin) => {
+            // Only set up CLAP plugins when yabridge has been compiled with CLAP support
+            if files.clap_chainloader.is_none() {
+                return PlannedAction::Skip(SkipReason::UnsupportedFormat);
+            }
+
+            let target_windows_plugin_path = clap_plugin.windows_target(Some(files));
+
+            let mut is_new = plan
+                .known_centralized_clap_files
+                .insert(native_target.clone());
+            is_new |= plan
+                .known_centralized_clap_files
+                .insert(target_windows_plugin_path.clone());
+            if !is_new {
+                plan.warnings
+                    .push(duplicate_warning(&target_windows_plugin_path));
+                return PlannedAction::Skip(SkipReason::Duplicate);
+            }
+        }
+    }
+
+    // This also applies with `--force`, since that's only meant to recreate our own bridges. The
+    // Windows plugin files are symlinked next to the bridge, and those symlinks must not replace
+    // unrelated files either.
+    if !config.overwrite_unrelated_files {
+        let backend = filesystem::current();
+        let symlink_targets = match plugin {
+            Plugin::Vst2(vst2_plugin) => match vst2_location {
+                Vst2InstallationLocation::Centralized => {
+                    vec![vst2_plugin.centralized_windows_target(config, Some(files))]
+                }
+                Vst2InstallationLocation::Inline => Vec::new(),
+            },
+            Plugin::Vst3(module) => {
+                let mut targets = vec![module.target_windows_module_path(config)];
+                if module.original_resources_dir().is_some() {
+                    targets.push(module.target_resources_dir(config));
+                }
+                targets
+            }
+            Plugin::Clap(clap_plugin) => vec![clap_plugin.windows_target(Some(files))],
+        };
+
+        let conflict = if was_installed && files::is_unrelated_file(backend, &native_target) {
+            Some(native_target)
+        } else {
+            symlink_targets
+                .into_iter()
+                .find(|target| files::is_unrelated_symlink_target(backend, target))
+        };
+        if let Some(conflict) = conflict {
+            plan.warnings.push(util::wrap(&format!(
+                "{}: {}",
+                "WARNING".red(),
+                ConflictError(conflict.clone())
+            )));
+            plan.conflicting_files.push(conflict);
+            return PlannedAction::Skip(SkipReason::Conflict);
+        }
+    }
+
+    PlannedAction::SetUp
+}
+
+/// Find the leftover files in `~/.clap/yabridge`, `~/.vst/yabridge`, and `~/.vst3/yabridge` after
+/// all plugins have been planned, and split the leftovers into `plan.orphans` and
+/// `plan.foreign_orphans`.
+fn find_orphans(
+    config: &Config,
+    files: &YabridgeFiles,
+    plan: &mut SetupPlan,
+    hostless_targets: Vec<PathBuf>,
+    partial: bool,
+) {
+    // The plugins from the directories that are not being synced have not been added to the known
+    // files, so they would all show up as leftovers here
+    if partial {
+        plan.orphans.clear();
+        return;
+    }
+
+    // For VST3 plugins we'll want to remove both unmanaged VST3 bundles in `~/.vst3/yabridge` as
+    // well as unmanged files within managed bundles. That's why we'll immediately filter out known
+    // files within VST3 bundles. For VST2 and CLAP plugins we can simply treat any file in
+    // `~/{.clap,.vst}/yabridge` that we did not add to `known_centralized_{clap,vst2}_files` as an
+    // orphan. We'll want to do this regardless of the VST2 installation location setting so
+    // switching between the two modes and then pruning works as expected.
+    let centralized_files = |home: PathBuf, extensions: &'static [&'static str]| {
+        WalkDir::new(home)
+            .follow_links(true)
+            .same_file_system(true)
+            .into_iter()
+            .filter_map(move |e| {
+                let path = match e {
+                    Ok(entry) => entry.path().to_owned(),
+                    Err(err) => err.path()?.to_owned(),
+                };
+
+                if !path.is_dir() && extensions.contains(&path.extension()?.to_str()?) {
+                    Some(path)
+                } else {
+                    None
+                }
+            })
+    };
+    let installed_vst3_bundles = WalkDir::new(yabridge_vst3_home(config))
+        .follow_links(true)
+        .same_file_system(true)
+        .into_iter()
+        .filter_entry(|entry| entry.file_type().is_dir())
+        .filter_map(|e| {
+            let path = match e {
+                Ok(entry) => entry.path().to_owned(),
+                Err(err) => err.path()?.to_owned(),
+            };
+
+            if path.extension()?.to_str()? == "vst3" {
+                Some(path)
+            } else {
+                None
+            }
+        });
+
+    for path in centralized_files(yabridge_vst2_home(config), &["dll", "so"]) {
+        if !plan.known_centralized_vst2_files.contains(&path) {
+            plan.orphans.extend(get_file_type(path));
+        }
+    }
+    for path in centralized_files(yabridge_clap_home(), &["clap", "clap-win", "so"]) {
+        if !plan.known_centralized_clap_files.contains(&path) {
+            plan.orphans.extend(get_file_type(path));
+        }
+    }
+    for bundle_path in installed_vst3_bundles {
+        match plan.known_centralized_vst3_files.get(&bundle_path) {
+            None => plan.orphans.push(NativeFile::Directory(bundle_path)),
+            Some(managed_vst3_bundle_files) => {
+                // Find orphan files and symlinks within this bundle. We need this to be able to
+                // switch between 32-bit and 64-bit versions of both yabridge and the Windows plugin
+                plan.orphans.extend(
+                    WalkDir::new(bundle_path)
+                        .follow_links(false)
+                        .into_iter()
+                        .filter_map(|e| {
+                            let path = match e {
+                                Ok(entry) => entry.path().to_owned(),
+                                Err(err) => err.path()?.to_owned(),
+                            };
+
+                            let managed_file = managed_vst3_bundle_files.contains(&path);
+                            match get_file_type(path)? {
+                                // Don't remove directories, since we're not tracking the
+                                // directories within the bundle
+                                NativeFile::Directory(_) => None,
+                                unknown_file if !managed_file => Some(unknown_file),
+                                _ => None,
+                            }
+                        }),
+                );
+            }
+        }
+    }
+
+    // The conflicting files weren't created by us, and the bridges for plugins that were skipped
+    // because there's no matching Wine host will be needed again later
+    let hostless_targets: Vec<PathBuf> = hostless_targets
+        .into_iter()
+        .map(|path| files::strip_name_hash(&path).unwrap_or(path))
+        .collect();
+    let conflicting_files = &plan.conflicting_files;
+    plan.orphans.retain(|file| {
+        let path = files::strip_name_hash(file.path()).unwrap_or_else(|| file.path().to_owned());
+        !conflicting_files.iter().any(|path| path == file.path())
+            && !hostless_targets
+                .iter()
+                .any(|target| path.starts_with(target))
+    });
+
+    // Leftover bridges from another yabridge installation, like a fork of yabridge, were not
+    // created by us and they may still be in use by that installation
+    let (foreign_orphans, orphans): (Vec<NativeFile>, Vec<NativeFile>) =
+        plan.orphans.drain(..).partition(|file| match file {
+            NativeFile::Regular(path) | NativeFile::Symlink(path) => {
+                files::is_foreign_bridge(path, files)
+            }
+            NativeFile::Directory(_) => false,
+        });
+    plan.orphans = orphans;
+    plan.foreign_orphans = foreign_orphans;
+}
@@ -19,21 +19,32 @@
 use anyhow::{anyhow, Context, Result};
 use rayon::prelude::*;
 use serde_derive::{Deserialize, Serialize};
-use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::env;
+use std::fmt;
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 use which::which;
 use xdg::BaseDirectories;
 
-use crate::files::{self, LibArchitecture, SearchResults};
+use crate::daw::DawIntegration;
+use crate::files::{self, LibArchitecture, Plugin, SearchResults};
 use crate::util;
 
 /// The name of the config file, relative to `$XDG_CONFIG_HOME/YABRIDGECTL_PREFIX`.
 pub const CONFIG_FILE_NAME: &str = "config.toml";
+/// The current version of the config file's schema. Config files without a `version` field are
+/// treated as version 1. When reading an older config file, it's migrated to this version using
+/// `migrate_config()` and then written back to disk.
+pub const CONFIG_VERSION: i64 = 2;
 /// The name of the XDG base directory prefix for yabridgectl, relative to `$XDG_CONFIG_HOME` and
 /// `$XDG_DATA_HOME`.
 const YABRIDGECTL_PREFIX: &str = "yabridgectl";
+/// Set through the global `--config-dir` option. This replaces `$XDG_CONFIG_HOME/yabridgectl`.
+static CONFIG_DIR: Mutex<Option<PathBuf>> = Mutex::new(None);
 
 /// The name of yabridge's CLAP chainloading library yabridgectl will create copies of.
 pub const CLAP_CHAINLOADER_NAME: &str = "libyabridge-chainloader-clap.so";
@@ -64,11 +75,101 @@ const YABRIDGE_VST2_HOME: &str = ".vst/yabridge";
 /// plugins.
 const YABRIDGE_VST3_HOME: &str = ".vst3/yabridge";
 
+/// The default for `Config::slow_directory_threshold`, in seconds.
+const DEFAULT_SLOW_DIRECTORY_THRESHOLD: u64 = 10;
+
+/// Resets one of the settings in `config` to its value in `defaults`.
+type ResetSetting = fn(config: &mut Config, defaults: Config);
+
+/// The settings that can be reset to their default values using `yabridgectl config unset`, and
+/// the functions that reset them. These match the setting's names in the config file. See
+/// `Config::unset()`.
+pub const UNSETTABLE_SETTINGS: [(&str, ResetSetting); 25] = [
+    ("yabridge_home", |config, defaults| {
+        config.yabridge_home = defaults.yabridge_home
+    }),
+    ("vst2_location", |config, defaults| {
+        config.vst2_location = defaults.vst2_location
+    }),
+    ("vst2_target", |config, defaults| {
+        config.vst2_target = defaults.vst2_target
+    }),
+    ("vst3_target", |config, defaults| {
+        config.vst3_target = defaults.vst3_target
+    }),
+    ("subdirectory_template", |config, defaults| {
+        config.subdirectory_template = defaults.subdirectory_template
+    }),
+    ("no_verify", |config, defaults| {
+        config.no_verify = defaults.no_verify
+    }),
+    ("include_hidden", |config, defaults| {
+        config.include_hidden = defaults.include_hidden
+    }),
+    ("relative_symlinks", |config, defaults| {
+        config.relative_symlinks = defaults.relative_symlinks
+    }),
+    ("relative_to", |config, defaults| {
+        config.relative_to = defaults.relative_to
+    }),
+    ("default_excludes", |config, defaults| {
+        config.default_excludes = defaults.default_excludes
+    }),
+    ("vst2_extensions", |config, defaults| {
+        config.vst2_extensions = defaults.vst2_extensions
+    }),
+    ("min_plugin_size", |config, defaults| {
+        config.min_plugin_size = defaults.min_plugin_size
+    }),
+    ("max_plugin_size", |config, defaults| {
+        config.max_plugin_size = defaults.max_plugin_size
+    }),
+    ("max_plugins", |config, defaults| {
+        config.max_plugins = defaults.max_plugins
+    }),
+    ("slow_directory_threshold", |config, defaults| {
+        config.slow_directory_threshold = defaults.slow_directory_threshold
+    }),
+    ("use_ignore_files", |config, defaults| {
+        config.use_ignore_files = defaults.use_ignore_files
+    }),
+    ("shortcuts", |config, defaults| {
+        config.shortcuts = defaults.shortcuts
+    }),
+    ("daw_integrations", |config, defaults| {
+        config.daw_integrations = defaults.daw_integrations
+    }),
+    ("bridge_mode", |config, defaults| {
+        config.bridge_mode = defaults.bridge_mode
+    }),
+    ("strict_paths", |config, defaults| {
+        config.strict_paths = defaults.strict_paths
+    }),
+    ("allowed_bases", |config, defaults| {
+        config.allowed_bases = defaults.allowed_bases
+    }),
+    ("skip_system_dlls", |config, defaults| {
+        config.skip_system_dlls = defaults.skip_system_dlls
+    }),
+    ("system_dlls", |config, defaults| {
+        config.system_dlls = defaults.system_dlls
+    }),
+    ("overwrite_unrelated_files", |config, defaults| {
+        config.overwrite_unrelated_files = defaults.overwrite_unrelated_files
+    }),
+    ("hashed_names", |config, defaults| {
+        config.hashed_names = defaults.hashed_names
+    }),
+];
+
 /// The configuration used for yabridgectl. This will be serialized to and deserialized from
 /// `$XDG_CONFIG_HOME/yabridge/config.toml`.
-#[derive(Debug, Default, Deserialize, Serialize)]
+#[derive(Debug, Default, Deserialize, Serialize, Clone)]
 #[serde(default)]
 pub struct Config {
+    /// The version of the config file's schema. This is always set to `CONFIG_VERSION` when the
+    /// config file gets written.
+    pub version: i64,
     /// The path to the directory containing `libyabridge-{chainloader,}-{clap,vst2,vst3}.so`. If not
     /// set, then yabridgectl will look in `/usr/lib` and `$XDG_DATA_HOME/yabridge` since those are
     /// the expected locations for yabridge to be installed in.
@@ -82,18 +183,213 @@ pub struct Config {
     /// Where VST2 plugins are setup. This can be either in `~/.vst/yabridge` or inline with the
     /// plugin's .dll` files.`
     pub vst2_location: Vst2InstallationLocation,
+    /// Overrides `vst2_location` for a single run. This is set from the `--vst2-location` option
+    /// or the `YABRIDGECTL_VST2_LOCATION` environment variable, and it's never written to the
+    /// config file. Use `Config::effective_vst2_location()` to get the location that should be
+    /// used.
+    #[serde(skip)]
+    pub vst2_location_override: Option<Vst2InstallationLocation>,
+    /// Set when the config file was just created or migrated from an older version by
+    /// `Config::read()`, meaning it still needs to be written to disk. This is deferred until
+    /// `main()` has made sure that it's not creating a config file owned by root.
+    #[serde(skip)]
+    pub needs_write: bool,
+    /// The settings from a config file written by a newer version of yabridgectl that this version
+    /// doesn't know about. These are written back as is so they're not lost when this version
+    /// changes something in the config file.
+    #[serde(skip)]
+    pub unknown_settings: toml::value::Table,
+    /// The directory bridged VST2 plugins should be set up in when using the centralized VST2
+    /// installation location, in place of `~/.vst`. Like with the default location, the plugins
+    /// are set up in a `yabridge` subdirectory so leftover files can be cleaned up without touching
+    /// other native plugins.
+    pub vst2_target: Option<PathBuf>,
+    /// The directory bridged VST3 modules should be set up in, in place of `~/.vst3`. The merged
+    /// bundles are again placed in a `yabridge` subdirectory of this directory.
+    pub vst3_target: Option<PathBuf>,
+    /// Determines the subdirectory of `~/.vst/yabridge`, `~/.vst3/yabridge`, and `~/.clap/yabridge`
+    /// a plugin is set up in. See `files::expand_subdirectory_template()` for the placeholders
+    /// that can be used. When this is not set, the plugin's original subdirectory within its plugin
+    /// directory is recreated, which is the same as using `{subdirectory}`.
+    pub subdirectory_template: Option<String>,
     /// Always skip post-installation setup checks. This can be set temporarily by passing the
     /// `--no-verify` option to `yabridgectl sync`.
     pub no_verify: bool,
+    /// Also search hidden directories inside of the plugin directories. These are skipped by
+    /// default. This can be enabled temporarily by passing the `--include-hidden` option to
+    /// `yabridgectl status` and `yabridgectl sync`.
+    pub include_hidden: bool,
+    /// Use relative paths for the symlinks to the Windows plugin files created during
+    /// `yabridgectl sync`, so they keep working when the plugin directories and the home directory
+    /// are moved or mounted somewhere else together. This can be overridden for a single run with
+    /// the `--relative` and `--absolute` options.
+    pub relative_symlinks: bool,
+    /// If set, then paths in the config file that are located under this base directory are stored
+    /// relative to that directory. These paths are resolved again when reading the config file.
+    /// This makes it possible to move a setup containing both the config file and the plugins to
+    /// another machine.
+    pub relative_to: Option<RelativePathBase>,
     /// Files and directories that should be skipped during the indexing process. If this contains a
     /// directory, then everything under that directory will also be skipped. Like with
     /// `plugin_dirs`, we're using a `BTreeSet` here because it looks nicer in the config file, even
     /// though a hash set would make much more sense.
     pub blacklist: BTreeSet<PathBuf>,
+    /// File name patterns for plugin files that should never be set up, in every plugin directory.
+    /// `*` and `?` wildcards can be used. Unlike the blacklist, which contains absolute paths,
+    /// these are matched against just the file name. The blacklist is applied first, so a
+    /// blacklisted directory is never searched regardless of these patterns.
+    pub default_excludes: BTreeSet<String>,
+    /// File extensions other than `.dll` that are also used for Windows VST2 plugins, in lowercase
+    /// and without a leading period. Some plugins are distributed as `.vst` files, for instance.
+    pub vst2_extensions: BTreeSet<String>,
+    /// Plugin files smaller than this many bytes are skipped during indexing. Useful for skipping
+    /// tiny stub libraries that aren't actually plugins.
+    pub min_plugin_size: Option<u64>,
+    /// Plugin files larger than this many bytes are skipped during indexing.
+    pub max_plugin_size: Option<u64>,
+    /// `yabridgectl sync` refuses to set up more than this many plugins without `--force`. This
+    /// guards against accidentally adding something like the home directory. This can be overridden
+    /// for a single run with `yabridgectl sync --max-plugins <n>`.
+    pub max_plugins: Option<usize>,
+    /// When using `--time`, plugin directories that take longer than this many seconds to search
+    /// or to set up are reported. `DEFAULT_SLOW_DIRECTORY_THRESHOLD` is used when this is not set.
+    pub slow_directory_threshold: Option<u64>,
+    /// Skip `.dll` files named like a well-known library that plugins depend on, like
+    /// `msvcp140.dll`, without trying to load them as a plugin. This can be enabled temporarily by
+    /// passing the `--skip-system-dlls` option to `yabridgectl status` and `yabridgectl sync`.
+    pub skip_system_dlls: bool,
+    /// File name patterns added to the built-in list from `files::SYSTEM_DLLS`. Patterns starting
+    /// with an exclamation mark instead remove that pattern from the built-in list.
+    pub system_dlls: BTreeSet<String>,
+    /// Honor `.yabridgeignore` files in the root of the plugin directories. These files contain
+    /// `.gitignore`-style patterns for files and directories that should be skipped during
+    /// indexing. This is disabled by default so existing setups don't suddenly change.
+    pub use_ignore_files: bool,
+    /// What to do with Windows `.lnk` shortcuts found in the plugin directories. These are ignored
+    /// by default.
+    pub shortcuts: ShortcutHandling,
+    /// DAWs that should be made to rescan their plugins after `yabridgectl sync` added or removed
+    /// plugins. See the `daw` module for how this works.
+    pub daw_integrations: BTreeSet<DawIntegration>,
+    /// The permissions copied bridges are created with, like `0o644`. Symlinks are not affected.
+    /// When this is not set, the permissions are determined by the umask like for any other file.
+    pub bridge_mode: Option<u32>,
+    /// Allow `yabridgectl sync` to replace existing files that were not created by yabridge, like a
+    /// native Linux library with the same name as a Windows plugin. These files are skipped and
+    /// reported as conflicts by default.
+    pub overwrite_unrelated_files: bool,
+    /// Insert a short hash of yabridge's chainloaders into the file names of VST2 bridges in the
+    /// centralized location and of CLAP bridges, like `Plugin.1a2b3c4d.so`. Updating yabridge then
+    /// results in bridges with new names, which works around hosts that cache plugins by their
+    /// file name. VST3 bundles and inline VST2 bridges need to keep their names, so they are not
+    /// affected by this.
+    pub hashed_names: bool,
+    /// Only allow `yabridgectl add` to add directories inside of a Wine prefix or inside of one of
+    /// the `allowed_bases`. This prevents accidentally adding something like the home directory,
+    /// which would result in a massive scan. `yabridgectl add --force` skips this check.
+    pub strict_paths: bool,
+    /// Directories outside of Wine prefixes that can still be added, along with their
+    /// subdirectories, when `strict_paths` is enabled.
+    pub allowed_bases: BTreeSet<PathBuf>,
     /// The last known combination of Wine and yabridge versions that would work together properly.
     /// This is mostly to diagnose issues with older Wine versions (such as those in Ubuntu's repos)
     /// early on.
     pub last_known_config: Option<KnownConfig>,
+    /// Settings that only apply to a single directory from `plugin_dirs`. Directories using the
+    /// default settings are not stored here.
+    pub directory_settings: BTreeMap<PathBuf, DirectorySettings>,
+}
+
+/// Settings for a single plugin directory. These are set when adding the directory with
+/// `yabridgectl add`.
+#[derive(Deserialize, Serialize, Debug, Default, PartialEq, Eq, Clone)]
+#[serde(default)]
+pub struct DirectorySettings {
+    /// Only search for plugins directly inside of this directory, without descending into
+    /// subdirectories. VST3 bundles directly inside of the directory are still picked up.
+    pub no_recurse: bool,
+    /// Never write anything to this directory. VST2 plugins in this directory are always set up
+    /// using the centralized installation location, and `.so` files found in this directory are
+    /// never considered to be leftover files.
+    pub read_only: bool,
+    /// The named groups this directory belongs to. `yabridgectl status --group <name>` and
+    /// `yabridgectl sync --group <name>` only process the directories in that group.
+    pub groups: BTreeSet<String>,
+}
+
+/// One-off overrides for the per-directory settings, passed through command line options to
+/// commands that search the plugin directories.
+#[derive(Debug, Default)]
+pub struct SearchOverrides {
+    /// Don't recurse into subdirectories for any of the plugin directories, regardless of their
+    /// settings.
+    pub no_recurse: bool,
+    /// Also search hidden directories, regardless of the `include_hidden` setting.
+    pub include_hidden: bool,
+    /// The order in which the plugin directories are walked.
+    pub traversal: files::Traversal,
+    /// Only search the plugin directories belonging to this group.
+    pub group: Option<String>,
+    /// Only search this plugin directory. This should be one of the paths from `plugin_dirs`.
+    pub only_dir: Option<PathBuf>,
+    /// Skip the plugin directories matching one of these paths. The paths are compared after
+    /// normalizing them, so they don't need to be spelled exactly like in the config file.
+    pub exclude_dirs: Vec<PathBuf>,
+    /// How the plugin directories on the same device are searched.
+    pub device_concurrency: DeviceConcurrency,
+    /// Use this minimum plugin size instead of the `min_plugin_size` setting.
+    pub min_size: Option<u64>,
+    /// Use this maximum plugin size instead of the `max_plugin_size` setting.
+    pub max_size: Option<u64>,
+    /// Skip system DLLs, regardless of the `skip_system_dlls` setting.
+    pub skip_system_dlls: bool,
+    /// Use these plugins instead of searching the directories. These come from
+    /// `Index::cached_plugins()`.
+    pub cached_plugins: HashMap<PathBuf, Vec<Plugin>>,
+}
+
+/// Determines which plugin directories are searched in parallel. Searching multiple directories on
+/// the same spinning disk at the same time makes the disk seek back and forth between them, which
+/// is much slower than searching them one after the other.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum DeviceConcurrency {
+    /// Search all directories in parallel.
+    Parallel,
+    /// Search directories on the same rotational disk one at a time, and search everything else
+    /// in parallel.
+    Auto,
+    /// Search directories on the same device one at a time. Directories on different devices are
+    /// still searched in parallel.
+    Serial,
+}
+
+impl fmt::Display for DeviceConcurrency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeviceConcurrency::Parallel => write!(f, "parallel"),
+            DeviceConcurrency::Auto => write!(f, "auto"),
+            DeviceConcurrency::Serial => write!(f, "serial"),
+        }
+    }
+}
+
+/// Plugin directories that are searched one after the other by `Config::search_directories()`,
+/// while the batches themselves are searched in parallel. See `Config::search_plan()`.
+#[derive(Debug, Serialize)]
+pub struct SearchBatch<'a> {
+    /// The device shared by all directories in this batch as `<major>:<minor>`, if the directories
+    /// were grouped by device.
+    pub device: Option<String>,
+    /// Whether the first directory in the batch is on a rotational disk, if this could be
+    /// determined.
+    pub rotational: Option<bool>,
+    pub directories: Vec<&'a Path>,
+}
+
+impl Default for DeviceConcurrency {
+    fn default() -> Self {
+        DeviceConcurrency::Parallel
+    }
 }
 
 /// Determines where VST2 plugins are set up. They can either be set up in `~/.vst/yabridge` by
@@ -111,13 +407,39 @@ pub enum Vst2InstallationLocation {
     Inline,
 }
 
+/// Determines how Windows `.lnk` shortcuts to `.dll` files are handled during indexing. Some
+/// installers place shortcuts to plugins installed elsewhere in the prefix into a central folder.
+#[derive(Deserialize, Serialize, Debug, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum ShortcutHandling {
+    /// Don't look at shortcuts at all.
+    Ignore,
+    /// Set up the plugin the shortcut points to as if it was located where the shortcut is. With
+    /// the centralized VST2 location the bridge is created in the shortcut's subdirectory.
+    Shortcut,
+    /// Set up the plugin the shortcut points to as if it was found at the target location. If the
+    /// target is outside of the plugin directory, then the bridge is created in the top level of
+    /// `~/.vst/yabridge`.
+    Target,
+}
+
+/// The base directory for storing relative paths in the config file. See `Config::relative_to`.
+#[derive(Deserialize, Serialize, Debug, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum RelativePathBase {
+    /// Paths are relative to the directory containing the config file, `$XDG_CONFIG_HOME/yabridgectl`.
+    Config,
+    /// Paths are relative to the user's home directory.
+    Home,
+}
+
 /// Stores information about a combination of Wine and yabridge that works together properly.
 /// Whenever we encounter a new version of Wine or yabridge, we'll check whether `yabridge-host.exe`
 /// can run without issues. This is needed because older versions of Wine won't be able to run newer
 /// winelibs, and Ubuntu ships with old versions of Wine. To prevent repeating unnecessarily
 /// repeating this check we'll keep track of the last combination of Wine and yabridge that would
 /// work together properly.
-#[derive(Deserialize, Serialize, Debug, PartialEq, Eq)]
+#[derive(Deserialize, Serialize, Debug, PartialEq, Eq, Clone)]
 pub struct KnownConfig {
     /// The output of `wine --version`, minus the trailing newline.
     pub wine_version: String,
@@ -131,6 +453,11 @@ pub struct KnownConfig {
     ///
     /// https://github.com/alexcrichton/toml-rs/issues/256
     pub yabridge_host_hash: i64,
+    /// The Wine prefix `yabridge-host.exe` was run in. Switching to another prefix with `--prefix`
+    /// or `$WINEPREFIX` will redo the check. This is missing for config files written by older
+    /// versions of yabridgectl.
+    #[serde(default)]
+    pub wine_prefix: Option<PathBuf>,
 }
 
 /// Paths to all of yabridge's files based on the `yabridge_home` setting. Created by
@@ -161,6 +488,84 @@ pub struct YabridgeFiles {
     /// The same as `yabridge_host_exe_so`, but for the 32-bit verison. We will hash this instead of
     /// there's no 64-bit version available.
     pub yabridge_host_32_exe_so: Option<PathBuf>,
+    /// The hash inserted into bridge file names when the `hashed_names` option is enabled. This is
+    /// `None` when the option is disabled. See `files::with_name_hash()`.
+    pub name_hash: Option<String>,
+}
+
+/// The error returned from [`Config::files()`] when `libyabridge-chainloader-vst2.so` could not be
+/// found. This is a separate type so the error can also be reported in a structured way when
+/// `--json` is passed to yabridgectl.
+#[derive(Debug)]
+pub enum MissingChainloaderError {
+    /// The file was not found in the directory set through `yabridgectl set --path=<path>`.
+    Explicit(PathBuf),
+    /// The file was not found in any of the default search locations. `searched_paths` contains
+    /// every directory that was searched, in order. The last one is always
+    /// `~/.local/share/yabridge`.
+    Auto {
+        searched_paths: Vec<PathBuf>,
+        user_path: PathBuf,
+    },
+}
+
+impl MissingChainloaderError {
+    /// The directory that should have contained `libyabridge-chainloader-vst2.so`. For the
+    /// automatic search this is the user's `~/.local/share/yabridge` directory, since that's where
+    /// yabridge should be installed to when using the prebuilt binaries.
+    pub fn path(&self) -> &Path {
+        match self {
+            MissingChainloaderError::Explicit(directory) => directory,
+            MissingChainloaderError::Auto { user_path, .. } => user_path,
+        }
+    }
+
+    /// All directories that were searched for `libyabridge-chainloader-vst2.so`.
+    pub fn searched_paths(&self) -> Vec<&Path> {
+        match self {
+            MissingChainloaderError::Explicit(directory) => vec![directory],
+            MissingChainloaderError::Auto { searched_paths, .. } => {
+                searched_paths.iter().map(|path| path.as_path()).collect()
+            }
+        }
+    }
+}
+
+impl fmt::Display for MissingChainloaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MissingChainloaderError::Explicit(directory) => write!(
+                f,
+                "Could not find '{}' in '{}'",
+                VST2_CHAINLOADER_NAME,
+                directory.display()
+            ),
+            MissingChainloaderError::Auto { searched_paths, .. } => {
+                writeln!(
+                    f,
+                    "Could not find '{}' in any of the following locations:",
+                    VST2_CHAINLOADER_NAME
+                )?;
+                for path in searched_paths {
+                    writeln!(f, "- {}", path.display())?;
+                }
+                write!(
+                    f,
+                    "Either install yabridge to one of these locations or point yabridgectl to \
+                     the directory containing yabridge's files using 'yabridgectl set \
+                     --path=<path>'."
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for MissingChainloaderError {}
+
+impl Default for ShortcutHandling {
+    fn default() -> Self {
+        ShortcutHandling::Ignore
+    }
 }
 
 impl Default for Vst2InstallationLocation {
@@ -169,42 +574,442 @@ impl Default for Vst2InstallationLocation {
     }
 }
 
+impl fmt::Display for Vst2InstallationLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Vst2InstallationLocation::Centralized => write!(f, "centralized"),
+            Vst2InstallationLocation::Inline => write!(f, "inline"),
+        }
+    }
+}
+
+impl fmt::Display for ShortcutHandling {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShortcutHandling::Ignore => write!(f, "ignore"),
+            ShortcutHandling::Shortcut => write!(f, "shortcut"),
+            ShortcutHandling::Target => write!(f, "target"),
+        }
+    }
+}
+
+impl fmt::Display for RelativePathBase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RelativePathBase::Config => write!(f, "config"),
+            RelativePathBase::Home => write!(f, "home"),
+        }
+    }
+}
+
+impl FromStr for Vst2InstallationLocation {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "centralized" => Ok(Vst2InstallationLocation::Centralized),
+            "inline" => Ok(Vst2InstallationLocation::Inline),
+            _ => Err(anyhow!(
+                "Unknown VST2 location '{}', expected either 'centralized' or 'inline'",
+                s
+            )),
+        }
+    }
+}
+
+impl RelativePathBase {
+    /// The absolute path to the base directory.
+    pub fn path(&self) -> Result<PathBuf> {
+        match self {
+            RelativePathBase::Config => config_home(),
+            RelativePathBase::Home => {
+                Ok(PathBuf::from(env::var("HOME").context("$HOME is not set")?))
+            }
+        }
+    }
+
+    /// Make `path` relative to `base` if it's located inside of that directory. Other paths are
+    /// returned as is.
+    pub fn relativize(base: &Path, path: &Path) -> PathBuf {
+        match path.strip_prefix(base) {
+            Ok(relative_path) if relative_path.as_os_str().is_empty() => PathBuf::from("."),
+            Ok(relative_path) => relative_path.to_owned(),
+            Err(_) => path.to_owned(),
+        }
+    }
+}
+
 impl Config {
     /// Try to read the config file, creating a new default file if necessary. This will fail if the
     /// file could not be created or if it could not be parsed.
     pub fn read() -> Result<Config> {
-        match yabridgectl_directories()?.find_config_file(CONFIG_FILE_NAME) {
+        match find_config_file()? {
             Some(path) => {
-                let toml_str = fs::read_to_string(&path).with_context(|| {
-                    format!("Could not read config file at '{}'", path.display())
-                })?;
+                let (mut config, needs_migration) = Config::parse_file(&path)?;
+
+                // Migrated configs are written back so this only happens once. A config file from
+                // a newer version is left alone.
+                config.needs_write = needs_migration;
 
-                toml::from_str(&toml_str)
-                    .with_context(|| format!("Failed to parse '{}'", path.display()))
+                Ok(config)
             }
-            None => {
-                let defaults = Config::default();
+            // If no existing config file exists, then a new config file with default values will
+            // be written
+            None => Ok(Config {
+                version: CONFIG_VERSION,
+                needs_write: true,
+                ..Config::default()
+            }),
+        }
+    }
+
+    /// Read a config file from an arbitrary location, like a config file copied from another
+    /// machine. Older config files are migrated in memory, but unlike with `Config::read()` nothing
+    /// is ever written back.
+    pub fn read_file(path: &Path) -> Result<Config> {
+        Config::parse_file(path).map(|(config, _)| config)
+    }
+
+    /// Parse and migrate the config file at `path`. Also returns whether the config file was
+    /// migrated from an older version and should thus be written back.
+    fn parse_file(path: &Path) -> Result<(Config, bool)> {
+        let toml_str = fs::read_to_string(path)
+            .with_context(|| format!("Could not read config file at '{}'", path.display()))?;
+
+        let mut toml_value: toml::Value = toml::from_str(&toml_str)
+            .with_context(|| format!("Failed to parse '{}'", path.display()))?;
+        let version = toml_value
+            .get("version")
+            .and_then(|version| version.as_integer())
+            .unwrap_or(1);
+        let needs_migration = version < CONFIG_VERSION;
+        if needs_migration {
+            migrate_config(&mut toml_value, version);
+        } else if version > CONFIG_VERSION {
+            eprintln!(
+                "{}",
+                util::wrap(&format!(
+                    "WARNING: '{}' was written by a newer version of yabridgectl (config version \
+                     {}, this version supports up to version {}). Settings this version doesn't \
+                     know about will be ignored.\n",
+                    path.display(),
+                    version,
+                    CONFIG_VERSION
+                ))
+            );
+        }
+
+        let mut config: Config = toml_value
+            .clone()
+            .try_into()
+            .with_context(|| format!("Failed to parse '{}'", path.display()))?;
+        if version > CONFIG_VERSION {
+            // Every setting this version knows about would still be present after serializing the
+            // config again
+            let known_settings = toml::Value::try_from(&config).context("Could not format TOML")?;
+            if let (toml::Value::Table(settings), toml::Value::Table(known_settings)) =
+                (toml_value, known_settings)
+            {
+                config.unknown_settings = settings
+                    .into_iter()
+                    .filter(|(key, _)| !known_settings.contains_key(key))
+                    .collect();
+            }
+        }
+        config.resolve_relative_paths()?;
+
+        Ok((config, needs_migration))
+    }
+
+    /// Read the config file at `path` for `yabridgectl config repair`. Unlike `Config::read()`,
+    /// settings that can't be parsed are removed instead of rejecting the entire file. The config
+    /// is then cleaned up:
+    ///
+    /// - All paths are normalized, which also merges paths that refer to the same directory.
+    /// - Plugin directories that no longer exist are removed, unless `keep_missing` is set.
+    /// - Directory settings for paths that are not a plugin directory, or that match the default
+    ///   settings, are removed. If multiple entries normalize to the same directory, then the
+    ///   entry that was already normalized is kept.
+    ///
+    /// Returns the repaired config along with a description of every change. Nothing is written to
+    /// disk.
+    pub fn repair(path: &Path, keep_missing: bool) -> Result<(Config, Vec<String>)> {
+        let toml_str = fs::read_to_string(path)
+            .with_context(|| format!("Could not read config file at '{}'", path.display()))?;
+        let mut toml_value: toml::Value = toml::from_str(&toml_str).with_context(|| {
+            format!(
+                "Failed to parse '{}', syntax errors need to be fixed by hand",
+                path.display()
+            )
+        })?;
 
-                // If no existing config file exists, then write a new config file with default
-                // values
-                defaults.write()?;
+        let mut changes: Vec<String> = Vec::new();
+        let version = toml_value
+            .get("version")
+            .and_then(|version| version.as_integer())
+            .unwrap_or(1);
+        if version < CONFIG_VERSION {
+            migrate_config(&mut toml_value, version);
+            changes.push(format!(
+                "Migrated the config from version {} to version {}",
+                version, CONFIG_VERSION
+            ));
+        }
 
-                Ok(defaults)
+        // Every setting is parsed on its own so a single invalid value only removes that setting
+        if let Some(table) = toml_value.as_table_mut() {
+            let keys: Vec<String> = table.keys().cloned().collect();
+            for key in keys {
+                let value = match table.get(&key) {
+                    Some(value) => value.clone(),
+                    None => continue,
+                };
+                let mut single_setting = toml::value::Table::new();
+                single_setting.insert(key.clone(), value);
+                if let Err(err) = toml::Value::Table(single_setting).try_into::<Config>() {
+                    table.remove(&key);
+                    changes.push(format!("Removed the invalid '{}' setting: {}", key, err));
+                }
+            }
+        }
+
+        let mut config: Config = toml_value
+            .try_into()
+            .with_context(|| format!("Failed to parse '{}'", path.display()))?;
+        config.resolve_relative_paths()?;
+
+        let mut normalize = |path: &Path, kind: &str| {
+            let normalized_path = util::normalize_path(path);
+            if normalized_path != path {
+                changes.push(format!(
+                    "Normalized {} '{}' to '{}'",
+                    kind,
+                    path.display(),
+                    normalized_path.display()
+                ));
+            }
+
+            normalized_path
+        };
+        config.yabridge_home = config
+            .yabridge_home
+            .as_deref()
+            .map(|path| normalize(path, "the yabridge path"));
+        config.vst2_target = config
+            .vst2_target
+            .as_deref()
+            .map(|path| normalize(path, "the VST2 target"));
+        config.vst3_target = config
+            .vst3_target
+            .as_deref()
+            .map(|path| normalize(path, "the VST3 target"));
+        // Paths that normalize to an already normalized path are dropped while collecting these
+        let plugin_dirs: Vec<PathBuf> = config
+            .plugin_dirs
+            .iter()
+            .map(|path| normalize(path, "plugin directory"))
+            .collect();
+        let blacklist: Vec<PathBuf> = config
+            .blacklist
+            .iter()
+            .map(|path| normalize(path, "blacklist entry"))
+            .collect();
+        let num_duplicates = (config.plugin_dirs.len() + config.blacklist.len())
+            - (plugin_dirs.iter().collect::<BTreeSet<_>>().len()
+                + blacklist.iter().collect::<BTreeSet<_>>().len());
+        if num_duplicates > 0 {
+            changes.push(format!(
+                "Merged {} duplicate {}",
+                num_duplicates,
+                if num_duplicates == 1 { "path" } else { "paths" }
+            ));
+        }
+        config.plugin_dirs = plugin_dirs.into_iter().collect();
+        config.blacklist = blacklist.into_iter().collect();
+
+        if !keep_missing {
+            config.plugin_dirs.retain(|path| {
+                let exists = path.exists();
+                if !exists {
+                    changes.push(format!(
+                        "Removed plugin directory '{}' since it no longer exists",
+                        path.display()
+                    ));
+                }
+
+                exists
+            });
+        }
+
+        let mut directory_settings: BTreeMap<PathBuf, DirectorySettings> = BTreeMap::new();
+        for (path, settings) in std::mem::take(&mut config.directory_settings) {
+            let normalized_path = util::normalize_path(&path);
+            if !config.plugin_dirs.contains(&normalized_path) {
+                changes.push(format!(
+                    "Removed the directory settings for '{}' since it's not a plugin directory",
+                    path.display()
+                ));
+                continue;
+            }
+            if settings == DirectorySettings::default() {
+                changes.push(format!(
+                    "Removed the directory settings for '{}' since these are the default settings",
+                    path.display()
+                ));
+                continue;
+            }
+
+            let is_normalized = normalized_path == path;
+            match directory_settings.get(&normalized_path) {
+                Some(existing_settings) if existing_settings != &settings && is_normalized => {
+                    changes.push(format!(
+                        "Replaced conflicting directory settings for '{}' with the settings \
+                         stored under its normalized path",
+                        normalized_path.display()
+                    ));
+                }
+                Some(existing_settings) if existing_settings != &settings => {
+                    changes.push(format!(
+                        "Dropped conflicting directory settings stored under '{}', keeping the \
+                         settings for '{}'",
+                        path.display(),
+                        normalized_path.display()
+                    ));
+                    continue;
+                }
+                Some(_) => continue,
+                None if !is_normalized => changes.push(format!(
+                    "Moved the directory settings for '{}' to '{}'",
+                    path.display(),
+                    normalized_path.display()
+                )),
+                None => (),
             }
+
+            directory_settings.insert(normalized_path, settings);
         }
+        config.directory_settings = directory_settings;
+
+        Ok((config, changes))
+    }
+
+    /// Resolve the relative paths read from the config file when `relative_to` is set. Everything
+    /// other than the config file itself works with absolute paths.
+    fn resolve_relative_paths(&mut self) -> Result<()> {
+        if let Some(relative_to) = self.relative_to {
+            let base = relative_to.path()?;
+            self.map_paths(|path| {
+                if path.is_relative() {
+                    util::normalize_path(&base.join(path))
+                } else {
+                    path.to_owned()
+                }
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Reset a setting from `UNSETTABLE_SETTINGS` to its default value. Returns an error for any
+    /// other setting.
+    pub fn unset(&mut self, setting: &str) -> Result<()> {
+        match UNSETTABLE_SETTINGS
+            .iter()
+            .find(|(name, _)| *name == setting)
+        {
+            Some((_, reset)) => reset(self, Config::default()),
+            None => anyhow::bail!(
+                "Unknown setting '{}', expected one of: {}",
+                setting,
+                UNSETTABLE_SETTINGS.map(|(name, _)| name).join(", ")
+            ),
+        }
+
+        Ok(())
     }
 
     /// Write the config to disk, creating the file if it does not yet exist.
     pub fn write(&self) -> Result<()> {
-        let toml_str = toml::to_string_pretty(&self).context("Could not format TOML")?;
-        let config_path = yabridgectl_directories()?
-            .place_config_file(CONFIG_FILE_NAME)
-            .context("Could not create config file")?;
+        // Config files written by a newer version of yabridgectl keep their version and the
+        // settings we don't know about, so the newer version doesn't try to migrate them again
+        // and none of its settings are lost
+        let mut config = self.clone();
+        config.version = config.version.max(CONFIG_VERSION);
+        if let Some(relative_to) = self.relative_to {
+            let base = relative_to.path()?;
+            config.map_paths(|path| RelativePathBase::relativize(&base, path));
+        }
+
+        let mut toml_value = toml::Value::try_from(&config).context("Could not format TOML")?;
+        if let toml::Value::Table(settings) = &mut toml_value {
+            for (key, value) in &self.unknown_settings {
+                if !settings.contains_key(key) {
+                    settings.insert(key.clone(), value.clone());
+                }
+            }
+        }
+        let toml_str = toml::to_string_pretty(&toml_value).context("Could not format TOML")?;
+        // `place_config_file()` would create the directory directly, even during a dry-run
+        let config_home = config_home()?;
+        util::create_dir_all(&config_home)?;
+        let config_path = config_home.join(CONFIG_FILE_NAME);
 
-        fs::write(&config_path, toml_str)
+        // The default config is written the first time yabridgectl is run, so this should never
+        // leave behind a partially written file
+        util::write_atomic(&config_path, toml_str)
             .with_context(|| format!("Failed to write config file to '{}'", config_path.display()))
     }
 
+    /// Write the new or migrated config file returned by `Config::read()` to disk. This does
+    /// nothing if the config file was already up to date.
+    pub fn write_if_needed(&self) -> Result<()> {
+        if self.needs_write {
+            self.write()?;
+        }
+
+        Ok(())
+    }
+
+    /// The plugin directories, sorted using `util::compare_paths()`. This is the order used by
+    /// `yabridgectl list`, and the numbers shown by `yabridgectl list --numbered` are indices into
+    /// this list starting at one.
+    pub fn sorted_plugin_dirs(&self) -> Vec<&Path> {
+        let mut plugin_dirs: Vec<&Path> = self.plugin_dirs.iter().map(|p| p.as_path()).collect();
+        plugin_dirs.sort_by(|a, b| util::compare_paths(a, b));
+
+        plugin_dirs
+    }
+
+    /// The VST2 installation location that should be used for this run. This is
+    /// `vst2_location_override` if it's set, and the stored `vst2_location` otherwise.
+    pub fn effective_vst2_location(&self) -> Vst2InstallationLocation {
+        self.vst2_location_override.unwrap_or(self.vst2_location)
+    }
+
+    /// The time after which a plugin directory is considered to be slow, see
+    /// `slow_directory_threshold`.
+    pub fn slow_directory_threshold(&self) -> Duration {
+        Duration::from_secs(
+            self.slow_directory_threshold
+                .unwrap_or(DEFAULT_SLOW_DIRECTORY_THRESHOLD),
+        )
+    }
+
+    /// Apply `f` to all paths stored in the config. Used to convert between absolute paths and
+    /// paths relative to `relative_to`.
+    fn map_paths(&mut self, f: impl Fn(&Path) -> PathBuf) {
+        self.yabridge_home = self.yabridge_home.as_deref().map(&f);
+        self.vst2_target = self.vst2_target.as_deref().map(&f);
+        self.vst3_target = self.vst3_target.as_deref().map(&f);
+        self.plugin_dirs = self.plugin_dirs.iter().map(|path| f(path)).collect();
+        self.blacklist = self.blacklist.iter().map(|path| f(path)).collect();
+        self.allowed_bases = self.allowed_bases.iter().map(|path| f(path)).collect();
+        self.directory_settings = std::mem::take(&mut self.directory_settings)
+            .into_iter()
+            .map(|(path, settings)| (f(&path), settings))
+            .collect();
+    }
+
     /// Find all of yabridge's files based on `yabridge_home`. For the binaries we'll search for
     /// them the exact same way as yabridge itself will.
     pub fn files(&self) -> Result<YabridgeFiles> {
@@ -217,22 +1022,17 @@ impl Config {
                 if candidate.exists() {
                     candidate
                 } else {
-                    return Err(anyhow!(
-                        "Could not find '{}' in '{}'",
-                        VST2_CHAINLOADER_NAME,
-                        directory.display()
-                    ));
+                    return Err(MissingChainloaderError::Explicit(directory.clone()).into());
                 }
             }
             None => {
                 // Search in the system library locations and in `~/.local/share/yabridge` if no
-                // path was set explicitely. We'll also search through `/usr/local/lib` just in case
-                // but since we advocate against installing yabridge there we won't list this path
-                // in the error message when `libyabridge-chainloader-vst2.so` can't be found.
-                let system_path = Path::new("/usr/lib");
+                // path was set explicitely. We'll also search through `/usr/local/lib` just in case,
+                // even though we advocate against installing yabridge there. All of these
+                // directories are listed in the error message if the file can't be found.
                 let user_path = xdg_dirs.get_data_home();
                 let lib_directories = [
-                    system_path,
+                    Path::new("/usr/lib"),
                     // Used on Debian based distros
                     Path::new("/usr/lib/x86_64-linux-gnu"),
                     // Used on Fedora
@@ -248,13 +1048,14 @@ impl Config {
                 match candidates.find(|directory| directory.exists()) {
                     Some(candidate) => candidate,
                     _ => {
-                        return Err(anyhow!(
-                            "Could not find '{}' in either '{}' or '{}'. You can override the \
-                             default search path using 'yabridgectl set --path=<path>'.",
-                            VST2_CHAINLOADER_NAME,
-                            system_path.display(),
-                            user_path.display()
-                        ));
+                        return Err(MissingChainloaderError::Auto {
+                            searched_paths: lib_directories
+                                .iter()
+                                .map(|directory| directory.to_path_buf())
+                                .collect(),
+                            user_path: user_path.clone(),
+                        }
+                        .into());
                     }
                 }
             }
@@ -303,17 +1104,46 @@ impl Config {
             _ => None,
         };
 
-        // `yabridge-host.exe` should either be in the search path, or it should be in
-        // `~/.local/share/yabridge` (which was appended to the `$PATH` at the start of `main()`)
-        let yabridge_host_exe = which(YABRIDGE_HOST_EXE_NAME).ok();
+        // Just like yabridge itself, we'll first look for `yabridge-host.exe` next to the
+        // libraries, and then in the search path. `~/.local/share/yabridge` was appended to the
+        // `$PATH` at the start of `main()`.
+        let find_host = |name: &str| match vst2_chainloader.with_file_name(name) {
+            path if path.exists() => Some(path),
+            _ => which(name).ok(),
+        };
+        let yabridge_host_exe = find_host(YABRIDGE_HOST_EXE_NAME);
         let yabridge_host_exe_so = yabridge_host_exe
             .as_ref()
             .map(|path| path.with_extension("exe.so"));
-        let yabridge_host_32_exe = which(YABRIDGE_HOST_32_EXE_NAME).ok();
+        let yabridge_host_32_exe = find_host(YABRIDGE_HOST_32_EXE_NAME);
         let yabridge_host_32_exe_so = yabridge_host_32_exe
             .as_ref()
             .map(|path| path.with_extension("exe.so"));
 
+        // The chainloaders only change when yabridge gets updated, so their contents are used to
+        // determine the bridges' names. This needs to be a stable hash, or the bridges would be
+        // renamed every time yabridgectl itself gets rebuilt.
+        let name_hash = if self.hashed_names {
+            let mut chainloaders = vec![&vst2_chainloader];
+            if let Some((path, _)) = &clap_chainloader {
+                chainloaders.push(path);
+            }
+            let contents = chainloaders
+                .into_iter()
+                .map(|path| {
+                    fs::read(path)
+                        .with_context(|| format!("Could not read contents of '{}'", path.display()))
+                })
+                .collect::<Result<Vec<_>>>()?;
+
+            Some(format!(
+                "{:08x}",
+                util::stable_hash(contents.iter().map(Vec::as_slice))
+            ))
+        } else {
+            None
+        };
+
         Ok(YabridgeFiles {
             vst2_chainloader,
             vst2_chainloader_arch,
@@ -323,22 +1153,232 @@ impl Config {
             yabridge_host_exe_so,
             yabridge_host_32_exe,
             yabridge_host_32_exe_so,
+            name_hash,
         })
     }
 
-    /// Search for VST2, VST3, and CLAP plugins in all of the registered plugins directories.
-    pub fn search_directories(&self) -> Result<BTreeMap<&Path, SearchResults>> {
-        let blacklist: HashSet<&Path> = self.blacklist.iter().map(|p| p.as_path()).collect();
+    /// The file name patterns for libraries that should not be considered to be plugins. These are
+    /// the built-in patterns from `files::SYSTEM_DLLS` combined with the `system_dlls` setting.
+    pub fn system_dll_patterns(&self) -> Vec<String> {
+        let removed: HashSet<&str> = self
+            .system_dlls
+            .iter()
+            .filter_map(|pattern| pattern.strip_prefix('!'))
+            .collect();
+
+        files::SYSTEM_DLLS
+            .iter()
+            .filter(|pattern| !removed.contains(*pattern))
+            .map(|pattern| pattern.to_string())
+            .chain(
+                self.system_dlls
+                    .iter()
+                    .filter(|pattern| !pattern.starts_with('!'))
+                    .cloned(),
+            )
+            .collect()
+    }
+
+    /// The configured plugin directories matching one of the paths in `exclude_dirs`. These paths
+    /// don't need to exist, since excluding a directory on an unmounted drive is a valid use case.
+    /// The paths are compared as is, and after resolving symlinks in `exclude_dirs`. The plugin
+    /// directories themselves are not resolved, since `yabridgectl add` already canonicalizes them
+    /// and resolving them could block on a directory that's on an unresponsive network share.
+    pub fn excluded_plugin_dirs(&self, exclude_dirs: &[PathBuf]) -> Vec<&Path> {
+        let exclude_dirs: HashSet<PathBuf> = exclude_dirs
+            .iter()
+            .flat_map(|path| [path.clone(), util::normalize_path(path)])
+            .collect();
 
         self.plugin_dirs
-            .par_iter()
-            .map(|path| {
-                files::index(path, &blacklist)
-                    .search()
-                    .map(|search_results| (path.as_path(), search_results))
-            })
+            .iter()
+            .filter(|path| exclude_dirs.contains(*path))
+            .map(|path| path.as_path())
             .collect()
     }
+
+    /// The plugin directories `search_directories()` searches with these overrides, in the order
+    /// they're stored in the config file.
+    pub fn searched_plugin_dirs(&self, overrides: &SearchOverrides) -> Result<Vec<&PathBuf>> {
+        if let Some(group) = &overrides.group {
+            if !self
+                .directory_settings
+                .values()
+                .any(|settings| settings.groups.contains(group))
+            {
+                anyhow::bail!("None of the plugin directories are in the '{group}' group");
+            }
+        }
+
+        let excluded_dirs = self.excluded_plugin_dirs(&overrides.exclude_dirs);
+        Ok(self
+            .plugin_dirs
+            .iter()
+            .filter(|path| match &overrides.group {
+                Some(group) => self
+                    .directory_settings
+                    .get(*path)
+                    .map_or(false, |settings| settings.groups.contains(group)),
+                None => true,
+            })
+            .filter(|path| !excluded_dirs.contains(&path.as_path()))
+            .filter(|path| overrides.only_dir.as_ref().map_or(true, |dir| dir == *path))
+            .collect())
+    }
+
+    /// The batches `search_directories()` would split the plugin directories into with these
+    /// overrides, without searching anything. Used for `yabridgectl sync --jobs-report`.
+    pub fn search_plan(&self, overrides: &SearchOverrides) -> Result<Vec<SearchBatch<'_>>> {
+        let plugin_dirs = self.searched_plugin_dirs(overrides)?;
+
+        Ok(group_by_device(plugin_dirs, overrides.device_concurrency)
+            .into_iter()
+            .map(|(device, directories)| SearchBatch {
+                device: device.map(|device| {
+                    let (major, minor) = util::split_device_id(device);
+                    format!("{major}:{minor}")
+                }),
+                rotational: directories
+                    .first()
+                    .and_then(|path| util::is_rotational(path)),
+                directories: directories.into_iter().map(|path| path.as_path()).collect(),
+            })
+            .collect())
+    }
+
+    /// Search for VST2, VST3, and CLAP plugins in all of the registered plugins directories. The
+    /// overrides take precedence over the settings stored in `directory_settings`. The directories
+    /// and the plugins within them are sorted by path using `util::compare_paths()`.
+    pub fn search_directories<'a>(
+        &'a self,
+        overrides: &SearchOverrides,
+    ) -> Result<Vec<(&'a Path, SearchResults)>> {
+        let plugin_dirs = self.searched_plugin_dirs(overrides)?;
+        let search = |path: &'a PathBuf| self.search_directory(path, overrides);
+
+        let results = match overrides.device_concurrency {
+            DeviceConcurrency::Parallel => plugin_dirs
+                .into_par_iter()
+                .map(search)
+                .collect::<Result<Vec<_>>>(),
+            concurrency => group_by_device(plugin_dirs, concurrency)
+                .into_par_iter()
+                .flat_map_iter(|(_, batch)| batch.into_iter().map(&search).collect::<Vec<_>>())
+                .collect::<Result<Vec<_>>>(),
+        };
+
+        results.map(|mut results| {
+            results.sort_by(|(a, _), (b, _)| util::compare_paths(a, b));
+            results
+        })
+    }
+
+    /// Search a single plugin directory the same way `search_directories()` does. This is used to
+    /// search the plugin directories one at a time for `yabridgectl status --json-stream`.
+    pub fn search_directory<'a>(
+        &'a self,
+        path: &'a Path,
+        overrides: &SearchOverrides,
+    ) -> Result<(&'a Path, SearchResults)> {
+        let start_time = Instant::now();
+        let settings = self.directory_settings.get(path);
+        // The subdirectory template has already been applied to the cached plugins
+        if let Some(plugins) = overrides.cached_plugins.get(path) {
+            let mut search_results = SearchResults::from_cache(plugins.clone());
+            search_results.read_only = settings.map(|s| s.read_only).unwrap_or(false);
+            search_results.search_time = start_time.elapsed();
+
+            return Ok((path, search_results));
+        }
+
+        let blacklist: HashSet<&Path> = self.blacklist.iter().map(|p| p.as_path()).collect();
+        let options = files::IndexOptions {
+            no_recurse: overrides.no_recurse || settings.map(|s| s.no_recurse).unwrap_or(false),
+            include_hidden: overrides.include_hidden || self.include_hidden,
+            excludes: self.default_excludes.iter().cloned().collect(),
+            vst2_extensions: self.vst2_extensions.iter().cloned().collect(),
+            system_dlls: if overrides.skip_system_dlls || self.skip_system_dlls {
+                self.system_dll_patterns()
+            } else {
+                Vec::new()
+            },
+            min_size: overrides.min_size.or(self.min_plugin_size),
+            max_size: overrides.max_size.or(self.max_plugin_size),
+            use_ignore_file: self.use_ignore_files,
+            traversal: overrides.traversal,
+            shortcuts: self.shortcuts,
+        };
+
+        files::index(path, &blacklist, &options)
+            .search()
+            .map(|mut search_results| {
+                search_results.read_only = settings.map(|s| s.read_only).unwrap_or(false);
+                if let Some(template) = &self.subdirectory_template {
+                    search_results.apply_subdirectory_template(template, path);
+                }
+                search_results.search_time = start_time.elapsed();
+                (path, search_results)
+            })
+    }
+}
+
+/// Split the plugin directories into batches that can be searched in parallel. The directories
+/// within a batch should be searched one after the other. Directories whose device can't be
+/// determined or that don't need to be serialized always get a batch of their own, and the other
+/// batches contain the ID of the device they're on.
+fn group_by_device(
+    plugin_dirs: Vec<&PathBuf>,
+    concurrency: DeviceConcurrency,
+) -> Vec<(Option<u64>, Vec<&PathBuf>)> {
+    let mut serialized: BTreeMap<u64, Vec<&PathBuf>> = BTreeMap::new();
+    let mut batches: Vec<(Option<u64>, Vec<&PathBuf>)> = Vec::new();
+    for path in plugin_dirs {
+        let serialize = match concurrency {
+            DeviceConcurrency::Parallel => false,
+            DeviceConcurrency::Auto => util::is_rotational(path).unwrap_or(false),
+            DeviceConcurrency::Serial => true,
+        };
+
+        match util::device_id(path).filter(|_| serialize) {
+            Some(device) => serialized.entry(device).or_default().push(path),
+            None => batches.push((None, vec![path])),
+        }
+    }
+
+    batches.extend(
+        serialized
+            .into_iter()
+            .map(|(device, batch)| (Some(device), batch)),
+    );
+    batches
+}
+
+/// Upgrade a parsed config file from an older schema version to `CONFIG_VERSION`. Every step only
+/// handles a single version, so older config files go through all of the steps in order.
+fn migrate_config(config: &mut toml::Value, from_version: i64) {
+    let table = match config.as_table_mut() {
+        Some(table) => table,
+        None => return,
+    };
+
+    if from_version < 2 {
+        // Version 1 config files may still contain the installation method setting from before
+        // yabridge 4.0. The symlink installation method has been removed since then, and copying
+        // is the only remaining method. Those versions always set up VST2 plugins next to their
+        // `.dll` files, so unless the location was changed since then we'll keep doing that.
+        // Otherwise all existing VST2 bridges would suddenly become leftovers.
+        if table.remove("method").is_some() && !table.contains_key("vst2_location") {
+            table.insert(
+                String::from("vst2_location"),
+                toml::Value::String(String::from("inline")),
+            );
+        }
+    }
+
+    table.insert(
+        String::from("version"),
+        toml::Value::Integer(CONFIG_VERSION),
+    );
 }
 
 /// Fetch the XDG base directories for yabridge's own files, converting any error messages if this
@@ -349,26 +1389,59 @@ pub fn yabridge_directories() -> Result<BaseDirectories> {
 }
 
 /// Fetch the XDG base directories used for yabridgectl, converting any error messages if this
-/// somehow fails into a printable string to reduce boiler plate.
+/// somehow fails into a printable string to reduce boiler plate. Use [`config_home()`] and
+/// [`find_config_file()`] for the config file, since those respect `--config-dir`.
 pub fn yabridgectl_directories() -> Result<BaseDirectories> {
     BaseDirectories::with_prefix(YABRIDGECTL_PREFIX).context("Error while parsing base directories")
 }
 
+/// Use `path` instead of `$XDG_CONFIG_HOME/yabridgectl` for the config file. Used for the global
+/// `--config-dir` option. The directory doesn't need to exist yet.
+pub fn set_config_dir(path: Option<PathBuf>) {
+    *CONFIG_DIR.lock().unwrap() = path;
+}
+
+/// The directory the config file is written to. This is the directory passed to `--config-dir`,
+/// or `$XDG_CONFIG_HOME/yabridgectl` otherwise.
+pub fn config_home() -> Result<PathBuf> {
+    match CONFIG_DIR.lock().unwrap().as_ref() {
+        Some(path) => Ok(path.clone()),
+        None => Ok(yabridgectl_directories()?.get_config_home()),
+    }
+}
+
+/// Find the existing config file, if there is one. Without `--config-dir` this also searches
+/// through `$XDG_CONFIG_DIRS`.
+pub fn find_config_file() -> Result<Option<PathBuf>> {
+    match CONFIG_DIR.lock().unwrap().as_ref() {
+        Some(path) => Ok(Some(path.join(CONFIG_FILE_NAME)).filter(|path| path.exists())),
+        None => Ok(yabridgectl_directories()?.find_config_file(CONFIG_FILE_NAME)),
+    }
+}
+
 // TODO: Use `lazy_static` for these things. `$HOME` can technically change at runtime but
 //       realistically it won't.
 
 /// Get the path where bridged VST2 plugin files should be placed when using the centralized
 /// installation location setting. This is a subdirectory of `~/.vst` so we can easily clean up
-/// leftover files without interfering with other native plugins.
-pub fn yabridge_vst2_home() -> PathBuf {
-    Path::new(&env::var("HOME").expect("$HOME is not set")).join(YABRIDGE_VST2_HOME)
+/// leftover files without interfering with other native plugins. Unless the `vst2_target` option
+/// is set, in which case this is a subdirectory of that directory.
+pub fn yabridge_vst2_home(config: &Config) -> PathBuf {
+    match &config.vst2_target {
+        Some(target) => target.join(YABRIDGE_PREFIX),
+        None => Path::new(&env::var("HOME").expect("$HOME is not set")).join(YABRIDGE_VST2_HOME),
+    }
 }
 
 /// Get the path where VST3 modules bridged by yabridgectl should be placed in. This is a
 /// subdirectory of `~/.vst3` so we can easily clean up leftover files without interfering with
-/// other native plugins.
-pub fn yabridge_vst3_home() -> PathBuf {
-    Path::new(&env::var("HOME").expect("$HOME is not set")).join(YABRIDGE_VST3_HOME)
+/// other native plugins. Unless the `vst3_target` option is set, in which case this is a
+/// subdirectory of that directory.
+pub fn yabridge_vst3_home(config: &Config) -> PathBuf {
+    match &config.vst3_target {
+        Some(target) => target.join(YABRIDGE_PREFIX),
+        None => Path::new(&env::var("HOME").expect("$HOME is not set")).join(YABRIDGE_VST3_HOME),
+    }
 }
 
 /// Get the path where CLAP modules bridged by yabridgectl should be placed in. This is a
@@ -396,3 +1469,134 @@ pub fn yabridge_clap_home() -> PathBuf {
         Path::new(&env::var("HOME").expect("$HOME is not set")).join(YABRIDGE_CLAP_HOME)
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::TempDir;
+
+    /// A config file written by a yabridgectl version from before yabridge 4.0, which did not have
+    /// a `version` field yet.
+    const V1_CONFIG: &str = r#"method = "symlink"
+plugin_dirs = ["/home/user/.wine/drive_c/Program Files/Steinberg/VstPlugins"]
+blacklist = ["/home/user/.wine/drive_c/Program Files/Steinberg/VstPlugins/Uninstall.dll"]
+no_verify = true
+"#;
+
+    #[test]
+    fn v1_configs_are_migrated() {
+        let dir = TempDir::new("v1-config");
+        let path = dir.path().join(CONFIG_FILE_NAME);
+        fs::write(&path, V1_CONFIG).unwrap();
+
+        let (config, needs_migration) = Config::parse_file(&path).unwrap();
+        assert!(needs_migration);
+        assert_eq!(config.version, CONFIG_VERSION);
+        assert_eq!(config.vst2_location, Vst2InstallationLocation::Inline);
+        assert_eq!(
+            config.plugin_dirs,
+            BTreeSet::from([PathBuf::from(
+                "/home/user/.wine/drive_c/Program Files/Steinberg/VstPlugins"
+            )])
+        );
+        assert_eq!(
+            config.blacklist,
+            BTreeSet::from([PathBuf::from(
+                "/home/user/.wine/drive_c/Program Files/Steinberg/VstPlugins/Uninstall.dll"
+            )])
+        );
+        assert!(config.no_verify);
+        assert!(config.unknown_settings.is_empty());
+    }
+
+    #[test]
+    fn v1_configs_keep_their_vst2_location() {
+        for (toml_str, expected_location) in [
+            // Written by yabridge 4.0 or later, which no longer stores the installation method
+            ("plugin_dirs = []\n", Vst2InstallationLocation::Centralized),
+            (
+                "method = \"copy\"\nvst2_location = \"centralized\"\n",
+                Vst2InstallationLocation::Centralized,
+            ),
+        ] {
+            let mut toml_value: toml::Value = toml::from_str(toml_str).unwrap();
+            migrate_config(&mut toml_value, 1);
+            assert!(toml_value.get("method").is_none());
+
+            let config: Config = toml_value.try_into().unwrap();
+            assert_eq!(config.version, CONFIG_VERSION);
+            assert_eq!(config.vst2_location, expected_location);
+        }
+    }
+
+    #[test]
+    fn plugin_directories_are_searched_in_a_stable_order() {
+        let dir = TempDir::new("stable-directory-order");
+        let names = ["b", "C", "a", "B"];
+        for name in names {
+            fs::create_dir(dir.path().join(name)).unwrap();
+        }
+        let config = Config {
+            plugin_dirs: names.iter().map(|name| dir.path().join(name)).collect(),
+            ..Default::default()
+        };
+
+        let expected: Vec<PathBuf> = ["a", "B", "b", "C"]
+            .iter()
+            .map(|name| dir.path().join(name))
+            .collect();
+        for device_concurrency in [
+            DeviceConcurrency::Parallel,
+            DeviceConcurrency::Serial,
+            DeviceConcurrency::Parallel,
+        ] {
+            let results = config
+                .search_directories(&SearchOverrides {
+                    device_concurrency,
+                    ..Default::default()
+                })
+                .unwrap();
+            let searched: Vec<&Path> = results.iter().map(|(path, _)| *path).collect();
+            assert_eq!(searched, expected);
+        }
+    }
+
+    #[test]
+    fn reading_old_configs_does_not_write_them() {
+        let dir = TempDir::new("v1-config-read-only");
+        let path = dir.path().join(CONFIG_FILE_NAME);
+        fs::write(&path, V1_CONFIG).unwrap();
+
+        // This is what the read-only commands do. Migrated config files are only written back by
+        // `main()` for commands that change something.
+        let config = Config::read_file(&path).unwrap();
+        assert!(!config.needs_write);
+        config.write_if_needed().unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), V1_CONFIG);
+    }
+
+    #[test]
+    fn empty_config_dirs_get_a_default_config() {
+        let dir = TempDir::new("empty-config-dir");
+        let config_dir = dir.path().join("yabridgectl");
+        set_config_dir(Some(config_dir.clone()));
+
+        let config = Config::read().unwrap();
+        assert_eq!(config.version, CONFIG_VERSION);
+        assert!(config.plugin_dirs.is_empty());
+        assert!(config.needs_write);
+
+        // The directory is created on the first write, without leaving a temporary file behind
+        config.write_if_needed().unwrap();
+        let mut entries: Vec<PathBuf> = fs::read_dir(&config_dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        entries.sort();
+        assert_eq!(entries, [config_dir.join(CONFIG_FILE_NAME)]);
+
+        let config = Config::read().unwrap();
+        assert!(!config.needs_write);
+        set_config_dir(None);
+    }
+}
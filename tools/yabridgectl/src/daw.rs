@@ -0,0 +1,172 @@
+// yabridge: a Wine plugin bridge
+// Copyright (C) 2020-2022 Robbert van der Helm
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Integrations that make DAWs rescan their plugins after `yabridgectl sync` changed the set of
+//! bridged plugins. Most DAWs cache the results of their plugin scans and only rescan plugins they
+//! haven't seen before, or they don't rescan at all until the user asks them to. Removing those
+//! caches forces a full rescan the next time the DAW starts. These integrations are opt-in through
+//! `yabridgectl set --daw=<name>`.
+//!
+//! Adding an integration for another DAW only requires a new `DawIntegration` variant along with
+//! the patterns for its cache files in `DawIntegration::cache_patterns()`.
+
+use anyhow::{Context, Result};
+use serde_derive::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use xdg::BaseDirectories;
+
+use crate::util;
+
+/// The names accepted by `yabridgectl set --daw`. These match the serialized names of the
+/// `DawIntegration` variants.
+pub const DAW_INTEGRATION_NAMES: [&str; 2] = ["ardour", "reaper"];
+
+/// A DAW whose plugin cache should be cleared after syncing.
+#[derive(Deserialize, Serialize, Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum DawIntegration {
+    /// Ardour stores the scan results for VST2 and VST3 plugins in `.v2i` and `.v3i` files in
+    /// `$XDG_CACHE_HOME/ardour<version>/{vst,vst3}`. Every major version of Ardour uses its own
+    /// cache directory.
+    Ardour,
+    /// REAPER stores its plugin scan results in `reaper-vstplugins*.ini` and `reaper-clap-*.ini`
+    /// files in `$XDG_CONFIG_HOME/REAPER`. These files are recreated on the next startup.
+    Reaper,
+}
+
+/// A glob pattern for the cache files of a DAW. The patterns are matched against the directories
+/// and files relative to one of the XDG base directories using `util::glob_matches()`.
+struct CachePattern {
+    base: BaseDirectory,
+    /// The patterns for each path component, relative to `base`. The last pattern matches the cache
+    /// files themselves.
+    components: &'static [&'static str],
+}
+
+#[derive(Clone, Copy)]
+enum BaseDirectory {
+    Cache,
+    Config,
+}
+
+impl DawIntegration {
+    fn cache_patterns(&self) -> &'static [CachePattern] {
+        match self {
+            DawIntegration::Ardour => &[
+                CachePattern {
+                    base: BaseDirectory::Cache,
+                    components: &["ardour*", "vst", "*.v2i"],
+                },
+                CachePattern {
+                    base: BaseDirectory::Cache,
+                    components: &["ardour*", "vst3", "*.v3i"],
+                },
+            ],
+            DawIntegration::Reaper => &[
+                CachePattern {
+                    base: BaseDirectory::Config,
+                    components: &["REAPER", "reaper-vstplugins*.ini"],
+                },
+                CachePattern {
+                    base: BaseDirectory::Config,
+                    components: &["REAPER", "reaper-clap-*.ini"],
+                },
+            ],
+        }
+    }
+
+    /// Find this DAW's plugin cache files. Returns an empty list if the DAW is not installed or if
+    /// it has not scanned any plugins yet.
+    pub fn cache_files(&self, dirs: &BaseDirectories) -> Vec<PathBuf> {
+        let mut files = Vec::new();
+        for pattern in self.cache_patterns() {
+            let base = match pattern.base {
+                BaseDirectory::Cache => dirs.get_cache_home(),
+                BaseDirectory::Config => dirs.get_config_home(),
+            };
+
+            find_matching(&base, pattern.components, &mut files);
+        }
+
+        files
+    }
+}
+
+impl fmt::Display for DawIntegration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DawIntegration::Ardour => write!(f, "ardour"),
+            DawIntegration::Reaper => write!(f, "reaper"),
+        }
+    }
+}
+
+/// Parse one of the names from `DAW_INTEGRATION_NAMES`.
+pub fn parse_daw_integration(name: &str) -> Option<DawIntegration> {
+    match name {
+        "ardour" => Some(DawIntegration::Ardour),
+        "reaper" => Some(DawIntegration::Reaper),
+        _ => None,
+    }
+}
+
+/// Recursively collect the paths under `directory` that match `components`, one pattern per path
+/// component. Directories that can't be read are skipped.
+fn find_matching(directory: &Path, components: &[&str], files: &mut Vec<PathBuf>) {
+    let (pattern, rest) = match components.split_first() {
+        Some(split) => split,
+        None => return,
+    };
+    let entries = match fs::read_dir(directory) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        if !util::glob_matches(pattern, &entry.file_name().to_string_lossy()) {
+            continue;
+        }
+
+        let path = entry.path();
+        if rest.is_empty() {
+            if path.is_file() {
+                files.push(path);
+            }
+        } else if path.is_dir() {
+            find_matching(&path, rest, files);
+        }
+    }
+}
+
+/// Remove the plugin caches for the enabled DAW integrations so the DAWs rescan their plugins.
+/// Returns the cache files that were removed, along with the integration they belong to.
+pub fn trigger_rescans<'a>(
+    integrations: impl IntoIterator<Item = &'a DawIntegration>,
+) -> Result<Vec<(DawIntegration, PathBuf)>> {
+    let dirs = BaseDirectories::new().context("Error while parsing base directories")?;
+
+    let mut removed_files = Vec::new();
+    for integration in integrations {
+        for path in integration.cache_files(&dirs) {
+            util::remove_file(&path)?;
+            removed_files.push((*integration, path));
+        }
+    }
+
+    Ok(removed_files)
+}
@@ -0,0 +1,170 @@
+// yabridge: a Wine plugin bridge
+// Copyright (C) 2020-2022 Robbert van der Helm
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Structured error reporting for when yabridgectl is run with `--json`.
+
+use serde_derive::Serialize;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::config::MissingChainloaderError;
+
+/// The exit code used for all errors. Both the human readable and the JSON error paths use this.
+pub const ERROR_EXIT_CODE: i32 = 1;
+/// The exit code used when a command was stopped with Ctrl+C, see [`InterruptedError`]. This
+/// follows the shell convention of 128 plus the signal number.
+pub const INTERRUPTED_EXIT_CODE: i32 = 128 + 2;
+
+/// Returned when a long running command like `yabridgectl sync` was stopped with Ctrl+C after
+/// finishing the file operation it was working on.
+#[derive(Debug)]
+pub struct InterruptedError;
+
+impl fmt::Display for InterruptedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Interrupted, stopped after finishing the current file operation"
+        )
+    }
+}
+
+impl std::error::Error for InterruptedError {}
+
+/// Returned when `yabridgectl sync` would replace a file that was not created by yabridge with a
+/// bridge. These files are left alone unless the `overwrite_unrelated_files` option is enabled.
+#[derive(Debug)]
+pub struct ConflictError(pub PathBuf);
+
+impl fmt::Display for ConflictError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "'{}' already exists and it is not a yabridge bridge, not overwriting it",
+            self.0.display()
+        )
+    }
+}
+
+impl std::error::Error for ConflictError {}
+
+/// The exit code for an error returned from `run()`.
+pub fn exit_code(err: &anyhow::Error) -> i32 {
+    if err.is::<InterruptedError>() {
+        INTERRUPTED_EXIT_CODE
+    } else {
+        ERROR_EXIT_CODE
+    }
+}
+
+/// The object printed to STDERR when an error occurs while using `--json`.
+#[derive(Serialize)]
+struct JsonError<'a> {
+    error: JsonErrorBody<'a>,
+}
+
+#[derive(Serialize)]
+struct JsonErrorBody<'a> {
+    /// A short machine readable identifier for the type of error that occurred.
+    kind: &'static str,
+    /// The entire error chain formatted as a single line.
+    message: String,
+    /// The path the error relates to, if known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    path: Option<&'a Path>,
+    /// The locations that were searched when yabridge's files could not be found.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    searched_paths: Option<Vec<&'a Path>>,
+}
+
+/// Format an error as a JSON object in the format `{"error": {"kind": "...", "message": "...",
+/// "path": "..."}}`. The kind is determined by looking for known error types in the error's chain.
+/// When yabridge's files could not be found there's also a `searched_paths` array listing every
+/// location that was searched.
+pub fn to_json(err: &anyhow::Error) -> String {
+    let mut kind = "other";
+    let mut path = None;
+    let mut searched_paths = None;
+    for cause in err.chain() {
+        if let Some(missing_chainloader) = cause.downcast_ref::<MissingChainloaderError>() {
+            kind = "missing_chainloader";
+            path = Some(missing_chainloader.path());
+            searched_paths = Some(missing_chainloader.searched_paths());
+            break;
+        } else if cause.is::<InterruptedError>() {
+            kind = "interrupted";
+            break;
+        } else if let Some(conflict) = cause.downcast_ref::<ConflictError>() {
+            kind = "conflict";
+            path = Some(conflict.0.as_path());
+            break;
+        } else if cause.is::<toml::de::Error>() {
+            kind = "invalid_config";
+            break;
+        } else if cause.is::<std::io::Error>() {
+            kind = "io";
+            break;
+        }
+    }
+
+    let json_error = JsonError {
+        error: JsonErrorBody {
+            kind,
+            message: format!("{:#}", err),
+            path,
+            searched_paths,
+        },
+    };
+
+    // This can't fail since the object only contains strings, but better safe than sorry
+    serde_jsonrc::to_string(&json_error).unwrap_or_else(|_| {
+        String::from(r#"{"error": {"kind": "other", "message": "Could not format error"}}"#)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_jsonrc::Value;
+
+    #[test]
+    fn missing_chainloader_errors_are_structured() {
+        let err = anyhow::Error::new(MissingChainloaderError::Auto {
+            searched_paths: vec![
+                PathBuf::from("/usr/lib"),
+                PathBuf::from("/home/user/.local/share/yabridge"),
+            ],
+            user_path: PathBuf::from("/home/user/.local/share/yabridge"),
+        })
+        .context("Could not sync");
+
+        let json: Value = serde_jsonrc::from_str(&to_json(&err)).unwrap();
+        assert_eq!(json["error"]["kind"], "missing_chainloader");
+        assert_eq!(json["error"]["path"], "/home/user/.local/share/yabridge");
+        assert_eq!(
+            json["error"]["searched_paths"],
+            Value::Array(vec![
+                Value::from("/usr/lib"),
+                Value::from("/home/user/.local/share/yabridge"),
+            ])
+        );
+        assert!(json["error"]["message"]
+            .as_str()
+            .unwrap()
+            .starts_with("Could not sync: Could not find"));
+        assert_eq!(exit_code(&err), ERROR_EXIT_CODE);
+    }
+}
@@ -18,16 +18,26 @@
 
 use anyhow::Result;
 use rayon::prelude::*;
-use std::collections::{BTreeMap, HashMap, HashSet};
+use serde_derive::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::ffi::OsStr;
 use std::fmt::Display;
+use std::fs;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 use walkdir::WalkDir;
 
 use crate::config::{
-    yabridge_clap_home, yabridge_vst2_home, yabridge_vst3_home, Config, YabridgeFiles,
+    yabridge_clap_home, yabridge_vst2_home, yabridge_vst3_home, Config, ShortcutHandling,
+    Vst2InstallationLocation, YabridgeFiles,
 };
+use crate::filesystem::{FileSystem, FileType};
+use crate::ignore_file::{IgnoreFile, IGNORE_FILE_NAME};
+use crate::shell_link::resolve_shell_link;
 use crate::symbols::parse_pe32_binary;
-use crate::util::get_file_type;
+use crate::util::{self, get_file_type};
 
 /// Stores the results from searching through a directory. We'll search for Windows VST2 plugin
 /// `.dll` files, Windows VST3 plugin modules, and native Linux `.so` files inside of a directory.
@@ -43,10 +53,50 @@ pub struct SearchResults {
     /// `.dll` files skipped over during the search. Used for printing statistics and shown when
     /// running `yabridgectl sync --verbose`.
     pub skipped_files: Vec<PathBuf>,
+    /// Hidden directories that were not searched because `IndexOptions::include_hidden` was not
+    /// set. Shown when running `yabridgectl sync --verbose`.
+    pub skipped_hidden_dirs: Vec<PathBuf>,
+    /// The number of files and directories that were skipped because they were blacklisted.
+    pub num_blacklisted: usize,
+    /// Plugin files that were skipped because their names matched one of the exclude patterns.
+    /// Shown when running `yabridgectl sync --verbose`.
+    pub excluded_files: Vec<PathBuf>,
+    /// Plugin files that were skipped because they were smaller than `IndexOptions::min_size` or
+    /// larger than `IndexOptions::max_size`, along with their sizes in bytes. Shown when running
+    /// `yabridgectl sync --verbose`.
+    pub size_filtered_files: Vec<(PathBuf, u64)>,
+    /// `.dll` files that were skipped because their names matched `IndexOptions::system_dlls`.
+    /// Shown when running `yabridgectl sync --verbose`.
+    pub system_dll_files: Vec<PathBuf>,
+    /// Files and directories that were skipped because they matched the directory's
+    /// `.yabridgeignore` file. Shown when running `yabridgectl sync --verbose`.
+    pub ignored_paths: Vec<PathBuf>,
+    /// The number of plugin candidates that were skipped because their PE32 headers could not be
+    /// parsed.
+    pub num_unreadable: usize,
+    /// Groups of `.dll` files in the same directory whose names only differ in casing. On
+    /// case-insensitive filesystems the `.so` files for these plugins would overwrite each other,
+    /// so `yabridgectl sync` skips these unless the `--force` option is set.
+    pub case_collisions: Vec<Vec<PathBuf>>,
 
     /// Absolute paths to any `.so` files inside of the directory, and whether they're a symlink or
     /// a regular file.
     pub so_files: Vec<NativeFile>,
+    /// Every directory that was searched, starting with the plugin directory itself. Their
+    /// modification times are used to tell whether the search results are still up to date, see
+    /// `Index::cached_plugins()`. This is empty for cached results.
+    pub directories: Vec<PathBuf>,
+
+    /// Whether the directory was added with `yabridgectl add --read-only`. This is set by
+    /// `Config::search_directories()`. See `SearchResults::vst2_location()`.
+    pub read_only: bool,
+
+    /// The wall clock time spent indexing and searching this directory. This is set by
+    /// `Config::search_directories()` and it's printed when using the `--time` option.
+    pub search_time: Duration,
+    /// Whether `plugins` contains the plugins found during the last sync instead of the results of
+    /// a new search. See `SearchResults::from_cache()`.
+    pub cached: bool,
 }
 
 /// The results of the first step of the search process. We'll first index all possibly relevant
@@ -64,6 +114,216 @@ pub struct SearchIndex {
     /// Absolute paths to any `.so` files inside of the directory, and whether they're a symlink or
     /// a regular file.
     pub so_files: Vec<NativeFile>,
+    /// Every directory that was indexed, including `directory` itself.
+    pub directories: Vec<PathBuf>,
+    /// Hidden directories that were skipped during indexing.
+    pub hidden_dirs: Vec<PathBuf>,
+    /// The number of files and directories that were skipped because they were blacklisted.
+    pub num_blacklisted: usize,
+    /// `.dll`, `.vst3`, and `.clap` files that matched one of the exclude patterns.
+    pub excluded_files: Vec<PathBuf>,
+    /// `.dll`, `.vst3`, and `.clap` files outside of the size limits, along with their sizes.
+    pub size_filtered_files: Vec<(PathBuf, u64)>,
+    /// `.dll` files named like a library that's commonly shipped alongside plugins.
+    pub system_dll_files: Vec<PathBuf>,
+    /// Files and directories that matched the directory's `.yabridgeignore` file.
+    pub ignored_paths: Vec<PathBuf>,
+}
+
+/// The reasons why a file may not result in a bridged plugin during `yabridgectl sync`. These are
+/// counted using a `SkipCounter` and summarized at the end of the syncing process.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SkipReason {
+    /// The file or directory is on the blacklist.
+    Blacklisted,
+    /// The file's name matches one of the default exclude patterns.
+    Excluded,
+    /// The file is smaller than the minimum plugin size or larger than the maximum plugin size.
+    OutsideSizeLimits,
+    /// The file or directory matches the plugin directory's `.yabridgeignore` file.
+    Ignored,
+    /// The directory is hidden and `--include-hidden` was not used.
+    Hidden,
+    /// The file's PE32 header could not be parsed.
+    Unreadable,
+    /// The `.dll`, `.vst3`, or `.clap` file does not contain a plugin.
+    NotAPlugin,
+    /// The plugin's file name only differs in casing from another plugin in the same directory.
+    CaseCollision,
+    /// The plugin has already been provided by another Wine prefix or plugin directory.
+    Duplicate,
+    /// Yabridge was not built with support for the plugin's format.
+    UnsupportedFormat,
+    /// The VST2 plugin doesn't use the `.dll` extension, so it can only be set up using the
+    /// centralized VST2 installation location.
+    NeedsCentralizedLocation,
+    /// The plugin has already been set up, and it did not need updating.
+    UpToDate,
+    /// The plugin has already been set up and `--install-missing-only` was used, so it was not
+    /// checked for updates.
+    AlreadyInstalled,
+    /// The plugin's file has not been modified within the period passed to `--older-than`.
+    Stale,
+    /// The `.dll` file is named like a library that plugins depend on, like the Visual C++
+    /// runtime, and `--skip-system-dlls` was used.
+    SystemLibrary,
+    /// A file that was not created by yabridge already exists where the bridge should be set up.
+    Conflict,
+    /// There is no Wine host with the plugin's architecture, so the bridge would never be able to
+    /// load the plugin. This check can be skipped with `--no-arch-check`.
+    NoMatchingHost,
+}
+
+impl Display for SkipReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self {
+            SkipReason::Blacklisted => write!(f, "blacklisted"),
+            SkipReason::Excluded => write!(f, "excluded by pattern"),
+            SkipReason::OutsideSizeLimits => write!(f, "outside of the size limits"),
+            SkipReason::Ignored => write!(f, "ignored by {IGNORE_FILE_NAME}"),
+            SkipReason::Hidden => write!(f, "hidden directory"),
+            SkipReason::Unreadable => write!(f, "could not be read"),
+            SkipReason::NotAPlugin => write!(f, "not a plugin"),
+            SkipReason::CaseCollision => write!(f, "name only differs in casing"),
+            SkipReason::Duplicate => write!(f, "already provided elsewhere"),
+            SkipReason::UnsupportedFormat => write!(f, "plugin format not supported"),
+            SkipReason::NeedsCentralizedLocation => {
+                write!(f, "needs the centralized VST2 location")
+            }
+            SkipReason::UpToDate => write!(f, "already up to date"),
+            SkipReason::AlreadyInstalled => write!(f, "already installed"),
+            SkipReason::Stale => write!(f, "not modified recently"),
+            SkipReason::SystemLibrary => write!(f, "likely a dependency"),
+            SkipReason::Conflict => write!(f, "would overwrite an unrelated file"),
+            SkipReason::NoMatchingHost => write!(f, "no Wine host for the plugin's architecture"),
+        }
+    }
+}
+
+/// Counts how many files were skipped for each `SkipReason`. This serializes to a JSON object
+/// mapping the reasons to their counts.
+#[derive(Debug, Default, Serialize)]
+#[serde(transparent)]
+pub struct SkipCounter(BTreeMap<SkipReason, usize>);
+
+impl SkipCounter {
+    /// Count a single skipped file.
+    pub fn increment(&mut self, reason: SkipReason) {
+        self.add(reason, 1);
+    }
+
+    /// Count `count` skipped files. Nothing is recorded if `count` is zero.
+    pub fn add(&mut self, reason: SkipReason, count: usize) {
+        if count > 0 {
+            *self.0.entry(reason).or_default() += count;
+        }
+    }
+
+    /// Whether nothing has been skipped.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Iterate over the skip reasons and their counts, in the order the reasons are defined in.
+    pub fn iter(&self) -> impl Iterator<Item = (SkipReason, usize)> + '_ {
+        self.0.iter().map(|(reason, count)| (*reason, *count))
+    }
+}
+
+/// Options for the indexing process. These are derived from the per-directory settings stored in
+/// the config file, combined with any one-off options passed on the command line.
+#[derive(Debug, Default, Clone)]
+pub struct IndexOptions {
+    /// Only index the files directly inside of the directory. VST3 bundles directly inside of the
+    /// directory are still indexed since their module files are always located in a subdirectory.
+    pub no_recurse: bool,
+    /// Also index hidden directories, i.e. directories with names starting with a period. These
+    /// are skipped by default to avoid indexing things like `.git` or `.cache` directories.
+    pub include_hidden: bool,
+    /// File name patterns for plugin files that should be skipped. See `util::glob_matches()` for
+    /// the syntax.
+    pub excludes: Vec<String>,
+    /// File extensions other than `.dll` that should also be indexed as VST2 plugins, in lowercase
+    /// and without a leading period.
+    pub vst2_extensions: Vec<String>,
+    /// File name patterns for VST2 plugin candidates that are almost certainly libraries the
+    /// plugins depend on instead of plugins. This is empty unless skipping these files has been
+    /// enabled. See `Config::system_dll_patterns()`.
+    pub system_dlls: Vec<String>,
+    /// Skip plugin files smaller than this many bytes.
+    pub min_size: Option<u64>,
+    /// Skip plugin files larger than this many bytes.
+    pub max_size: Option<u64>,
+    /// Skip the files and directories matched by the `.yabridgeignore` file in the root of the
+    /// directory, if it exists.
+    pub use_ignore_file: bool,
+    /// The order in which the directory tree is walked.
+    pub traversal: Traversal,
+    /// Whether `.lnk` shortcuts to `.dll` files should be resolved, and which subdirectory to use
+    /// for the resolved plugin.
+    pub shortcuts: ShortcutHandling,
+}
+
+/// File name patterns for well-known libraries that are often installed next to plugins, but that
+/// never contain plugins themselves. Used when skipping system DLLs during indexing, and these
+/// patterns can be extended and overridden using the `system_dlls` setting.
+pub const SYSTEM_DLLS: [&str; 24] = [
+    "api-ms-win-*.dll",
+    "avcodec-*.dll",
+    "avformat-*.dll",
+    "avutil-*.dll",
+    "concrt*.dll",
+    "d3dcompiler_*.dll",
+    "libcrypto-*.dll",
+    "libcurl*.dll",
+    "libgcc_s_*.dll",
+    "libssl-*.dll",
+    "libstdc++-*.dll",
+    "libwinpthread-*.dll",
+    "mfc*.dll",
+    "msvcp*.dll",
+    "msvcr*.dll",
+    "qt5*.dll",
+    "qt6*.dll",
+    "sqlite3.dll",
+    "ucrtbase.dll",
+    "vcomp*.dll",
+    "vcruntime*.dll",
+    "vulkan-1.dll",
+    "webview2loader.dll",
+    "zlib*.dll",
+];
+
+/// Paths of this length or longer are skipped during indexing. This is Linux's `PATH_MAX`, so these
+/// paths would fail with `ENAMETOOLONG` when trying to open them anyways.
+const MAX_PATH_LENGTH: usize = 4096;
+
+/// Every one of yabridge's chainloaders contains the name of the plugin library it loads, and forks
+/// of yabridge use similarly named libraries. The names match those set in yabridge's
+/// `meson.build`, and they're stored as NUL terminated C-strings.
+const YABRIDGE_LIBRARY_NAME_PREFIX: &[u8] = b"libyabridge-";
+const YABRIDGE_LIBRARY_NAMES: [&[u8]; 3] = [
+    b"libyabridge-clap.so\0",
+    b"libyabridge-vst2.so\0",
+    b"libyabridge-vst3.so\0",
+];
+
+/// The order in which `index()` walks a plugin directory. This only affects the order in which
+/// files are encountered. The search results are always sorted afterwards.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Traversal {
+    /// Walk every subdirectory completely before moving on to the next one.
+    DepthFirst,
+    /// Index all files at one depth before descending any further. Files near the top of the
+    /// directory are always found before descending into large subdirectories.
+    BreadthFirst,
+}
+
+impl Default for Traversal {
+    fn default() -> Self {
+        Traversal::DepthFirst
+    }
 }
 
 /// Native `.so` files and VST3 bundle directories we found during a search.
@@ -85,16 +345,383 @@ impl NativeFile {
     }
 }
 
-/// A plugin as found during the search. This can be either a VST2 plugin or a VST3 module.
+/// The installation status of a plugin's bridge, as determined by
+/// `SearchResults::installation_status()`. Every command that needs to know whether a plugin has
+/// been set up correctly should use this instead of looking at the bridge itself.
 #[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InstallState {
+    /// The plugin has not been set up yet.
+    NotInstalled,
+    /// The bridge is a copy of one of yabridge's chainloading libraries. This is how
+    /// `yabridgectl sync` sets up plugins.
+    Copy(PathBuf, CopyState),
+    /// The bridge is a symlink. `yabridgectl sync` only creates copies, so this was either created
+    /// by hand or by a very old version of yabridgectl.
+    Symlink(PathBuf, SymlinkState),
+    /// There's a directory where the bridge should be.
+    Invalid(PathBuf),
+    /// The bridge was set up by a different yabridge installation, like a fork of yabridge, see
+    /// [`is_foreign_bridge()`]. `yabridgectl sync` will replace it with a bridge for the configured
+    /// yabridge installation.
+    Foreign(PathBuf),
+}
+
+/// The state of a symlinked bridge, see [`InstallState::Symlink`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymlinkState {
+    /// The symlink's target exists and can be read.
+    Ok,
+    /// The symlink's target doesn't exist.
+    Broken,
+    /// The symlink's target exists, but it can't be read. This happens when the target's
+    /// permissions don't allow reading it, or when it's on a filesystem that's no longer mounted.
+    UnreadableTarget,
+}
+
+/// The state of a copied bridge, see [`InstallState::Copy`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CopyState {
+    /// The copy matches the chainloader it was copied from. This is also used when yabridge's files
+    /// could not be found, since there's nothing to compare the copy to in that case.
+    UpToDate,
+    /// The copy's contents differ from the current chainloader. The next `yabridgectl sync` will
+    /// update it.
+    Outdated,
+    /// The copy is not a valid ELF library.
+    Corrupt,
+}
+
+impl InstallState {
+    /// Determine the installation status based on the file found at the bridge's location.
+    /// `chainloader` contains the size and the hash of the chainloader the bridge should be a copy
+    /// of, if yabridge's files could be found.
+    pub fn new(
+        file: Option<NativeFile>,
+        chainloader: Option<(u64, i64)>,
+        files: Option<&YabridgeFiles>,
+    ) -> Self {
+        let is_foreign = |path: &Path| files.map_or(false, |files| is_foreign_bridge(path, files));
+
+        match file {
+            None => InstallState::NotInstalled,
+            Some(NativeFile::Regular(path)) => {
+                let state = if util::get_elf_architecture(&path).is_err() {
+                    CopyState::Corrupt
+                } else {
+                    // A copy with a different size can't be up to date, so it doesn't need to be
+                    // read in full to compare the hashes
+                    match chainloader {
+                        Some((expected_size, _))
+                            if path
+                                .metadata()
+                                .map_or(false, |metadata| metadata.len() != expected_size) =>
+                        {
+                            CopyState::Outdated
+                        }
+                        Some((_, expected_hash))
+                            if util::hash_file(&path)
+                                .map_or(false, |hash| hash != expected_hash) =>
+                        {
+                            CopyState::Outdated
+                        }
+                        _ => CopyState::UpToDate,
+                    }
+                };
+
+                // Only copies that differ from the configured chainloader can be foreign
+                if state == CopyState::Outdated && is_foreign(&path) {
+                    InstallState::Foreign(path)
+                } else {
+                    InstallState::Copy(path, state)
+                }
+            }
+            Some(NativeFile::Symlink(path)) => {
+                let state = if !path.exists() {
+                    SymlinkState::Broken
+                } else if util::verify_readable(&path).is_err() {
+                    SymlinkState::UnreadableTarget
+                } else {
+                    SymlinkState::Ok
+                };
+
+                if state == SymlinkState::Ok && is_foreign(&path) {
+                    InstallState::Foreign(path)
+                } else {
+                    InstallState::Symlink(path, state)
+                }
+            }
+            Some(NativeFile::Directory(path)) => InstallState::Invalid(path),
+        }
+    }
+
+    /// The path to the bridge, if there's anything at the bridge's location.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            InstallState::NotInstalled => None,
+            InstallState::Copy(path, _)
+            | InstallState::Symlink(path, _)
+            | InstallState::Invalid(path)
+            | InstallState::Foreign(path) => Some(path),
+        }
+    }
+}
+
+impl Display for InstallState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InstallState::NotInstalled => write!(f, "not yet synced"),
+            InstallState::Copy(_, CopyState::UpToDate) => write!(f, "synced"),
+            InstallState::Copy(_, CopyState::Outdated) => write!(f, "outdated"),
+            InstallState::Copy(_, CopyState::Corrupt) => write!(f, "corrupt"),
+            InstallState::Symlink(_, SymlinkState::Ok) => write!(f, "symlink"),
+            InstallState::Symlink(_, SymlinkState::Broken) => write!(f, "broken symlink"),
+            InstallState::Symlink(_, SymlinkState::UnreadableTarget) => {
+                write!(f, "symlink (unreadable target)")
+            }
+            InstallState::Invalid(_) => write!(f, "invalid"),
+            InstallState::Foreign(_) => write!(f, "foreign"),
+        }
+    }
+}
+
+/// Check whether the bridge at `path` belongs to a different yabridge installation than the one in
+/// `files`. This happens after switching between yabridge and a fork of yabridge, or between
+/// multiple copies of yabridge installed in different locations. This is decided based on the
+/// bridge's contents rather than its name. A bridge is foreign if it contains the name of a
+/// `libyabridge-*` library like yabridge's chainloaders do, and either:
+///
+/// - It's a symlink to a library outside of the directory containing the configured chainloaders.
+/// - It's a copy that doesn't load any of yabridge's own plugin libraries.
+///
+/// Files that can't be read are never considered foreign.
+pub fn is_foreign_bridge(path: &Path, files: &YabridgeFiles) -> bool {
+    let resolved_path = match path.canonicalize() {
+        Ok(path) => path,
+        Err(_) => return false,
+    };
+    let contents = match fs::read(&resolved_path) {
+        Ok(contents) => contents,
+        Err(_) => return false,
+    };
+    let contains = |needle: &[u8]| {
+        contents
+            .windows(needle.len())
+            .any(|window| window == needle)
+    };
+    if !contains(YABRIDGE_LIBRARY_NAME_PREFIX) {
+        return false;
+    }
+
+    let is_symlink = path
+        .symlink_metadata()
+        .map_or(false, |metadata| metadata.file_type().is_symlink());
+    if is_symlink {
+        let yabridge_home = files
+            .vst2_chainloader
+            .parent()
+            .and_then(|directory| directory.canonicalize().ok());
+
+        resolved_path.parent() != yabridge_home.as_deref()
+    } else {
+        !YABRIDGE_LIBRARY_NAMES.iter().any(|name| contains(name))
+    }
+}
+
+/// Check whether the file at `path` is a bridge created from one of yabridge's chainloaders. Just
+/// like in [`is_foreign_bridge()`], this is decided based on the file's contents. Files that can't
+/// be read are never considered to be bridges. The file is read through `backend`.
+pub fn is_bridge(backend: &dyn FileSystem, path: &Path) -> bool {
+    match backend.read(path) {
+        Ok(contents) => contents
+            .windows(YABRIDGE_LIBRARY_NAME_PREFIX.len())
+            .any(|window| window == YABRIDGE_LIBRARY_NAME_PREFIX),
+        Err(_) => false,
+    }
+}
+
+/// Check whether the existing file at `path` is something yabridgectl should not replace with a
+/// bridge, like a native Linux version of a plugin with the same name. Only bridges and broken
+/// symlinks to one of yabridge's libraries can be replaced. Anything that can't be read counts as
+/// an unrelated file.
+pub fn is_unrelated_file(backend: &dyn FileSystem, path: &Path) -> bool {
+    match backend.symlink_metadata(path) {
+        Ok(metadata)
+            if metadata.file_type == FileType::Symlink && backend.metadata(path).is_err() =>
+        {
+            !backend
+                .read_link(path)
+                .ok()
+                .and_then(|target| {
+                    target.file_name().map(|name| {
+                        name.to_string_lossy()
+                            .as_bytes()
+                            .starts_with(YABRIDGE_LIBRARY_NAME_PREFIX)
+                    })
+                })
+                .unwrap_or(false)
+        }
+        Ok(_) => !is_bridge(backend, path),
+        Err(_) => false,
+    }
+}
+
+/// Like [`is_unrelated_file()`], but for the places where yabridgectl symlinks the Windows plugin
+/// files to. Any existing symlink can be replaced there, but the only other files that can be
+/// replaced are bridges.
+pub fn is_unrelated_symlink_target(backend: &dyn FileSystem, path: &Path) -> bool {
+    match backend.symlink_metadata(path) {
+        Ok(metadata) => metadata.file_type != FileType::Symlink && !is_bridge(backend, path),
+        Err(_) => false,
+    }
+}
+
+/// Insert the hash from `YabridgeFiles::name_hash` right before `file_name`'s extension, like
+/// `Plugin.1a2b3c4d.so`. The file name is returned as is when the `hashed_names` option is
+/// disabled.
+fn with_name_hash(file_name: PathBuf, files: Option<&YabridgeFiles>) -> PathBuf {
+    match (
+        files.and_then(|files| files.name_hash.as_ref()),
+        file_name.extension(),
+    ) {
+        (Some(hash), Some(extension)) => {
+            let extension = format!("{}.{}", hash, extension.to_string_lossy());
+            file_name.with_extension(extension)
+        }
+        _ => file_name,
+    }
+}
+
+/// The inverse of [`with_name_hash()`]. If `path`'s file name contains a hash of eight hexadecimal
+/// digits right before its extension, then this returns the same path without that hash.
+pub fn strip_name_hash(path: &Path) -> Option<PathBuf> {
+    let file_name = path.file_name()?.to_str()?;
+    let (stem, extension) = file_name.rsplit_once('.')?;
+    let (name, hash) = stem.rsplit_once('.')?;
+    if name.is_empty() || hash.len() != 8 || !hash.bytes().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    Some(path.with_file_name(format!("{name}.{extension}")))
+}
+
+/// The placeholders that can be used in `Config::subdirectory_template`.
+pub const SUBDIRECTORY_TEMPLATE_PLACEHOLDERS: [&str; 3] =
+    ["{subdirectory}", "{vendor}", "{directory}"];
+
+/// Expand a `Config::subdirectory_template` for a plugin found in `subdirectory` of the plugin
+/// directory `directory`. `{subdirectory}` is replaced by the plugin's original subdirectory,
+/// `{vendor}` by that subdirectory's first component, and `{directory}` by the plugin directory's
+/// name. Empty components are dropped, so the plugin is set up in the root of the centralized
+/// directory if the template expands to an empty path.
+pub fn expand_subdirectory_template(
+    template: &str,
+    directory: &Path,
+    subdirectory: Option<&Path>,
+) -> Option<PathBuf> {
+    let subdirectory_name = subdirectory
+        .map(|path| path.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let vendor_name = subdirectory
+        .and_then(|path| path.components().next())
+        .map(|component| component.as_os_str().to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let directory_name = directory
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    let expanded = template
+        .replace("{subdirectory}", &subdirectory_name)
+        .replace("{vendor}", &vendor_name)
+        .replace("{directory}", &directory_name);
+    // `..` components are dropped so the bridges can never end up outside of the centralized
+    // directories
+    let path: PathBuf = expanded
+        .split('/')
+        .filter(|component| !component.is_empty() && *component != "." && *component != "..")
+        .collect();
+    if path.as_os_str().is_empty() {
+        None
+    } else {
+        Some(path)
+    }
+}
+
+/// Hash the paths of the plugins found in a plugin directory, relative to that `directory`. This is
+/// stored in the index so `yabridgectl sync` can tell whether the set of plugins in a directory
+/// changed since the last sync. The plugins in `SearchResults` are already sorted, so the order is
+/// stable. This uses `util::stable_hash()` since the hash needs to stay the same between
+/// yabridgectl versions.
+pub fn hash_plugin_paths(plugins: &[Plugin], directory: &Path) -> u32 {
+    let relative_paths: Vec<&Path> = plugins
+        .iter()
+        .map(|plugin| {
+            let path = plugin.path();
+            path.strip_prefix(directory).unwrap_or(path)
+        })
+        .collect();
+
+    // The paths are separated by null bytes so `a.dll` and `bc.dll` don't end up with the same
+    // hash as `a.dllb` and `c.dll`
+    util::stable_hash(
+        relative_paths
+            .iter()
+            .flat_map(|path| [path.as_os_str().as_bytes(), &b"\0"[..]]),
+    )
+}
+
+/// A plugin as found during the search. This can be either a VST2 plugin or a VST3 module.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Plugin {
     Vst2(Vst2Plugin),
     Vst3(Vst3Module),
     Clap(ClapPlugin),
 }
 
+impl Plugin {
+    /// Return the path to the plugin's original `.dll`, `.vst3`, or `.clap` file or bundle.
+    pub fn path(&self) -> &Path {
+        match &self {
+            Plugin::Vst2(Vst2Plugin { path, .. }) | Plugin::Clap(ClapPlugin { path, .. }) => path,
+            Plugin::Vst3(module) => module.original_path(),
+        }
+    }
+
+    /// The plugin's architecture. For VST3 modules this is the architecture of the module's
+    /// library file.
+    pub fn architecture(&self) -> LibArchitecture {
+        match &self {
+            Plugin::Vst2(Vst2Plugin { architecture, .. })
+            | Plugin::Clap(ClapPlugin { architecture, .. }) => *architecture,
+            Plugin::Vst3(module) => module.architecture,
+        }
+    }
+
+    /// Return the path to the copy of yabridge's chainloading library `yabridgectl sync` would
+    /// create for this plugin with the current settings. `vst2_location` should be obtained from
+    /// `SearchResults::vst2_location()` for the search results containing this plugin.
+    pub fn native_target(
+        &self,
+        config: &Config,
+        vst2_location: Vst2InstallationLocation,
+        files: Option<&YabridgeFiles>,
+    ) -> PathBuf {
+        match &self {
+            Plugin::Vst2(vst2_plugin) => match vst2_location {
+                Vst2InstallationLocation::Centralized => {
+                    vst2_plugin.centralized_native_target(config, files)
+                }
+                Vst2InstallationLocation::Inline => vst2_plugin.inline_native_target(),
+            },
+            Plugin::Vst3(module) => module.target_native_module_path(config, files),
+            Plugin::Clap(clap_plugin) => clap_plugin.native_target(files),
+        }
+    }
+}
+
 /// VST2 plugins we found during a search along with their architecture.
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
 pub struct Vst2Plugin {
     /// The absolute path to the VST2 plugin's `.dll` file.
     pub path: PathBuf,
@@ -106,7 +733,7 @@ pub struct Vst2Plugin {
 }
 
 /// VST3 modules we found during a search.
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
 pub struct Vst3Module {
     /// The absolute path to the actual VST3 module and its type.
     pub module: Vst3ModuleType,
@@ -118,7 +745,7 @@ pub struct Vst3Module {
 }
 
 /// CLAP plugins we found during a search along with their architecture.
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
 pub struct ClapPlugin {
     /// The absolute path to the Windows CLAP plugin's `.clap` file.
     pub path: PathBuf,
@@ -132,7 +759,8 @@ pub struct ClapPlugin {
 }
 
 /// The type of the VST3 module. VST 3.6.10 style bundles require slightly different handling
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Vst3ModuleType {
     /// Old, pre-VST 3.6.10 style `.vst3` modules. These are simply `.dll` files with a different p
     /// refix. Even though this is a legacy format, almost all VST3 plugins in the wild still use
@@ -146,35 +774,49 @@ pub enum Vst3ModuleType {
 
 impl Vst2Plugin {
     /// Get the absolute path to the `.so` file we should create in `~/.vst/yabridge` for this
-    /// plugin when using the centralized VST installation location mode.
-    pub fn centralized_native_target(&self) -> PathBuf {
+    /// plugin when using the centralized VST installation location mode. This directory can be
+    /// changed with the `vst2_target` option. The file name contains a hash when the `hashed_names`
+    /// option is enabled.
+    pub fn centralized_native_target(
+        &self,
+        config: &Config,
+        files: Option<&YabridgeFiles>,
+    ) -> PathBuf {
         let file_name = self
             .path
             .file_name()
             .unwrap()
             .to_str()
             .expect("Plugin name contains invalid UTF-8");
-        let file_name = Path::new(file_name).with_extension("so");
+        let file_name = with_name_hash(Path::new(file_name).with_extension("so"), files);
 
         match &self.subdirectory {
-            Some(directory) => yabridge_vst2_home().join(directory).join(file_name),
-            None => yabridge_vst2_home().join(file_name),
+            Some(directory) => yabridge_vst2_home(config).join(directory).join(file_name),
+            None => yabridge_vst2_home(config).join(file_name),
         }
     }
 
     /// Get the absolute path to the `.dll` file we should symlink to `~/.vst/yabridge` when setting
-    /// this plugin up with the centralized VST2 installation location setting.
-    pub fn centralized_windows_target(&self) -> PathBuf {
+    /// this plugin up with the centralized VST2 installation location setting. Yabridge looks for
+    /// a file with a lowercase `.dll` extension next to the bridge, so plugins named `Plugin.DLL`
+    /// or using one of the `vst2_extensions` are symlinked as `Plugin.dll`. This uses the same hash
+    /// as `centralized_native_target()`.
+    pub fn centralized_windows_target(
+        &self,
+        config: &Config,
+        files: Option<&YabridgeFiles>,
+    ) -> PathBuf {
         let file_name = self
             .path
             .file_name()
             .unwrap()
             .to_str()
             .expect("Plugin name contains invalid UTF-8");
+        let file_name = with_name_hash(Path::new(file_name).with_extension("dll"), files);
 
         match &self.subdirectory {
-            Some(directory) => yabridge_vst2_home().join(directory).join(file_name),
-            None => yabridge_vst2_home().join(file_name),
+            Some(directory) => yabridge_vst2_home(config).join(directory).join(file_name),
+            None => yabridge_vst2_home(config).join(file_name),
         }
     }
 
@@ -183,6 +825,23 @@ impl Vst2Plugin {
     pub fn inline_native_target(&self) -> PathBuf {
         self.path.with_extension("so")
     }
+
+    /// The plugin's file extension in lowercase, like `dll` or one of the `vst2_extensions`.
+    pub fn extension(&self) -> Option<String> {
+        windows_extension(&self.path)
+    }
+
+    /// Whether the plugin uses the standard `.dll` extension, in any casing.
+    pub fn has_dll_extension(&self) -> bool {
+        self.extension().as_deref() == Some("dll")
+    }
+
+    /// Whether the plugin can be set up using the inline VST2 installation location. Yabridge
+    /// looks for a `.dll` file next to the bridge, and it can't rename the plugin in that mode, so
+    /// this only works if the extension is exactly `.dll`.
+    pub fn supports_inline_location(&self) -> bool {
+        self.path.extension() == Some(OsStr::new("dll"))
+    }
 }
 
 impl Vst3Module {
@@ -256,16 +915,17 @@ impl Vst3Module {
 
     /// Get the path to the bundle in `~/.vst3` corresponding to the bridged version of this module.
     /// We will try to recreate the original subdirectory structure so plugins are still grouped by
-    /// manufacturer.
+    /// manufacturer. The `~/.vst3` directory can be changed with the `vst3_target` option.
     ///
     /// FIXME: How do we solve naming clashes from the same VST3 plugin being installed to multiple
     ///        Wine prefixes?
-    pub fn target_bundle_home(&self) -> PathBuf {
+    pub fn target_bundle_home(&self, config: &Config) -> PathBuf {
+        // Linux hosts only look for bundles ending in exactly `.vst3`, but the Windows module may
+        // have been called `Plugin.VST3`
+        let bundle_name = Path::new(self.original_module_name()).with_extension("vst3");
         match &self.subdirectory {
-            Some(directory) => yabridge_vst3_home()
-                .join(directory)
-                .join(self.original_module_name()),
-            None => yabridge_vst3_home().join(self.original_module_name()),
+            Some(directory) => yabridge_vst3_home(config).join(directory).join(bundle_name),
+            None => yabridge_vst3_home(config).join(bundle_name),
         }
     }
 
@@ -273,7 +933,11 @@ impl Vst3Module {
     /// version of this module. The path here depends on whether we're using a 32-bit or 64-bit
     /// version of yabridge. If the configuration is not given (for instance, becuase yabridge is
     /// not set up properly) we'll assume the module should be 64-bit.
-    pub fn target_native_module_path(&self, files: Option<&YabridgeFiles>) -> PathBuf {
+    pub fn target_native_module_path(
+        &self,
+        config: &Config,
+        files: Option<&YabridgeFiles>,
+    ) -> PathBuf {
         let native_module_name = match &self.module {
             Vst3ModuleType::Legacy(path) | Vst3ModuleType::Bundle(path) => path
                 .with_extension("so")
@@ -284,7 +948,7 @@ impl Vst3Module {
                 .to_owned(),
         };
 
-        let mut path = self.target_bundle_home();
+        let mut path = self.target_bundle_home(config);
         path.push("Contents");
 
         #[allow(clippy::wildcard_in_or_patterns)]
@@ -301,20 +965,21 @@ impl Vst3Module {
     }
 
     /// Get the path to where we'll symlink `original_module_path`. This is part of the merged VST3
-    /// bundle in `~/.vst3/yabridge`.
-    pub fn target_windows_module_path(&self) -> PathBuf {
-        let mut path = self.target_bundle_home();
+    /// bundle in `~/.vst3/yabridge`. Yabridge only looks for a module with a lowercase `.vst3`
+    /// extension, so `Plugin.VST3` is symlinked as `Plugin.vst3`.
+    pub fn target_windows_module_path(&self, config: &Config) -> PathBuf {
+        let mut path = self.target_bundle_home(config);
         path.push("Contents");
         path.push(self.architecture.vst_arch());
-        path.push(self.original_module_name());
+        path.push(Path::new(self.original_module_name()).with_extension("vst3"));
         path
     }
 
     /// If the Windows VST3 plugin we're bridging was in a VST 3.6.10 style bundle and had a
     /// resources directory, then we'll symlink that directory to here so the host can access all
     /// its original resources.
-    pub fn target_resources_dir(&self) -> PathBuf {
-        let mut path = self.target_bundle_home();
+    pub fn target_resources_dir(&self, config: &Config) -> PathBuf {
+        let mut path = self.target_bundle_home(config);
         path.push("Contents");
         path.push("Resources");
         path
@@ -322,8 +987,8 @@ impl Vst3Module {
 
     /// If the Windows VST3 plugin had a `moduleinfo.json` file, then it should be translated using
     /// `ModuleInfo::rewrite_uid_byte_orders()` and then written to this path.
-    pub fn target_moduleinfo_path(&self) -> PathBuf {
-        let mut path = self.target_bundle_home();
+    pub fn target_moduleinfo_path(&self, config: &Config) -> PathBuf {
+        let mut path = self.target_bundle_home(config);
         path.push("Contents");
         path.push("moduleinfo.json");
         path
@@ -340,15 +1005,15 @@ impl Vst3Module {
 
 impl ClapPlugin {
     /// Get the absolute path to the `.clap` file we should create in `~/.clap/yabridge` for this
-    /// plugin.
-    pub fn native_target(&self) -> PathBuf {
+    /// plugin. The file name contains a hash when the `hashed_names` option is enabled.
+    pub fn native_target(&self, files: Option<&YabridgeFiles>) -> PathBuf {
         let file_name = self
             .path
             .file_name()
             .unwrap()
             .to_str()
             .expect("Plugin name contains invalid UTF-8");
-        let file_name = Path::new(file_name).with_extension("clap");
+        let file_name = with_name_hash(Path::new(file_name).with_extension("clap"), files);
 
         match &self.subdirectory {
             Some(directory) => yabridge_clap_home().join(directory).join(file_name),
@@ -359,14 +1024,14 @@ impl ClapPlugin {
     /// Get the absolute path to the `.clap-win` file in `~/.clap/yabrdge` the Windows `.clap`
     /// plugin should be symlinked to. This uses a different file extension so we can use the same
     /// setup as for VST2 plugins without confusing DAWs.
-    pub fn windows_target(&self) -> PathBuf {
+    pub fn windows_target(&self, files: Option<&YabridgeFiles>) -> PathBuf {
         let file_name = self
             .path
             .file_name()
             .unwrap()
             .to_str()
             .expect("Plugin name contains invalid UTF-8");
-        let file_name = Path::new(file_name).with_extension("clap-win");
+        let file_name = with_name_hash(Path::new(file_name).with_extension("clap-win"), files);
 
         match &self.subdirectory {
             Some(directory) => yabridge_clap_home().join(directory).join(file_name),
@@ -377,7 +1042,8 @@ impl ClapPlugin {
 
 /// The architecture of a library file (either `.dll` or `.so` depending on the context). Needed so
 /// we can create a merged bundle for VST3 plugins.
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Copy)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum LibArchitecture {
     Lib32,
     Lib64,
@@ -404,35 +1070,109 @@ impl LibArchitecture {
 }
 
 impl SearchResults {
-    /// Create a map out of all found Windows plugins and their current installation status, if the
-    /// plugin has already been set up.
+    /// Create search results from the plugins found in a directory during the last sync, as stored
+    /// in the index. Only the `.so` files next to VST2 plugins are looked up, since those are
+    /// needed to determine the installation status with the inline VST2 location. Everything else
+    /// that's only gathered during an actual search is left empty.
+    pub fn from_cache(plugins: Vec<Plugin>) -> SearchResults {
+        let so_files = plugins
+            .iter()
+            .filter_map(|plugin| match plugin {
+                Plugin::Vst2(vst2_plugin) => get_file_type(vst2_plugin.inline_native_target()),
+                _ => None,
+            })
+            .collect();
+
+        SearchResults {
+            plugins,
+            skipped_files: Vec::new(),
+            skipped_hidden_dirs: Vec::new(),
+            num_blacklisted: 0,
+            excluded_files: Vec::new(),
+            size_filtered_files: Vec::new(),
+            system_dll_files: Vec::new(),
+            ignored_paths: Vec::new(),
+            num_unreadable: 0,
+            case_collisions: Vec::new(),
+            so_files,
+            directories: Vec::new(),
+            read_only: false,
+            search_time: Duration::ZERO,
+            cached: true,
+        }
+    }
+
+    /// Change the subdirectories the plugins from `directory` are set up in within the centralized
+    /// plugin directories according to `template`. See [`expand_subdirectory_template()`]. This
+    /// doesn't affect VST2 plugins set up with the inline VST2 location.
+    pub fn apply_subdirectory_template(&mut self, template: &str, directory: &Path) {
+        for plugin in &mut self.plugins {
+            let subdirectory = match plugin {
+                Plugin::Vst2(Vst2Plugin { subdirectory, .. })
+                | Plugin::Vst3(Vst3Module { subdirectory, .. })
+                | Plugin::Clap(ClapPlugin { subdirectory, .. }) => subdirectory,
+            };
+
+            *subdirectory =
+                expand_subdirectory_template(template, directory, subdirectory.as_deref());
+        }
+    }
+
+    /// The VST2 installation location used for the plugins in this directory. This is the
+    /// configured location, unless the directory is read-only. Bridges for plugins in read-only
+    /// directories can't be created next to the plugin, so these always use the centralized
+    /// location.
+    pub fn vst2_location(&self, config: &Config) -> Vst2InstallationLocation {
+        if self.read_only {
+            Vst2InstallationLocation::Centralized
+        } else {
+            config.effective_vst2_location()
+        }
+    }
+
+    /// Create a list of all found Windows plugins and their current installation status. The list
+    /// is sorted by path using `util::compare_paths()`. This hashes every bridge with the same size
+    /// as its chainloader to check whether it's up to date, so it's not free. The bridges are
+    /// checked in parallel.
     pub fn installation_status(
         &self,
         config: &Config,
         files: Option<&YabridgeFiles>,
-    ) -> BTreeMap<PathBuf, (&Plugin, Option<NativeFile>)> {
+    ) -> Vec<(PathBuf, (&Plugin, InstallState))> {
         let so_files: HashMap<&Path, &NativeFile> = self
             .so_files
             .iter()
             .map(|file| (file.path(), file))
             .collect();
 
-        self.plugins
+        // The bridges are compared to these to find outdated copies
+        let size_and_hash =
+            |path: &Path| Some((path.metadata().ok()?.len(), util::hash_file(path).ok()?));
+        let vst2_chainloader = files.and_then(|files| size_and_hash(&files.vst2_chainloader));
+        let vst3_chainloader = files
+            .and_then(|files| files.vst3_chainloader.as_ref())
+            .and_then(|(path, _)| size_and_hash(path));
+        let clap_chainloader = files
+            .and_then(|files| files.clap_chainloader.as_ref())
+            .and_then(|(path, _)| size_and_hash(path));
+
+        let mut status: Vec<(PathBuf, (&Plugin, InstallState))> = self
+            .plugins
             .iter()
             .map(|plugin| match plugin {
                 Plugin::Vst2(vst2_plugin) => {
                     // For VST2 plugins depending on the VST2 installation location setting we'll
                     // either look for a matching file in `~/.vst` or we'll just look at the
                     // similarly named `.so` file right next to the plugin `.dll` file
-                    match config.vst2_location {
-                        crate::config::Vst2InstallationLocation::Centralized => (
+                    match self.vst2_location(config) {
+                        Vst2InstallationLocation::Centralized => (
                             vst2_plugin.path.clone(),
                             (
                                 plugin,
-                                get_file_type(vst2_plugin.centralized_native_target()),
+                                get_file_type(vst2_plugin.centralized_native_target(config, files)),
                             ),
                         ),
-                        crate::config::Vst2InstallationLocation::Inline => {
+                        Vst2InstallationLocation::Inline => {
                             match so_files.get(vst2_plugin.inline_native_target().as_path()) {
                                 Some(&file_type) => {
                                     (vst2_plugin.path.clone(), (plugin, Some(file_type.clone())))
@@ -448,21 +1188,56 @@ impl SearchResults {
                     vst3_module.original_path().to_owned(),
                     (
                         plugin,
-                        get_file_type(vst3_module.target_native_module_path(files)),
+                        get_file_type(vst3_module.target_native_module_path(config, files)),
                     ),
                 ),
                 Plugin::Clap(clap_plugin) => (
                     clap_plugin.path.clone(),
-                    (plugin, get_file_type(clap_plugin.native_target())),
+                    (plugin, get_file_type(clap_plugin.native_target(files))),
                 ),
             })
+            // This deduplicates the paths, and the results are then sorted case-insensitively
+            .collect::<BTreeMap<_, _>>()
+            .into_par_iter()
+            .map(|(path, (plugin, file))| {
+                let chainloader = match plugin {
+                    Plugin::Vst2(_) => vst2_chainloader,
+                    Plugin::Vst3(_) => vst3_chainloader,
+                    Plugin::Clap(_) => clap_chainloader,
+                };
+
+                (path, (plugin, InstallState::new(file, chainloader, files)))
+            })
+            .collect();
+        status.sort_by(|(a, _), (b, _)| util::compare_paths(a, b));
+
+        status
+    }
+
+    /// Find the `.so` files next to `.dll` files that were skipped because they don't export any
+    /// VST2 entry points. These are likely bridges for installers or helper libraries set up by
+    /// an older version of yabridgectl, or by hand. With `yabridgectl sync --skip-non-vst` they're
+    /// treated as orphans.
+    pub fn non_vst_bridges(&self) -> HashSet<PathBuf> {
+        let so_files: HashSet<&Path> = self.so_files.iter().map(NativeFile::path).collect();
+
+        self.skipped_files
+            .iter()
+            .filter(|path| windows_extension(path).as_deref() == Some("dll"))
+            .map(|path| path.with_extension("so"))
+            .filter(|path| so_files.contains(path.as_path()))
             .collect()
     }
 
     /// Find all `.so` files in the search results that do not belong to a VST2 plugin `.dll` file.
     /// This depends on the VST2 installation location setting. Centralized VST2 and VST3 orphans
-    /// should be detected separately.
+    /// should be detected separately. Files in read-only directories are never orphans since
+    /// yabridgectl didn't create them.
     pub fn vst2_inline_orphans(&self, config: &Config) -> Vec<&NativeFile> {
+        if self.read_only {
+            return Vec::new();
+        }
+
         // We need to store these in a map so we can easily entries with corresponding `.dll` files
         let mut orphans: HashMap<&Path, &NativeFile> = self
             .so_files
@@ -470,12 +1245,12 @@ impl SearchResults {
             .map(|file_type| (file_type.path(), file_type))
             .collect();
 
-        match config.vst2_location {
+        match config.effective_vst2_location() {
             // When we set up the plugin in `~/.vst`, any `.so` file in a VST2 plugin search
             // directory should be considered an orphan. This can happen when switching between the
             // two modes.
-            crate::config::Vst2InstallationLocation::Centralized => (),
-            crate::config::Vst2InstallationLocation::Inline => {
+            Vst2InstallationLocation::Centralized => (),
+            Vst2InstallationLocation::Inline => {
                 for plugin in &self.plugins {
                     if let Plugin::Vst2(Vst2Plugin { path, .. }) = plugin {
                         orphans.remove(path.with_extension("so").as_path());
@@ -488,14 +1263,96 @@ impl SearchResults {
     }
 }
 
+/// Find plugins with identical contents, for instance because the same plugin has been installed in
+/// two different plugin directories. Plugins are compared by the contents of their `.dll`, VST3
+/// module, or `.clap` file, so only plugins of the same format can be duplicates. Paths that
+/// resolve to the same file are not considered to be duplicates. Every returned set contains at
+/// least two plugins, in the same order as `plugins`. The first plugin in each set is the one to
+/// keep when using `yabridgectl sync --keep-first`.
+pub fn find_duplicates<'a>(plugins: impl IntoIterator<Item = &'a Plugin>) -> Vec<Vec<&'a Plugin>> {
+    // Hashing every plugin would be slow, so we'll only hash plugins with the same format and the
+    // same file size
+    let mut seen_files: HashSet<PathBuf> = HashSet::new();
+    let mut candidates: BTreeMap<(&'static str, u64), Vec<(usize, &'a Plugin, PathBuf)>> =
+        BTreeMap::new();
+    for (idx, plugin) in plugins.into_iter().enumerate() {
+        let (format, contents_path) = match plugin {
+            Plugin::Vst2(vst2_plugin) => ("vst2", vst2_plugin.path.clone()),
+            Plugin::Vst3(module) => ("vst3", module.original_module_path()),
+            Plugin::Clap(clap_plugin) => ("clap", clap_plugin.path.clone()),
+        };
+        if !seen_files.insert(util::normalize_path(&contents_path)) {
+            continue;
+        }
+
+        if let Ok(metadata) = contents_path.metadata() {
+            candidates
+                .entry((format, metadata.len()))
+                .or_default()
+                .push((idx, plugin, contents_path));
+        }
+    }
+
+    let mut duplicates: Vec<Vec<(usize, &'a Plugin)>> = candidates
+        .into_values()
+        .filter(|group| group.len() > 1)
+        .flat_map(|group| {
+            let mut by_hash: HashMap<i64, Vec<(usize, &'a Plugin)>> = HashMap::new();
+            let hashes: Vec<_> = group
+                .into_par_iter()
+                .filter_map(|(idx, plugin, contents_path)| {
+                    util::hash_file(&contents_path)
+                        .ok()
+                        .map(|hash| (hash, idx, plugin))
+                })
+                .collect();
+            for (hash, idx, plugin) in hashes {
+                by_hash.entry(hash).or_default().push((idx, plugin));
+            }
+
+            by_hash.into_values().filter(|set| set.len() > 1)
+        })
+        .collect();
+
+    // The sets and the plugins within them should be in the same order as the plugins were passed
+    // in, so the results are deterministic
+    for set in &mut duplicates {
+        set.sort_by_key(|(idx, _)| *idx);
+    }
+    duplicates.sort_by_key(|set| set[0].0);
+
+    duplicates
+        .into_iter()
+        .map(|set| set.into_iter().map(|(_, plugin)| plugin).collect())
+        .collect()
+}
+
+/// Add a file found by `index()` to `files`.
+fn push_indexed_file(files: &mut Vec<PathBuf>, path: PathBuf) {
+    files.push(path);
+
+    // This is a bit of an odd warning, but I can see it happening that someone adds their entire
+    // home directory by accident. Removing the home directory would cause yabridgectl to scan for
+    // leftover `.so` files, which would of course take an enternity. This warning will at least
+    // tell the user what's happening and that they can safely cancel the scan.
+    if files.len() == 100_001 {
+        eprintln!(
+            "Indexed over 100.000 files, press Ctrl+C to cancel this operation if this was not \
+             intentional."
+        )
+    }
+}
+
 /// Find all `.dll`, `.vst3`, `.clap`, and `.so` files under a directory. These results can be
 /// filtered down to actual VST2 plugins, VST3 modules, and CLAP plugins using `search()`. Any path
 /// found in the blacklist will be pruned immediately, so this can be used to both not index
-/// individual files and to skip an entire directory.
+/// individual files and to skip an entire directory. If `options.no_recurse` is set, then
+/// subdirectories are not searched, with the exception of VST3 bundles. Hidden directories are
+/// skipped unless `options.include_hidden` is set.
 ///
 /// For VST3 plugin _bundles_ the subdirectory also contains the `foo.vst3/Contents/x86_64-win`
 /// suffix. This needs to be stripped out to get the bundle root.
-pub fn index(directory: &Path, blacklist: &HashSet<&Path>) -> SearchIndex {
+pub fn index(directory: &Path, blacklist: &HashSet<&Path>, options: &IndexOptions) -> SearchIndex {
     // These are pairs of `(absolute_path, subdirectory)`. The subdirectory is used for setting up
     // VST3 and CLAP plugins and for setting up VST2 plugins in the centralized installation
     // location mode.
@@ -503,46 +1360,253 @@ pub fn index(directory: &Path, blacklist: &HashSet<&Path>) -> SearchIndex {
     let mut vst3_files: Vec<(PathBuf, Option<PathBuf>)> = Vec::new();
     let mut clap_files: Vec<(PathBuf, Option<PathBuf>)> = Vec::new();
     let mut so_files: Vec<NativeFile> = Vec::new();
-    for (file_idx, path) in WalkDir::new(directory)
-        .follow_links(true)
-        .into_iter()
-        .filter_entry(|e| {
-            // The blacklist entries are canonicalized to resolve symlinks and to normalize slashes,
-            // so we should do the same thing here as well
-            e.path()
-                .canonicalize()
-                .map(|p| !blacklist.contains(p.as_path()))
-                .unwrap_or(false)
+    let mut directories: Vec<PathBuf> = Vec::new();
+    let mut hidden_dirs: Vec<PathBuf> = Vec::new();
+    let mut num_blacklisted: usize = 0;
+    let mut excluded_files: Vec<PathBuf> = Vec::new();
+    let mut size_filtered_files: Vec<(PathBuf, u64)> = Vec::new();
+    let mut system_dll_files: Vec<PathBuf> = Vec::new();
+    let mut ignored_paths: Vec<PathBuf> = Vec::new();
+    // A broken ignore file shouldn't prevent the directory from being indexed
+    let ignore_file = if options.use_ignore_file {
+        IgnoreFile::read(directory).unwrap_or_else(|err| {
+            eprintln!("WARNING: Could not read the ignore file: {err:#}\n");
+            None
         })
-        .filter_map(|e| {
-            // NOTE: Broken symlinks will also get an `Err` entry, so we'll use `err.path()` to
-            //       still include them in the index
-            let path = match e {
-                Ok(entry) => entry.path().to_owned(),
-                Err(err) => err.path()?.to_owned(),
-            };
+    } else {
+        None
+    };
+    // Returns whether an entry should be indexed, or for directories, whether they should be
+    // descended into. This is shared between both traversal orders, and it records the entries it
+    // skips.
+    let mut should_index = |path: &Path, depth: usize, is_dir: bool| -> bool {
+        // When not recursing we'll still need to descend into bundles since that's where VST3
+        // bundles store their actual module files
+        if options.no_recurse && depth > 1 && !is_in_vst3_bundle(directory, path) {
+            return false;
+        }
+
+        // The plugin directory itself may of course be inside of a hidden directory, like
+        // `~/.wine`
+        if !options.include_hidden
+            && depth > 0
+            && is_dir
+            && path
+                .file_name()
+                .map_or(false, |name| name.to_string_lossy().starts_with('.'))
+        {
+            hidden_dirs.push(path.to_owned());
+            return false;
+        }
 
-            if !path.is_dir() {
-                Some(path)
-            } else {
-                None
+        if let Some(ignore_file) = &ignore_file {
+            let is_ignored = path.strip_prefix(directory).map_or(false, |relative_path| {
+                ignore_file.is_ignored(relative_path, is_dir)
+            });
+            if is_ignored {
+                ignored_paths.push(path.to_owned());
+                return false;
             }
-        })
-        .enumerate()
-    {
-        // This is a bit of an odd warning, but I can see it happening that someone adds their
-        // entire home directory by accident. Removing the home directory would cause yabridgectl to
-        // scan for leftover `.so` files, which would of course take an enternity. This warning will
-        // at least tell the user what's happening and that they can safely cancel the scan.
-        if file_idx == 100_000 {
+        }
+
+        // Paths this long can't be opened anyways. Deeply nested vendor directories can somehow
+        // end up like this, and they shouldn't abort the entire scan.
+        if path.as_os_str().len() >= MAX_PATH_LENGTH {
             eprintln!(
-                "Indexed over 100.000 files, press Ctrl+C to cancel this operation if this was \
-                 not intentional."
-            )
+                "WARNING: Skipping '{}' during scan: The path is too long\n",
+                path.display()
+            );
+            return false;
         }
 
-        match path.extension().and_then(|os| os.to_str()) {
-            Some("dll") => {
+        // The blacklist entries are canonicalized to resolve symlinks and to normalize slashes, so
+        // we should do the same thing here as well
+        match path.canonicalize() {
+            Ok(p) if blacklist.contains(p.as_path()) => {
+                num_blacklisted += 1;
+                false
+            }
+            Ok(_) => true,
+            Err(err) => {
+                eprintln!(
+                    "WARNING: Skipping '{}' during scan: {}\n",
+                    path.display(),
+                    err
+                );
+                false
+            }
+        }
+    };
+
+    // Both traversals only collect the files here. They're sorted into the different file types
+    // below.
+    let mut files: Vec<PathBuf> = Vec::new();
+    match options.traversal {
+        Traversal::DepthFirst => {
+            // `WalkDir` keeps its own stack instead of recursing, so very deep directory trees
+            // can't overflow the stack
+            for entry in WalkDir::new(directory)
+                .follow_links(true)
+                .sort_by(|a, b| util::compare_paths(a.path(), b.path()))
+                .into_iter()
+                .filter_entry(|e| should_index(e.path(), e.depth(), e.file_type().is_dir()))
+            {
+                // NOTE: Broken symlinks will also get an `Err` entry, so we'll use `err.path()` to
+                //       still include them in the index. Other errors, like symlink loops or
+                //       paths that are too long to open, are reported and then skipped.
+                let path = match entry {
+                    Ok(entry) => entry.into_path(),
+                    Err(err) => {
+                        let is_broken_symlink = err
+                            .io_error()
+                            .map_or(false, |io_err| io_err.kind() == io::ErrorKind::NotFound);
+                        match err.path() {
+                            Some(path) if is_broken_symlink => path.to_owned(),
+                            _ => {
+                                eprintln!("WARNING: Skipping file during scan: {}\n", err);
+                                continue;
+                            }
+                        }
+                    }
+                };
+
+                if path.is_dir() {
+                    directories.push(path);
+                } else {
+                    push_indexed_file(&mut files, path);
+                }
+            }
+        }
+        Traversal::BreadthFirst => {
+            // This is a plain queue of directories that still need to be read. Every directory
+            // also stores the canonicalized paths of itself and of the directories it was found
+            // in, since following symlinks could otherwise cause us to loop forever.
+            let mut queue: VecDeque<(PathBuf, usize, Vec<PathBuf>)> = VecDeque::new();
+            if should_index(directory, 0, true) {
+                let ancestors = directory.canonicalize().into_iter().collect();
+                directories.push(directory.to_owned());
+                queue.push_back((directory.to_owned(), 0, ancestors));
+            }
+
+            while let Some((current_dir, depth, ancestors)) = queue.pop_front() {
+                let mut entries: Vec<PathBuf> = match fs::read_dir(&current_dir)
+                    .and_then(|entries| entries.map(|entry| Ok(entry?.path())).collect())
+                {
+                    Ok(entries) => entries,
+                    // This matches `WalkDir`, which also silently skips a missing plugin directory
+                    Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+                    Err(err) => {
+                        eprintln!(
+                            "WARNING: Skipping '{}' during scan: {}\n",
+                            current_dir.display(),
+                            err
+                        );
+                        continue;
+                    }
+                };
+                entries.sort_by(|a, b| util::compare_paths(a, b));
+
+                for path in entries {
+                    // Symlinks are followed, and broken symlinks are still indexed so they can be
+                    // cleaned up like with the depth-first traversal
+                    let is_dir = match fs::metadata(&path) {
+                        Ok(metadata) => metadata.is_dir(),
+                        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                            push_indexed_file(&mut files, path);
+                            continue;
+                        }
+                        Err(err) => {
+                            eprintln!(
+                                "WARNING: Skipping '{}' during scan: {}\n",
+                                path.display(),
+                                err
+                            );
+                            continue;
+                        }
+                    };
+                    if !should_index(&path, depth + 1, is_dir) {
+                        continue;
+                    }
+                    if !is_dir {
+                        push_indexed_file(&mut files, path);
+                        continue;
+                    }
+
+                    let canonical_path = match path.canonicalize() {
+                        Ok(canonical_path) => canonical_path,
+                        Err(_) => continue,
+                    };
+                    if ancestors.contains(&canonical_path) {
+                        eprintln!(
+                            "WARNING: Skipping '{}' during scan: It points to one of its parent \
+                             directories\n",
+                            path.display()
+                        );
+                        continue;
+                    }
+
+                    let mut subdirectory_ancestors = ancestors.clone();
+                    subdirectory_ancestors.push(canonical_path);
+                    directories.push(path.clone());
+                    queue.push_back((path, depth + 1, subdirectory_ancestors));
+                }
+            }
+        }
+    }
+
+    for path in files {
+        // The exclude patterns are only matched against the plugin files themselves
+        let extension = windows_extension(&path);
+        let is_vst2_extension = |extension: &str| {
+            extension == "dll" || options.vst2_extensions.iter().any(|e| e == extension)
+        };
+        let is_plugin_candidate = match extension.as_deref() {
+            Some("vst3" | "clap") => true,
+            Some(extension) => is_vst2_extension(extension),
+            None => false,
+        };
+        if is_plugin_candidate {
+            let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+            if options
+                .excludes
+                .iter()
+                .any(|pattern| util::glob_matches(pattern, &file_name))
+            {
+                excluded_files.push(path);
+                continue;
+            }
+
+            // Reading the PE32 exports would also reveal that these aren't plugins, but that's
+            // not possible for every file and it's much slower than looking at the name
+            if !matches!(extension.as_deref(), Some("vst3" | "clap"))
+                && options
+                    .system_dlls
+                    .iter()
+                    .any(|pattern| util::glob_matches(pattern, &file_name))
+            {
+                system_dll_files.push(path);
+                continue;
+            }
+
+            // VST3 bundles are directories, so only their module files are checked here
+            if options.min_size.is_some() || options.max_size.is_some() {
+                let size = fs::metadata(&path)
+                    .ok()
+                    .filter(|metadata| metadata.is_file())
+                    .map(|metadata| metadata.len());
+                if let Some(size) = size {
+                    if options.min_size.map_or(false, |min_size| size < min_size)
+                        || options.max_size.map_or(false, |max_size| size > max_size)
+                    {
+                        size_filtered_files.push((path, size));
+                        continue;
+                    }
+                }
+            }
+        }
+
+        match extension.as_deref() {
+            Some(extension) if is_vst2_extension(extension) => {
                 let subdirectory = path
                     .parent()
                     .and_then(|p| p.strip_prefix(directory).ok())
@@ -565,7 +1629,31 @@ pub fn index(directory: &Path, blacklist: &HashSet<&Path>) -> SearchIndex {
                     .map(|p| p.to_owned());
                 clap_files.push((path, subdirectory));
             }
-            Some("so") => {
+            Some("lnk") if options.shortcuts != ShortcutHandling::Ignore => {
+                // Shortcuts that can't be resolved or that point to something other than a
+                // `.dll` file are silently skipped, since most shortcuts will be to
+                // uninstallers and manuals
+                let target = match resolve_shell_link(&path) {
+                    Ok(target)
+                        if windows_extension(&target).as_deref() == Some("dll")
+                            && target.exists() =>
+                    {
+                        target
+                    }
+                    _ => continue,
+                };
+
+                let subdirectory = match options.shortcuts {
+                    ShortcutHandling::Shortcut => path.parent(),
+                    _ => target.parent(),
+                }
+                .and_then(|p| p.strip_prefix(directory).ok())
+                .map(|p| p.to_owned());
+                dll_files.push((target, subdirectory));
+            }
+            // Unlike the Windows files, the `.so` files should have exactly this extension.
+            // Otherwise files with other extensions could be considered to be leftover files.
+            _ if path.extension() == Some("so".as_ref()) => {
                 if path.is_symlink() {
                     so_files.push(NativeFile::Symlink(path));
                 } else {
@@ -576,14 +1664,80 @@ pub fn index(directory: &Path, blacklist: &HashSet<&Path>) -> SearchIndex {
         }
     }
 
+    // A shortcut or a symlink may point to a plugin that's also in the plugin directory itself, so
+    // we'll only keep the first occurrence of every `.dll` file. Symlinks to files outside of the
+    // plugin directory are kept as is, so their bridges are created next to the symlink and named
+    // after the symlink rather than after its target.
+    let mut seen_dll_files: HashSet<PathBuf> = HashSet::new();
+    dll_files.retain(|(path, _)| seen_dll_files.insert(util::normalize_path(path)));
+
     SearchIndex {
         dll_files,
         vst3_files,
         clap_files,
         so_files,
+        directories,
+        hidden_dirs,
+        num_blacklisted,
+        excluded_files,
+        size_filtered_files,
+        system_dll_files,
+        ignored_paths,
+    }
+}
+
+/// Check whether `path` is located inside of a `.vst3` bundle directory directly inside of
+/// `directory`. Used to still be able to index VST3 bundles when not recursing into subdirectories.
+fn is_in_vst3_bundle(directory: &Path, path: &Path) -> bool {
+    path.strip_prefix(directory)
+        .ok()
+        .and_then(|relative_path| relative_path.components().next())
+        .map(|component| {
+            windows_extension(Path::new(component.as_os_str())).as_deref() == Some("vst3")
+        })
+        .unwrap_or(false)
+}
+
+/// Describe why a file from `SearchResults::skipped_files` was skipped, based on its extension.
+pub fn non_plugin_reason(path: &Path) -> &'static str {
+    match windows_extension(path).as_deref() {
+        Some("vst3") => "not a VST3 plugin",
+        Some("clap") => "not a CLAP plugin",
+        _ => "not a VST plugin",
     }
 }
 
+/// Get a Windows file's extension in lowercase. Windows file names are case-insensitive, so
+/// `Plugin.DLL` is just as much of a VST2 plugin as `Plugin.dll`.
+fn windows_extension(path: &Path) -> Option<String> {
+    path.extension()
+        .and_then(|os| os.to_str())
+        .map(|extension| extension.to_ascii_lowercase())
+}
+
+/// Find groups of files in the same directory whose names only differ in casing, like `Plugin.dll`
+/// and `plugin.dll`. These would collide on case-insensitive filesystems like exFAT and NTFS.
+fn find_case_collisions(files: &[(PathBuf, Option<PathBuf>)]) -> Vec<Vec<PathBuf>> {
+    // Only the file names are compared case-insensitively. Directories that differ in casing are
+    // different directories, so their files can't collide with each other.
+    let mut files_by_lowercase_name: BTreeMap<(&Path, String), Vec<PathBuf>> = BTreeMap::new();
+    for (path, _) in files {
+        let (parent, file_name) = match (path.parent(), path.file_name()) {
+            (Some(parent), Some(file_name)) => (parent, file_name),
+            _ => continue,
+        };
+        files_by_lowercase_name
+            .entry((parent, file_name.to_string_lossy().to_lowercase()))
+            .or_default()
+            .push(path.clone());
+    }
+
+    files_by_lowercase_name
+        .into_values()
+        .filter(|paths| paths.len() > 1)
+        .collect()
+}
+
 impl SearchIndex {
     /// Filter these indexing results down to actual VST2 plugins and VST3 modules. This will skip
     /// all invalid files, such as regular `.dll` libraries.
@@ -593,6 +1747,12 @@ impl SearchIndex {
         // This is a constant with external linkage, not a function
         const CLAP_ENTRY_POINTS: [&str; 1] = ["clap_entry"];
 
+        // This needs to be done before the `.dll` files are consumed below
+        let case_collisions = find_case_collisions(&self.dll_files);
+        // Files that could not be parsed are filtered out below, so we'll count them by comparing
+        // the number of candidates with the number of results
+        let num_candidates = self.dll_files.len() + self.vst3_files.len() + self.clap_files.len();
+
         // We'll have to figure out which `.dll` files are VST2 plugins and which should be skipped
         // by checking whether the file contains one of the VST2 entry point functions. This vector
         // will contain an `Err(path)` if `path` was not a valid VST2 plugin.
@@ -759,6 +1919,9 @@ impl SearchIndex {
             })
             .collect();
 
+        let num_unreadable =
+            num_candidates - (is_vst2_plugin.len() + is_vst3_module.len() + is_clap_plugin.len());
+
         let mut plugins: Vec<Plugin> = Vec::new();
         let mut skipped_files: Vec<PathBuf> = Vec::new();
         for dandidate in is_vst2_plugin {
@@ -780,10 +1943,407 @@ impl SearchIndex {
             }
         }
 
+        // The indexing order depends on the filesystem, so we'll sort the results to make the
+        // output deterministic
+        plugins.sort_by(|a, b| util::compare_paths(a.path(), b.path()));
+        skipped_files.sort_by(|a, b| util::compare_paths(a, b));
+
         Ok(SearchResults {
             plugins,
             skipped_files,
+            skipped_hidden_dirs: self.hidden_dirs,
+            num_blacklisted: self.num_blacklisted,
+            excluded_files: self.excluded_files,
+            size_filtered_files: self.size_filtered_files,
+            system_dll_files: self.system_dll_files,
+            ignored_paths: self.ignored_paths,
+            num_unreadable,
+            case_collisions,
             so_files: self.so_files,
+            directories: self.directories,
+            read_only: false,
+            search_time: Duration::ZERO,
+            cached: false,
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::TempDir;
+    use std::os::unix::fs::PermissionsExt;
+
+    /// The names of the `.dll` files found by `index()`, sorted.
+    fn indexed_dll_names(index: &SearchIndex) -> Vec<String> {
+        let mut names: Vec<String> = index
+            .dll_files
+            .iter()
+            .map(|(path, _)| path.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+
+        names
+    }
+
+    #[test]
+    fn index_dll_extensions_in_any_casing() {
+        let dir = TempDir::new("dll-extension-casing");
+        for name in ["Lower.dll", "Mixed.Dll", "Upper.DLL"] {
+            fs::write(dir.path().join(name), b"").unwrap();
+        }
+
+        let index = index(dir.path(), &HashSet::new(), &IndexOptions::default());
+        assert_eq!(
+            indexed_dll_names(&index),
+            ["Lower.dll", "Mixed.Dll", "Upper.DLL"]
+        );
+        assert!(find_case_collisions(&index.dll_files).is_empty());
+    }
+
+    #[test]
+    fn deep_directory_trees_are_indexed() {
+        let dir = TempDir::new("deep-directory-trees");
+
+        // Far deeper than any real plugin directory, while still being well within `PATH_MAX`
+        let deep_dir = (0..250).fold(dir.path().join("deep"), |path, _| path.join("d"));
+        fs::create_dir_all(&deep_dir).unwrap();
+        fs::write(deep_dir.join("Deep.dll"), b"").unwrap();
+
+        // And a file with a path that's too long to open. Paths this long can't be created
+        // directly, so the directory containing it is moved into another directory afterwards.
+        let long_name = "l".repeat(200);
+        let long_dir = (0..18).fold(dir.path().join("long"), |path, _| path.join(&long_name));
+        fs::create_dir_all(&long_dir).unwrap();
+        fs::write(long_dir.join("TooLong.dll"), b"").unwrap();
+        let outer_dir = (0..3).fold(dir.path().join("outer"), |path, _| path.join(&long_name));
+        fs::create_dir_all(&outer_dir).unwrap();
+        fs::rename(dir.path().join("long"), outer_dir.join("long")).unwrap();
+
+        for traversal in [Traversal::DepthFirst, Traversal::BreadthFirst] {
+            let index = index(
+                dir.path(),
+                &HashSet::new(),
+                &IndexOptions {
+                    traversal,
+                    ..Default::default()
+                },
+            );
+            assert_eq!(indexed_dll_names(&index), ["Deep.dll"]);
+        }
+    }
+
+    #[test]
+    fn index_order_does_not_depend_on_creation_order() {
+        let names = [
+            "Zeta.dll",
+            "alpha.dll",
+            "Beta.dll",
+            "beta/Nested.dll",
+            "Alpha/Nested.dll",
+        ];
+        let forwards = TempDir::new("index-order-forwards");
+        let backwards = TempDir::new("index-order-backwards");
+        for (dir, names) in [
+            (&forwards, names.to_vec()),
+            (&backwards, names.iter().rev().copied().collect()),
+        ] {
+            for name in names {
+                let path = dir.path().join(name);
+                fs::create_dir_all(path.parent().unwrap()).unwrap();
+                fs::write(path, b"").unwrap();
+            }
+        }
+
+        let indexed_paths = |dir: &TempDir, traversal: Traversal| -> Vec<PathBuf> {
+            index(
+                dir.path(),
+                &HashSet::new(),
+                &IndexOptions {
+                    traversal,
+                    ..Default::default()
+                },
+            )
+            .dll_files
+            .into_iter()
+            .map(|(path, _)| path.strip_prefix(dir.path()).unwrap().to_owned())
+            .collect()
+        };
+        for traversal in [Traversal::DepthFirst, Traversal::BreadthFirst] {
+            let expected = indexed_paths(&forwards, traversal);
+            assert_eq!(indexed_paths(&backwards, traversal), expected);
+            assert_eq!(indexed_paths(&forwards, traversal), expected);
+        }
+        assert_eq!(
+            indexed_paths(&forwards, Traversal::DepthFirst),
+            [
+                "Alpha/Nested.dll",
+                "alpha.dll",
+                "beta/Nested.dll",
+                "Beta.dll",
+                "Zeta.dll"
+            ]
+            .map(PathBuf::from)
+        );
+    }
+
+    #[test]
+    fn hidden_directories_are_skipped_unless_included() {
+        // The plugin directory itself may be hidden, like `~/.wine`
+        let dir = TempDir::new("hidden-directories");
+        let plugin_dir = dir.path().join(".wine");
+        fs::create_dir_all(plugin_dir.join(".cache")).unwrap();
+        fs::create_dir_all(plugin_dir.join("Vendor/.git/objects")).unwrap();
+        fs::write(plugin_dir.join(".cache/Cached.dll"), b"").unwrap();
+        fs::write(plugin_dir.join("Vendor/.git/objects/Object.dll"), b"").unwrap();
+        fs::write(plugin_dir.join("Vendor/Plugin.dll"), b"").unwrap();
+        // Only directories are hidden this way, not files
+        fs::write(plugin_dir.join(".Hidden.dll"), b"").unwrap();
+
+        for traversal in [Traversal::DepthFirst, Traversal::BreadthFirst] {
+            let skipped = index(
+                &plugin_dir,
+                &HashSet::new(),
+                &IndexOptions {
+                    traversal,
+                    ..Default::default()
+                },
+            );
+            assert_eq!(indexed_dll_names(&skipped), [".Hidden.dll", "Plugin.dll"]);
+            let mut hidden_dirs = skipped.hidden_dirs.clone();
+            hidden_dirs.sort();
+            assert_eq!(
+                hidden_dirs,
+                [plugin_dir.join(".cache"), plugin_dir.join("Vendor/.git")]
+            );
+
+            let included = index(
+                &plugin_dir,
+                &HashSet::new(),
+                &IndexOptions {
+                    include_hidden: true,
+                    traversal,
+                    ..Default::default()
+                },
+            );
+            assert_eq!(
+                indexed_dll_names(&included),
+                [".Hidden.dll", "Cached.dll", "Object.dll", "Plugin.dll"]
+            );
+            assert!(included.hidden_dirs.is_empty());
+        }
+    }
+
+    #[test]
+    fn case_collisions_are_limited_to_the_same_directory() {
+        let files: Vec<(PathBuf, Option<PathBuf>)> = [
+            "/plugins/Vendor/Plugin.dll",
+            "/plugins/Vendor/PLUGIN.dll",
+            "/plugins/vendor/plugin.dll",
+            "/plugins/Other.dll",
+        ]
+        .into_iter()
+        .map(|path| (PathBuf::from(path), None))
+        .collect();
+
+        assert_eq!(
+            find_case_collisions(&files),
+            [vec![
+                PathBuf::from("/plugins/Vendor/Plugin.dll"),
+                PathBuf::from("/plugins/Vendor/PLUGIN.dll")
+            ]]
+        );
+    }
+
+    #[test]
+    fn breadth_first_index_finds_shallow_files_first() {
+        let dir = TempDir::new("breadth-first");
+        fs::create_dir_all(dir.path().join("a/b")).unwrap();
+        fs::write(dir.path().join("a/b/Deep.dll"), b"").unwrap();
+        fs::write(dir.path().join("a/Middle.dll"), b"").unwrap();
+        fs::write(dir.path().join("z.dll"), b"").unwrap();
+        // This would cause an infinite loop if symlinks to parent directories were followed
+        std::os::unix::fs::symlink(dir.path(), dir.path().join("a/b/loop")).unwrap();
+
+        let breadth_first = index(
+            dir.path(),
+            &HashSet::new(),
+            &IndexOptions {
+                traversal: Traversal::BreadthFirst,
+                ..Default::default()
+            },
+        );
+        let found: Vec<_> = breadth_first
+            .dll_files
+            .iter()
+            .map(|(path, _)| path.strip_prefix(dir.path()).unwrap().to_owned())
+            .collect();
+        assert_eq!(
+            found,
+            [
+                PathBuf::from("z.dll"),
+                PathBuf::from("a/Middle.dll"),
+                PathBuf::from("a/b/Deep.dll")
+            ]
+        );
+
+        let depth_first = index(dir.path(), &HashSet::new(), &IndexOptions::default());
+        assert_eq!(
+            indexed_dll_names(&breadth_first),
+            indexed_dll_names(&depth_first)
+        );
+    }
+
+    #[test]
+    fn symlinked_dlls_are_bridged_at_the_symlink() {
+        let vendor_dir = TempDir::new("symlinked-dll-vendor");
+        let plugin_dir = TempDir::new("symlinked-dll-plugins");
+        fs::write(vendor_dir.path().join("Target.dll"), b"").unwrap();
+        std::os::unix::fs::symlink(
+            vendor_dir.path().join("Target.dll"),
+            plugin_dir.path().join("Linked.dll"),
+        )
+        .unwrap();
+
+        let index = index(plugin_dir.path(), &HashSet::new(), &IndexOptions::default());
+        assert_eq!(index.dll_files.len(), 1);
+        let (path, subdirectory) = index.dll_files[0].clone();
+        assert_eq!(path, plugin_dir.path().join("Linked.dll"));
+
+        let plugin = Vst2Plugin {
+            path,
+            architecture: LibArchitecture::Lib64,
+            subdirectory,
+        };
+        let config = Config {
+            vst2_target: Some(PathBuf::from("/vst")),
+            ..Default::default()
+        };
+        assert_eq!(
+            plugin.inline_native_target(),
+            plugin_dir.path().join("Linked.so")
+        );
+        assert_eq!(
+            plugin.centralized_native_target(&config, None),
+            Path::new("/vst/yabridge/Linked.so")
+        );
+    }
+
+    #[test]
+    fn symlinks_to_unreadable_libraries_are_reported() {
+        // Root can read the file regardless of its permissions
+        if unsafe { libc::geteuid() } == 0 {
+            return;
+        }
+
+        let dir = TempDir::new("unreadable-symlink-target");
+        let target = dir.path().join("libyabridge-chainloader-vst2.so");
+        fs::write(&target, b"\x7fELF").unwrap();
+        fs::set_permissions(&target, fs::Permissions::from_mode(0o000)).unwrap();
+        let bridge = dir.path().join("Plugin.so");
+        std::os::unix::fs::symlink(&target, &bridge).unwrap();
+
+        assert_eq!(
+            InstallState::new(Some(NativeFile::Symlink(bridge.clone())), None, None),
+            InstallState::Symlink(bridge, SymlinkState::UnreadableTarget)
+        );
+    }
+
+    #[test]
+    fn bridges_use_lowercase_extensions() {
+        let config = Config {
+            vst2_target: Some(PathBuf::from("/vst")),
+            vst3_target: Some(PathBuf::from("/vst3")),
+            ..Default::default()
+        };
+
+        for (name, supports_inline_location) in [("dll", true), ("Dll", false), ("DLL", false)]
+            .map(|(extension, inline)| (format!("Plugin.{extension}"), inline))
+        {
+            let plugin = Vst2Plugin {
+                path: Path::new("/plugins").join(&name),
+                architecture: LibArchitecture::Lib64,
+                subdirectory: None,
+            };
+            assert_eq!(
+                plugin.centralized_native_target(&config, None),
+                Path::new("/vst/yabridge/Plugin.so")
+            );
+            assert_eq!(
+                plugin.centralized_windows_target(&config, None),
+                Path::new("/vst/yabridge/Plugin.dll")
+            );
+            assert_eq!(plugin.supports_inline_location(), supports_inline_location);
+        }
+
+        let module = Vst3Module {
+            module: Vst3ModuleType::Legacy(PathBuf::from("/plugins/Plugin.VST3")),
+            architecture: LibArchitecture::Lib64,
+            subdirectory: None,
+        };
+        assert_eq!(
+            module.target_windows_module_path(&config),
+            Path::new("/vst3/yabridge/Plugin.vst3/Contents/x86_64-win/Plugin.vst3")
+        );
+    }
+
+    #[test]
+    fn plugin_path_hashes_are_relative_to_the_directory() {
+        let vst2_plugin = |path: &str| {
+            Plugin::Vst2(Vst2Plugin {
+                path: PathBuf::from(path),
+                architecture: LibArchitecture::Lib64,
+                subdirectory: None,
+            })
+        };
+
+        assert_eq!(
+            hash_plugin_paths(&[vst2_plugin("/a/Plugin.dll")], Path::new("/a")),
+            hash_plugin_paths(&[vst2_plugin("/b/Plugin.dll")], Path::new("/b"))
+        );
+        assert_ne!(
+            hash_plugin_paths(
+                &[vst2_plugin("/a/a.dll"), vst2_plugin("/a/bc.dll")],
+                Path::new("/a")
+            ),
+            hash_plugin_paths(
+                &[vst2_plugin("/a/a.dllb"), vst2_plugin("/a/c.dll")],
+                Path::new("/a")
+            )
+        );
+        assert_ne!(
+            hash_plugin_paths(&[], Path::new("/a")),
+            hash_plugin_paths(&[vst2_plugin("/a/Plugin.dll")], Path::new("/a"))
+        );
+    }
+
+    #[test]
+    fn bridges_for_non_vst_dlls_are_detected() {
+        let dir = TempDir::new("non-vst-bridges");
+        let mut results = index(dir.path(), &HashSet::new(), &IndexOptions::default())
+            .search()
+            .unwrap();
+        results.skipped_files = vec![
+            PathBuf::from("/plugins/Helper.dll"),
+            PathBuf::from("/plugins/Uninstall.dll"),
+            PathBuf::from("/plugins/Plugin.vst3"),
+        ];
+        results.so_files = vec![
+            NativeFile::Regular(PathBuf::from("/plugins/Plugin.so")),
+            NativeFile::Regular(PathBuf::from("/plugins/Uninstall.so")),
+        ];
+
+        assert_eq!(
+            results.non_vst_bridges(),
+            HashSet::from([PathBuf::from("/plugins/Uninstall.so")])
+        );
+        assert_eq!(
+            non_plugin_reason(Path::new("/plugins/Uninstall.DLL")),
+            "not a VST plugin"
+        );
+        assert_eq!(
+            non_plugin_reason(Path::new("/plugins/Plugin.vst3")),
+            "not a VST3 plugin"
+        );
+    }
+}
@@ -0,0 +1,842 @@
+// yabridge: a Wine plugin bridge
+// Copyright (C) 2020-2022 Robbert van der Helm
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! An abstraction over the filesystem operations yabridgectl uses to set up bridges. Every change
+//! yabridgectl makes goes through one of these backends, which makes it possible to swap out the
+//! real filesystem for one that only reports what would have been done. The code that sets up the
+//! bridges takes the backend as an argument and also reads through it, so it can be tested against
+//! an in-memory filesystem.
+
+use anyhow::{Context, Result};
+use colored::Colorize;
+#[cfg(test)]
+use std::cell::Cell;
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::io::Write;
+use std::os::unix::fs::{self as unix_fs, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Set through the global `--dry-run` option. This selects the backend returned by [`current()`].
+static DRY_RUN: AtomicBool = AtomicBool::new(false);
+/// The shell commands recorded by [`DryRunFileSystem`] after calling [`record_script()`].
+static SCRIPT: Mutex<Option<Vec<String>>> = Mutex::new(None);
+/// The backend used for `--dry-run`, which reads from the real filesystem.
+static DRY_RUN_FILESYSTEM: DryRunFileSystem = DryRunFileSystem::new(&RealFileSystem);
+
+#[cfg(test)]
+thread_local! {
+    /// The backend and dry-run mode set by [`with_backend()`] for the current thread.
+    static TEST_BACKEND: Cell<Option<(&'static dyn FileSystem, bool)>> = Cell::new(None);
+}
+
+/// The type of a file, as returned by [`FileSystem::metadata()`] and
+/// [`FileSystem::symlink_metadata()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileType {
+    File,
+    Directory,
+    Symlink,
+}
+
+/// The parts of a file's metadata yabridgectl needs. Unlike [`std::fs::Metadata`], this can also
+/// be constructed by backends that don't use the real filesystem.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Metadata {
+    pub file_type: FileType,
+    /// The file's size in bytes.
+    pub len: u64,
+    pub modified: SystemTime,
+    /// The file's permissions, like `0o644`.
+    pub mode: u32,
+}
+
+impl Metadata {
+    fn from_std(metadata: fs::Metadata) -> std::io::Result<Metadata> {
+        let file_type = metadata.file_type();
+        Ok(Metadata {
+            file_type: if file_type.is_symlink() {
+                FileType::Symlink
+            } else if file_type.is_dir() {
+                FileType::Directory
+            } else {
+                FileType::File
+            },
+            len: metadata.len(),
+            modified: metadata.modified()?,
+            mode: metadata.permissions().mode() & 0o7777,
+        })
+    }
+}
+
+/// The operations used to inspect and modify the filesystem. The wrappers in `util` forward to the
+/// backend returned by [`current()`], so the rest of yabridgectl doesn't need to know which backend
+/// is in use.
+pub trait FileSystem: Sync {
+    /// Get the metadata for the file at `path`, following symlinks.
+    fn metadata(&self, path: &Path) -> Result<Metadata>;
+    /// Get the metadata for the file at `path`. If `path` is a symlink, then this returns the
+    /// metadata for the symlink itself.
+    fn symlink_metadata(&self, path: &Path) -> Result<Metadata>;
+    /// Read the entire file at `path`, following symlinks.
+    fn read(&self, path: &Path) -> Result<Vec<u8>>;
+    /// Get the paths to the entries in the directory at `path`, sorted by file name.
+    fn read_dir(&self, path: &Path) -> Result<Vec<PathBuf>>;
+    /// Get the target of the symlink at `path`.
+    fn read_link(&self, path: &Path) -> Result<PathBuf>;
+    /// Check whether the file at `path` can be opened for reading, following symlinks.
+    fn verify_readable(&self, path: &Path) -> Result<()>;
+
+    /// Copy or reflink `from` to `to`. Returns the number of bytes copied, if the file was copied.
+    fn copy(&self, from: &Path, to: &Path) -> Result<Option<u64>>;
+    /// Create `path` and all of its parent directories.
+    fn create_dir_all(&self, path: &Path) -> Result<()>;
+    /// Remove the empty directory at `path`.
+    fn remove_dir(&self, path: &Path) -> Result<()>;
+    /// Recursively remove the directory at `path`.
+    fn remove_dir_all(&self, path: &Path) -> Result<()>;
+    /// Remove the file or symlink at `path`.
+    fn remove_file(&self, path: &Path) -> Result<()>;
+    /// Change the modification time of the file at `path` to `mtime`.
+    fn set_modified(&self, path: &Path, mtime: SystemTime) -> Result<()>;
+    /// Change the permissions of the file at `path` to the octal `mode`.
+    fn set_permissions(&self, path: &Path, mode: u32) -> Result<()>;
+    /// Create a symlink at `dst` pointing to `src`.
+    fn symlink(&self, src: &Path, dst: &Path) -> Result<()>;
+    /// Write `contents` to `path`, replacing the file if it already exists.
+    fn write(&self, path: &Path, contents: &[u8]) -> Result<()>;
+    /// Like [`write()`](FileSystem::write), but the file is replaced atomically.
+    fn write_atomic(&self, path: &Path, contents: &[u8]) -> Result<()>;
+}
+
+/// Enable or disable dry-run mode. This changes the backend returned by [`current()`].
+pub fn set_dry_run(enabled: bool) {
+    DRY_RUN.store(enabled, Ordering::Relaxed);
+}
+
+/// Whether `--dry-run` was passed to yabridgectl.
+pub fn is_dry_run() -> bool {
+    #[cfg(test)]
+    if let Some((_, dry_run)) = TEST_BACKEND.with(Cell::get) {
+        return dry_run;
+    }
+
+    DRY_RUN.load(Ordering::Relaxed)
+}
+
+/// Get the backend that should be used for modifying the filesystem.
+pub fn current() -> &'static dyn FileSystem {
+    #[cfg(test)]
+    if let Some((backend, _)) = TEST_BACKEND.with(Cell::get) {
+        return backend;
+    }
+
+    if is_dry_run() {
+        &DRY_RUN_FILESYSTEM
+    } else {
+        &RealFileSystem
+    }
+}
+
+/// Run `f` with `backend` taking the place of the real filesystem on the current thread. If
+/// `dry_run` is set, then `f` runs in dry-run mode and only reads from `backend`. This doesn't
+/// affect other threads, so the tests can still run in parallel.
+#[cfg(test)]
+pub fn with_backend<T>(
+    backend: &'static dyn FileSystem,
+    dry_run: bool,
+    f: impl FnOnce() -> T,
+) -> T {
+    let backend: &'static dyn FileSystem = if dry_run {
+        Box::leak(Box::new(DryRunFileSystem::new(backend)))
+    } else {
+        backend
+    };
+
+    let previous = TEST_BACKEND.with(|cell| cell.replace(Some((backend, dry_run))));
+    let result = f();
+    TEST_BACKEND.with(|cell| cell.set(previous));
+
+    result
+}
+
+/// The backend that actually modifies the filesystem using `std::fs`.
+pub struct RealFileSystem;
+
+impl FileSystem for RealFileSystem {
+    fn metadata(&self, path: &Path) -> Result<Metadata> {
+        fs::metadata(path)
+            .and_then(Metadata::from_std)
+            .with_context(|| format!("Could not read the metadata for '{}'", path.display()))
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> Result<Metadata> {
+        fs::symlink_metadata(path)
+            .and_then(Metadata::from_std)
+            .with_context(|| format!("Could not read the metadata for '{}'", path.display()))
+    }
+
+    fn read(&self, path: &Path) -> Result<Vec<u8>> {
+        fs::read(path).with_context(|| format!("Could not read contents of '{}'", path.display()))
+    }
+
+    fn read_dir(&self, path: &Path) -> Result<Vec<PathBuf>> {
+        let mut paths = fs::read_dir(path)
+            .and_then(|entries| {
+                entries
+                    .map(|entry| entry.map(|entry| entry.path()))
+                    .collect::<std::io::Result<Vec<_>>>()
+            })
+            .with_context(|| format!("Could not read directory '{}'", path.display()))?;
+        paths.sort();
+
+        Ok(paths)
+    }
+
+    fn read_link(&self, path: &Path) -> Result<PathBuf> {
+        fs::read_link(path).with_context(|| format!("Could not read symlink '{}'", path.display()))
+    }
+
+    fn verify_readable(&self, path: &Path) -> Result<()> {
+        fs::File::open(path)
+            .map(|_| ())
+            .with_context(|| format!("Could not read '{}'", path.display()))
+    }
+
+    fn copy(&self, from: &Path, to: &Path) -> Result<Option<u64>> {
+        reflink::reflink_or_copy(from, to).with_context(|| {
+            format!(
+                "Error reflinking '{}' to '{}'",
+                from.display(),
+                to.display()
+            )
+        })
+    }
+
+    fn create_dir_all(&self, path: &Path) -> Result<()> {
+        fs::create_dir_all(path)
+            .with_context(|| format!("Error creating directories for '{}'", path.display(),))
+    }
+
+    fn remove_dir(&self, path: &Path) -> Result<()> {
+        fs::remove_dir(path)
+            .with_context(|| format!("Could not remove directory '{}'", path.display()))
+    }
+
+    fn remove_dir_all(&self, path: &Path) -> Result<()> {
+        fs::remove_dir_all(path)
+            .with_context(|| format!("Could not remove directory '{}'", path.display()))
+    }
+
+    fn remove_file(&self, path: &Path) -> Result<()> {
+        fs::remove_file(path).with_context(|| format!("Could not remove '{}'", path.display()))
+    }
+
+    fn set_modified(&self, path: &Path, mtime: SystemTime) -> Result<()> {
+        fs::File::options()
+            .write(true)
+            .open(path)
+            .and_then(|file| file.set_modified(mtime))
+            .with_context(|| {
+                format!(
+                    "Could not change the modification time of '{}'",
+                    path.display()
+                )
+            })
+    }
+
+    fn set_permissions(&self, path: &Path, mode: u32) -> Result<()> {
+        fs::set_permissions(path, fs::Permissions::from_mode(mode))
+            .with_context(|| format!("Could not change the permissions of '{}'", path.display()))
+    }
+
+    fn symlink(&self, src: &Path, dst: &Path) -> Result<()> {
+        unix_fs::symlink(src, dst).with_context(|| {
+            format!(
+                "Error symlinking '{}' to '{}'",
+                src.display(),
+                dst.display()
+            )
+        })
+    }
+
+    fn write(&self, path: &Path, contents: &[u8]) -> Result<()> {
+        fs::write(path, contents)
+            .with_context(|| format!("Could not write to '{}'", path.display()))
+    }
+
+    /// The contents are first written to a temporary file in the same directory, which then
+    /// replaces `path`. This way a crash or a full disk can never leave behind a half written
+    /// file. If `path` already exists, then its permissions are preserved. Otherwise the file is
+    /// created with the default permissions according to the user's umask.
+    fn write_atomic(&self, path: &Path, contents: &[u8]) -> Result<()> {
+        let file_name = path
+            .file_name()
+            .with_context(|| format!("'{}' is not a valid file path", path.display()))?;
+        let mut temp_file_name = OsString::from(".");
+        temp_file_name.push(file_name);
+        temp_file_name.push(".tmp");
+        let temp_path = path.with_file_name(temp_file_name);
+
+        let result = (|| {
+            // A previous write that was killed halfway through may have left its temporary file
+            // behind. That gets replaced instead of written through, in case it's now a symlink.
+            match fs::remove_file(&temp_path) {
+                Err(err) if err.kind() != std::io::ErrorKind::NotFound => return Err(err),
+                _ => (),
+            }
+
+            let mut file = fs::File::create(&temp_path)?;
+            file.write_all(contents)?;
+            if let Ok(metadata) = fs::metadata(path) {
+                file.set_permissions(metadata.permissions())?;
+            }
+            file.sync_all()?;
+
+            fs::rename(&temp_path, path)
+        })();
+        if result.is_err() {
+            let _ = fs::remove_file(&temp_path);
+        }
+
+        result.with_context(|| format!("Could not write to '{}'", path.display()))
+    }
+}
+
+/// The backend used for `--dry-run`. This prints the operations that would have been performed
+/// instead of performing them. If [`record_script()`] has been called, then the operations are also
+/// recorded as shell commands. Reading is done through another backend, which is the real
+/// filesystem outside of the tests.
+pub struct DryRunFileSystem<'a> {
+    inner: &'a dyn FileSystem,
+}
+
+impl<'a> DryRunFileSystem<'a> {
+    pub const fn new(inner: &'a dyn FileSystem) -> DryRunFileSystem<'a> {
+        DryRunFileSystem { inner }
+    }
+
+    fn report(&self, operation: std::fmt::Arguments) {
+        println!("{} {}", "Would".bright_white(), operation);
+    }
+
+    /// Add a shell command to the script, if a script is being recorded. The arguments should
+    /// already be quoted using [`shell_quote()`].
+    fn record(&self, command: String) {
+        if let Some(script) = SCRIPT.lock().unwrap().as_mut() {
+            script.push(command);
+        }
+    }
+}
+
+impl FileSystem for DryRunFileSystem<'_> {
+    fn metadata(&self, path: &Path) -> Result<Metadata> {
+        self.inner.metadata(path)
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> Result<Metadata> {
+        self.inner.symlink_metadata(path)
+    }
+
+    fn read(&self, path: &Path) -> Result<Vec<u8>> {
+        self.inner.read(path)
+    }
+
+    fn read_dir(&self, path: &Path) -> Result<Vec<PathBuf>> {
+        self.inner.read_dir(path)
+    }
+
+    fn read_link(&self, path: &Path) -> Result<PathBuf> {
+        self.inner.read_link(path)
+    }
+
+    fn verify_readable(&self, path: &Path) -> Result<()> {
+        self.inner.verify_readable(path)
+    }
+
+    fn copy(&self, from: &Path, to: &Path) -> Result<Option<u64>> {
+        self.report(format_args!(
+            "copy '{}' to '{}'",
+            from.display(),
+            to.display()
+        ));
+        self.record(format!(
+            "cp --reflink=auto -- {} {}",
+            shell_quote(from.as_os_str())?,
+            shell_quote(to.as_os_str())?
+        ));
+        Ok(None)
+    }
+
+    fn create_dir_all(&self, path: &Path) -> Result<()> {
+        // This would be printed for every single plugin, and nothing is ever removed this way. The
+        // script does need these directories to exist though.
+        self.record(format!("mkdir -p -- {}", shell_quote(path.as_os_str())?));
+        Ok(())
+    }
+
+    fn remove_dir(&self, path: &Path) -> Result<()> {
+        self.report(format_args!("remove '{}'", path.display()));
+        self.record(format!("rmdir -- {}", shell_quote(path.as_os_str())?));
+        Ok(())
+    }
+
+    fn remove_dir_all(&self, path: &Path) -> Result<()> {
+        self.report(format_args!("remove '{}'", path.display()));
+        self.record(format!("rm -rf -- {}", shell_quote(path.as_os_str())?));
+        Ok(())
+    }
+
+    fn remove_file(&self, path: &Path) -> Result<()> {
+        self.report(format_args!("remove '{}'", path.display()));
+        self.record(format!("rm -f -- {}", shell_quote(path.as_os_str())?));
+        Ok(())
+    }
+
+    fn set_modified(&self, path: &Path, mtime: SystemTime) -> Result<()> {
+        // This is not worth printing since it only ever happens for copies, but the script should
+        // still end up with the same timestamps
+        let mtime = mtime.duration_since(UNIX_EPOCH).unwrap_or_default();
+        self.record(format!(
+            "touch -m -d @{}.{:09} -- {}",
+            mtime.as_secs(),
+            mtime.subsec_nanos(),
+            shell_quote(path.as_os_str())?
+        ));
+        Ok(())
+    }
+
+    fn set_permissions(&self, path: &Path, mode: u32) -> Result<()> {
+        self.report(format_args!(
+            "change the permissions of '{}' to {:04o}",
+            path.display(),
+            mode
+        ));
+        self.record(format!(
+            "chmod {:04o} -- {}",
+            mode,
+            shell_quote(path.as_os_str())?
+        ));
+        Ok(())
+    }
+
+    fn symlink(&self, src: &Path, dst: &Path) -> Result<()> {
+        self.report(format_args!(
+            "symlink '{}' to '{}'",
+            src.display(),
+            dst.display()
+        ));
+        self.record(format!(
+            "ln -s -- {} {}",
+            shell_quote(src.as_os_str())?,
+            shell_quote(dst.as_os_str())?
+        ));
+        Ok(())
+    }
+
+    fn write(&self, path: &Path, contents: &[u8]) -> Result<()> {
+        self.report(format_args!("write '{}'", path.display()));
+        // The script is a text file, so binary contents are written using escape sequences instead
+        self.record(match std::str::from_utf8(contents) {
+            Ok(contents) => format!(
+                "printf '%s' {} > {}",
+                shell_quote(OsStr::new(contents))?,
+                shell_quote(path.as_os_str())?
+            ),
+            Err(_) => format!(
+                "printf -- {} > {}",
+                printf_format(contents),
+                shell_quote(path.as_os_str())?
+            ),
+        });
+        Ok(())
+    }
+
+    fn write_atomic(&self, path: &Path, contents: &[u8]) -> Result<()> {
+        self.write(path, contents)
+    }
+}
+
+/// An in-memory filesystem for tests. Paths should be absolute, and symlinks with relative targets
+/// are resolved relative to the symlink's directory. The root directory always exists.
+#[cfg(test)]
+#[derive(Default)]
+pub struct MemoryFileSystem {
+    entries: Mutex<std::collections::BTreeMap<PathBuf, MemoryEntry>>,
+}
+
+#[cfg(test)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MemoryEntry {
+    File {
+        contents: Vec<u8>,
+        modified: SystemTime,
+        mode: u32,
+    },
+    Directory,
+    Symlink(PathBuf),
+}
+
+#[cfg(test)]
+impl MemoryFileSystem {
+    pub fn new() -> MemoryFileSystem {
+        MemoryFileSystem::default()
+    }
+
+    /// A copy of every entry in the filesystem, used to check whether anything was changed.
+    pub fn snapshot(&self) -> std::collections::BTreeMap<PathBuf, MemoryEntry> {
+        self.entries.lock().unwrap().clone()
+    }
+
+    /// Resolve the symlinks in `path`'s last component, if any.
+    fn resolve(&self, path: &Path) -> PathBuf {
+        let entries = self.entries.lock().unwrap();
+        let mut path = path.to_owned();
+        // Symlink loops are simply cut off after a while
+        for _ in 0..32 {
+            match entries.get(&path) {
+                Some(MemoryEntry::Symlink(target)) => {
+                    path = path.parent().unwrap_or_else(|| Path::new("/")).join(target)
+                }
+                _ => break,
+            }
+        }
+
+        path
+    }
+
+    fn entry(&self, path: &Path) -> Result<MemoryEntry> {
+        if path == Path::new("/") {
+            return Ok(MemoryEntry::Directory);
+        }
+
+        self.entries
+            .lock()
+            .unwrap()
+            .get(path)
+            .cloned()
+            .with_context(|| format!("'{}' does not exist", path.display()))
+    }
+
+    /// Add an entry at `path`. The parent directory needs to exist, and `path` must not exist yet
+    /// unless `replace` is set and the existing entry is not a directory.
+    fn insert(&self, path: &Path, entry: MemoryEntry, replace: bool) -> Result<()> {
+        let parent = path.parent().unwrap_or_else(|| Path::new("/"));
+        if !matches!(self.entry(parent), Ok(MemoryEntry::Directory)) {
+            anyhow::bail!("'{}' is not a directory", parent.display());
+        }
+
+        let mut entries = self.entries.lock().unwrap();
+        match entries.get(path) {
+            Some(MemoryEntry::Directory) => {
+                anyhow::bail!("'{}' is a directory", path.display())
+            }
+            Some(_) if !replace => anyhow::bail!("'{}' already exists", path.display()),
+            _ => {
+                entries.insert(path.to_owned(), entry);
+                Ok(())
+            }
+        }
+    }
+
+    fn update_file(&self, path: &Path, f: impl FnOnce(&mut SystemTime, &mut u32)) -> Result<()> {
+        let path = self.resolve(path);
+        match self.entries.lock().unwrap().get_mut(&path) {
+            Some(MemoryEntry::File { modified, mode, .. }) => {
+                f(modified, mode);
+                Ok(())
+            }
+            _ => anyhow::bail!("'{}' is not a file", path.display()),
+        }
+    }
+}
+
+#[cfg(test)]
+impl FileSystem for MemoryFileSystem {
+    fn metadata(&self, path: &Path) -> Result<Metadata> {
+        self.symlink_metadata(&self.resolve(path))
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> Result<Metadata> {
+        Ok(match self.entry(path)? {
+            MemoryEntry::File {
+                contents,
+                modified,
+                mode,
+            } => Metadata {
+                file_type: FileType::File,
+                len: contents.len() as u64,
+                modified,
+                mode,
+            },
+            MemoryEntry::Directory => Metadata {
+                file_type: FileType::Directory,
+                len: 0,
+                modified: UNIX_EPOCH,
+                mode: 0o755,
+            },
+            MemoryEntry::Symlink(target) => Metadata {
+                file_type: FileType::Symlink,
+                len: target.as_os_str().len() as u64,
+                modified: UNIX_EPOCH,
+                mode: 0o777,
+            },
+        })
+    }
+
+    fn read(&self, path: &Path) -> Result<Vec<u8>> {
+        match self.entry(&self.resolve(path))? {
+            MemoryEntry::File { contents, .. } => Ok(contents),
+            _ => anyhow::bail!("'{}' is not a file", path.display()),
+        }
+    }
+
+    fn read_dir(&self, path: &Path) -> Result<Vec<PathBuf>> {
+        let path = self.resolve(path);
+        if !matches!(self.entry(&path)?, MemoryEntry::Directory) {
+            anyhow::bail!("'{}' is not a directory", path.display());
+        }
+
+        // The map is sorted, so the entries are sorted by file name
+        Ok(self
+            .entries
+            .lock()
+            .unwrap()
+            .keys()
+            .filter(|entry_path| entry_path.parent() == Some(path.as_path()))
+            .cloned()
+            .collect())
+    }
+
+    fn read_link(&self, path: &Path) -> Result<PathBuf> {
+        match self.entry(path)? {
+            MemoryEntry::Symlink(target) => Ok(target),
+            _ => anyhow::bail!("'{}' is not a symlink", path.display()),
+        }
+    }
+
+    fn verify_readable(&self, path: &Path) -> Result<()> {
+        self.metadata(path).map(|_| ())
+    }
+
+    fn copy(&self, from: &Path, to: &Path) -> Result<Option<u64>> {
+        let contents = self.read(from)?;
+        let len = contents.len() as u64;
+        let mode = self.metadata(from)?.mode;
+        self.insert(
+            &self.resolve(to),
+            MemoryEntry::File {
+                contents,
+                modified: SystemTime::now(),
+                mode,
+            },
+            true,
+        )?;
+
+        Ok(Some(len))
+    }
+
+    fn create_dir_all(&self, path: &Path) -> Result<()> {
+        for ancestor in path.ancestors().collect::<Vec<_>>().into_iter().rev() {
+            match self.entry(ancestor) {
+                Ok(MemoryEntry::Directory) => (),
+                Ok(_) => anyhow::bail!("'{}' is not a directory", ancestor.display()),
+                Err(_) => self.insert(ancestor, MemoryEntry::Directory, false)?,
+            }
+        }
+
+        Ok(())
+    }
+
+    fn remove_dir(&self, path: &Path) -> Result<()> {
+        if !self.read_dir(path)?.is_empty() {
+            anyhow::bail!("'{}' is not empty", path.display());
+        }
+
+        self.entries.lock().unwrap().remove(path);
+        Ok(())
+    }
+
+    fn remove_dir_all(&self, path: &Path) -> Result<()> {
+        if !matches!(self.entry(path)?, MemoryEntry::Directory) {
+            anyhow::bail!("'{}' is not a directory", path.display());
+        }
+
+        self.entries
+            .lock()
+            .unwrap()
+            .retain(|entry_path, _| !entry_path.starts_with(path));
+        Ok(())
+    }
+
+    fn remove_file(&self, path: &Path) -> Result<()> {
+        match self.entry(path)? {
+            MemoryEntry::Directory => anyhow::bail!("'{}' is a directory", path.display()),
+            _ => {
+                self.entries.lock().unwrap().remove(path);
+                Ok(())
+            }
+        }
+    }
+
+    fn set_modified(&self, path: &Path, mtime: SystemTime) -> Result<()> {
+        self.update_file(path, |modified, _| *modified = mtime)
+    }
+
+    fn set_permissions(&self, path: &Path, mode: u32) -> Result<()> {
+        self.update_file(path, |_, file_mode| *file_mode = mode)
+    }
+
+    fn symlink(&self, src: &Path, dst: &Path) -> Result<()> {
+        self.insert(dst, MemoryEntry::Symlink(src.to_owned()), false)
+    }
+
+    fn write(&self, path: &Path, contents: &[u8]) -> Result<()> {
+        let path = self.resolve(path);
+        let mode = match self.entry(&path) {
+            Ok(MemoryEntry::File { mode, .. }) => mode,
+            _ => 0o644,
+        };
+
+        self.insert(
+            &path,
+            MemoryEntry::File {
+                contents: contents.to_vec(),
+                modified: SystemTime::now(),
+                mode,
+            },
+            true,
+        )
+    }
+
+    fn write_atomic(&self, path: &Path, contents: &[u8]) -> Result<()> {
+        self.write(path, contents)
+    }
+}
+
+/// Start recording the operations performed through [`DryRunFileSystem`] as a shell script. Used
+/// for `yabridgectl sync --dry-run --emit-script`.
+pub fn record_script() {
+    *SCRIPT.lock().unwrap() = Some(Vec::new());
+}
+
+/// Write the shell script recorded since [`record_script()`] was called to `path`, and make it
+/// executable. This bypasses the backends since it needs to happen during a dry-run.
+pub fn write_script(path: &Path) -> Result<()> {
+    let commands = SCRIPT.lock().unwrap().take().unwrap_or_default();
+
+    let mut script = String::from(
+        "#!/bin/sh\n\
+         # The changes 'yabridgectl sync' would have made, generated using '--emit-script'\n\
+         set -e\n\n",
+    );
+    for command in commands {
+        script.push_str(&command);
+        script.push('\n');
+    }
+
+    fs::write(path, script)
+        .and_then(|_| fs::set_permissions(path, fs::Permissions::from_mode(0o755)))
+        .with_context(|| format!("Could not write the script to '{}'", path.display()))
+}
+
+/// Quote `arg` so it's passed to a command as a single argument when used in a POSIX shell
+/// script. This works for any string except for those containing null bytes. Paths that aren't
+/// valid UTF-8 can't be written to the script without changing them, so those return an error
+/// instead.
+fn shell_quote(arg: &OsStr) -> Result<String> {
+    let arg = match arg.to_str() {
+        Some(arg) => arg,
+        None => anyhow::bail!(
+            "'{}' is not valid UTF-8, so it can't be written to the script",
+            arg.to_string_lossy()
+        ),
+    };
+    if !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "/._-+=:,@".contains(c))
+    {
+        return Ok(arg.to_owned());
+    }
+
+    Ok(format!("'{}'", arg.replace('\'', "'\\''")))
+}
+
+/// Encode `contents` as a quoted format string for `printf` that prints exactly those bytes.
+/// Everything other than printable ASCII characters is written as an octal escape sequence.
+fn printf_format(contents: &[u8]) -> String {
+    let mut format = String::from("'");
+    for &byte in contents {
+        match byte {
+            b'\\' => format.push_str("\\\\"),
+            b'%' => format.push_str("%%"),
+            b'\'' => format.push_str("'\\''"),
+            b' '..=b'~' => format.push(byte as char),
+            _ => format.push_str(&format!("\\{:03o}", byte)),
+        }
+    }
+    format.push('\'');
+
+    format
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::ffi::OsStrExt;
+    use std::process::Command;
+
+    #[test]
+    fn printf_format_round_trips_binary_contents() {
+        let contents = b"-\xff%s\\n 'quoted'\n\x00\x7f";
+        let output = Command::new("sh")
+            .arg("-c")
+            .arg(format!("printf -- {}", printf_format(contents)))
+            .output()
+            .unwrap();
+
+        assert_eq!(output.stdout, contents);
+    }
+
+    #[test]
+    fn shell_quote_rejects_invalid_utf8() {
+        assert_eq!(
+            shell_quote(OsStr::new("plain/path.so")).unwrap(),
+            "plain/path.so"
+        );
+        assert_eq!(shell_quote(OsStr::new("it's")).unwrap(), "'it'\\''s'");
+        assert!(shell_quote(OsStr::from_bytes(b"\xffoo")).is_err());
+    }
+
+    #[test]
+    fn write_atomic_replaces_stale_temporary_files() {
+        let dir = crate::util::TempDir::new("stale-temporary-file");
+        let path = dir.path().join("config.toml");
+        let temp_path = dir.path().join(".config.toml.tmp");
+        let other_path = dir.path().join("other.toml");
+        fs::write(&other_path, "other").unwrap();
+        unix_fs::symlink(&other_path, &temp_path).unwrap();
+
+        RealFileSystem.write_atomic(&path, b"new").unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"new");
+        assert_eq!(fs::read(&other_path).unwrap(), b"other");
+        assert!(fs::symlink_metadata(&temp_path).is_err());
+    }
+}
@@ -0,0 +1,70 @@
+// yabridge: a Wine plugin bridge
+// Copyright (C) 2020-2022 Robbert van der Helm
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Parsing and matching for `.yabridgeignore` files. These files use the same syntax as
+//! `.gitignore` files, and they can be placed in the root of a plugin directory to skip files and
+//! directories during indexing. The patterns are parsed and matched using the same implementation
+//! ripgrep uses for `.gitignore` files, so they behave exactly like they would with git.
+
+use anyhow::{Context, Result};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use std::path::Path;
+
+use crate::util;
+
+/// The name of the ignore file, relative to the root of a plugin directory.
+pub const IGNORE_FILE_NAME: &str = ".yabridgeignore";
+
+/// The rules from a `.yabridgeignore` file. When multiple rules match a path, the last one wins.
+#[derive(Debug, Clone)]
+pub struct IgnoreFile {
+    matcher: Gitignore,
+}
+
+impl IgnoreFile {
+    /// Read the ignore file in `directory`, if it exists.
+    pub fn read(directory: &Path) -> Result<Option<IgnoreFile>> {
+        let path = directory.join(IGNORE_FILE_NAME);
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        IgnoreFile::parse(directory, &util::read_to_string(&path)?)
+            .with_context(|| format!("Could not parse '{}'", path.display()))
+            .map(Some)
+    }
+
+    /// Parse the contents of the ignore file in `directory`. This fails if one of the patterns is
+    /// not a valid glob.
+    pub fn parse(directory: &Path, contents: &str) -> Result<IgnoreFile> {
+        let mut builder = GitignoreBuilder::new(directory);
+        for line in contents.lines() {
+            builder
+                .add_line(None, line)
+                .with_context(|| format!("Invalid pattern '{line}'"))?;
+        }
+
+        Ok(IgnoreFile {
+            matcher: builder.build()?,
+        })
+    }
+
+    /// Whether `relative_path`, relative to the plugin directory, should be skipped. Directories
+    /// that are ignored should not be descended into, so their contents don't need to be checked.
+    pub fn is_ignored(&self, relative_path: &Path, is_dir: bool) -> bool {
+        self.matcher.matched(relative_path, is_dir).is_ignore()
+    }
+}
@@ -0,0 +1,286 @@
+// yabridge: a Wine plugin bridge
+// Copyright (C) 2020-2022 Robbert van der Helm
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! A sidecar index containing information about the files yabridgectl has set up. Unlike the
+//! config file this is not meant to be edited by the user, so it's stored as JSON in
+//! `$XDG_DATA_HOME/yabridgectl`.
+
+use anyhow::{Context, Result};
+use serde_derive::{Deserialize, Serialize};
+use std::collections::btree_map::Entry;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config::yabridgectl_directories;
+use crate::files::{self, Plugin};
+use crate::ignore_file::IGNORE_FILE_NAME;
+use crate::util;
+
+/// The name of the index file, relative to `$XDG_DATA_HOME/YABRIDGECTL_PREFIX`.
+pub const INDEX_FILE_NAME: &str = "index.json";
+
+/// The version of yabridge this version of yabridgectl belongs to. Yabridge and yabridgectl are
+/// always released together, so these versions are the same.
+pub const YABRIDGE_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Information about the files set up by yabridgectl. This will be serialized to and deserialized
+/// from `$XDG_DATA_HOME/yabridgectl/index.json`.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Index {
+    /// Information about every bridge (a copy of one of yabridge's chainloading libraries) created
+    /// by yabridgectl, indexed by the bridge's normalized absolute path.
+    pub bridges: BTreeMap<PathBuf, BridgeInfo>,
+    /// Information about the plugin directories as of the last sync, indexed by the directory's
+    /// path as it appears in the config file.
+    pub directories: BTreeMap<PathBuf, DirectoryInfo>,
+    /// Whether the message printed after the first successful `yabridgectl sync` has already been
+    /// shown.
+    pub first_sync_message_shown: bool,
+    /// When `yabridgectl sync` last finished, in seconds since the Unix epoch.
+    pub last_sync: Option<u64>,
+    /// Notes added to plugins using `yabridgectl note`, indexed by the plugin's normalized absolute
+    /// path.
+    pub notes: BTreeMap<PathBuf, String>,
+}
+
+/// Information about a single bridge created by `yabridgectl sync`.
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq, Clone)]
+pub struct BridgeInfo {
+    /// The version of yabridge that created or last updated this bridge.
+    pub installed_by: String,
+    /// Whether this bridge was set up by hand and recorded using `yabridgectl adopt`. The
+    /// `installed_by` version is unknown for these bridges until `yabridgectl sync` updates them.
+    #[serde(default)]
+    pub adopted: bool,
+}
+
+/// Information about a plugin directory, recorded by `yabridgectl sync`.
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq, Clone)]
+pub struct DirectoryInfo {
+    /// The number of plugins found in this directory during the last sync.
+    pub num_plugins: usize,
+    /// A hash of the paths of the plugins found in this directory during the last sync, as
+    /// computed by `files::hash_plugin_paths()`. Not set for directories that were last synced by
+    /// an older version of yabridgectl.
+    #[serde(default)]
+    pub plugins_hash: Option<u32>,
+    /// The modification times during the last sync of every directory that was searched and of the
+    /// directory's ignore file, as returned by `util::modification_time()`. Adding or removing a
+    /// file changes the modification time of the directory containing it.
+    #[serde(default)]
+    pub mtimes: BTreeMap<PathBuf, Option<i64>>,
+    /// The plugins found in this directory during the last sync. `yabridgectl status` uses these
+    /// instead of searching the directory again if none of the modification times in `mtimes`
+    /// have changed since then.
+    #[serde(default)]
+    pub plugins: Vec<Plugin>,
+}
+
+impl Index {
+    /// Try to read the index file. If the file does not yet exist, then this returns an empty
+    /// index. This will fail if the file exists but could not be parsed.
+    pub fn read() -> Result<Index> {
+        match yabridgectl_directories()?.find_data_file(INDEX_FILE_NAME) {
+            Some(path) => {
+                let json_str = util::read_to_string(&path)?;
+
+                serde_jsonrc::from_str(&json_str)
+                    .with_context(|| format!("Failed to parse '{}'", path.display()))
+            }
+            None => Ok(Index::default()),
+        }
+    }
+
+    /// Write the index to disk, creating the file if it does not yet exist.
+    pub fn write(&self) -> Result<()> {
+        let json_str = serde_jsonrc::to_string_pretty(&self).context("Could not format JSON")?;
+        // `place_data_file()` would create the directory directly, even during a dry-run
+        let data_home = yabridgectl_directories()?.get_data_home();
+        util::create_dir_all(&data_home)?;
+        let index_path = data_home.join(INDEX_FILE_NAME);
+
+        util::write_atomic(&index_path, json_str)
+    }
+
+    /// Record that the bridge at `path` has just been created or updated by this version of
+    /// yabridge. `path` should be normalized with `util::normalize_path()`.
+    pub fn record_bridge(&mut self, path: PathBuf) {
+        self.bridges.insert(
+            path,
+            BridgeInfo {
+                installed_by: YABRIDGE_VERSION.to_owned(),
+                adopted: false,
+            },
+        );
+    }
+
+    /// Record a bridge that was set up by hand, found by `yabridgectl adopt`. Bridges that are
+    /// already in the index are left alone. `path` should be normalized with
+    /// `util::normalize_path()`.
+    pub fn record_adopted_bridge(&mut self, path: PathBuf) -> bool {
+        match self.bridges.entry(path) {
+            Entry::Occupied(_) => false,
+            Entry::Vacant(entry) => {
+                entry.insert(BridgeInfo {
+                    installed_by: String::from("unknown"),
+                    adopted: true,
+                });
+                true
+            }
+        }
+    }
+
+    /// Get the information stored for the bridge at `path`, if it was created by a version of
+    /// yabridgectl that kept track of this. The path will be normalized first.
+    pub fn bridge(&self, path: &Path) -> Option<&BridgeInfo> {
+        self.bridges.get(&util::normalize_path(path))
+    }
+
+    /// Record that a sync has just finished.
+    pub fn record_sync(&mut self) {
+        self.last_sync = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .ok()
+            .map(|duration| duration.as_secs());
+    }
+
+    /// Record the plugins found in the plugin directory at `path` during this sync, along with a
+    /// hash of their paths as computed by `files::hash_plugin_paths()`. If `cacheable` is set, then
+    /// the plugins themselves and the modification times of `directories` and the ignore file are
+    /// also stored so `yabridgectl status` can reuse them. This should not be set when the search
+    /// used options that are not stored in the config file.
+    pub fn record_directory(
+        &mut self,
+        path: PathBuf,
+        plugins: &[Plugin],
+        directories: &[PathBuf],
+        cacheable: bool,
+    ) {
+        let (mtimes, cached_plugins) = if cacheable && !directories.is_empty() {
+            let ignore_file = path.join(IGNORE_FILE_NAME);
+            let mtimes = directories
+                .iter()
+                .chain(std::iter::once(&ignore_file))
+                .map(|path| (path.clone(), util::modification_time(path)))
+                .collect();
+
+            (mtimes, plugins.to_vec())
+        } else {
+            (BTreeMap::new(), Vec::new())
+        };
+
+        self.directories.insert(
+            path.clone(),
+            DirectoryInfo {
+                num_plugins: plugins.len(),
+                plugins_hash: Some(files::hash_plugin_paths(plugins, &path)),
+                mtimes,
+                plugins: cached_plugins,
+            },
+        );
+    }
+
+    /// Get the plugins found during the last sync for every plugin directory that has not been
+    /// modified since then, indexed by the directory's path. A directory counts as modified if any
+    /// of its subdirectories or its ignore file has been modified. New subdirectories change the
+    /// modification time of the directory they were created in, so those are detected as well.
+    pub fn cached_plugins(&self) -> HashMap<PathBuf, Vec<Plugin>> {
+        self.directories
+            .iter()
+            .filter(|(_, info)| {
+                !info.mtimes.is_empty()
+                    && info
+                        .mtimes
+                        .iter()
+                        .all(|(path, mtime)| *mtime == util::modification_time(path))
+            })
+            .map(|(path, info)| (path.clone(), info.plugins.clone()))
+            .collect()
+    }
+
+    /// Get the information stored for the plugin directory at `path`, if it has been synced before.
+    pub fn directory(&self, path: &Path) -> Option<&DirectoryInfo> {
+        self.directories.get(path)
+    }
+
+    /// Update the entries for the plugin directory at `from` after it has been moved to `to`. The
+    /// notes and the inline bridges inside of the directory move along with it. The cached plugins
+    /// contain the old paths, so the directory will be searched again the next time.
+    pub fn move_directory(&mut self, from: &Path, to: &Path) {
+        if let Some(mut info) = self.directories.remove(from) {
+            info.mtimes.clear();
+            info.plugins.clear();
+            self.directories.insert(to.to_owned(), info);
+        }
+
+        let normalized_from = util::normalize_path(from);
+        let normalized_to = util::normalize_path(to);
+        let rebase = |path: PathBuf| match path.strip_prefix(&normalized_from) {
+            Ok(suffix) => normalized_to.join(suffix),
+            Err(_) => path,
+        };
+        self.notes = std::mem::take(&mut self.notes)
+            .into_iter()
+            .map(|(path, note)| (rebase(path), note))
+            .collect();
+        self.bridges = std::mem::take(&mut self.bridges)
+            .into_iter()
+            .map(|(path, info)| (rebase(path), info))
+            .collect();
+    }
+
+    /// Remove the entries for plugin directories that are no longer in `plugin_dirs`.
+    pub fn prune_directories(&mut self, plugin_dirs: &BTreeSet<PathBuf>) {
+        self.directories
+            .retain(|path, _| plugin_dirs.contains(path));
+    }
+
+    /// Set the note for the plugin at `path`, replacing the previous note if there was one.
+    pub fn set_note(&mut self, path: &Path, note: String) {
+        self.notes.insert(util::normalize_path(path), note);
+    }
+
+    /// Remove the note for the plugin at `path`. Returns the removed note, if there was one.
+    pub fn remove_note(&mut self, path: &Path) -> Option<String> {
+        self.notes.remove(&util::normalize_path(path))
+    }
+
+    /// Get the note for the plugin at `path`, if it has one. The path will be normalized first.
+    pub fn note(&self, path: &Path) -> Option<&str> {
+        self.notes
+            .get(&util::normalize_path(path))
+            .map(|note| note.as_str())
+    }
+
+    /// Remove the notes for plugins that no longer exist, returning the removed notes. Plugins that
+    /// are only skipped because of the blacklist or the current sync options keep their notes.
+    pub fn prune_notes(&mut self) -> Vec<(PathBuf, String)> {
+        let (removed, kept) = std::mem::take(&mut self.notes)
+            .into_iter()
+            .partition(|(path, _)| path.symlink_metadata().is_err());
+        self.notes = kept;
+
+        removed.into_iter().collect()
+    }
+
+    /// Remove the entries for bridges that no longer exist.
+    pub fn prune_bridges(&mut self) {
+        self.bridges
+            .retain(|path, _| path.symlink_metadata().is_ok());
+    }
+}
@@ -14,23 +14,87 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use anyhow::Result;
-use clap::{command, Arg, Command};
+use anyhow::{Context, Result};
+use clap::{command, Arg, ArgMatches, Command};
 use colored::Colorize;
-use std::collections::HashSet;
+use std::collections::{BTreeSet, HashSet};
 use std::env;
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::io::{self, IsTerminal, Write};
+use std::iter;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::MetadataExt;
 use std::path::{Path, PathBuf};
+use std::process;
 
-use crate::config::Config;
+use crate::config::{Config, DeviceConcurrency, DirectorySettings, Vst2InstallationLocation};
+use crate::files::Traversal;
 
 mod actions;
 mod config;
+mod daw;
+mod error;
 mod files;
+mod filesystem;
+mod ignore_file;
+mod index;
+mod messages;
+mod shell_link;
 mod symbols;
 mod util;
 mod vst3_moduleinfo;
 
-fn main() -> Result<()> {
+/// The environment variable that can be used to override the VST2 location for a single run.
+const VST2_LOCATION_ENV: &str = "YABRIDGECTL_VST2_LOCATION";
+
+fn main() {
+    // Errors should also be machine readable when using `--json`. This needs to be checked before
+    // the command line arguments are parsed because reading the config file can already fail.
+    let json = has_global_flag("--json");
+
+    if let Err(err) = run() {
+        if json {
+            eprintln!("{}", error::to_json(&err));
+        } else {
+            eprintln!("{}", messages::error(&err));
+        }
+
+        process::exit(error::exit_code(&err));
+    }
+}
+
+/// Check whether a global flag like `--json` was passed before the command line arguments are
+/// parsed. Like with [`global_option_value()`], arguments after `--` are ignored.
+fn has_global_flag(flag: &str) -> bool {
+    env::args_os()
+        .skip(1)
+        .take_while(|arg| arg != "--")
+        .any(|arg| arg == flag)
+}
+
+/// Find the value passed to a global option like `--config-dir` before the command line arguments
+/// are parsed. Both `--option value` and `--option=value` are supported. Everything after `--` is
+/// a positional argument, so that part is ignored.
+fn global_option_value(option: &str) -> Option<OsString> {
+    let args: Vec<OsString> = env::args_os()
+        .skip(1)
+        .take_while(|arg| arg != "--")
+        .collect();
+
+    args.iter().enumerate().find_map(|(i, arg)| {
+        if arg == option {
+            args.get(i + 1).cloned()
+        } else {
+            arg.as_bytes()
+                .strip_prefix(option.as_bytes())
+                .and_then(|value| value.strip_prefix(b"="))
+                .map(|value| OsString::from(OsStr::from_bytes(value)))
+        }
+    })
+}
+
+fn run() -> Result<()> {
     // We'll modify our `PATH` environment variable so it matches up with
     // `get_augmented_search_path()` from `src/plugin/utils.h` for easier setup
     let yabridge_home = config::yabridge_directories()?.get_data_home();
@@ -42,7 +106,28 @@ fn main() -> Result<()> {
         },
     );
 
-    let mut config = Config::read()?;
+    // The config file is read before the command line arguments are parsed, so `--config-dir`
+    // also needs to be handled here
+    config::set_config_dir(global_option_value("--config-dir").map(PathBuf::from));
+
+    // Like with `--json`, this needs to be known before reading the config file, since that may
+    // write a new or migrated config file
+    filesystem::set_dry_run(has_global_flag("--dry-run"));
+
+    // `yabridgectl config repair` should still work when the config file can't be parsed
+    let is_repair_command = env::args_os()
+        .skip(1)
+        .collect::<Vec<_>>()
+        .windows(2)
+        .any(|args| args[0] == "config" && args[1] == "repair");
+    let mut config = match Config::read() {
+        Ok(config) => config,
+        Err(err) if is_repair_command => {
+            eprintln!("{}", messages::warning(&err));
+            Config::default()
+        }
+        Err(err) => return Err(err),
+    };
 
     // Used for validation in `yabridgectl rm <path>`
     let plugin_directories: HashSet<&Path> = config
@@ -50,6 +135,8 @@ fn main() -> Result<()> {
         .iter()
         .map(|path| path.as_path())
         .collect();
+    // `yabridgectl rm` also accepts the numbers printed by `yabridgectl list --numbered`
+    let numbered_plugin_directories: Vec<&Path> = config.sorted_plugin_dirs();
     // Used for validation in `yabridgectl blacklist rm <path>`
     let blacklist_entries: HashSet<&Path> =
         config.blacklist.iter().map(|path| path.as_path()).collect();
@@ -57,6 +144,116 @@ fn main() -> Result<()> {
     let matches = command!()
         .subcommand_required(true)
         .arg_required_else_help(true)
+        .arg(
+            Arg::new("color")
+                .long("color")
+                .global(true)
+                .help("When to use colors in the output")
+                .long_help(
+                    "When to use colors in the output. 'auto' (the default) only uses colors when \
+                     STDOUT is a terminal and the 'NO_COLOR' environment variable is not set. \
+                     'always' also uses colors when the output is piped to another program, and \
+                     'never' disables colors entirely.",
+                )
+                .possible_values(["auto", "always", "never"])
+                .default_value("auto"),
+        )
+        .arg(
+            Arg::new("dry-run")
+                .long("dry-run")
+                .help("Print the changes that would be made without changing anything")
+                .long_help(
+                    "Print the changes that would be made to the filesystem without changing \
+                     anything. This works with every subcommand, including 'yabridgectl sync \
+                     --prune' and the commands that only change the config file. The \
+                     post-installation setup checks are skipped during a dry-run.",
+                )
+                .global(true),
+        )
+        .arg(
+            Arg::new("assume-yes")
+                .short('y')
+                .long("assume-yes")
+                .global(true)
+                .help("Answer yes to every question")
+                .long_help(
+                    "Answer yes to every question yabridgectl would otherwise ask, like whether \
+                     to remove leftover files after 'yabridgectl rm'. Without this option, \
+                     questions are answered with no when STDIN is not a terminal so scripts \
+                     don't wait for input that never comes.",
+                ),
+        )
+        .arg(
+            Arg::new("allow-root")
+                .long("allow-root")
+                .global(true)
+                .help("Allow changes to be made while running as root")
+                .long_help(
+                    "Allow changes to be made while running as root. Running yabridgectl with \
+                     'sudo' creates files owned by root that your DAW can't use or replace, so \
+                     yabridgectl refuses to change anything when it runs as root while the \
+                     config directory or the plugin directories belong to a regular user. \
+                     Commands that don't change anything only print a warning.",
+                ),
+        )
+        .arg(
+            Arg::new("json")
+                .long("json")
+                .global(true)
+                .help("Report errors and summaries as JSON objects")
+                .long_help(
+                    "Report errors and summaries as JSON objects. Errors are printed to STDERR in \
+                     the format '{\"error\": {\"kind\": \"...\", \"message\": \"...\", \"path\": \
+                     \"...\"}}'. 'yabridgectl sync' also prints a summary of the skipped files in \
+                     the format '{\"skipped\": {\"<reason>\": <count>}}'.",
+                ),
+        )
+        .arg(
+            Arg::new("time")
+                .long("time")
+                .global(true)
+                .help("Print how long the indexing and syncing phases took")
+                .long_help(
+                    "Print how long the indexing and syncing phases took. This only affects \
+                     'yabridgectl status' and 'yabridgectl sync'. Combine this with '--verbose' \
+                     to also show these timings for every plugin directory. Directories that took \
+                     longer than the threshold set with 'yabridgectl set \
+                     --slow-directory-threshold' are always pointed out. With 'yabridgectl sync \
+                     --json', the timings are also included in the JSON summary.",
+                ),
+        )
+        .arg(
+            Arg::new("prefix")
+                .long("prefix")
+                .global(true)
+                .help("The Wine prefix to use")
+                .long_help(
+                    "The Wine prefix to use. This sets 'WINEPREFIX' for every Wine process \
+                     yabridgectl spawns, and it's also used to detect whether the prefix is a \
+                     32-bit prefix. Use this to check a specific prefix with 'yabridgectl doctor' \
+                     or the post-installation checks from 'yabridgectl sync'. Without this option \
+                     the 'WINEPREFIX' environment variable is used, or '~/.wine' if that is not \
+                     set.",
+                )
+                .value_name("PATH")
+                .takes_value(true)
+                .validator(validate_directory),
+        )
+        .arg(
+            Arg::new("config-dir")
+                .long("config-dir")
+                .global(true)
+                .help("Use a different directory for the config file")
+                .long_help(
+                    "Read and write the config file in this directory instead of in \
+                     '~/.config/yabridgectl'. The directory and a default config file are created \
+                     the first time a command changes the config. Paths relative to the config \
+                     file's directory are also relative to this directory. The index and the \
+                     cache are not affected by this option.",
+                )
+                .value_name("PATH")
+                .takes_value(true),
+        )
         .subcommand(
             Command::new("add")
                 .about("Add a plugin install location")
@@ -67,126 +264,1393 @@ fn main() -> Result<()> {
                         .validator(validate_directory)
                         .takes_value(true)
                         .required(true),
+                )
+                .arg(no_recurse_arg().long_help(
+                    "Don't search for plugins in subdirectories. VST3 bundles directly inside of \
+                     the directory will still be picked up. This setting is stored for this \
+                     directory, run 'yabridgectl add' again without this option to revert it.",
+                ))
+                .arg(
+                    Arg::new("group")
+                        .long("group")
+                        .help("Add the directory to a named group")
+                        .long_help(
+                            "Add the directory to a named group. 'yabridgectl status --group \
+                             <name>' and 'yabridgectl sync --group <name>' only process the \
+                             directories in that group. This option can be passed multiple times \
+                             to add the directory to multiple groups. Like with the other \
+                             options, run 'yabridgectl add' again with a different set of groups \
+                             to change them.",
+                        )
+                        .value_name("NAME")
+                        .takes_value(true)
+                        .multiple_occurrences(true),
+                )
+                .arg(strict_paths_arg())
+                .arg(
+                    Arg::new("force")
+                        .short('f')
+                        .long("force")
+                        .help("Add the directory even if it's not inside of a Wine prefix")
+                        .long_help(
+                            "Add the directory even if it's not inside of a Wine prefix, when \
+                             strict paths have been enabled using '--strict-paths' or \
+                             'yabridgectl set --strict-paths=true'.",
+                        ),
+                )
+                .arg(
+                    Arg::new("read-only")
+                        .long("read-only")
+                        .alias("readonly")
+                        .help("Never write any files to this directory")
+                        .long_help(
+                            "Never write any files to this directory. This is useful for plugins \
+                             stored on read-only filesystems or in immutable plugin stores. VST2 \
+                             plugins in this directory are always set up in the centralized VST2 \
+                             location regardless of the 'yabridgectl set --vst2-location' \
+                             setting, and '.so' files in this directory are never pruned. This \
+                             setting is stored for this directory, run 'yabridgectl add' again \
+                             without this option to revert it.",
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("adopt")
+                .about("Add a plugin install location that was set up by hand")
+                .long_about(
+                    "Add a plugin install location containing plugins that were already set up \
+                     by hand, without using yabridgectl. The bridges found for these plugins are \
+                     recorded, and everything 'yabridgectl sync' would still need to change about \
+                     them is reported. Bridges that are already up to date are left alone by the \
+                     next sync.",
+                )
+                .display_order(1)
+                .arg(
+                    Arg::new("path")
+                        .help("Path to a directory containing Windows VST2, VST3, or CLAP plugins")
+                        .validator(validate_directory)
+                        .takes_value(true)
+                        .required(true),
+                )
+                .arg(strict_paths_arg())
+                .arg(
+                    Arg::new("force")
+                        .short('f')
+                        .long("force")
+                        .help("Add the directory even if it's not inside of a Wine prefix"),
+                ),
+        )
+        .subcommand(
+            Command::new("rm")
+                .about("Remove a plugin install location")
+                .display_order(2)
+                .arg(
+                    Arg::new("path")
+                        .help("Path to a previously added directory")
+                        .long_help(
+                            "Path to a previously added directory, or the directory's number as \
+                             shown by 'yabridgectl list --numbered'.",
+                        )
+                        .validator(|path| {
+                            match_plugin_directory(
+                                path,
+                                &plugin_directories,
+                                &numbered_plugin_directories,
+                            )
+                        })
+                        .takes_value(true)
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            Command::new("move")
+                .about("Update a plugin install location after moving it")
+                .long_about(
+                    "Update a plugin install location after moving it\n\nThis changes the \
+                     directory's path in place, so its settings, groups, blacklist entries, and \
+                     the notes for its plugins are kept. Move the directory itself first, since \
+                     the new location needs to exist.",
+                )
+                .visible_alias("rename")
+                .display_order(2)
+                .arg(
+                    Arg::new("from")
+                        .help("Path to a previously added directory")
+                        .long_help(
+                            "Path to a previously added directory, or the directory's number as \
+                             shown by 'yabridgectl list --numbered'.",
+                        )
+                        .validator(|path| {
+                            match_plugin_directory(
+                                path,
+                                &plugin_directories,
+                                &numbered_plugin_directories,
+                            )
+                        })
+                        .takes_value(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("to")
+                        .help("The directory's new location")
+                        .validator(validate_directory)
+                        .takes_value(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("fix-links")
+                        .long("fix-links")
+                        .help("Point the symlinks in the centralized locations at the new path")
+                        .long_help(
+                            "Point the symlinks to the Windows plugins in '~/.vst/yabridge', \
+                             '~/.vst3/yabridge', and '~/.clap/yabridge' at the new location. \
+                             Without this option these symlinks are fixed during the next \
+                             'yabridgectl sync'.",
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("list")
+                .about("List the plugin install locations")
+                .display_order(3)
+                .arg(
+                    Arg::new("numbered")
+                        .short('n')
+                        .long("numbered")
+                        .help("Number the entries so they can be passed to 'yabridgectl rm'"),
+                )
+                .arg(
+                    Arg::new("verbose")
+                        .short('v')
+                        .long("verbose")
+                        .help("Also show the directories' settings and plugin counts")
+                        .long_help(
+                            "Also show the directories' settings and the number of plugins found \
+                             in them during the last sync. With '--json' the directories are \
+                             always printed along with their settings, and this adds the plugin \
+                             counts.",
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("status")
+                .about("Show the installation status for all plugins")
+                .display_order(4)
+                .arg(vst2_location_arg())
+                .args(traversal_args())
+                .arg(
+                    Arg::new("group")
+                        .long("group")
+                        .help("Only show the plugin directories in this group")
+                        .long_help(
+                            "Only show the plugin directories in this group. Directories can be \
+                             added to groups using 'yabridgectl add <path> --group <name>'.",
+                        )
+                        .value_name("NAME")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("exclude-dir")
+                        .long("exclude-dir")
+                        .help("Skip this plugin directory for this run")
+                        .long_help(
+                            "Skip this plugin directory for this run without removing it from \
+                             the config file. The path is compared to the plugin directories \
+                             both as is and after resolving symlinks, and it does not need to \
+                             exist. This option can be passed multiple times.",
+                        )
+                        .value_name("PATH")
+                        .takes_value(true)
+                        .multiple_occurrences(true),
+                )
+                .arg(
+                    Arg::new("group-by")
+                        .long("group-by")
+                        .help("Group the plugins by something other than their directory")
+                        .long_help(
+                            "Group the plugins by something other than the plugin directory they \
+                             were found in. 'method' groups the plugins by how they have been set \
+                             up, 'arch' groups them by their architecture, and 'kind' groups them \
+                             by their plugin format. Every group shows the number of plugins in \
+                             it.",
+                        )
+                        .possible_values(["directory", "method", "arch", "kind"])
+                        .default_value("directory"),
+                )
+                .arg(
+                    Arg::new("json-stream")
+                        .long("json-stream")
+                        .help("Print the status as newline-delimited JSON")
+                        .long_help(
+                            "Print the status as newline-delimited JSON, with one JSON object per \
+                             line. The first line is a header with the plugin directories that \
+                             will be searched, and it's printed before anything is searched. The \
+                             plugin directories are then searched one at a time, and the lines \
+                             for a directory's plugins are printed as soon as that directory has \
+                             been searched. The last line is a summary with the number of plugins \
+                             for each status. Every object has a 'type' field that is either \
+                             'header', 'plugin', or 'summary'. This is meant for frontends that \
+                             want to show the results for large plugin collections while they are \
+                             still coming in. This can't be combined with '--output', since that \
+                             only writes the file once the report is complete.",
+                        )
+                        .conflicts_with_all(&["json", "output"]),
+                )
+                .arg(
+                    Arg::new("check-host-match")
+                        .long("check-host-match")
+                        .help("Mark plugins without a matching host binary")
+                        .long_help(
+                            "Mark the plugins that won't load because there's no \
+                             'yabridge-host.exe' or 'yabridge-host-32.exe' binary matching their \
+                             architecture. When combined with '--json', every plugin is printed \
+                             as a JSON object containing the path to the host binary that would be \
+                             used, or 'null' if there's no matching host binary.",
+                        ),
+                )
+                .arg(include_hidden_arg())
+                .arg(
+                    Arg::new("skip-system-dlls")
+                        .long("skip-system-dlls")
+                        .help("Skip DLLs named like libraries plugins depend on")
+                        .long_help(
+                            "Skip '.dll' files named like well-known libraries that plugins \
+                             depend on, like 'msvcp140.dll'. These are almost certainly not \
+                             plugins. This can be enabled permanently with 'yabridgectl set \
+                             --skip-system-dlls=true'.",
+                        ),
+                )
+                .arg(no_recurse_arg())
+                .arg(
+                    Arg::new("no-cache")
+                        .long("no-cache")
+                        .help("Search every plugin directory again")
+                        .long_help(
+                            "Search every plugin directory again. Without this option, the \
+                             plugins found during the last 'yabridgectl sync' are reused for \
+                             plugin directories that have not been modified since then. A plugin \
+                             directory counts as modified when any of its subdirectories or its \
+                             '.yabridgeignore' file has been modified. With '--verbose' the \
+                             plugins from the cache are marked as cached.",
+                        ),
+                )
+                .arg(output_arg(false))
+                .arg(
+                    Arg::new("sort")
+                        .long("sort")
+                        .help("How the plugins are sorted")
+                        .long_help(
+                            "How the plugins are sorted within each directory, or within each \
+                             group when using '--group-by'. 'name' sorts the plugins \
+                             alphabetically, 'status' lists the plugins that have not been set up \
+                             correctly first, and 'mtime' lists the most recently modified \
+                             Windows plugin files first.",
+                        )
+                        .possible_values(["name", "status", "mtime"])
+                        .default_value("name"),
+                )
+                .arg(
+                    Arg::new("verbose")
+                        .short('v')
+                        .long("verbose")
+                        .help("Show which version of yabridge set up each plugin"),
+                ),
+        )
+        .subcommand(
+            Command::new("diff")
+                .about("Show what the next sync would change")
+                .long_about(
+                    "Show what the next sync would change\n\nThis lists the plugins that would be \
+                     set up or updated by the next 'yabridgectl sync', as well as the leftover \
+                     files that no longer belong to a plugin and would be removed with \
+                     'yabridgectl sync --prune'. Nothing is changed on disk.",
+                )
+                .display_order(5)
+                .arg(vst2_location_arg())
+                .args(traversal_args())
+                .arg(include_hidden_arg())
+                .arg(no_recurse_arg())
+                .arg(output_arg(true)),
+        )
+        .subcommand(
+            Command::new("duplicates")
+                .about("List plugins that have been installed more than once")
+                .long_about(
+                    "List plugins that have been installed more than once\n\nThis lists the \
+                     plugins with identical contents across all plugin directories. These would \
+                     otherwise show up twice in your DAW. Nothing is changed on disk. Use \
+                     'yabridgectl sync --keep-first' to only set up the first plugin from each \
+                     set.",
+                )
+                .display_order(6)
+                .arg(include_hidden_arg())
+                .arg(no_recurse_arg()),
+        )
+        .subcommand(
+            Command::new("doctor")
+                .about("Check the setup for problems that would prevent plugins from loading")
+                .long_about(
+                    "Check the setup for problems that would prevent plugins from loading\n\nThis \
+                     checks whether the host binaries needed for the 32-bit and 64-bit plugins \
+                     in your plugin directories are installed, and recommends how to fix any \
+                     problems it finds. Nothing is changed on disk unless '--fix' is used. \
+                     yabridgectl exits with a non-zero exit code if a problem was found.",
+                )
+                .display_order(7)
+                .arg(
+                    Arg::new("fix")
+                        .long("fix")
+                        .value_name("FIXES")
+                        .help("Fix the problems that can be fixed safely")
+                        .long_help(
+                            "Fix the problems that can be fixed safely instead of only reporting \
+                             them. Without a value, every fix is enabled. Otherwise this takes a \
+                             comma separated list of fixes, like '--fix=missing-dirs,permissions'. \
+                             'missing-dirs' removes plugin directories that no longer exist from \
+                             the config, 'broken-symlinks' replaces symlinked bridges with a \
+                             missing target with copies of the current chainloaders, \
+                             'permissions' gives copied bridges the permissions from the \
+                             'bridge_mode' setting, and 'search-path' adds the directory \
+                             containing the host binaries to your login shell's search path after \
+                             asking for confirmation. Every change is printed. Problems that can't \
+                             be fixed safely are still only reported.",
+                        )
+                        .possible_values([
+                            "missing-dirs",
+                            "broken-symlinks",
+                            "permissions",
+                            "search-path",
+                        ])
+                        .takes_value(true)
+                        .min_values(0)
+                        .require_equals(true)
+                        .use_value_delimiter(true),
+                )
+                .arg(
+                    Arg::new("report")
+                        .long("report")
+                        .takes_value(true)
+                        .value_name("PATH")
+                        .help("Also write a diagnostic report for bug reports to PATH")
+                        .long_help(
+                            "Also write a diagnostic report for bug reports to PATH. The report \
+                             is a JSON file containing the effective config, the output of \
+                             'yabridgectl status --verbose', the results from this command, the \
+                             versions of yabridgectl and Wine, and the contents of the index \
+                             file. Nothing is removed from the report, so check it before sharing \
+                             it. If PATH is a directory, then the report is written to a \
+                             timestamped file in that directory.",
+                        ),
+                )
+                .arg(include_hidden_arg())
+                .arg(no_recurse_arg()),
+        )
+        .subcommand(
+            Command::new("loadable")
+                .about("List the plugins that can be loaded right now")
+                .long_about(
+                    "List the plugins that can be loaded right now\n\nThis only lists the \
+                     plugins that have an up to date bridge, that can be read, and that have a \
+                     host binary matching their architecture. A plugin that's missing from this \
+                     list won't show up in your DAW, and 'yabridgectl status' and 'yabridgectl \
+                     doctor' can help find out why. Nothing is changed on disk.",
+                )
+                .display_order(9)
+                .arg(include_hidden_arg())
+                .arg(no_recurse_arg()),
+        )
+        .subcommand(
+            Command::new("self-test")
+                .about("Check that setting up plugins works")
+                .long_about(
+                    "Check that setting up plugins works\n\nThis sets up a fake plugin in a \
+                     temporary directory using the same code as 'yabridgectl sync', checks \
+                     whether the result is detected correctly, and then removes the temporary \
+                     directory again. Your plugins are never touched. yabridgectl exits with a \
+                     non-zero exit code if one of the checks failed.",
+                )
+                .display_order(10),
+        )
+        .subcommand(
+            Command::new("stats")
+                .about("Show aggregate numbers for all plugins")
+                .long_about(
+                    "Show aggregate numbers for all plugins\n\nThis prints the number of plugins \
+                     per plugin format, architecture, and installation status, the number of \
+                     leftover files, the disk space used by the bridges, and when \
+                     'yabridgectl sync' was last run. Use 'yabridgectl status' to see the \
+                     individual plugins.",
+                )
+                .display_order(8)
+                .arg(include_hidden_arg())
+                .arg(no_recurse_arg()),
+        )
+        .subcommand(
+            Command::new("sync")
+                .about("Set up or update yabridge for all plugins")
+                .display_order(100)
+                .arg(
+                    Arg::new("force")
+                        .short('f')
+                        .long("force")
+                        .help("Always update files, even not necessary")
+                        .long_help(
+                            "Always update files, even not necessary. This deletes and recreates \
+                             every bridge and every symlink to a Windows plugin, regardless of \
+                             whether they were already up to date. This also allows pruning when \
+                             a plugin directory that previously contained plugins is now empty, \
+                             and it ignores the '--max-plugins' limit.",
+                        ),
+                )
+                .args(traversal_args())
+                .arg(
+                    Arg::new("concurrency-per-device")
+                        .long("concurrency-per-device")
+                        .help("Don't search directories on the same hard drive in parallel")
+                        .long_help(
+                            "Choose which plugin directories are searched in parallel based on \
+                             the devices they're stored on. With 'auto', which is used when no \
+                             value is given, directories on the same spinning hard drive are \
+                             searched one after the other so the drive doesn't have to seek \
+                             between them, while directories on SSDs and other drives are still \
+                             searched in parallel. 'serial' does the same for every device, and \
+                             'parallel' (the default without this option) always searches all \
+                             directories in parallel.",
+                        )
+                        .value_name("MODE")
+                        .possible_values(["auto", "serial", "parallel"])
+                        .takes_value(true)
+                        .min_values(0)
+                        .require_equals(true)
+                        .default_missing_value("auto"),
+                )
+                .arg(
+                    Arg::new("min-size")
+                        .long("min-size")
+                        .help("Skip plugin files smaller than this size")
+                        .long_help(
+                            "Skip plugin files smaller than this size, like '100KB'. This \
+                             overrides the size set with 'yabridgectl set --min-size' for this \
+                             sync. The skipped files and their sizes are shown with '--verbose'. \
+                             Leftover bridges for the skipped plugins are removed when combined \
+                             with '--prune'.",
+                        )
+                        .value_name("SIZE")
+                        .takes_value(true)
+                        .validator(validate_size),
+                )
+                .arg(
+                    Arg::new("max-size")
+                        .long("max-size")
+                        .help("Skip plugin files larger than this size")
+                        .long_help(
+                            "Skip plugin files larger than this size, like '2GB'. This overrides \
+                             the size set with 'yabridgectl set --max-size' for this sync.",
+                        )
+                        .value_name("SIZE")
+                        .takes_value(true)
+                        .validator(validate_size),
+                )
+                .arg(
+                    Arg::new("max-plugins")
+                        .long("max-plugins")
+                        .value_name("N")
+                        .help("Abort if more than this many plugins were found")
+                        .long_help(
+                            "Abort before changing anything if more than this many plugins were \
+                             found, to guard against accidentally adding a directory like your \
+                             home directory. This overrides the limit set with 'yabridgectl set \
+                             --max-plugins' for this sync. The limit is ignored when using \
+                             '--force'.",
+                        )
+                        .takes_value(true)
+                        .validator(validate_plugin_count),
+                )
+                .arg(
+                    Arg::new("skip-system-dlls")
+                        .long("skip-system-dlls")
+                        .help("Skip DLLs named like libraries plugins depend on")
+                        .long_help(
+                            "Skip '.dll' files named like well-known libraries that plugins \
+                             depend on, like 'msvcp140.dll'. These are almost certainly not \
+                             plugins. The skipped files are shown with '--verbose'. This can be \
+                             enabled permanently with 'yabridgectl set --skip-system-dlls=true'.",
+                        ),
+                )
+                .arg(
+                    Arg::new("skip-non-vst")
+                        .long("skip-non-vst")
+                        .help("Remove bridges for DLLs that aren't VST plugins")
+                        .long_help(
+                            "'.dll' files that don't export any VST2 entry points, like \
+                             uninstallers and vendor helper libraries, are never set up. Bridges \
+                             for these files that were created by older versions of yabridgectl \
+                             are kept by default. With this option they are treated as leftovers \
+                             and removed with '--prune'. This will become the default in a future \
+                             version.",
+                        ),
+                )
+                .arg(
+                    Arg::new("group")
+                        .long("group")
+                        .help("Only sync the plugin directories in this group")
+                        .long_help(
+                            "Only sync the plugin directories in this group. Directories can be \
+                             added to groups using 'yabridgectl add <path> --group <name>'. \
+                             Leftover bridges are not removed when this option is used, since \
+                             the plugins from the other directories would otherwise be \
+                             considered leftovers.",
+                        )
+                        .value_name("NAME")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("exclude-dir")
+                        .long("exclude-dir")
+                        .help("Skip this plugin directory for this run")
+                        .long_help(
+                            "Skip this plugin directory for this run without removing it from \
+                             the config file. The path is compared to the plugin directories \
+                             both as is and after resolving symlinks, and it does not need to \
+                             exist. This option can be passed multiple times. Like with \
+                             '--group', leftover bridges are not removed when this option is \
+                             used.",
+                        )
+                        .value_name("PATH")
+                        .takes_value(true)
+                        .multiple_occurrences(true),
+                )
+                .arg(include_hidden_arg().long_help(
+                    "Also search hidden directories. Directories with names starting with a \
+                     period, like '.git', are skipped by default. Use 'yabridgectl set \
+                     --include-hidden=true' to always search these directories.",
+                ))
+                .arg(
+                    Arg::new("fail-fast")
+                        .long("fail-fast")
+                        .help("Stop at the first plugin that can't be set up")
+                        .long_help(
+                            "Stop at the first plugin that can't be set up. By default the \
+                             syncing process continues with the other plugins, and the errors are \
+                             summarized at the end. In both cases yabridgectl exits with a \
+                             non-zero exit code when a plugin could not be set up.",
+                        )
+                        .overrides_with("keep-going"),
+                )
+                .arg(
+                    Arg::new("keep-going")
+                        .long("keep-going")
+                        .help("Continue setting up other plugins after an error (default)")
+                        .overrides_with("fail-fast"),
+                )
+                .arg(
+                    Arg::new("ignore-errors-exit-zero")
+                        .long("ignore-errors-exit-zero")
+                        .help("Exit with a zero exit code even if some plugins failed")
+                        .long_help(
+                            "Exit with a zero exit code even if some plugins could not be set \
+                             up. The errors are still printed and summarized at the end. This \
+                             only changes the exit code, '--keep-going' (the default) and \
+                             '--fail-fast' determine whether the other plugins are still set up \
+                             after an error, so this cannot be combined with '--fail-fast'. \
+                             Errors that stop the entire sync, like yabridge's files not being \
+                             found, still result in a non-zero exit code.",
+                        )
+                        .conflicts_with("fail-fast"),
+                )
+                .arg(
+                    Arg::new("keep-first")
+                        .long("keep-first")
+                        .help("Skip plugins that have already been installed elsewhere")
+                        .long_help(
+                            "Skip plugins that have already been installed elsewhere. When the \
+                             same plugin is found more than once across the plugin directories, \
+                             only the first one is set up. Run 'yabridgectl duplicates' to see \
+                             which plugins this applies to. Combine this with '--prune' to \
+                             remove the bridges for the duplicates.",
+                        ),
+                )
+                .arg(
+                    Arg::new("install-missing-only")
+                        .long("install-missing-only")
+                        .help("Only set up plugins that have not been set up yet")
+                        .long_help(
+                            "Only set up plugins that have not been set up yet. Plugins that \
+                             have already been set up are not touched at all, even if they're \
+                             outdated or broken, and leftover files are not reported. This is \
+                             useful after installing new plugins.",
+                        )
+                        .conflicts_with_all(&["force", "prune"]),
+                )
+                .arg(no_recurse_arg().long_help(
+                    "Don't search for plugins in subdirectories. This applies to all plugin \
+                     directories for this run only. Use 'yabridgectl add --no-recurse <path>' to \
+                     change this permanently for a single directory.",
+                ))
+                .arg(
+                    Arg::new("no-verify")
+                        .short('n')
+                        .long("no-verify")
+                        .help("Skip post-installation setup checks"),
+                )
+                .arg(
+                    Arg::new("no-arch-check")
+                        .long("no-arch-check")
+                        .help("Set up plugins even if there's no Wine host for them")
+                        .long_help(
+                            "Set up plugins even if there's no Wine host with the same \
+                             architecture. By default 32-bit plugins are skipped when \
+                             'yabridge-host-32.exe' cannot be found, and 64-bit plugins are \
+                             skipped when 'yabridge-host.exe' cannot be found, since their \
+                             bridges would never be able to load them.",
+                        ),
+                )
+                .arg(
+                    Arg::new("emit-script")
+                        .long("emit-script")
+                        .value_name("PATH")
+                        .help("With --dry-run, write the changes to a shell script")
+                        .long_help(
+                            "With --dry-run, write the changes 'yabridgectl sync' would have made \
+                             to an executable shell script at PATH. The script contains the \
+                             'cp', 'ln', and 'rm' commands for every change so it can be reviewed \
+                             and run by hand. Empty directories left behind after pruning are not \
+                             removed by the script.",
+                        )
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("preserve-mtime")
+                        .long("preserve-mtime")
+                        .help("Give copied bridges the same modification time as yabridge")
+                        .long_help(
+                            "Give copied bridges the same modification time as yabridge's own \
+                             libraries instead of the current time. This way syncing again won't \
+                             change the timestamps of files that are already up to date, which \
+                             avoids needless work for backup and file synchronization tools. \
+                             Symlinks are not affected.",
+                        ),
+                )
+                .arg(
+                    Arg::new("prune")
+                        .short('p')
+                        .long("prune")
+                        .help("Remove unrelated or leftover .so files"),
+                )
+                .arg(
+                    Arg::new("jobs-report")
+                        .long("jobs-report")
+                        .help("Print the execution plan without syncing")
+                        .long_help(
+                            "Print the order in which the plugin directories would be searched \
+                             and the steps that would be performed after that, without doing \
+                             anything. This shows which directories are searched one after the \
+                             other because of '--concurrency-per-device'. Unlike '--dry-run', no \
+                             plugins are searched for or set up. Combine this with '--json' to \
+                             get the plan as JSON.",
+                        )
+                        .conflicts_with("emit-script"),
+                )
+                .arg(
+                    Arg::new("older-than")
+                        .long("older-than")
+                        .value_name("DURATION")
+                        .help("With --prune, also remove bridges for plugins not modified recently")
+                        .long_help(
+                            "With --prune, also remove the bridges for plugins whose Windows \
+                             plugin file hasn't been modified within DURATION, like '90d'. The \
+                             supported units are 's', 'm', 'h', 'd', and 'w', and a number \
+                             without a unit is a number of days. These plugins are only skipped \
+                             while '--older-than' is passed, so the next sync without this option \
+                             sets them up again. Plugin installers often keep the original \
+                             modification times of the files they install, so plugins you \
+                             installed recently may still be considered old. Only files that are \
+                             actually yabridge bridges are removed. Combine this with '--dry-run' \
+                             to see which bridges would be removed.",
+                        )
+                        .takes_value(true)
+                        .requires("prune")
+                        .validator(validate_duration),
+                )
+                .arg(
+                    Arg::new("relative")
+                        .long("relative")
+                        .help("Symlink Windows plugin files using relative paths")
+                        .long_help(
+                            "Symlink Windows plugin files using relative paths. This overrides \
+                             the 'relative_symlinks' setting for this run. Existing symlinks are \
+                             recreated if needed.",
+                        )
+                        .conflicts_with("absolute"),
+                )
+                .arg(
+                    Arg::new("absolute")
+                        .long("absolute")
+                        .help("Symlink Windows plugin files using absolute paths")
+                        .long_help(
+                            "Symlink Windows plugin files using absolute paths. This overrides \
+                             the 'relative_symlinks' setting for this run. Existing symlinks are \
+                             recreated if needed.",
+                        ),
+                )
+                .arg(vst2_location_arg())
+                .arg(
+                    Arg::new("verbose")
+                        .short('v')
+                        .long("verbose")
+                        .help("Print information about plugins being set up or skipped"),
+                ),
+        )
+        .subcommand(
+            Command::new("set")
+                .about("Change yabridgectl's settings (advanced)")
+                .display_order(200)
+                .arg_required_else_help(true)
+                .arg(
+                    Arg::new("path")
+                        .long("path")
+                        .help(
+                            "Path to the directory containing \
+                             'libyabridge-chainloader-{clap,vst2,vst3}.so'",
+                        )
+                        .long_help(
+                            "Path to the directory containing \
+                             'libyabridge-chainloader-{clap,vst2,vst3}.so'. If this is not set, \
+                             then yabridgectl will look in both '/usr/lib' and \
+                             '~/.local/share/yabridge' by default.",
+                        )
+                        .validator(validate_path)
+                        .takes_value(true)
+                        .conflicts_with("path_auto"),
+                )
+                .arg(
+                    Arg::new("path_auto")
+                        .long("path-auto")
+                        .help("Automatically locate yabridge's files")
+                        .long_help(
+                            "Automatically locate yabridge's files. This can be used after \
+                             manually setting a path with the '--path' option to revert back to \
+                             the default auto detection behaviour.",
+                        ),
+                )
+                .arg(
+                    Arg::new("vst2_location")
+                        .long("vst2-location")
+                        .help("Where to set up VST2 plugins")
+                        .long_help(
+                            format!(
+                                "Where to set up VST2 plugins. '{}' (the default) causes bridged \
+                                 VST2 plugins to be set up in `~/.vst/yabridge.` '{}' causes \
+                                 bridged VST2 plugins to be set up next to the original '.dll' \
+                                 file.",
+                                "centralized".bright_white(),
+                                "inline".bright_white()
+                            )
+                            .as_ref(),
+                        )
+                        .possible_values(["centralized", "inline"])
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("no_verify")
+                        .long("no-verify")
+                        .help("Always skip post-installation setup checks")
+                        .long_help(
+                            "Always skip post-installation setup checks. This can be set \
+                             temporarily by passing the '--no-verify' option to 'yabridgectl \
+                             sync'.",
+                        )
+                        .possible_values(["true", "false"])
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("include_hidden")
+                        .long("include-hidden")
+                        .help("Always search hidden directories")
+                        .long_help(
+                            "Always search hidden directories. Directories with names starting \
+                             with a period are skipped by default. This can be set temporarily \
+                             by passing the '--include-hidden' option to 'yabridgectl sync'.",
+                        )
+                        .possible_values(["true", "false"])
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("relative_symlinks")
+                        .long("relative-symlinks")
+                        .help("Symlink Windows plugin files using relative paths")
+                        .long_help(
+                            "Symlink Windows plugin files using relative paths, so they keep \
+                             working when the plugin directories and your home directory are \
+                             moved or mounted somewhere else together. This can be set temporarily \
+                             by passing the '--relative' or '--absolute' options to 'yabridgectl \
+                             sync'.",
+                        )
+                        .possible_values(["true", "false"])
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("relative_to")
+                        .long("relative-to")
+                        .help("Store paths relative to a base directory")
+                        .long_help(
+                            format!(
+                                "Store paths in the config file relative to a base directory. \
+                                 This makes it possible to move a setup containing both the \
+                                 config file and the plugins to another machine. '{}' stores \
+                                 paths relative to '~/.config/yabridgectl', '{}' stores paths \
+                                 relative to your home directory, and '{}' (the default) always \
+                                 stores absolute paths. Only paths inside of the base directory \
+                                 are stored as relative paths.",
+                                "config".bright_white(),
+                                "home".bright_white(),
+                                "none".bright_white()
+                            )
+                            .as_ref(),
+                        )
+                        .possible_values(["config", "home", "none"])
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("vst2_target")
+                        .long("vst2-target")
+                        .help("Set up VST2 plugins in another directory")
+                        .long_help(
+                            "Set up bridged VST2 plugins in a 'yabridge' subdirectory of this \
+                             directory, in place of '~/.vst'. This also changes the VST2 \
+                             location to 'centralized'.",
+                        )
+                        .validator(validate_absolute_path)
+                        .takes_value(true)
+                        .conflicts_with("vst2_target_default"),
+                )
+                .arg(
+                    Arg::new("vst2_target_default")
+                        .long("vst2-target-default")
+                        .help("Set up VST2 plugins in '~/.vst' again"),
+                )
+                .arg(
+                    Arg::new("vst3_target")
+                        .long("vst3-target")
+                        .help("Set up VST3 plugins in another directory")
+                        .long_help(
+                            "Set up bridged VST3 plugins in a 'yabridge' subdirectory of this \
+                             directory, in place of '~/.vst3'.",
+                        )
+                        .validator(validate_absolute_path)
+                        .takes_value(true)
+                        .conflicts_with("vst3_target_default"),
+                )
+                .arg(
+                    Arg::new("vst3_target_default")
+                        .long("vst3-target-default")
+                        .help("Set up VST3 plugins in '~/.vst3' again"),
+                )
+                .arg(
+                    Arg::new("subdirectory_template")
+                        .long("subdirectory-template")
+                        .help("Organize the bridged plugins into subdirectories using a template")
+                        .long_help(
+                            "Change the subdirectories of '~/.vst/yabridge', '~/.vst3/yabridge', \
+                             and '~/.clap/yabridge' the bridged plugins are set up in. By \
+                             default the plugins' original subdirectories within their plugin \
+                             directories are recreated. '{subdirectory}' is replaced by that \
+                             original subdirectory, '{vendor}' by the first directory in it, \
+                             which is usually the plugin's vendor, and '{directory}' by the name \
+                             of the plugin directory the plugin was found in. An empty template \
+                             sets up all plugins directly in those directories. This doesn't \
+                             affect VST2 plugins when using the inline VST2 location. Run \
+                             'yabridgectl sync --prune' after changing this to remove the bridges \
+                             in the old layout.",
+                        )
+                        .validator(validate_subdirectory_template)
+                        .takes_value(true)
+                        .conflicts_with("subdirectory_template_default"),
+                )
+                .arg(
+                    Arg::new("subdirectory_template_default")
+                        .long("subdirectory-template-default")
+                        .help("Recreate the plugins' original subdirectories again"),
+                )
+                .arg(
+                    Arg::new("shortcuts")
+                        .long("shortcuts")
+                        .help("How to handle '.lnk' shortcuts to plugins")
+                        .long_help(
+                            format!(
+                                "How to handle Windows '.lnk' shortcuts to '.dll' files in the \
+                                 plugin directories. '{}' (the default) skips all shortcuts. \
+                                 '{}' sets up the plugin the shortcut points to as if it was \
+                                 located where the shortcut is. With the centralized VST2 \
+                                 location, the bridge will be created in the shortcut's \
+                                 subdirectory. '{}' treats the plugin as if it was found at the \
+                                 shortcut's target instead. With the inline VST2 location the \
+                                 bridge is always created next to the target '.dll' file. \
+                                 Shortcuts that don't point to a '.dll' file are skipped.",
+                                "ignore".bright_white(),
+                                "shortcut".bright_white(),
+                                "target".bright_white()
+                            )
+                            .as_ref(),
+                        )
+                        .possible_values(["ignore", "shortcut", "target"])
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("default_exclude")
+                        .long("default-exclude")
+                        .help("Never set up plugin files matching this pattern")
+                        .long_help(
+                            "Never set up plugin files with file names matching this pattern, in \
+                             any plugin directory. '*' and '?' wildcards can be used, and \
+                             patterns are matched case-insensitively. This option can be passed \
+                             multiple times. The blacklist is applied before these patterns, so \
+                             blacklisted directories are never searched. The patterns are \
+                             applied to all files that were not blacklisted. Run 'yabridgectl \
+                             sync --verbose' to see which files were excluded.",
+                        )
+                        .takes_value(true)
+                        .multiple_occurrences(true),
+                )
+                .arg(
+                    Arg::new("vst2_extension")
+                        .long("add-extension")
+                        .value_name("EXTENSION")
+                        .help("Also treat files with this extension as VST2 plugins")
+                        .long_help(
+                            "Also treat files with this extension as VST2 plugins, in addition to \
+                             '.dll' files. Some plugins are distributed as '.vst' files, for \
+                             instance. Extensions are matched case-insensitively, and this option \
+                             can be passed multiple times. These plugins can only be set up using \
+                             the centralized VST2 location since yabridge looks for a '.dll' file \
+                             next to the bridge.",
+                        )
+                        .validator(validate_vst2_extension)
+                        .takes_value(true)
+                        .multiple_occurrences(true),
+                )
+                .arg(
+                    Arg::new("clear_vst2_extensions")
+                        .long("clear-extensions")
+                        .help("Only treat '.dll' files as VST2 plugins")
+                        .long_help(
+                            "Only treat '.dll' files as VST2 plugins. When combined with \
+                             '--add-extension', the old extensions are removed before adding the \
+                             new ones.",
+                        ),
+                )
+                .arg(
+                    Arg::new("use_ignore_files")
+                        .long("use-ignore-files")
+                        .help("Honor .yabridgeignore files in the plugin directories")
+                        .long_help(
+                            "Honor '.yabridgeignore' files in the root of the plugin directories. \
+                             These files use the same syntax as '.gitignore' files, and the files \
+                             and directories matching the patterns in them are skipped during \
+                             indexing. Run 'yabridgectl sync --verbose' to see which files were \
+                             ignored.",
+                        )
+                        .possible_values(["true", "false"])
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("daw")
+                        .long("daw")
+                        .help("Make a DAW rescan its plugins after syncing")
+                        .long_help(
+                            "Make a DAW rescan its plugins after 'yabridgectl sync' added or \
+                             removed plugins. This removes the DAW's plugin cache, so the next \
+                             time the DAW starts it will scan all of its plugins again. This \
+                             option can be passed multiple times.",
+                        )
+                        .possible_values(daw::DAW_INTEGRATION_NAMES)
+                        .takes_value(true)
+                        .multiple_occurrences(true),
+                )
+                .arg(
+                    Arg::new("clear_daws")
+                        .long("clear-daws")
+                        .help("Disable all DAW rescan integrations")
+                        .long_help(
+                            "Disable all DAW rescan integrations. When combined with '--daw', the \
+                             old integrations are disabled before enabling the new ones.",
+                        ),
+                )
+                .arg(
+                    Arg::new("bridge_mode")
+                        .long("bridge-mode")
+                        .value_name("MODE")
+                        .help("The permissions for copied bridges, like '0644'")
+                        .long_help(
+                            "The permissions for copied bridges as an octal file mode, like \
+                             '0644' or '0664' for bridges that should be readable or writable by \
+                             a shared group. The owner always needs to be able to read and write \
+                             the bridges. Symlinks are not affected. Existing bridges get these \
+                             permissions during the next sync. Use 'yabridgectl config unset \
+                             bridge_mode' to use the default permissions again.",
+                        )
+                        .validator(validate_file_mode)
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("overwrite_unrelated_files")
+                        .long("overwrite-unrelated-files")
+                        .help("Allow replacing files that were not created by yabridge")
+                        .long_help(
+                            "Allow 'yabridgectl sync' to replace existing files that were not \
+                             created by yabridge with a bridge, like a native Linux library that \
+                             happens to have the same name as a Windows plugin. By default these \
+                             files are left alone and reported as conflicts, even when using \
+                             '--force'.",
+                        )
+                        .possible_values(["true", "false"])
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("hashed_names")
+                        .long("hashed-names")
+                        .help("Include a hash of yabridge's files in bridge names")
+                        .long_help(
+                            "Include a short hash of yabridge's chainloaders in the file names \
+                             of VST2 bridges in the centralized location and of CLAP bridges, \
+                             like 'Plugin.1a2b3c4d.so'. Updating yabridge then gives the bridges \
+                             new names, which works around hosts that cache plugins by their \
+                             file name. Bridges with an older name are removed automatically \
+                             during the next sync. VST3 bundles and inline VST2 bridges keep \
+                             their names. Note that hosts that refer to plugins by their file \
+                             name may not find the plugins used in existing projects after \
+                             updating yabridge.",
+                        )
+                        .possible_values(["true", "false"])
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("strict_paths")
+                        .long("strict-paths")
+                        .help("Only allow adding directories inside of Wine prefixes")
+                        .long_help(
+                            "Only allow 'yabridgectl add' to add directories inside of a Wine \
+                             prefix or inside of one of the directories added with \
+                             '--allow-base'. A directory counts as being inside of a Wine prefix \
+                             if it's inside of a 'drive_c' directory or inside of the Wine prefix \
+                             set through '--prefix' or '$WINEPREFIX'. This prevents accidentally \
+                             adding a huge directory like your home directory. Use 'yabridgectl \
+                             add --force' to add another directory anyways.",
+                        )
+                        .possible_values(["true", "false"])
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("allowed_base")
+                        .long("allow-base")
+                        .value_name("PATH")
+                        .help("Allow adding directories inside of PATH with strict paths")
+                        .long_help(
+                            "Allow 'yabridgectl add' to add PATH and the directories inside of it \
+                             when strict paths have been enabled with '--strict-paths=true'. \
+                             This option can be passed multiple times.",
+                        )
+                        .validator(validate_directory)
+                        .takes_value(true)
+                        .multiple_occurrences(true),
+                )
+                .arg(
+                    Arg::new("clear_allowed_bases")
+                        .long("clear-allowed-bases")
+                        .help("Remove all directories added with --allow-base")
+                        .long_help(
+                            "Remove all directories added with '--allow-base'. When combined \
+                             with '--allow-base', the old directories are removed before adding \
+                             the new ones.",
+                        ),
+                )
+                .arg(
+                    Arg::new("clear_default_excludes")
+                        .long("clear-default-excludes")
+                        .help("Remove all default exclude patterns")
+                        .long_help(
+                            "Remove all default exclude patterns. When combined with \
+                             '--default-exclude', the old patterns are removed before adding the \
+                             new ones.",
+                        ),
+                )
+                .arg(
+                    Arg::new("min_plugin_size")
+                        .long("min-size")
+                        .help("Skip plugin files smaller than this size")
+                        .long_help(
+                            "Skip plugin files smaller than this size, like '100KB'. This can be \
+                             used to skip tiny stub libraries that aren't actually plugins. Sizes \
+                             can be written in bytes or with a KB, MB, or GB suffix, which are \
+                             all multiples of 1024. Use 'yabridgectl config unset \
+                             min_plugin_size' to remove this limit again.",
+                        )
+                        .value_name("SIZE")
+                        .takes_value(true)
+                        .validator(validate_size),
+                )
+                .arg(
+                    Arg::new("max_plugin_size")
+                        .long("max-size")
+                        .help("Skip plugin files larger than this size")
+                        .long_help(
+                            "Skip plugin files larger than this size, like '2GB'. The size is \
+                             written the same way as with '--min-size'. Use 'yabridgectl config \
+                             unset max_plugin_size' to remove this limit again.",
+                        )
+                        .value_name("SIZE")
+                        .takes_value(true)
+                        .validator(validate_size),
+                )
+                .arg(
+                    Arg::new("slow_directory_threshold")
+                        .long("slow-directory-threshold")
+                        .value_name("DURATION")
+                        .help("Point out directories slower than this when using '--time'")
+                        .long_help(
+                            "Point out plugin directories that take longer than this to search \
+                             or to set up when using '--time', like '30s'. The supported units \
+                             are 's', 'm', 'h', 'd', and 'w'. A number without a unit is a number \
+                             of seconds. This defaults to 10 seconds.",
+                        )
+                        .takes_value(true)
+                        .validator(validate_duration_secs),
+                )
+                .arg(
+                    Arg::new("max_plugins")
+                        .long("max-plugins")
+                        .value_name("N")
+                        .help("Make 'yabridgectl sync' abort if it finds more plugins than this")
+                        .long_help(
+                            "Make 'yabridgectl sync' abort before changing anything if it finds \
+                             more than this many plugins. This guards against accidentally \
+                             adding a directory like your home directory. Use 'yabridgectl \
+                             config unset max_plugins' to remove this limit again.",
+                        )
+                        .takes_value(true)
+                        .validator(validate_plugin_count),
+                )
+                .arg(
+                    Arg::new("skip_system_dlls")
+                        .long("skip-system-dlls")
+                        .help("Always skip DLLs named like libraries plugins depend on")
+                        .long_help(
+                            "Always skip '.dll' files named like well-known libraries that \
+                             plugins depend on, like 'msvcp140.dll'. This can be set temporarily \
+                             by passing the '--skip-system-dlls' option to 'yabridgectl sync'. \
+                             Run 'yabridgectl sync --verbose' to see which files were skipped \
+                             and which patterns were used.",
+                        )
+                        .possible_values(["true", "false"])
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("system_dll")
+                        .long("system-dll")
+                        .value_name("PATTERN")
+                        .help("Also treat DLLs matching this pattern as dependencies")
+                        .long_help(
+                            "Also treat '.dll' files with file names matching this pattern as \
+                             dependencies when skipping system DLLs. '*' and '?' wildcards can be \
+                             used, and patterns are matched case-insensitively. Prefix a pattern \
+                             with an exclamation mark to remove it from the built-in list \
+                             instead, like '!qt5*.dll'. This option can be passed multiple times.",
+                        )
+                        .takes_value(true)
+                        .multiple_occurrences(true),
+                )
+                .arg(
+                    Arg::new("clear_system_dlls")
+                        .long("clear-system-dlls")
+                        .help("Only use the built-in list of system DLLs")
+                        .long_help(
+                            "Only use the built-in list of system DLLs. When combined with \
+                             '--system-dll', the old patterns are removed before adding the new \
+                             ones.",
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("config")
+                .about("Inspect or reset yabridgectl's configuration")
+                .display_order(201)
+                .subcommand_required(true)
+                .arg_required_else_help(true)
+                .subcommand(
+                    Command::new("show")
+                        .about("Print the effective configuration")
+                        .long_about(
+                            "Print the effective configuration\n\nThis prints the settings \
+                             yabridgectl is going to use after applying all command line and \
+                             environment variable overrides, with the default values filled in. \
+                             This includes the paths yabridge's files and the bridged plugins \
+                             are resolved to, all plugin directories along with their settings, \
+                             and the exclude patterns and blacklist.",
+                        )
+                        .display_order(1)
+                        .arg(vst2_location_arg().long_help(
+                            "Override the VST2 location for this run without changing the config \
+                             file. See 'yabridgectl sync --help' for more information.",
+                        ))
+                        .arg(output_arg(true)),
+                )
+                .subcommand(
+                    Command::new("diff")
+                        .about("Compare the configuration to another config file")
+                        .long_about(
+                            "Compare the configuration to another config file\n\nThis prints \
+                             the settings, plugin directories, exclude patterns, and blacklisted \
+                             paths that differ between the current effective configuration and \
+                             another config file, like one copied from another machine. Entries \
+                             marked with a '-' only exist in the current configuration, and \
+                             entries marked with a '+' only exist in the other config file. \
+                             Nothing is changed on disk.",
+                        )
+                        .display_order(2)
+                        .arg(
+                            Arg::new("path")
+                                .help("Path to the other config file")
+                                .validator(validate_path)
+                                .takes_value(true)
+                                .required(true),
+                        )
+                        .arg(vst2_location_arg().long_help(
+                            "Override the VST2 location for this run without changing the config \
+                             file. See 'yabridgectl sync --help' for more information.",
+                        )),
+                )
+                .subcommand(
+                    Command::new("unset")
+                        .about("Reset a setting to its default value")
+                        .long_about(
+                            "Reset a setting to its default value\n\nThis removes a setting \
+                             changed using 'yabridgectl set' from the config file so the default \
+                             value is used again. The settings are named the same as in the \
+                             config file, and 'yabridgectl config show' shows their current \
+                             values.",
+                        )
+                        .display_order(3)
+                        .arg(
+                            Arg::new("setting")
+                                .help("The name of the setting")
+                                .possible_values(config::UNSETTABLE_SETTINGS.map(|(name, _)| name))
+                                .takes_value(true)
+                                .required(true),
+                        ),
+                )
+                .subcommand(
+                    Command::new("repair")
+                        .about("Fix common problems in the config file")
+                        .long_about(
+                            "Fix common problems in the config file\n\nThis removes settings \
+                             with invalid values, normalizes and deduplicates paths, removes \
+                             plugin directories that no longer exist, and removes directory \
+                             settings that are no longer used. Every change is printed, and the \
+                             original config file is backed up to 'config.toml.bak'. Syntax \
+                             errors in the config file still need to be fixed by hand.",
+                        )
+                        .display_order(4)
+                        .arg(
+                            Arg::new("keep_missing")
+                                .long("keep-missing")
+                                .help("Don't remove plugin directories that no longer exist"),
+                        ),
                 ),
         )
         .subcommand(
-            Command::new("rm")
-                .about("Remove a plugin install location")
-                .display_order(2)
+            Command::new("import")
+                .about("Replace the config with a config file from another machine")
+                .display_order(201)
+                .long_about(
+                    "Replace the config with a config file from another machine\n\nThe config \
+                     file is first checked for problems on this machine, like plugin \
+                     directories that don't exist or yabridge's files not being found. The \
+                     config is only imported if there are no such problems. The current config \
+                     file is backed up to 'config.toml.bak'.",
+                )
+                .arg(
+                    Arg::new("check")
+                        .long("check")
+                        .help("Only check the config file without importing it")
+                        .long_help(
+                            "Only check the config file without importing it. The exit code is \
+                             nonzero if the config file can't be used on this machine.",
+                        ),
+                )
                 .arg(
                     Arg::new("path")
-                        .help("Path to a previously added directory")
-                        .validator(|path| match_in_path_list(Path::new(path), &plugin_directories))
+                        .help("Path to the config file to import")
+                        .validator(validate_path)
                         .takes_value(true)
                         .required(true),
                 ),
         )
         .subcommand(
-            Command::new("list")
-                .about("List the plugin install locations")
-                .display_order(3),
-        )
-        .subcommand(
-            Command::new("status")
-                .about("Show the installation status for all plugins")
-                .display_order(4),
-        )
-        .subcommand(
-            Command::new("sync")
-                .about("Set up or update yabridge for all plugins")
-                .display_order(100)
-                .arg(
-                    Arg::new("force")
-                        .short('f')
-                        .long("force")
-                        .help("Always update files, even not necessary"),
-                )
-                .arg(
-                    Arg::new("no-verify")
-                        .short('n')
-                        .long("no-verify")
-                        .help("Skip post-installation setup checks"),
+            Command::new("query")
+                .about("Show everything yabridgectl knows about a single plugin")
+                .long_about(
+                    "Show everything yabridgectl knows about a single plugin\n\nThis searches \
+                     the plugin directories for the plugin and prints its format, \
+                     architecture, installation status, the location of its bridge, the host \
+                     binary that will run it, whether it can be loaded right now, and its note. \
+                     If multiple plugins match, then all of them are shown. Nothing is changed \
+                     on disk.",
                 )
+                .display_order(11)
                 .arg(
-                    Arg::new("prune")
-                        .short('p')
-                        .long("prune")
-                        .help("Remove unrelated or leftover .so files"),
+                    Arg::new("plugin")
+                        .help("The plugin's path, or its file name without the extension")
+                        .long_help(
+                            "The path to the Windows plugin's .dll, .vst3, or .clap file, or the \
+                             plugin's file name without the extension. File names are matched \
+                             case-insensitively.",
+                        )
+                        .takes_value(true)
+                        .required(true),
                 )
-                .arg(
-                    Arg::new("verbose")
-                        .short('v')
-                        .long("verbose")
-                        .help("Print information about plugins being set up or skipped"),
-                ),
+                .arg(include_hidden_arg())
+                .arg(no_recurse_arg()),
         )
         .subcommand(
-            Command::new("set")
-                .about("Change the yabridge path (advanced)")
-                .display_order(200)
-                .arg_required_else_help(true)
+            Command::new("note")
+                .about("Add a note to a plugin")
+                .long_about(
+                    "Add a note to a plugin\n\nNotes are a way to keep track of things like \
+                     plugins that are known to crash. They don't change how plugins are set up, \
+                     and they are shown in 'yabridgectl status --verbose'. Without a note this \
+                     prints the plugin's current note. A note is kept until the plugin no longer \
+                     exists, at which point 'yabridgectl sync' removes it.",
+                )
+                .display_order(202)
                 .arg(
-                    Arg::new("path")
-                        .long("path")
-                        .help(
-                            "Path to the directory containing \
-                             'libyabridge-chainloader-{clap,vst2,vst3}.so'",
-                        )
-                        .long_help(
-                            "Path to the directory containing \
-                             'libyabridge-chainloader-{clap,vst2,vst3}.so'. If this is not set, \
-                             then yabridgectl will look in both '/usr/lib' and \
-                             '~/.local/share/yabridge' by default.",
-                        )
-                        .validator(validate_path)
-                        .takes_value(true)
-                        .conflicts_with("path_auto"),
+                    Arg::new("list")
+                        .long("list")
+                        .help("List all plugins that have a note")
+                        .conflicts_with_all(&["plugin", "note", "remove"]),
                 )
                 .arg(
-                    Arg::new("path_auto")
-                        .long("path-auto")
-                        .help("Automatically locate yabridge's files")
-                        .long_help(
-                            "Automatically locate yabridge's files. This can be used after \
-                             manually setting a path with the '--path' option to revert back to \
-                             the default auto detection behaviour.",
-                        ),
+                    Arg::new("remove")
+                        .long("remove")
+                        .help("Remove the plugin's note")
+                        .conflicts_with("note"),
                 )
                 .arg(
-                    Arg::new("vst2_location")
-                        .long("vst2-location")
-                        .help("Where to set up VST2 plugins")
-                        .long_help(
-                            format!(
-                                "Where to set up VST2 plugins. '{}' (the default) causes bridged \
-                                 VST2 plugins to be set up in `~/.vst/yabridge.` '{}' causes \
-                                 bridged VST2 plugins to be set up next to the original '.dll' \
-                                 file.",
-                                "centralized".bright_white(),
-                                "inline".bright_white()
-                            )
-                            .as_ref(),
-                        )
-                        .possible_values(["centralized", "inline"])
-                        .takes_value(true),
+                    Arg::new("plugin")
+                        .help("Path to the Windows plugin's .dll, .vst3, or .clap file")
+                        .validator(validate_path)
+                        .takes_value(true)
+                        .required_unless_present("list"),
                 )
                 .arg(
-                    Arg::new("no_verify")
-                        .long("no-verify")
-                        .help("Always skip post-installation setup checks")
-                        .long_help(
-                            "Always skip post-installation setup checks. This can be set \
-                             temporarily by passing the '--no-verify' option to 'yabridgectl \
-                             sync'.",
-                        )
-                        .possible_values(["true", "false"])
+                    Arg::new("note")
+                        .help("The note to add, replacing any existing note")
                         .takes_value(true),
                 ),
         )
         .subcommand(
             Command::new("blacklist")
                 .about("Manage the indexing blacklist (advanced)")
-                .display_order(201)
+                .display_order(202)
                 .subcommand_required(true)
                 .arg_required_else_help(true)
                 .long_about(
@@ -232,43 +1696,371 @@ fn main() -> Result<()> {
                         .display_order(4),
                 ),
         )
+        .subcommand(
+            Command::new("clean-cache")
+                .about("Remove yabridgectl's cache and state files (advanced)")
+                .display_order(203)
+                .long_about(
+                    "Remove yabridgectl's cache and state files (advanced)\n\nThis removes \
+                     yabridgectl's index file and cache directory. Your config file is never \
+                     touched. These files will be recreated the next time you run 'yabridgectl \
+                     sync'.",
+                ),
+        )
+        .subcommand(
+            Command::new("dump-index")
+                .about("Print yabridgectl's index file for debugging purposes")
+                .long_about(
+                    "Print yabridgectl's index file for debugging purposes\n\nThis prints the \
+                     state 'yabridgectl sync' stores about the bridges and plugin directories it \
+                     set up as formatted JSON, without changing anything. The path to the index \
+                     file is printed to STDERR.",
+                )
+                .hide(true),
+        )
         .get_matches();
 
+    // `colored` doesn't check whether STDOUT is a terminal by itself
+    match matches.value_of("color") {
+        Some("always") => colored::control::set_override(true),
+        Some("never") => colored::control::set_override(false),
+        _ => {
+            if env::var_os("NO_COLOR").is_some() || !io::stdout().is_terminal() {
+                colored::control::set_override(false);
+            }
+        }
+    }
+
+    // Everything that runs Wine or inspects the Wine prefix goes through `$WINEPREFIX`
+    if let Some(prefix) = matches
+        .subcommand()
+        .and_then(|(_, options)| options.value_of("prefix"))
+    {
+        env::set_var(
+            "WINEPREFIX",
+            Path::new(prefix)
+                .canonicalize()
+                .with_context(|| messages::could_not_resolve(prefix))?,
+        );
+    }
+
+    util::set_assume_yes(
+        matches
+            .subcommand()
+            .map_or(false, |(_, options)| options.is_present("assume-yes")),
+    );
+
+    // When running as root, the commands that don't change anything only print a warning
+    let mutating_command = match matches.subcommand() {
+        Some(("blacklist", blacklist)) => !matches!(blacklist.subcommand(), Some(("list", _))),
+        Some(("config", config_command)) => {
+            matches!(config_command.subcommand(), Some(("unset" | "repair", _)))
+        }
+        Some(("import", options)) => !options.is_present("check"),
+        Some(("sync", options)) => !options.is_present("jobs-report"),
+        Some(("doctor", options)) => options.is_present("fix"),
+        Some(("note", options)) => options.is_present("note") || options.is_present("remove"),
+        Some((
+            "list" | "status" | "diff" | "duplicates" | "loadable" | "query" | "self-test"
+            | "stats" | "dump-index",
+            _,
+        )) => false,
+        _ => true,
+    };
+    let can_write_as_root = check_root(
+        &config,
+        mutating_command,
+        matches
+            .subcommand()
+            .map_or(false, |(_, options)| options.is_present("allow-root")),
+    )?;
+    // Read-only commands never write to the config file, not even to migrate it
+    if mutating_command && can_write_as_root {
+        config.write_if_needed()?;
+    }
+
     // We're calling canonicalize when adding and setting paths since relative paths would cause
     // some weird behaviour. There's no built-in way to make relative paths absoltue without
     // resolving symlinks, but I don't think this will cause any issues.
     //
     // https://github.com/rust-lang/rust/issues/59117
     match matches.subcommand() {
-        Some(("add", options)) => actions::add_directory(
-            &mut config,
-            options
-                .value_of_t_or_exit::<PathBuf>("path")
-                .canonicalize()?,
-        ),
+        Some(("add", options)) => {
+            let strict_paths = (config.strict_paths || options.is_present("strict-paths"))
+                && !options.is_present("force");
+
+            actions::add_directory(
+                &mut config,
+                options
+                    .value_of_t_or_exit::<PathBuf>("path")
+                    .canonicalize()?,
+                DirectorySettings {
+                    no_recurse: options.is_present("no-recurse"),
+                    read_only: options.is_present("read-only"),
+                    groups: options
+                        .values_of("group")
+                        .map(|values| values.map(String::from).collect())
+                        .unwrap_or_default(),
+                },
+                strict_paths,
+            )
+        }
+        Some(("adopt", options)) => {
+            let strict_paths = (config.strict_paths || options.is_present("strict-paths"))
+                && !options.is_present("force");
+
+            actions::adopt_directory(
+                &mut config,
+                &actions::AdoptOptions {
+                    path: options
+                        .value_of_t_or_exit::<PathBuf>("path")
+                        .canonicalize()?,
+                    strict_paths,
+                },
+            )
+        }
         Some(("rm", options)) => {
             // Clap sadly doesn't have custom parsers/transforms, so we need to rerun the validator
             // to get the result
-            let path = match_in_path_list(
-                &options.value_of_t_or_exit::<PathBuf>("path"),
+            let path = match_plugin_directory(
+                options.value_of("path").unwrap(),
                 &plugin_directories,
+                &numbered_plugin_directories,
             )
             .unwrap()
             .to_owned();
 
             actions::remove_directory(&mut config, &path)
         }
-        Some(("list", _)) => actions::list_directories(&config),
-        Some(("status", _)) => actions::show_status(&config),
-        Some(("sync", options)) => actions::do_sync(
-            &mut config,
-            &actions::SyncOptions {
-                force: options.is_present("force"),
-                no_verify: options.is_present("no-verify"),
-                prune: options.is_present("prune"),
+        Some(("move", options)) => {
+            let from = match_plugin_directory(
+                options.value_of("from").unwrap(),
+                &plugin_directories,
+                &numbered_plugin_directories,
+            )
+            .unwrap()
+            .to_owned();
+
+            actions::move_directory(
+                &mut config,
+                &actions::MoveOptions {
+                    from,
+                    to: options.value_of_t_or_exit::<PathBuf>("to").canonicalize()?,
+                    fix_links: options.is_present("fix-links"),
+                },
+            )
+        }
+        Some(("list", options)) => actions::list_directories(
+            &config,
+            &actions::ListOptions {
+                numbered: options.is_present("numbered"),
                 verbose: options.is_present("verbose"),
+                json: options.is_present("json"),
+            },
+        ),
+        Some(("status", options)) => {
+            config.vst2_location_override = vst2_location_override(options)?;
+            with_output(options, |out| {
+                actions::show_status(
+                    &config,
+                    &actions::StatusOptions {
+                        check_host_match: options.is_present("check-host-match"),
+                        group: options.value_of("group").map(String::from),
+                        exclude_dirs: exclude_dirs(&config, options),
+                        group_by: match options.value_of("group-by") {
+                            Some("method") => actions::StatusGrouping::Method,
+                            Some("arch") => actions::StatusGrouping::Arch,
+                            Some("kind") => actions::StatusGrouping::Kind,
+                            _ => actions::StatusGrouping::Directory,
+                        },
+                        include_hidden: options.is_present("include-hidden"),
+                        no_recurse: options.is_present("no-recurse"),
+                        no_cache: options.is_present("no-cache"),
+                        skip_system_dlls: options.is_present("skip-system-dlls"),
+                        sort: match options.value_of("sort") {
+                            Some("status") => actions::StatusSorting::Status,
+                            Some("mtime") => actions::StatusSorting::Mtime,
+                            _ => actions::StatusSorting::Name,
+                        },
+                        traversal: if options.is_present("breadth-first") {
+                            Traversal::BreadthFirst
+                        } else {
+                            Traversal::DepthFirst
+                        },
+                        time: options.is_present("time"),
+                        verbose: options.is_present("verbose"),
+                        json: options.is_present("json"),
+                        json_stream: options.is_present("json-stream"),
+                    },
+                    out,
+                )
+            })
+        }
+        Some(("diff", options)) => {
+            config.vst2_location_override = vst2_location_override(options)?;
+            with_output(options, |out| {
+                actions::show_diff(
+                    &config,
+                    &actions::DiffOptions {
+                        include_hidden: options.is_present("include-hidden"),
+                        no_recurse: options.is_present("no-recurse"),
+                        traversal: if options.is_present("breadth-first") {
+                            Traversal::BreadthFirst
+                        } else {
+                            Traversal::DepthFirst
+                        },
+                        json: options.is_present("json"),
+                    },
+                    out,
+                )
+            })
+        }
+        Some(("duplicates", options)) => actions::show_duplicates(
+            &config,
+            &actions::DuplicatesOptions {
+                include_hidden: options.is_present("include-hidden"),
+                no_recurse: options.is_present("no-recurse"),
+                json: options.is_present("json"),
+            },
+        ),
+        Some(("self-test", _)) => actions::self_test(&config),
+        Some(("loadable", options)) => actions::show_loadable(
+            &config,
+            &actions::LoadableOptions {
+                include_hidden: options.is_present("include-hidden"),
+                no_recurse: options.is_present("no-recurse"),
+                json: options.is_present("json"),
+            },
+        ),
+        Some(("query", options)) => actions::query(
+            &config,
+            &actions::QueryOptions {
+                plugin: options.value_of("plugin").unwrap(),
+                include_hidden: options.is_present("include-hidden"),
+                no_recurse: options.is_present("no-recurse"),
+                json: options.is_present("json"),
             },
         ),
+        Some(("doctor", options)) => {
+            let fix = |name: &str| match name {
+                "missing-dirs" => actions::DoctorFix::MissingDirectories,
+                "broken-symlinks" => actions::DoctorFix::BrokenSymlinks,
+                "permissions" => actions::DoctorFix::Permissions,
+                _ => actions::DoctorFix::SearchPath,
+            };
+            let mut fixes: BTreeSet<actions::DoctorFix> = options
+                .values_of("fix")
+                .map(|values| values.map(fix).collect())
+                .unwrap_or_default();
+            // `--fix` without a value enables every fix
+            if options.is_present("fix") && fixes.is_empty() {
+                fixes = [
+                    "missing-dirs",
+                    "broken-symlinks",
+                    "permissions",
+                    "search-path",
+                ]
+                .into_iter()
+                .map(fix)
+                .collect();
+            }
+
+            actions::doctor(
+                &mut config,
+                &actions::DoctorOptions {
+                    include_hidden: options.is_present("include-hidden"),
+                    no_recurse: options.is_present("no-recurse"),
+                    json: options.is_present("json"),
+                    report: options.value_of("report").map(Path::new),
+                    fixes,
+                },
+            )
+        }
+        Some(("stats", options)) => actions::show_stats(
+            &config,
+            &actions::StatsOptions {
+                include_hidden: options.is_present("include-hidden"),
+                no_recurse: options.is_present("no-recurse"),
+                json: options.is_present("json"),
+            },
+        ),
+        Some(("sync", options)) => {
+            config.vst2_location_override = vst2_location_override(options)?;
+            let emit_script = options.value_of("emit-script").map(PathBuf::from);
+            if emit_script.is_some() {
+                if !filesystem::is_dry_run() {
+                    anyhow::bail!(messages::emit_script_requires_dry_run());
+                }
+
+                filesystem::record_script();
+            }
+
+            let exclude_dirs = exclude_dirs(&config, options);
+            let result = actions::do_sync(
+                &mut config,
+                &actions::SyncOptions {
+                    fail_fast: options.is_present("fail-fast"),
+                    force: options.is_present("force"),
+                    ignore_errors_exit_zero: options.is_present("ignore-errors-exit-zero"),
+                    group: options.value_of("group").map(String::from),
+                    exclude_dirs,
+                    include_hidden: options.is_present("include-hidden"),
+                    install_missing_only: options.is_present("install-missing-only"),
+                    keep_first: options.is_present("keep-first"),
+                    no_recurse: options.is_present("no-recurse"),
+                    traversal: if options.is_present("breadth-first") {
+                        Traversal::BreadthFirst
+                    } else {
+                        Traversal::DepthFirst
+                    },
+                    device_concurrency: match options.value_of("concurrency-per-device") {
+                        Some("auto") => DeviceConcurrency::Auto,
+                        Some("serial") => DeviceConcurrency::Serial,
+                        Some("parallel") | None => DeviceConcurrency::Parallel,
+                        Some(s) => unreachable!("Unexpected concurrency mode '{}'", s),
+                    },
+                    min_size: options
+                        .value_of("min-size")
+                        .map(util::parse_size)
+                        .transpose()?,
+                    max_size: options
+                        .value_of("max-size")
+                        .map(util::parse_size)
+                        .transpose()?,
+                    max_plugins: options.value_of_t::<usize>("max-plugins").ok(),
+                    skip_system_dlls: options.is_present("skip-system-dlls"),
+                    skip_non_vst: options.is_present("skip-non-vst"),
+                    no_verify: options.is_present("no-verify"),
+                    no_arch_check: options.is_present("no-arch-check"),
+                    json: options.is_present("json"),
+                    preserve_mtime: options.is_present("preserve-mtime"),
+                    prune: options.is_present("prune"),
+                    jobs_report: options.is_present("jobs-report"),
+                    older_than: options
+                        .value_of("older-than")
+                        .map(util::parse_duration)
+                        .transpose()?,
+                    relative_symlinks: if options.is_present("relative") {
+                        Some(true)
+                    } else if options.is_present("absolute") {
+                        Some(false)
+                    } else {
+                        None
+                    },
+                    time: options.is_present("time"),
+                    verbose: options.is_present("verbose"),
+                },
+            );
+
+            // The script is also written when some plugins could not be set up, since it then
+            // contains the changes for all other plugins
+            if let Some(script_path) = &emit_script {
+                filesystem::write_script(script_path)?;
+                println!("{}", messages::wrote_script(script_path));
+            }
+
+            result
+        }
         Some(("set", options)) => actions::set_settings(
             &mut config,
             &actions::SetOptions {
@@ -281,8 +2073,116 @@ fn main() -> Result<()> {
                 path_auto: options.is_present("path_auto"),
                 vst2_location: options.value_of("vst2_location"),
                 no_verify: options.value_of("no_verify").map(|value| value == "true"),
+                include_hidden: options
+                    .value_of("include_hidden")
+                    .map(|value| value == "true"),
+                relative_symlinks: options
+                    .value_of("relative_symlinks")
+                    .map(|value| value == "true"),
+                relative_to: options.value_of("relative_to"),
+                vst2_target: options.value_of_t::<PathBuf>("vst2_target").ok(),
+                vst2_target_default: options.is_present("vst2_target_default"),
+                vst3_target: options.value_of_t::<PathBuf>("vst3_target").ok(),
+                vst3_target_default: options.is_present("vst3_target_default"),
+                subdirectory_template: options.value_of("subdirectory_template"),
+                subdirectory_template_default: options.is_present("subdirectory_template_default"),
+                default_excludes: options
+                    .values_of("default_exclude")
+                    .map(|values| values.map(String::from).collect())
+                    .unwrap_or_default(),
+                clear_default_excludes: options.is_present("clear_default_excludes"),
+                vst2_extensions: options
+                    .values_of("vst2_extension")
+                    .map(|values| values.map(String::from).collect())
+                    .unwrap_or_default(),
+                clear_vst2_extensions: options.is_present("clear_vst2_extensions"),
+                min_plugin_size: options
+                    .value_of("min_plugin_size")
+                    .map(util::parse_size)
+                    .transpose()?,
+                max_plugin_size: options
+                    .value_of("max_plugin_size")
+                    .map(util::parse_size)
+                    .transpose()?,
+                max_plugins: options.value_of_t::<usize>("max_plugins").ok(),
+                slow_directory_threshold: options
+                    .value_of("slow_directory_threshold")
+                    .map(util::parse_duration_secs)
+                    .transpose()?
+                    .map(|duration| duration.as_secs()),
+                skip_system_dlls: options
+                    .value_of("skip_system_dlls")
+                    .map(|value| value == "true"),
+                system_dlls: options
+                    .values_of("system_dll")
+                    .map(|values| values.map(String::from).collect())
+                    .unwrap_or_default(),
+                clear_system_dlls: options.is_present("clear_system_dlls"),
+                use_ignore_files: options
+                    .value_of("use_ignore_files")
+                    .map(|value| value == "true"),
+                shortcuts: options.value_of("shortcuts"),
+                daws: options
+                    .values_of("daw")
+                    .map(|values| values.collect())
+                    .unwrap_or_default(),
+                clear_daws: options.is_present("clear_daws"),
+                strict_paths: options
+                    .value_of("strict_paths")
+                    .map(|value| value == "true"),
+                allowed_bases: options
+                    .values_of("allowed_base")
+                    .map(|values| {
+                        values
+                            .map(|path| Path::new(path).canonicalize())
+                            .collect::<io::Result<_>>()
+                    })
+                    .transpose()?
+                    .unwrap_or_default(),
+                clear_allowed_bases: options.is_present("clear_allowed_bases"),
+                bridge_mode: options
+                    .value_of("bridge_mode")
+                    .map(util::parse_file_mode)
+                    .transpose()?,
+                overwrite_unrelated_files: options
+                    .value_of("overwrite_unrelated_files")
+                    .map(|value| value == "true"),
+                hashed_names: options
+                    .value_of("hashed_names")
+                    .map(|value| value == "true"),
             },
         ),
+        Some(("config", config_command)) => match config_command.subcommand() {
+            Some(("show", options)) => {
+                config.vst2_location_override = vst2_location_override(options)?;
+                with_output(options, |out| {
+                    actions::show_config(
+                        &config,
+                        &actions::ShowConfigOptions {
+                            json: options.is_present("json"),
+                        },
+                        out,
+                    )
+                })
+            }
+            Some(("diff", options)) => {
+                config.vst2_location_override = vst2_location_override(options)?;
+                actions::diff_config(
+                    &config,
+                    &actions::DiffConfigOptions {
+                        other_config: &options.value_of_t_or_exit::<PathBuf>("path"),
+                        json: options.is_present("json"),
+                    },
+                )
+            }
+            Some(("unset", options)) => {
+                actions::unset_setting(&mut config, options.value_of("setting").unwrap())
+            }
+            Some(("repair", options)) => actions::repair_config(&actions::RepairConfigOptions {
+                keep_missing: options.is_present("keep_missing"),
+            }),
+            _ => unreachable!(),
+        },
         Some(("blacklist", blacklist)) => match blacklist.subcommand() {
             Some(("add", options)) => actions::blacklist::add_path(
                 &mut config,
@@ -304,10 +2204,207 @@ fn main() -> Result<()> {
             Some(("clear", _)) => actions::blacklist::clear(&mut config),
             _ => unreachable!(),
         },
+        Some(("import", options)) => actions::import_config(&actions::ImportConfigOptions {
+            path: &options.value_of_t_or_exit::<PathBuf>("path"),
+            check: options.is_present("check"),
+        }),
+        Some(("note", options)) => actions::note(&actions::NoteOptions {
+            plugin: options
+                .value_of("plugin")
+                .map(|path| Path::new(path).canonicalize())
+                .transpose()?
+                .as_deref(),
+            note: options.value_of("note"),
+            remove: options.is_present("remove"),
+            list: options.is_present("list"),
+            json: options.is_present("json"),
+        }),
+        Some(("clean-cache", _)) => actions::clean_cache(),
+        Some(("dump-index", _)) => actions::dump_index(),
         _ => unreachable!(),
     }
 }
 
+/// The `--include-hidden` option for the commands that search the plugin directories.
+fn include_hidden_arg() -> Arg<'static> {
+    Arg::new("include-hidden")
+        .long("include-hidden")
+        .help("Also search hidden directories")
+}
+
+/// The `--no-recurse` option for the commands that search the plugin directories.
+fn no_recurse_arg() -> Arg<'static> {
+    Arg::new("no-recurse")
+        .long("no-recurse")
+        .help("Don't search for plugins in subdirectories")
+}
+
+/// The `--breadth-first` and `--depth-first` options for the commands that search the plugin
+/// directories.
+fn traversal_args() -> [Arg<'static>; 2] {
+    [
+        Arg::new("breadth-first")
+            .long("breadth-first")
+            .help("Search all files at one depth before descending further")
+            .long_help(
+                "Search all files at one depth before descending any further into the plugin \
+                 directories. Plugins close to the top of a plugin directory are then found before \
+                 descending into large subdirectories. The results are the same as with \
+                 '--depth-first'.",
+            ),
+        Arg::new("depth-first")
+            .long("depth-first")
+            .help("Search every subdirectory completely before the next (default)")
+            .conflicts_with("breadth-first"),
+    ]
+}
+
+/// The `--vst2-location` option for the commands that need to know where VST2 plugins are set up.
+/// This is parsed using `vst2_location_override()`.
+fn vst2_location_arg() -> Arg<'static> {
+    Arg::new("vst2-location")
+        .long("vst2-location")
+        .help("Override the VST2 location for this run")
+        .long_help(
+            "Override the VST2 location for this run without changing the config file. The \
+             'YABRIDGECTL_VST2_LOCATION' environment variable can be used for the same purpose, \
+             but this option takes precedence over that. See 'yabridgectl set --help' for more \
+             information on these locations.",
+        )
+        .possible_values(["centralized", "inline"])
+        .takes_value(true)
+}
+
+/// The `--strict-paths` option for `yabridgectl add` and `yabridgectl adopt`.
+fn strict_paths_arg() -> Arg<'static> {
+    Arg::new("strict-paths")
+        .long("strict-paths")
+        .help("Only add the directory if it's inside of a Wine prefix")
+        .long_help(
+            "Only add the directory if it's inside of a Wine prefix or inside of one of the \
+             directories added with 'yabridgectl set --allow-base'. Use 'yabridgectl set \
+             --strict-paths=true' to always do this.",
+        )
+}
+
+/// The `--output` option for the commands that print a report. `json_report` should be set when
+/// the report itself can also be printed as JSON using `--json`.
+fn output_arg(json_report: bool) -> Arg<'static> {
+    Arg::new("output")
+        .long("output")
+        .value_name("FILE")
+        .help("Write the report to a file instead of STDOUT")
+        .long_help(if json_report {
+            "Write the report to a file instead of STDOUT. The file is replaced atomically, and \
+             the report is written without colors. Combine this with '--json' to write the report \
+             as JSON. Errors are still printed to STDERR."
+        } else {
+            "Write the report to a file instead of STDOUT. The file is replaced atomically, and \
+             the report is written without colors. Errors are still printed to STDERR."
+        })
+        .takes_value(true)
+}
+
+/// Run a command that prints a report. If `--output <file>` was passed then the report is written
+/// to that file instead of to STDOUT. The file is only written once the entire report has been
+/// generated, and it's replaced atomically so a failed run never leaves behind a partial report.
+fn with_output(options: &ArgMatches, f: impl FnOnce(&mut dyn Write) -> Result<()>) -> Result<()> {
+    match options.value_of("output") {
+        Some(path) => {
+            // The escape codes would otherwise end up in the file
+            colored::control::set_override(false);
+
+            let mut report: Vec<u8> = Vec::new();
+            f(&mut report)?;
+            util::write_atomic(path, report)
+        }
+        None => f(&mut io::stdout().lock()),
+    }
+}
+
+/// Running yabridgectl through `sudo` creates bridges owned by root that the user's DAW can't use
+/// or replace. When running as root while the config directory or one of the plugin directories
+/// belongs to a regular user, this prints a warning, and commands that change something are
+/// refused unless `--allow-root` was passed. This doesn't apply to dry-runs. Returns whether
+/// yabridgectl may write files, which is not the case for the other commands when this warning was
+/// printed. The config file is only written after this check since it would otherwise end up being
+/// owned by root.
+fn check_root(config: &Config, mutating_command: bool, allow_root: bool) -> Result<bool> {
+    if unsafe { libc::geteuid() } != 0 {
+        return Ok(true);
+    }
+
+    let config_home = config::config_home()?;
+    let user_owned_path = iter::once(config_home.as_path())
+        .chain(config.plugin_dirs.iter().map(|path| path.as_path()))
+        .find(|path| fs::metadata(path).map_or(false, |metadata| metadata.uid() != 0));
+    let user_owned_path = match user_owned_path {
+        Some(path) => path,
+        None => return Ok(true),
+    };
+
+    eprintln!(
+        "{}",
+        util::wrap(&messages::running_as_root(user_owned_path))
+    );
+    if mutating_command && !allow_root && !filesystem::is_dry_run() {
+        anyhow::bail!(messages::refusing_as_root());
+    }
+
+    Ok(mutating_command)
+}
+
+/// Get the VST2 location override for `yabridgectl status` and `yabridgectl sync`. The
+/// `--vst2-location` option takes precedence over the `YABRIDGECTL_VST2_LOCATION` environment
+/// variable. If neither is set, then the location from the config file is used.
+fn vst2_location_override(options: &ArgMatches) -> Result<Option<Vst2InstallationLocation>> {
+    if let Some(location) = options.value_of("vst2-location") {
+        return location.parse().map(Some);
+    }
+
+    match env::var(VST2_LOCATION_ENV) {
+        Ok(location) => location
+            .parse()
+            .map(Some)
+            .with_context(|| messages::invalid_environment_variable(VST2_LOCATION_ENV)),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Get the paths passed to `--exclude-dir` for `yabridgectl status` and `yabridgectl sync`.
+/// Relative paths are made absolute, and a warning is printed for every path that doesn't match any
+/// of the plugin directories since that's most likely a typo.
+fn exclude_dirs(config: &Config, options: &ArgMatches) -> Vec<PathBuf> {
+    let exclude_dirs: Vec<PathBuf> = options
+        .values_of("exclude-dir")
+        .map(|values| {
+            values
+                .map(|path| {
+                    let path = Path::new(path);
+                    if path.is_absolute() {
+                        path.to_owned()
+                    } else {
+                        env::current_dir()
+                            .expect("Couldn't get current directory")
+                            .join(path)
+                    }
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    for path in &exclude_dirs {
+        if config
+            .excluded_plugin_dirs(std::slice::from_ref(path))
+            .is_empty()
+        {
+            eprintln!("{}", util::wrap(&messages::unknown_exclude_dir(path)));
+        }
+    }
+
+    exclude_dirs
+}
+
 /// Verify that a path exists and that is is either a directory or a symlink to a directory.
 fn validate_directory(path: &str) -> Result<(), String> {
     validate_path(path)?;
@@ -316,7 +2413,7 @@ fn validate_directory(path: &str) -> Result<(), String> {
     if path.is_dir() {
         Ok(())
     } else {
-        Err(format!("'{}' is not a directory", path.display()))
+        Err(messages::not_a_directory(path))
     }
 }
 
@@ -327,10 +2424,107 @@ fn validate_path(path: &str) -> Result<(), String> {
     if path.exists() {
         Ok(())
     } else {
-        Err(format!(
-            "File or directory '{}' could not be found",
-            path.display()
+        Err(messages::path_not_found(path))
+    }
+}
+
+/// Verify that a path is absolute. Used for paths that don't need to exist yet.
+fn validate_absolute_path(path: &str) -> Result<(), String> {
+    let path = Path::new(path);
+
+    if path.is_absolute() {
+        Ok(())
+    } else {
+        Err(messages::not_an_absolute_path(path))
+    }
+}
+
+/// Verify that a size can be parsed using `util::parse_size()`.
+fn validate_size(size: &str) -> Result<(), String> {
+    util::parse_size(size)
+        .map(|_| ())
+        .map_err(|err| format!("{:#}", err))
+}
+
+/// Verify that a limit passed to `--max-plugins` is a positive number.
+fn validate_plugin_count(count: &str) -> Result<(), String> {
+    match count.parse::<usize>() {
+        Ok(count) if count > 0 => Ok(()),
+        _ => Err(messages::not_a_positive_number(count)),
+    }
+}
+
+/// Verify that a file mode passed to `yabridgectl set --bridge-mode` can be used.
+fn validate_file_mode(mode: &str) -> Result<(), String> {
+    util::parse_file_mode(mode)
+        .map(|_| ())
+        .map_err(|err| format!("{:#}", err))
+}
+
+/// Verify that a duration passed to `yabridgectl sync --older-than` can be parsed.
+fn validate_duration(duration: &str) -> Result<(), String> {
+    util::parse_duration(duration)
+        .map(|_| ())
+        .map_err(|err| format!("{:#}", err))
+}
+
+/// Verify that a duration passed to `yabridgectl sync --slow-directory-threshold` can be parsed.
+fn validate_duration_secs(duration: &str) -> Result<(), String> {
+    util::parse_duration_secs(duration)
+        .map(|_| ())
+        .map_err(|err| format!("{:#}", err))
+}
+
+/// Verify that an extension passed to `yabridgectl set --add-extension` can be used for VST2
+/// plugins. The extensions used for other kinds of files can't be reused.
+fn validate_vst2_extension(extension: &str) -> Result<(), String> {
+    let normalized = extension.trim_start_matches('.').to_ascii_lowercase();
+    if normalized.is_empty() || normalized.contains(['/', '.']) {
+        Err(messages::invalid_extension(extension))
+    } else if ["dll", "vst3", "clap", "lnk", "so"].contains(&normalized.as_str()) {
+        Err(messages::reserved_extension(&normalized))
+    } else {
+        Ok(())
+    }
+}
+
+/// Verify that a subdirectory template only contains known placeholders.
+fn validate_subdirectory_template(template: &str) -> Result<(), String> {
+    let mut remaining = template.to_owned();
+    for placeholder in files::SUBDIRECTORY_TEMPLATE_PLACEHOLDERS {
+        remaining = remaining.replace(placeholder, "");
+    }
+
+    if remaining.contains('{') || remaining.contains('}') {
+        Err(messages::unknown_placeholder(
+            template,
+            &files::SUBDIRECTORY_TEMPLATE_PLACEHOLDERS,
         ))
+    } else {
+        Ok(())
+    }
+}
+
+/// Find a plugin directory for `yabridgectl rm`. This can either be a path, which is matched using
+/// `match_in_path_list()`, or a number from `yabridgectl list --numbered`. Paths take precedence
+/// so a directory that's literally called `1` can still be removed.
+fn match_plugin_directory<'a>(
+    path_or_number: &str,
+    candidates: &'a HashSet<&Path>,
+    numbered_candidates: &[&'a Path],
+) -> Result<&'a Path, String> {
+    let path_error = match match_in_path_list(Path::new(path_or_number), candidates) {
+        Ok(path) => return Ok(path),
+        Err(err) => err,
+    };
+
+    match path_or_number.parse::<usize>() {
+        Ok(number) => number
+            .checked_sub(1)
+            .and_then(|idx| numbered_candidates.get(idx))
+            .copied()
+            .ok_or_else(|| messages::unknown_directory_number(number, numbered_candidates.len())),
+        Err(_) => Err(path_error),
     }
 }
 
@@ -375,9 +2569,5 @@ fn match_in_path_list<'a>(path: &Path, candidates: &'a HashSet<&Path>) -> Result
         return Ok(path);
     }
 
-    Err(format!(
-        "'{}' is not a known path.\n\n\tPossible options are: {}",
-        path.display(),
-        format!("{:?}", candidates).green()
-    ))
+    Err(messages::unknown_path(path, candidates))
 }
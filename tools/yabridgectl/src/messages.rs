@@ -0,0 +1,291 @@
+// yabridge: a Wine plugin bridge
+// Copyright (C) 2020-2022 Robbert van der Helm
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! The messages printed by `main.rs`. These are looked up by key in a single catalog so their
+//! wording stays consistent, and so translations can be added later without touching the code that
+//! prints them. Only English messages are included for now. The help texts are defined alongside
+//! the command line arguments in `main.rs`.
+
+use colored::Colorize;
+use std::fmt::Display;
+use std::path::Path;
+
+/// The English messages, indexed by their key. Named placeholders like `{path}` are filled in by
+/// [`format()`].
+const ENGLISH: &[(&str, &str)] = &[
+    ("error", "Error: {error}"),
+    ("warning", "Warning: {error}"),
+    ("warning-label", "WARNING"),
+    (
+        "emit-script-requires-dry-run",
+        "'--emit-script' can only be used together with '--dry-run'",
+    ),
+    ("wrote-script", "Wrote the changes to '{path}'"),
+    (
+        "running-as-root",
+        "{warning}: yabridgectl is running as root, but '{path}' belongs to a regular user. Files \
+         created as root can't be used or replaced by your DAW. Run yabridgectl without 'sudo' \
+         instead.\n",
+    ),
+    (
+        "refusing-as-root",
+        "Refusing to make changes as root, pass '--allow-root' to do so anyway",
+    ),
+    ("could-not-resolve", "Could not resolve '{path}'"),
+    (
+        "invalid-environment-variable",
+        "Invalid value for ${variable}",
+    ),
+    (
+        "unknown-exclude-dir",
+        "{warning}: '{path}' passed to '--exclude-dir' is not one of the plugin directories\n",
+    ),
+    ("not-a-directory", "'{path}' is not a directory"),
+    (
+        "path-not-found",
+        "File or directory '{path}' could not be found",
+    ),
+    ("not-an-absolute-path", "'{path}' is not an absolute path"),
+    (
+        "not-a-positive-number",
+        "'{value}' is not a positive number",
+    ),
+    (
+        "invalid-extension",
+        "'{extension}' is not a valid file extension",
+    ),
+    (
+        "reserved-extension",
+        "'.{extension}' files can't be used as VST2 plugins",
+    ),
+    (
+        "unknown-placeholder",
+        "'{template}' contains an unknown placeholder, the supported placeholders are \
+         {placeholders}",
+    ),
+    (
+        "unknown-directory-number",
+        "There is no plugin directory with number {number}, the valid numbers are 1 through \
+         {count}. Run 'yabridgectl list --numbered' to see all directories with their numbers.",
+    ),
+    (
+        "unknown-path",
+        "'{path}' is not a known path.\n\n\tPossible options are: {candidates}",
+    ),
+];
+
+/// Look up the message for `key` and replace its named placeholders with the values in `args`.
+/// Placeholders without a value are left as is. This panics if `key` is not in the catalog, which
+/// is checked for every message in the tests.
+fn format(key: &str, args: &[(&str, &dyn Display)]) -> String {
+    let template = ENGLISH
+        .iter()
+        .find(|(message_key, _)| *message_key == key)
+        .map(|(_, template)| *template)
+        .unwrap_or_else(|| panic!("Unknown message key '{key}'"));
+
+    // This is done in a single pass so values containing braces are left alone
+    let mut message = String::with_capacity(template.len());
+    let mut remaining = template;
+    while let Some(start) = remaining.find('{') {
+        message.push_str(&remaining[..start]);
+        remaining = &remaining[start..];
+
+        let value = remaining.find('}').and_then(|end| {
+            args.iter()
+                .find(|(name, _)| *name == &remaining[1..end])
+                .map(|(_, value)| (end, value))
+        });
+        match value {
+            Some((end, value)) => {
+                message.push_str(&value.to_string());
+                remaining = &remaining[end + 1..];
+            }
+            None => {
+                message.push('{');
+                remaining = &remaining[1..];
+            }
+        }
+    }
+    message.push_str(remaining);
+
+    message
+}
+
+/// The `WARNING` label printed in front of warnings.
+fn warning_label() -> impl Display {
+    format("warning-label", &[]).red()
+}
+
+/// An error that caused yabridgectl to exit.
+pub fn error(err: &anyhow::Error) -> String {
+    format("error", &[("error", &format_args!("{err:?}"))])
+}
+
+/// An error that was ignored, like the config file not being readable during `yabridgectl config
+/// repair`.
+pub fn warning(err: &anyhow::Error) -> String {
+    format("warning", &[("error", &format_args!("{err:#}"))])
+}
+
+pub fn emit_script_requires_dry_run() -> String {
+    format("emit-script-requires-dry-run", &[])
+}
+
+pub fn wrote_script(path: &Path) -> String {
+    format("wrote-script", &[("path", &path.display())])
+}
+
+/// The warning printed when running as root while the config or plugin directories belong to a
+/// regular user. This still needs to be wrapped.
+pub fn running_as_root(user_owned_path: &Path) -> String {
+    format(
+        "running-as-root",
+        &[
+            ("warning", &warning_label()),
+            ("path", &user_owned_path.display()),
+        ],
+    )
+}
+
+pub fn refusing_as_root() -> String {
+    format("refusing-as-root", &[])
+}
+
+pub fn could_not_resolve(path: &str) -> String {
+    format("could-not-resolve", &[("path", &path)])
+}
+
+pub fn invalid_environment_variable(variable: &str) -> String {
+    format("invalid-environment-variable", &[("variable", &variable)])
+}
+
+/// The warning printed for `--exclude-dir` paths that aren't plugin directories. This still needs
+/// to be wrapped.
+pub fn unknown_exclude_dir(path: &Path) -> String {
+    format(
+        "unknown-exclude-dir",
+        &[("warning", &warning_label()), ("path", &path.display())],
+    )
+}
+
+pub fn not_a_directory(path: &Path) -> String {
+    format("not-a-directory", &[("path", &path.display())])
+}
+
+pub fn path_not_found(path: &Path) -> String {
+    format("path-not-found", &[("path", &path.display())])
+}
+
+pub fn not_an_absolute_path(path: &Path) -> String {
+    format("not-an-absolute-path", &[("path", &path.display())])
+}
+
+pub fn not_a_positive_number(value: &str) -> String {
+    format("not-a-positive-number", &[("value", &value)])
+}
+
+pub fn invalid_extension(extension: &str) -> String {
+    format("invalid-extension", &[("extension", &extension)])
+}
+
+/// `extension` should be normalized, without the leading period.
+pub fn reserved_extension(extension: &str) -> String {
+    format("reserved-extension", &[("extension", &extension)])
+}
+
+pub fn unknown_placeholder(template: &str, placeholders: &[&str]) -> String {
+    format(
+        "unknown-placeholder",
+        &[
+            ("template", &template),
+            ("placeholders", &placeholders.join(", ")),
+        ],
+    )
+}
+
+pub fn unknown_directory_number(number: usize, count: usize) -> String {
+    format(
+        "unknown-directory-number",
+        &[("number", &number), ("count", &count)],
+    )
+}
+
+pub fn unknown_path(path: &Path, candidates: &dyn std::fmt::Debug) -> String {
+    format(
+        "unknown-path",
+        &[
+            ("path", &path.display()),
+            ("candidates", &format!("{candidates:?}").green()),
+        ],
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn every_message_resolves() {
+        let path = Path::new("/path");
+        let err = anyhow::anyhow!("Something went wrong");
+        let messages = [
+            error(&err),
+            warning(&err),
+            emit_script_requires_dry_run(),
+            wrote_script(path),
+            running_as_root(path),
+            refusing_as_root(),
+            could_not_resolve("path"),
+            invalid_environment_variable("VARIABLE"),
+            unknown_exclude_dir(path),
+            not_a_directory(path),
+            path_not_found(path),
+            not_an_absolute_path(path),
+            not_a_positive_number("0"),
+            invalid_extension("."),
+            reserved_extension("dll"),
+            unknown_placeholder("{foo}", &["{bar}"]),
+            unknown_directory_number(2, 1),
+            unknown_path(path, &[path]),
+        ];
+
+        // Every message is used exactly once above, and every placeholder has been replaced
+        assert_eq!(messages.len() + 1, ENGLISH.len());
+        for message in messages {
+            assert!(!message.is_empty());
+            assert!(
+                !message
+                    .replace("{foo}", "")
+                    .replace("{bar}", "")
+                    .contains('{'),
+                "{message}"
+            );
+        }
+
+        let keys: HashSet<&str> = ENGLISH.iter().map(|(key, _)| *key).collect();
+        assert_eq!(keys.len(), ENGLISH.len());
+    }
+
+    #[test]
+    fn placeholders_in_values_are_not_replaced() {
+        assert_eq!(
+            not_a_directory(Path::new("/{path}")),
+            "'/{path}' is not a directory"
+        );
+    }
+}
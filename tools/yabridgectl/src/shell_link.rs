@@ -0,0 +1,226 @@
+// yabridge: a Wine plugin bridge
+// Copyright (C) 2020-2022 Robbert van der Helm
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! A minimal parser for Windows `.lnk` shortcuts, as described in the [MS-SHLLINK
+//! specification](https://learn.microsoft.com/en-us/openspecs/windows_protocols/ms-shllink/).
+//! Only the parts needed to find the shortcut's target file are parsed.
+
+use anyhow::{anyhow, bail, Context, Result};
+use std::path::{Path, PathBuf};
+
+use crate::util;
+
+/// The size of the fixed shell link header. This is also the header's first field.
+const HEADER_SIZE: u32 = 0x4C;
+/// The CLSID every shell link header starts with, `00021401-0000-0000-C000-000000000046`.
+const LINK_CLSID: [u8; 16] = [
+    0x01, 0x14, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0xC0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x46,
+];
+
+// These are the relevant `LinkFlags` from the shell link header
+const HAS_LINK_TARGET_ID_LIST: u32 = 0x1;
+const HAS_LINK_INFO: u32 = 0x2;
+const HAS_NAME: u32 = 0x4;
+const HAS_RELATIVE_PATH: u32 = 0x8;
+const IS_UNICODE: u32 = 0x80;
+
+/// The `LinkInfoFlags` flag that indicates that the link info contains a local base path.
+const VOLUME_ID_AND_LOCAL_BASE_PATH: u32 = 0x1;
+
+/// The target of a shortcut, as stored in the shortcut. These are Windows paths.
+#[derive(Debug, Default)]
+pub struct ShellLink {
+    /// The absolute path to the target, like `C:\Program Files\Plugin\Plugin.dll`.
+    pub local_path: Option<String>,
+    /// The path to the target relative to the shortcut file.
+    pub relative_path: Option<String>,
+}
+
+/// Parse a `.lnk` file and return the absolute Unix path to the file it points to. Drive letters
+/// are resolved through the `dosdevices` directory of the Wine prefix containing the shortcut. If
+/// the shortcut isn't inside of a Wine prefix, then the shortcut's relative path is used instead.
+///
+/// This does not check whether the target exists.
+pub fn resolve_shell_link(path: &Path) -> Result<PathBuf> {
+    let bytes = util::read(path)?;
+    let link = parse_shell_link(&bytes)
+        .with_context(|| format!("Could not parse '{}' as a shortcut", path.display()))?;
+
+    if let Some(target) = link
+        .local_path
+        .as_deref()
+        .and_then(|local_path| translate_windows_path(path, local_path))
+    {
+        return Ok(target);
+    }
+
+    match (&link.relative_path, path.parent()) {
+        (Some(relative_path), Some(directory)) => {
+            Ok(directory.join(relative_path.replace('\\', "/")))
+        }
+        _ => Err(anyhow!(
+            "'{}' does not point to a file in a Wine prefix",
+            path.display()
+        )),
+    }
+}
+
+/// Parse the target paths from the contents of a `.lnk` file.
+pub fn parse_shell_link(bytes: &[u8]) -> Result<ShellLink> {
+    if read_u32(bytes, 0)? != HEADER_SIZE || bytes.get(4..20) != Some(&LINK_CLSID) {
+        bail!("Missing shell link header");
+    }
+
+    let link_flags = read_u32(bytes, 0x14)?;
+    let mut pos = HEADER_SIZE as usize;
+    let mut link = ShellLink::default();
+
+    // The item ID list is not needed since the same information is also stored in the link info
+    if link_flags & HAS_LINK_TARGET_ID_LIST != 0 {
+        pos += 2 + read_u16(bytes, pos)? as usize;
+    }
+
+    if link_flags & HAS_LINK_INFO != 0 {
+        let link_info_size = read_u32(bytes, pos)? as usize;
+        let link_info_header_size = read_u32(bytes, pos + 4)?;
+        let link_info_flags = read_u32(bytes, pos + 8)?;
+
+        if link_info_flags & VOLUME_ID_AND_LOCAL_BASE_PATH != 0 {
+            // Newer shortcuts can also store the paths as UTF-16, which should be preferred
+            let unicode_offsets = if link_info_header_size >= 0x24 {
+                Some((read_u32(bytes, pos + 28)?, read_u32(bytes, pos + 32)?))
+            } else {
+                None
+            };
+            let (base_path, path_suffix) = match unicode_offsets {
+                Some((base_offset, suffix_offset)) if base_offset != 0 => (
+                    read_utf16_cstr(bytes, pos + base_offset as usize)?,
+                    read_utf16_cstr(bytes, pos + suffix_offset as usize)?,
+                ),
+                _ => (
+                    read_ansi_cstr(bytes, pos + read_u32(bytes, pos + 16)? as usize)?,
+                    read_ansi_cstr(bytes, pos + read_u32(bytes, pos + 24)? as usize)?,
+                ),
+            };
+
+            link.local_path = Some(base_path + &path_suffix);
+        }
+
+        pos += link_info_size;
+    }
+
+    // The string data section contains the relative path right after the optional description
+    let is_unicode = link_flags & IS_UNICODE != 0;
+    if link_flags & HAS_NAME != 0 {
+        let (_, size) = read_string_data(bytes, pos, is_unicode)?;
+        pos += size;
+    }
+    if link_flags & HAS_RELATIVE_PATH != 0 {
+        let (relative_path, _) = read_string_data(bytes, pos, is_unicode)?;
+        link.relative_path = Some(relative_path);
+    }
+
+    Ok(link)
+}
+
+/// Translate a path like `C:\Program Files\Plugin.dll` to a path in the Wine prefix containing
+/// `shortcut`. Returns `None` if this is not a path with a drive letter or if the shortcut is not
+/// inside of a Wine prefix.
+fn translate_windows_path(shortcut: &Path, windows_path: &str) -> Option<PathBuf> {
+    let (drive, rest) = windows_path.split_once(':')?;
+    if drive.len() != 1 {
+        return None;
+    }
+
+    let prefix = shortcut
+        .ancestors()
+        .find(|directory| directory.join("dosdevices").is_dir())?;
+    let mut path = prefix
+        .join("dosdevices")
+        .join(format!("{}:", drive.to_lowercase()));
+    for component in rest.split('\\').filter(|component| !component.is_empty()) {
+        path.push(component);
+    }
+
+    Some(path)
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> Result<u16> {
+    bytes
+        .get(offset..offset + 2)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]))
+        .ok_or_else(|| anyhow!("Unexpected end of file at offset {offset}"))
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32> {
+    bytes
+        .get(offset..offset + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| anyhow!("Unexpected end of file at offset {offset}"))
+}
+
+/// Read a null terminated string in the system code page. We'll just assume that's Latin-1.
+fn read_ansi_cstr(bytes: &[u8], offset: usize) -> Result<String> {
+    let string = bytes
+        .get(offset..)
+        .ok_or_else(|| anyhow!("Unexpected end of file at offset {offset}"))?;
+    let length = string
+        .iter()
+        .position(|&c| c == 0)
+        .ok_or_else(|| anyhow!("Unterminated string at offset {offset}"))?;
+
+    Ok(string[..length].iter().map(|&c| c as char).collect())
+}
+
+/// Read a null terminated UTF-16 string.
+fn read_utf16_cstr(bytes: &[u8], offset: usize) -> Result<String> {
+    let mut units = Vec::new();
+    let mut pos = offset;
+    loop {
+        match read_u16(bytes, pos)? {
+            0 => break,
+            unit => units.push(unit),
+        }
+        pos += 2;
+    }
+
+    String::from_utf16(&units).with_context(|| format!("Invalid UTF-16 at offset {offset}"))
+}
+
+/// Read a string from the string data section, which is prefixed by its length in characters.
+/// Returns the string and the total number of bytes it took up.
+fn read_string_data(bytes: &[u8], offset: usize, is_unicode: bool) -> Result<(String, usize)> {
+    let num_chars = read_u16(bytes, offset)? as usize;
+    let start = offset + 2;
+    if is_unicode {
+        let units = (0..num_chars)
+            .map(|i| read_u16(bytes, start + (i * 2)))
+            .collect::<Result<Vec<u16>>>()?;
+        let string = String::from_utf16(&units)
+            .with_context(|| format!("Invalid UTF-16 at offset {start}"))?;
+
+        Ok((string, 2 + (num_chars * 2)))
+    } else {
+        let string = bytes
+            .get(start..start + num_chars)
+            .ok_or_else(|| anyhow!("Unexpected end of file at offset {start}"))?
+            .iter()
+            .map(|&c| c as char)
+            .collect();
+
+        Ok((string, 2 + num_chars))
+    }
+}
@@ -19,19 +19,29 @@
 use anyhow::{anyhow, Context, Result};
 use colored::Colorize;
 use is_executable::IsExecutable;
+use std::cmp::Ordering;
 use std::collections::hash_map::DefaultHasher;
 use std::env;
 use std::fs;
 use std::hash::Hasher;
-use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
-use std::os::unix::fs as unix_fs;
+use std::io::{self, BufRead, BufReader, IsTerminal, Read, Seek, SeekFrom};
+use std::os::unix::fs::MetadataExt;
 use std::os::unix::process::CommandExt;
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
 use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering as AtomicOrdering};
+use std::time::Duration;
 use textwrap::Wrapper;
 
 use crate::config::{self, Config, KnownConfig, YABRIDGE_HOST_32_EXE_NAME, YABRIDGE_HOST_EXE_NAME};
+use crate::error;
 use crate::files::{LibArchitecture, NativeFile};
+use crate::filesystem;
+
+/// Set through the global `--assume-yes` option. See [`confirm()`].
+static ASSUME_YES: AtomicBool = AtomicBool::new(false);
+/// Set by the Ctrl+C handler installed through [`handle_interrupts()`].
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
 
 /// (Part of) the expected output when running `yabridge-host.exe`. Used to verify that everything's
 /// working correctly. We'll only match this prefix so we can modify the exact output at a later
@@ -44,24 +54,13 @@ const LIBDBUS_FALLBACK_NAME: &str = "libdbus-1.so";
 /// Wrapper around [`reflink::reflink_or_copy()`](reflink::reflink_or_copy) with a human readable
 /// error message.
 pub fn copy_or_reflink<P: AsRef<Path>, Q: AsRef<Path>>(from: P, to: Q) -> Result<Option<u64>> {
-    reflink::reflink_or_copy(&from, &to).with_context(|| {
-        format!(
-            "Error reflinking '{}' to '{}'",
-            from.as_ref().display(),
-            to.as_ref().display()
-        )
-    })
+    filesystem::current().copy(from.as_ref(), to.as_ref())
 }
 
 /// Wrapper around [`std::fs::create_dir_all()`](std::fs::create_dir_all) with a human readable
 /// error message.
 pub fn create_dir_all<P: AsRef<Path>>(path: P) -> Result<()> {
-    fs::create_dir_all(&path).with_context(|| {
-        format!(
-            "Error creating directories for '{}'",
-            path.as_ref().display(),
-        )
-    })
+    filesystem::current().create_dir_all(path.as_ref())
 }
 
 /// Wrapper around [`std::fs::read()`](std::fs::read) with a human readable error message.
@@ -76,36 +75,45 @@ pub fn read_to_string<P: AsRef<Path>>(path: P) -> Result<String> {
         .with_context(|| format!("Could not read file '{}'", path.as_ref().display()))
 }
 
+/// Wrapper around [`std::fs::remove_dir()`](std::fs::remove_dir) with a human readable error
+/// message.
+pub fn remove_dir<P: AsRef<Path>>(path: P) -> Result<()> {
+    filesystem::current().remove_dir(path.as_ref())
+}
+
 /// Wrapper around [`std::fs::remove_dir_all()`](std::fs::remove_dir_all) with a human readable
 /// error message.
 pub fn remove_dir_all<P: AsRef<Path>>(path: P) -> Result<()> {
-    fs::remove_dir_all(&path)
-        .with_context(|| format!("Could not remove directory '{}'", path.as_ref().display()))
+    filesystem::current().remove_dir_all(path.as_ref())
 }
 
 /// Wrapper around [`std::fs::remove_file()`](std::fs::remove_file) with a human readable error
 /// message.
 pub fn remove_file<P: AsRef<Path>>(path: P) -> Result<()> {
-    fs::remove_file(&path)
-        .with_context(|| format!("Could not remove '{}'", path.as_ref().display()))
+    filesystem::current().remove_file(path.as_ref())
+}
+
+/// Wrapper around [`std::fs::set_permissions()`] that sets `path`'s permissions to the octal
+/// `mode`, with a human readable error message.
+pub fn set_permissions<P: AsRef<Path>>(path: P, mode: u32) -> Result<()> {
+    filesystem::current().set_permissions(path.as_ref(), mode)
 }
 
 /// Wrapper around [`std::os::unix::fs::symlink()`](std::os::unix::fs::symlink) with a human
 /// readable error message.
 pub fn symlink<P: AsRef<Path>, Q: AsRef<Path>>(src: P, dst: Q) -> Result<()> {
-    unix_fs::symlink(&src, &dst).with_context(|| {
-        format!(
-            "Error symlinking '{}' to '{}'",
-            src.as_ref().display(),
-            dst.as_ref().display()
-        )
-    })
+    filesystem::current().symlink(src.as_ref(), dst.as_ref())
 }
 
 /// Wrapper around [`std::fs::write()`](std::fs::write) with a human readable error message.
 pub fn write<P: AsRef<Path>, C: AsRef<[u8]>>(path: P, contents: C) -> Result<()> {
-    fs::write(&path, contents)
-        .with_context(|| format!("Could write to '{}'", path.as_ref().display()))
+    filesystem::current().write(path.as_ref(), contents.as_ref())
+}
+
+/// Write `contents` to `path` atomically, so a crash or a full disk can never leave behind a half
+/// written file. If `path` already exists, then its permissions are preserved.
+pub fn write_atomic<P: AsRef<Path>, C: AsRef<[u8]>>(path: P, contents: C) -> Result<()> {
+    filesystem::current().write_atomic(path.as_ref(), contents.as_ref())
 }
 
 /// Get the architecture of the ELF file at `path`. This detection is a bit naive, but we'd rather
@@ -156,12 +164,32 @@ pub fn get_file_type(path: PathBuf) -> Option<NativeFile> {
     }
 }
 
-/// Get the architecture (either 64-bit or 32-bit) of the default Wine prefix in `~/.wine`. Defaults
-/// to 64-bit if `~/.wine` doesn't exist or if the prefix is invalid.
-pub fn get_default_wine_prefix_arch() -> LibArchitecture {
-    let wine_system_reg_path = PathBuf::from(env::var("HOME").expect("$HOME is not set"))
-        .join(".wine")
-        .join("system.reg");
+/// Get the Wine prefix yabridgectl operates on. This is `$WINEPREFIX` if it is set, which is also
+/// how the global `--prefix` option is implemented, and the default `~/.wine` prefix otherwise.
+pub fn wine_prefix() -> PathBuf {
+    match env::var_os("WINEPREFIX") {
+        Some(prefix) if !prefix.is_empty() => PathBuf::from(prefix),
+        _ => PathBuf::from(env::var("HOME").expect("$HOME is not set")).join(".wine"),
+    }
+}
+
+/// Check whether `path` is a Wine prefix or a directory inside of one. This is the case when `path`
+/// is inside of the prefix returned by [`wine_prefix()`], when it's inside of a `drive_c`
+/// directory, or when it contains a `drive_c` directory itself. `path` should be absolute.
+pub fn is_in_wine_prefix(path: &Path) -> bool {
+    let path = normalize_path(path);
+
+    path.starts_with(normalize_path(&wine_prefix()))
+        || path
+            .ancestors()
+            .any(|ancestor| ancestor.file_name() == Some("drive_c".as_ref()))
+        || path.join("drive_c").is_dir()
+}
+
+/// Get the architecture (either 64-bit or 32-bit) of the Wine prefix returned by [`wine_prefix()`].
+/// Defaults to 64-bit if the prefix doesn't exist or if the prefix is invalid.
+pub fn get_wine_prefix_arch() -> LibArchitecture {
+    let wine_system_reg_path = wine_prefix().join("system.reg");
 
     // Fall back to 64-bit if the prefix doesn't exist
     let wine_system_reg = match fs::File::open(wine_system_reg_path) {
@@ -191,13 +219,34 @@ pub fn get_default_wine_prefix_arch() -> LibArchitecture {
 /// We convert the hash to an i64 because the TOML library can't deserialize large u64 values since
 /// it uses i64s internally.
 pub fn hash_file(file: &Path) -> Result<i64> {
+    Ok(hash_contents(&fs::read(file).with_context(|| {
+        format!("Could not read contents of '{}'", file.display())
+    })?))
+}
+
+/// Hash a file's contents the same way [`hash_file()`] does. Used when the file has been read
+/// through a [`FileSystem`](crate::filesystem::FileSystem) backend.
+pub fn hash_contents(contents: &[u8]) -> i64 {
     let mut hasher = DefaultHasher::new();
-    hasher.write(
-        &fs::read(file)
-            .with_context(|| format!("Could not read contents of '{}'", file.display()))?,
-    );
+    hasher.write(contents);
 
-    Ok(hasher.finish() as i64)
+    hasher.finish() as i64
+}
+
+/// Hash the contents of one or more files using 32-bit FNV-1a. Unlike [`hash_file()`], the result
+/// is guaranteed to be the same for every build of yabridgectl, so this can be used for hashes that
+/// end up in file names.
+pub fn stable_hash<'a>(contents: impl IntoIterator<Item = &'a [u8]>) -> u32 {
+    const FNV_OFFSET_BASIS: u32 = 0x811c9dc5;
+    const FNV_PRIME: u32 = 0x01000193;
+
+    let mut hash = FNV_OFFSET_BASIS;
+    for byte in contents.into_iter().flatten() {
+        hash ^= *byte as u32;
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+
+    hash
 }
 
 /// Resolve symlinks in a path, like the `realpath` coreutil, but don't throw any errors of `path`
@@ -216,6 +265,303 @@ pub fn normalize_path(path: &Path) -> PathBuf {
     path.to_owned()
 }
 
+/// Compute a relative path from `directory` to `path`, such that `directory.join(result)` refers to
+/// `path`. Both paths are normalized first using `normalize_path()` so symlinked directories don't
+/// result in broken relative paths. Used to create relative symlinks.
+pub fn relative_path(path: &Path, directory: &Path) -> PathBuf {
+    let path = normalize_path(path);
+    let directory = normalize_path(directory);
+
+    let mut path_components = path.components().peekable();
+    let mut directory_components = directory.components().peekable();
+    while let (Some(a), Some(b)) = (path_components.peek(), directory_components.peek()) {
+        if a != b {
+            break;
+        }
+
+        path_components.next();
+        directory_components.next();
+    }
+
+    directory_components
+        .map(|_| Component::ParentDir)
+        .chain(path_components)
+        .collect()
+}
+
+/// Compare two paths for sorting them in a case-insensitive way. Paths that only differ in their
+/// casing are ordered by their exact bytes so the ordering is always total and deterministic.
+///
+/// All plugin directories and the plugins within them are sorted using this order before they're
+/// returned from `Config::search_directories()` and `SearchResults::installation_status()`, so the
+/// output of yabridgectl doesn't depend on the order in which the filesystem returns its entries.
+pub fn compare_paths(a: &Path, b: &Path) -> Ordering {
+    a.to_string_lossy()
+        .to_lowercase()
+        .cmp(&b.to_string_lossy().to_lowercase())
+        .then_with(|| a.cmp(b))
+}
+
+/// Parse a human readable file size like `512`, `800KB`, `2MB`, or `1.5GiB`. The suffixes are
+/// case-insensitive, and both the decimal looking and the binary suffixes are powers of 1024 since
+/// that's what file managers usually show.
+pub fn parse_size(size: &str) -> Result<u64> {
+    let trimmed = size.trim();
+    let number_len = trimmed
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(trimmed.len());
+    let (number, suffix) = trimmed.split_at(number_len);
+    let multiplier: u64 = match suffix.trim().to_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" | "kib" => 1024,
+        "m" | "mb" | "mib" => 1024 * 1024,
+        "g" | "gb" | "gib" => 1024 * 1024 * 1024,
+        _ => anyhow::bail!(
+            "'{}' is not a valid size, expected a number followed by B, KB, MB, or GB",
+            size
+        ),
+    };
+    let number: f64 = number
+        .parse()
+        .with_context(|| format!("'{}' is not a valid size", size))?;
+
+    Ok((number * multiplier as f64).round() as u64)
+}
+
+/// Parse an octal file mode like `0644` or `664` for the `bridge_mode` setting. Only the permission
+/// bits can be set, and the owner always needs to be able to read and write the file so yabridgectl
+/// can still update it.
+pub fn parse_file_mode(mode: &str) -> Result<u32> {
+    let digits = mode.trim();
+    let digits = digits.strip_prefix("0o").unwrap_or(digits);
+    let parsed = u32::from_str_radix(digits, 8)
+        .with_context(|| format!("'{}' is not a valid octal file mode", mode))?;
+    if parsed > 0o777 {
+        anyhow::bail!(
+            "'{}' is not a valid file mode, only the permission bits up to 0777 can be set",
+            mode
+        );
+    }
+    if parsed & 0o600 != 0o600 {
+        anyhow::bail!(
+            "'{}' would prevent yabridgectl from updating the bridges, the owner needs to be able \
+             to read and write them",
+            mode
+        );
+    }
+
+    Ok(parsed)
+}
+
+/// Parse a human readable duration like `90d`, `2w`, `12h`, or `30m`. A number without a suffix is
+/// a number of days.
+pub fn parse_duration(duration: &str) -> Result<Duration> {
+    parse_duration_with_default_unit(duration, 60 * 60 * 24)
+}
+
+/// The same as [`parse_duration()`], but a number without a suffix is a number of seconds. Used for
+/// options where durations of a few seconds are much more common than durations of several days.
+pub fn parse_duration_secs(duration: &str) -> Result<Duration> {
+    parse_duration_with_default_unit(duration, 1)
+}
+
+/// The implementation for [`parse_duration()`] and [`parse_duration_secs()`]. `default_multiplier`
+/// is the number of seconds a number without a suffix gets multiplied by.
+fn parse_duration_with_default_unit(duration: &str, default_multiplier: u64) -> Result<Duration> {
+    let trimmed = duration.trim();
+    let number_len = trimmed
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(trimmed.len());
+    let (number, suffix) = trimmed.split_at(number_len);
+    let multiplier: u64 = match suffix.trim().to_lowercase().as_str() {
+        "" => default_multiplier,
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 60 * 60 * 24,
+        "w" => 60 * 60 * 24 * 7,
+        _ => anyhow::bail!(
+            "'{}' is not a valid duration, expected a number followed by s, m, h, d, or w",
+            duration
+        ),
+    };
+    let number: u64 = number
+        .parse()
+        .with_context(|| format!("'{}' is not a valid duration", duration))?;
+
+    Ok(Duration::from_secs(number.saturating_mul(multiplier)))
+}
+
+/// Format a file size in bytes for display purposes, using the same units as [`parse_size()`].
+pub fn format_size(size: u64) -> String {
+    const UNITS: [&str; 3] = ["KiB", "MiB", "GiB"];
+
+    if size < 1024 {
+        return format!("{} B", size);
+    }
+
+    let mut value = size as f64 / 1024.0;
+    let mut unit = UNITS[0];
+    for next_unit in &UNITS[1..] {
+        if value < 1024.0 {
+            break;
+        }
+
+        value /= 1024.0;
+        unit = next_unit;
+    }
+
+    format!("{:.1} {}", value, unit)
+}
+
+/// Check whether two paths are on the same filesystem by comparing their device IDs. Returns `None`
+/// if either path's metadata could not be read.
+pub fn same_filesystem(a: &Path, b: &Path) -> Option<bool> {
+    let a = fs::metadata(a).ok()?;
+    let b = fs::metadata(b).ok()?;
+
+    Some(a.dev() == b.dev())
+}
+
+/// Get the modification time of `path` in nanoseconds since the Unix epoch. Returns `None` if the
+/// path's metadata could not be read.
+pub fn modification_time(path: &Path) -> Option<i64> {
+    fs::metadata(path)
+        .ok()
+        .map(|metadata| metadata.mtime() * 1_000_000_000 + metadata.mtime_nsec())
+}
+
+/// Get the ID of the device `path` is stored on. Returns `None` if the path's metadata could not be
+/// read.
+pub fn device_id(path: &Path) -> Option<u64> {
+    fs::metadata(path).ok().map(|metadata| metadata.dev())
+}
+
+/// Split a device ID from `device_id()` into its major and minor numbers. These are the `major()`
+/// and `minor()` macros from glibc.
+pub fn split_device_id(dev: u64) -> (u64, u64) {
+    let major = ((dev >> 8) & 0xfff) | ((dev >> 32) & !0xfff);
+    let minor = (dev & 0xff) | ((dev >> 12) & !0xff);
+
+    (major, minor)
+}
+
+/// Check whether `path` is stored on a rotational disk, according to the kernel. Returns `None` if
+/// this can't be determined, for instance because the path is on a network share or a FUSE
+/// filesystem.
+pub fn is_rotational(path: &Path) -> Option<bool> {
+    let (major, minor) = split_device_id(device_id(path)?);
+
+    // Partitions don't have a queue of their own, so we'll need to check the parent device instead
+    let device = PathBuf::from(format!("/sys/dev/block/{major}:{minor}"));
+    [
+        device.join("queue/rotational"),
+        device.join("../queue/rotational"),
+    ]
+    .iter()
+    .find_map(|path| fs::read_to_string(path).ok())
+    .map(|rotational| rotational.trim() == "1")
+}
+
+/// Check whether the file `path` points to exists and can be opened for reading. Unlike
+/// `Path::exists()`, this also catches symlinks pointing to files with the wrong permissions or to
+/// files on filesystems that are no longer mounted.
+pub fn verify_readable(path: &Path) -> Result<()> {
+    fs::File::open(path)
+        .map(|_| ())
+        .with_context(|| format!("Could not read '{}'", path.display()))
+}
+
+/// Check whether a file name matches a glob pattern. `*` matches any number of characters and `?`
+/// matches exactly one character. Matching is case-insensitive since these are Windows file names.
+pub fn glob_matches(pattern: &str, file_name: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let file_name: Vec<char> = file_name.to_lowercase().chars().collect();
+
+    // This is the usual greedy wildcard matching algorithm that backtracks to the last `*`
+    let (mut p, mut f) = (0, 0);
+    let mut last_star: Option<(usize, usize)> = None;
+    while f < file_name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                last_star = Some((p, f));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == file_name[f] => {
+                p += 1;
+                f += 1;
+            }
+            _ => match last_star {
+                Some((star_p, star_f)) => {
+                    p = star_p + 1;
+                    f = star_f + 1;
+                    last_star = Some((star_p, star_f + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Catch Ctrl+C so long running commands can stop at a point where everything on disk is still
+/// consistent. After this has been called, pressing Ctrl+C only sets a flag the command should
+/// check through [`is_interrupted()`] between file operations. Pressing Ctrl+C a second time
+/// terminates the process immediately.
+pub fn handle_interrupts() -> Result<()> {
+    ctrlc::set_handler(|| {
+        if INTERRUPTED.swap(true, AtomicOrdering::Relaxed) {
+            std::process::exit(error::INTERRUPTED_EXIT_CODE);
+        }
+    })
+    .context("Could not install the Ctrl+C handler")
+}
+
+/// Whether Ctrl+C has been pressed since [`handle_interrupts()`] was called.
+pub fn is_interrupted() -> bool {
+    INTERRUPTED.load(AtomicOrdering::Relaxed)
+}
+
+/// Answer every question asked through [`confirm()`] with yes.
+pub fn set_assume_yes(enabled: bool) {
+    ASSUME_YES.store(enabled, AtomicOrdering::Relaxed);
+}
+
+/// Ask the user to confirm something. The question is only answered with yes if the user enters
+/// `YES`. With `--assume-yes` this returns true without asking. When STDIN is not a terminal the
+/// question is answered with no instead, so scripts never hang waiting for input. Every command
+/// that asks a question should go through this function.
+pub fn confirm(question: &str) -> bool {
+    if let Some(answer) = automatic_answer(
+        question,
+        ASSUME_YES.load(AtomicOrdering::Relaxed),
+        io::stdin().is_terminal(),
+    ) {
+        return answer;
+    }
+
+    matches!(
+        promptly::prompt_opt::<String, &str>(question),
+        Ok(Some(answer)) if answer == "YES"
+    )
+}
+
+/// The answer [`confirm()`] gives without asking, if the question should not be asked.
+fn automatic_answer(question: &str, assume_yes: bool, stdin_is_terminal: bool) -> Option<bool> {
+    if assume_yes {
+        println!("{question}: YES (--assume-yes)");
+        Some(true)
+    } else if !stdin_is_terminal {
+        println!(
+            "{question}: no (STDIN is not a terminal, pass '--assume-yes' to answer yes instead)"
+        );
+        Some(false)
+    } else {
+        None
+    }
+}
+
 /// Verify that `yabridge-host.exe` can be found when yabridge is run in a host launched from the
 /// GUI. We do this by launching a login shell, appending `~/.local/share/yabridge` to the login
 /// shell's search path since that's what yabridge also does, and then making the the file can be
@@ -347,11 +693,8 @@ pub fn verify_path_setup(config: &Config) -> Result<bool> {
     }
 }
 
-/// Verify that the installed versions of Wine and yabridge will work together properly. This check
-/// is only performed once per combination of Wine and yabridge, and we'll update the config with
-/// the versions we just tested if the check succeeds. Will return `Err` values if either Wine or
-/// `yabridge-host.exe` can't be run.
-pub fn verify_wine_setup(config: &mut Config) -> Result<()> {
+/// Get the output of `wine --version`, minus the trailing newline. This respects `$WINELOADER`.
+pub fn wine_version() -> Result<String> {
     // These winelib scripts respect `$WINELOADER`, so we'll do the same thing
     let wine_binary = env::var("WINELOADER").unwrap_or_else(|_| String::from("wine"));
     let wine_version_output = Command::new(&wine_binary)
@@ -373,6 +716,16 @@ pub fn verify_wine_setup(config: &mut Config) -> Result<()> {
         )
     })?;
 
+    Ok(wine_version)
+}
+
+/// Verify that the installed versions of Wine and yabridge will work together properly. This check
+/// is only performed once per combination of Wine and yabridge, and we'll update the config with
+/// the versions we just tested if the check succeeds. Will return `Err` values if either Wine or
+/// `yabridge-host.exe` can't be run.
+pub fn verify_wine_setup(config: &mut Config) -> Result<()> {
+    let wine_version = wine_version()?;
+
     let files = config
         .files()
         .context(format!("Could not find '{}'", YABRIDGE_HOST_EXE_NAME))?;
@@ -389,17 +742,19 @@ pub fn verify_wine_setup(config: &mut Config) -> Result<()> {
 
     // Since these checks can take over a second if wineserver isn't already running we'll only
     // perform them when something has changed
+    let wine_prefix = wine_prefix();
     let current_config = KnownConfig {
         wine_version: wine_version.clone(),
         yabridge_host_hash,
+        wine_prefix: Some(wine_prefix.clone()),
     };
     if config.last_known_config.as_ref() == Some(&current_config) {
         return Ok(());
     }
 
-    // It could be that the default Wine prefix was created with `WINEARCH=win32` set. In that case
-    // we should run the 32-bit `yabridge-host.exe` since the 64-bit verison won't be able to run.
-    let host_binary_path = match get_default_wine_prefix_arch() {
+    // It could be that the Wine prefix was created with `WINEARCH=win32` set. In that case we
+    // should run the 32-bit `yabridge-host.exe` since the 64-bit verison won't be able to run.
+    let host_binary_path = match get_wine_prefix_arch() {
         LibArchitecture::Lib32 => files
             .yabridge_host_32_exe
             .with_context(|| format!("Could not find '{}'", YABRIDGE_HOST_32_EXE_NAME)),
@@ -450,8 +805,9 @@ pub fn verify_wine_setup(config: &mut Config) -> Result<()> {
                  Make sure that you have downloaded the correct version of yabridge for your distro.\n\
                  This can also happen when using a version of Wine that's not compatible with this \
                  version of yabridge, in which case you'll need to upgrade Wine. Your current Wine \
-                 version is '{wine_version}'. \
-                 See the link below for instructions on how to upgrade your installation of Wine.\n\
+                 version is '{wine_version}', and the Wine prefix that was used is \
+                 '{wine_prefix}'. See the link below for instructions on how to upgrade your \
+                 installation of Wine.\n\
                  \n\
                  https://github.com/robbert-vdh/yabridge#troubleshooting-common-issues",
                 yabridge_host = "yabridge-host.exe".bright_white(),
@@ -460,6 +816,7 @@ pub fn verify_wine_setup(config: &mut Config) -> Result<()> {
                     .strip_prefix("wine-")
                     .unwrap_or(&wine_version)
                     .bright_white(),
+                wine_prefix = wine_prefix.display().to_string().bright_white(),
             ))
         )
     }
@@ -496,3 +853,66 @@ pub fn wrap(text: &str) -> String {
 
     wrapper.fill(text)
 }
+
+/// A uniquely named temporary directory for tests. The directory and everything in it is removed
+/// again when this goes out of scope.
+#[cfg(test)]
+pub struct TempDir(PathBuf);
+
+#[cfg(test)]
+impl TempDir {
+    /// Create an empty directory. `name` should be unique among the tests, since tests run in
+    /// parallel.
+    pub fn new(name: &str) -> TempDir {
+        let path = env::temp_dir().join(format!("yabridgectl-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).expect("Could not create a temporary directory");
+
+        TempDir(path)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bare_durations_use_the_default_unit() {
+        assert_eq!(
+            parse_duration("2").unwrap(),
+            Duration::from_secs(2 * 60 * 60 * 24)
+        );
+        assert_eq!(parse_duration_secs("2").unwrap(), Duration::from_secs(2));
+        assert_eq!(parse_duration_secs("2m").unwrap(), Duration::from_secs(120));
+        assert!(parse_duration_secs("2x").is_err());
+    }
+
+    #[test]
+    fn questions_are_answered_automatically_without_a_terminal() {
+        assert_eq!(automatic_answer("Continue?", false, false), Some(false));
+        assert_eq!(automatic_answer("Continue?", true, false), Some(true));
+        assert_eq!(automatic_answer("Continue?", true, true), Some(true));
+        assert_eq!(automatic_answer("Continue?", false, true), None);
+    }
+
+    #[test]
+    fn stable_hash_matches_fnv1a() {
+        assert_eq!(stable_hash([]), 0x811c9dc5);
+        assert_eq!(stable_hash([b"a".as_slice()]), 0xe40c292c);
+        assert_eq!(
+            stable_hash([b"foo".as_slice(), b"bar"]),
+            stable_hash([b"foobar".as_slice()])
+        );
+    }
+}